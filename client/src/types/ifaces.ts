@@ -3,6 +3,17 @@ import type { IUser, Item } from './models';
 export interface AuthRes {
     user: IUser;
     token: string;
+    refresh_token: string;
+};
+
+export interface RefreshReq {
+    refresh_token: string;
+};
+
+export interface Page<T> {
+    items: T[];
+    total: number;
+    next_cursor?: string;
 };
 
 export interface DeleteItemReq {
@@ -32,7 +43,6 @@ export type AddOrderReq = {
     item_name: string;
     quantity: number;
     quantity_diff: number;
-    price: number;
 };
 
 export type DeleteOrderReq = {
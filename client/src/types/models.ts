@@ -1,5 +1,11 @@
 import type { Role } from './ifaces';
 
+export interface Money {
+    amount: string;
+    currency: string;
+    minor_units: number;
+};
+
 export interface IUser {
     _key: string;
     _rev: string;
@@ -19,7 +25,29 @@ export interface Item {
     user_id: string;
     description: string;
     quantity: number;
-    price: number;
+    price: Money;
+};
+
+export interface OrderLine {
+    item_id: string;
+    item_name: string;
+    quantity: number;
+    unit_price: Money;
+    total: Money;
+};
+
+export type OrderStatus =
+    | 'PENDING'
+    | 'PAID'
+    | 'SHIPPED'
+    | 'DELIVERED'
+    | 'CANCELLED'
+    | 'REFUNDED';
+
+export interface StatusChange {
+    status: OrderStatus;
+    changed_at: Date;
+    changed_by: string;
 };
 
 export interface IOrder {
@@ -27,9 +55,9 @@ export interface IOrder {
     _rev: string;
     _id: string;
     user_id: string;
-    item_id: string;
-    item_name: string;
-    quantity: number;
-    price: number;
+    lines: OrderLine[];
+    subtotal: Money;
+    status: OrderStatus;
+    status_history: StatusChange[];
     date: Date;
 };
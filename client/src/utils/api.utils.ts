@@ -1,7 +1,7 @@
 import axios, { type InternalAxiosRequestConfig } from 'axios';
 import { get } from 'svelte/store';
 import authStore, { jwtStore } from '../store/auth.store';
-import type { AuthRes } from '../types/ifaces';
+import type { AuthRes, RefreshReq } from '../types/ifaces';
 import { clearState, setState } from './utils';
 
 interface ApiResponse<T> {
@@ -125,24 +125,37 @@ export async function axiosDelete<T, B>(
 }
 
 const refresh = async () => {
-    const email = get(authStore).email;
+    const refreshToken = localStorage.getItem('refresh_token');
 
-    if (!email) {
+    if (!refreshToken) {
         clearState();
         return;
     }
 
-    const response = await axiosGet<AuthRes, unknown>(
-        `/api/auth/refresh/${email}`
-    );
+    const response = await axiosPost<AuthRes, RefreshReq>('/api/auth/refresh', {
+        refresh_token: refreshToken,
+    });
 
-    if (!response.data.content || response.error) {
+    if (!response.data?.content || response.error) {
         clearState();
+        return;
     }
 
-    const { user, token } = response.data.content;
+    const { user, token, refresh_token } = response.data.content;
 
     try {
-        setState(user, token);
+        setState(user, token, refresh_token);
     } catch { }
 };
+
+export const logout = async () => {
+    const refreshToken = localStorage.getItem('refresh_token');
+
+    if (refreshToken) {
+        await axiosPost<boolean, RefreshReq>('/api/auth/logout', {
+            refresh_token: refreshToken,
+        });
+    }
+
+    clearState(true, false);
+};
@@ -1,5 +1,5 @@
 import authStore, { jwtStore } from '../store/auth.store';
-import type { IUser } from '../types/models';
+import type { IUser, Money } from '../types/models';
 
 export function objectDifference<T, U extends Record<string, any>>(
     first: U,
@@ -37,11 +37,22 @@ export const formatNumberLiteral = (value: number) => {
     return value;
 };
 
-export const setState = (user: IUser, token: string) => {
+export const formatMoney = (money: Money, times: number = 1) => {
+    const formatter = new Intl.NumberFormat('en-US', {
+        style: 'currency',
+        currency: money.currency,
+    });
+    const digits = formatter.resolvedOptions().maximumFractionDigits;
+
+    return formatter.format((money.minor_units * times) / 10 ** digits);
+};
+
+export const setState = (user: IUser, token: string, refreshToken: string) => {
     jwtStore.set(token);
     authStore.set(user);
 
     localStorage.setItem('jwt', token);
+    localStorage.setItem('refresh_token', refreshToken);
     localStorage.setItem('user', JSON.stringify(user));
 };
 
@@ -55,6 +66,7 @@ export const clearState = (
 
     if (clearStorage) {
         localStorage.removeItem('jwt');
+        localStorage.removeItem('refresh_token');
         localStorage.removeItem('user');
     }
 
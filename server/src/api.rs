@@ -1,11 +1,35 @@
+use crate::db::{ DatabaseError, DOCUMENT_NOT_FOUND, UNIQUE_CONSTRAINT_VIOLATED, WRITE_CONFLICT };
+use crate::models::Item;
+use arangors::{ ArangoError, ClientError };
+use axum::http::{ header, HeaderMap, HeaderValue, StatusCode };
+use axum::response::{ IntoResponse, Response };
 use axum::Json;
 use schemars::JsonSchema;
 use serde::{ Deserialize, Serialize };
+use serde_json::Value;
 use utoipa::ToSchema;
 
 #[derive(Deserialize, Serialize, JsonSchema, ToSchema)]
 pub struct ErrorResponse {
     pub error_msg: String,
+    /// Stable, machine-readable identifier of the error kind (e.g. `not_found`, `conflict`).
+    pub error_code: String,
+    /// Per-field details, only present on `invalid_fields` errors.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+    /// Current version of the document, only present on `precondition_failed` errors.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current: Option<Value>,
+}
+
+/// A request field that failed validation.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, JsonSchema, ToSchema)]
+pub struct FieldError {
+    /// Path of the offending field in the request body (e.g. `price`).
+    pub field: String,
+    /// Name of the rule that failed (e.g. `range`, `email`).
+    pub code: String,
+    pub message: String,
 }
 
 #[derive(Serialize)]
@@ -15,10 +39,250 @@ pub enum ApiResponse<T> {
     Error(ErrorResponse),
 }
 
-pub fn generate_error<T>(msg: &str) -> Json<ApiResponse<T>> {
-    Json(
-        ApiResponse::Error(ErrorResponse {
-            error_msg: msg.to_string(),
-        })
-    )
+/// One page of a paginated listing. `next_cursor` is absent on the last page.
+#[derive(Serialize, ToSchema)]
+#[aliases(ItemsPage = Page<Item>)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: u64,
+    pub next_cursor: Option<String>,
+}
+
+pub type ApiResult<T> = Result<Json<ApiResponse<T>>, ApiError>;
+
+/// Successful response for a single document, sent with the document's `_rev` as its `ETag` so
+/// clients can make their next edit conditional with `If-Match`.
+pub struct Tagged<T> {
+    pub rev: String,
+    pub body: Json<ApiResponse<T>>,
+}
+
+impl<T> Tagged<T> {
+    pub fn new(rev: String, content: T) -> Self {
+        Self { rev, body: Json(ApiResponse::Success(content)) }
+    }
+}
+
+impl<T: Serialize> IntoResponse for Tagged<T> {
+    fn into_response(self) -> Response {
+        match etag(&self.rev) {
+            Some(etag) => ([(header::ETAG, etag)], self.body).into_response(),
+            None => self.body.into_response(),
+        }
+    }
+}
+
+pub type TaggedResult<T> = Result<Tagged<T>, ApiError>;
+
+fn etag(rev: &str) -> Option<HeaderValue> {
+    HeaderValue::from_str(&format!("\"{}\"", rev)).ok()
+}
+
+/// Revision a write is based on, taken from the `If-Match` header or else from the `_rev` sent
+/// in the body. `If-Match: *` matches any revision.
+pub fn expected_rev(
+    headers: &HeaderMap,
+    body_rev: Option<String>
+) -> Result<Option<String>, ApiError> {
+    let Some(value) = headers.get(header::IF_MATCH) else {
+        return Ok(body_rev);
+    };
+
+    let value = value
+        .to_str()
+        .map_err(|_| ApiError::Validation("If-Match must be a quoted revision".to_string()))?
+        .trim();
+    if value == "*" {
+        return Ok(body_rev);
+    }
+
+    let rev = value
+        .strip_prefix("W/")
+        .unwrap_or(value)
+        .strip_prefix('"')
+        .and_then(|rev| rev.strip_suffix('"'))
+        .ok_or_else(|| ApiError::Validation("If-Match must be a quoted revision".to_string()))?;
+
+    match body_rev {
+        Some(body_rev) if body_rev != rev => {
+            Err(ApiError::Validation("If-Match and _rev name different revisions".to_string()))
+        }
+        _ => Ok(Some(rev.to_string())),
+    }
+}
+
+/// Error returned by handlers and middleware. Converts into the matching HTTP status and an
+/// `ApiResponse::Error` body, so handlers can propagate failures with `?`.
+#[derive(Debug)]
+pub enum ApiError {
+    NotFound(String),
+    Conflict(String),
+    Validation(String),
+    InvalidFields(Vec<FieldError>),
+    Unauthorized(String),
+    Forbidden(String),
+    /// The document changed since the revision the client sent. Carries the current version so
+    /// the client can reapply its changes.
+    PreconditionFailed {
+        message: String,
+        current: Option<Value>,
+    },
+    /// The client has to wait `retry_after` seconds before trying again. Sent with a
+    /// `Retry-After` header.
+    TooManyRequests {
+        message: String,
+        retry_after: u64,
+    },
+    Database(DatabaseError),
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Validation(_) => StatusCode::BAD_REQUEST,
+            ApiError::InvalidFields(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::PreconditionFailed { .. } => StatusCode::PRECONDITION_FAILED,
+            ApiError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Database(_) | ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::Validation(_) => "validation_error",
+            ApiError::InvalidFields(_) => "invalid_fields",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::PreconditionFailed { .. } => "precondition_failed",
+            ApiError::TooManyRequests { .. } => "too_many_requests",
+            ApiError::Database(_) => "database_error",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ApiError::NotFound(msg) |
+            ApiError::Conflict(msg) |
+            ApiError::Validation(msg) |
+            ApiError::Unauthorized(msg) |
+            ApiError::Forbidden(msg) |
+            ApiError::Internal(msg) |
+            ApiError::PreconditionFailed { message: msg, .. } |
+            ApiError::TooManyRequests { message: msg, .. } => msg.to_owned(),
+            ApiError::InvalidFields(fields) =>
+                match fields.as_slice() {
+                    [field] => format!("{}: {}", field.field, field.message),
+                    _ => "One or more fields are invalid".to_string(),
+                }
+            // Driver errors can leak query details, so they are only logged.
+            ApiError::Database(_) => "Error querying the database".to_string(),
+        }
+    }
+
+    pub fn fields(&self) -> &[FieldError] {
+        match self {
+            ApiError::InvalidFields(fields) => fields,
+            _ => &[],
+        }
+    }
+
+    pub fn current(&self) -> Option<&Value> {
+        match self {
+            ApiError::PreconditionFailed { current, .. } => current.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Seconds to wait before retrying, only present on `too_many_requests` errors.
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            ApiError::TooManyRequests { retry_after, .. } => Some(*retry_after),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::Database(err) => write!(f, "{}", err),
+            _ => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+impl From<DatabaseError> for ApiError {
+    fn from(error: DatabaseError) -> Self {
+        match error {
+            DatabaseError::NotFound(msg) => ApiError::NotFound(msg),
+            DatabaseError::Conflict(msg) => ApiError::Conflict(msg),
+            DatabaseError::PreconditionFailed(msg) => {
+                ApiError::PreconditionFailed { message: msg, current: None }
+            }
+            error => {
+                let arango = arango_error(&error).map(|err| (err.error_num(), err.message()));
+
+                match arango {
+                    Some((DOCUMENT_NOT_FOUND, msg)) => ApiError::NotFound(msg.to_string()),
+                    Some((UNIQUE_CONSTRAINT_VIOLATED | WRITE_CONFLICT, msg)) =>
+                        ApiError::Conflict(msg.to_string()),
+                    _ => ApiError::Database(error),
+                }
+            }
+        }
+    }
+}
+
+impl From<ClientError> for ApiError {
+    fn from(error: ClientError) -> Self {
+        DatabaseError::from(error).into()
+    }
+}
+
+fn arango_error(error: &DatabaseError) -> Option<&ArangoError> {
+    match error {
+        DatabaseError::ArangoError(err) => Some(err),
+        DatabaseError::ClientError(ClientError::Arango(err)) => Some(err),
+        _ => None,
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+
+        if status.is_server_error() {
+            eprintln!("{} error: {}", status.as_u16(), self);
+            log::error!("{} | {}", self.error_code(), self);
+        }
+
+        let current_etag = self
+            .current()
+            .and_then(|current| current["_rev"].as_str())
+            .and_then(etag);
+        let body: ApiResponse<()> = ApiResponse::Error(ErrorResponse {
+            error_msg: self.message(),
+            error_code: self.error_code().to_string(),
+            fields: self.fields().to_vec(),
+            current: self.current().cloned(),
+        });
+
+        let mut response = (status, Json(body)).into_response();
+        if let Some(etag) = current_etag {
+            response.headers_mut().insert(header::ETAG, etag);
+        }
+        if let Some(retry_after) = self.retry_after() {
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+
+        response
+    }
 }
\ No newline at end of file
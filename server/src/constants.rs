@@ -1,5 +1,18 @@
-pub static DEV_CONFIG_PATH: &'static str = "../config/config.local.toml";
-pub static PROD_CONFIG_PATH: &'static str = "/etc/rans/config.toml";
-pub static INFO_LOG_FILE: &'static str = "info.log";
-pub static ERROR_LOG_FILE: &'static str = "error.log";
-pub static LOG_TS_FORMAT: &'static str = "%Y-%m-%dT%H:%M:%S";
\ No newline at end of file
+pub static DEV_CONFIG_PATH: &str = "../config/config.local.toml";
+pub static PROD_CONFIG_PATH: &str = "/etc/rans/config.toml";
+pub static CONFIG_PATH_ENV: &str = "RANS_CONFIG";
+pub static INFO_LOG_FILE: &str = "info.log";
+pub static ERROR_LOG_FILE: &str = "error.log";
+pub static LOG_TS_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";pub static ACCESS_TOKEN_TTL_MINUTES: i64 = 15;
+pub static REFRESH_TOKEN_TTL_DAYS: i64 = 7;
+pub static DEFAULT_PAGE_SIZE: u64 = 20;
+pub static MAX_PAGE_SIZE: u64 = 100;
+/// `user_id` given to the orders of deleted accounts.
+pub static DELETED_USER_ID: &str = "deleted";
+pub static PASSWORD_RESET_TTL_MINUTES: i64 = 30;
+/// Password resets that can be requested for one email within `PASSWORD_RESET_WINDOW_MINUTES`.
+pub static PASSWORD_RESET_LIMIT: u64 = 3;
+pub static PASSWORD_RESET_WINDOW_MINUTES: i64 = 60;
+pub static EMAIL_VERIFICATION_TTL_HOURS: i64 = 48;
+/// How long the challenge of a login waiting for a TOTP code is valid.
+pub static MFA_CHALLENGE_TTL_MINUTES: i64 = 5;
\ No newline at end of file
@@ -1,11 +1,23 @@
 use arangors::{
+    transaction::Transaction,
     uclient::surf::SurfClient,
     ArangoError,
+    ClientError,
     Connection,
     Database as ArangoDatabase,
     GenericConnection,
 };
 
+/// ArangoDB `ERROR_ARANGO_DOCUMENT_NOT_FOUND` error number.
+pub const DOCUMENT_NOT_FOUND: u16 = 1202;
+/// ArangoDB `ERROR_ARANGO_CONFLICT` error number, raised on write-write conflicts and `_rev`
+/// mismatches.
+pub const WRITE_CONFLICT: u16 = 1200;
+/// ArangoDB `ERROR_ARANGO_UNIQUE_CONSTRAINT_VIOLATED` error number.
+pub const UNIQUE_CONSTRAINT_VIOLATED: u16 = 1210;
+/// HTTP status ArangoDB answers with when a document's `_rev` does not match the expected one.
+pub const PRECONDITION_FAILED: u16 = 412;
+
 #[derive(Clone)]
 pub struct Database {
     pub arango_db: ArangoDatabase<SurfClient>,
@@ -22,6 +34,12 @@ pub struct DBConnector {
 pub enum DatabaseError {
     ConnectionError(String),
     ArangoError(ArangoError),
+    ClientError(ClientError),
+    QueryError(String),
+    NotFound(String),
+    Conflict(String),
+    /// The document changed since the revision the write was based on.
+    PreconditionFailed(String),
 }
 
 impl From<ArangoError> for DatabaseError {
@@ -30,6 +48,26 @@ impl From<ArangoError> for DatabaseError {
     }
 }
 
+impl From<ClientError> for DatabaseError {
+    fn from(error: ClientError) -> Self {
+        DatabaseError::ClientError(error)
+    }
+}
+
+impl std::fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DatabaseError::ConnectionError(msg) => write!(f, "{}", msg),
+            DatabaseError::ArangoError(err) => write!(f, "{}", err),
+            DatabaseError::ClientError(err) => write!(f, "{}", err),
+            DatabaseError::QueryError(msg) => write!(f, "{}", msg),
+            DatabaseError::NotFound(msg) => write!(f, "{}", msg),
+            DatabaseError::Conflict(msg) => write!(f, "{}", msg),
+            DatabaseError::PreconditionFailed(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
 impl Database {
     pub async fn new(connector: DBConnector) -> Result<Self, DatabaseError> {
         let arango_conn: GenericConnection<SurfClient> = Connection::establish_basic_auth(
@@ -37,17 +75,13 @@ impl Database {
             &connector.db_username,
             &connector.db_password
         ).await.map_err(|err| {
-            DatabaseError::ConnectionError(
-                format!("Failed to connect to database {}", err.to_string())
-            )
+            DatabaseError::ConnectionError(format!("Failed to connect to database {}", err))
         })?;
 
         let arango_db: ArangoDatabase<SurfClient> = arango_conn
             .db(&connector.db_name).await
             .map_err(|err| {
-                DatabaseError::ConnectionError(
-                    format!("Failed to connect to database {}", err.to_string())
-                )
+                DatabaseError::ConnectionError(format!("Failed to connect to database {}", err))
             })?;
 
         Ok(Database { arango_db })
@@ -62,4 +96,23 @@ impl ArangoProvider for Database {
     fn get_db(&self) -> &ArangoDatabase<SurfClient> {
         &self.arango_db
     }
+}
+
+/// Commits `transaction` if `result` is a success and aborts it otherwise.
+pub(crate) async fn finish<T>(
+    transaction: Transaction<SurfClient>,
+    result: Result<T, DatabaseError>
+) -> Result<T, DatabaseError> {
+    match result {
+        Ok(value) => {
+            transaction.commit().await?;
+            Ok(value)
+        }
+        Err(err) => {
+            if let Err(abort_err) = transaction.abort().await {
+                eprintln!("Error aborting transaction: {}", abort_err);
+            }
+            Err(err)
+        }
+    }
 }
\ No newline at end of file
@@ -2,12 +2,38 @@ pub mod api;
 pub mod constants;
 pub mod db;
 pub mod logs;
+pub mod mail;
+pub mod migrations;
 pub mod models;
+pub mod openapi;
+pub mod password;
+pub mod purge;
+pub mod rate_limit;
+pub mod search;
+pub mod state;
 pub mod toml_env;
+pub mod totp;
+pub mod repositories {
+    pub mod audit;
+    pub mod carts;
+    pub mod items;
+    pub mod login_attempts;
+    pub mod memory;
+    pub mod orders;
+    pub mod password_resets;
+    pub mod tokens;
+    pub mod users;
+}
 pub mod requests {
+    pub mod admin;
     pub mod auth;
+    pub mod carts;
     pub mod items;
     pub mod jwt;
     pub mod orders;
     pub mod routes;
+    pub mod security;
+    pub mod two_factor;
+    pub mod users;
+    pub mod validation;
 }
\ No newline at end of file
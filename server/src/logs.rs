@@ -51,10 +51,12 @@ impl Log for Logger {
     }
 }
 
+/// Installs the file logger. Only the first call in a process takes effect.
 pub fn set_log(path: &str, level: LevelFilter) {
     let logger = Logger::new(path);
-    log::set_boxed_logger(Box::new(logger)).unwrap();
-    log::set_max_level(level);
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(level);
+    }
 }
 
 fn create_new_file(name: &PathBuf) -> fs::File {
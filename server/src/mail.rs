@@ -0,0 +1,139 @@
+use crate::toml_env::{ MailConfig, SmtpConfig };
+use async_trait::async_trait;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{ AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor };
+use log::info;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{ AtomicU64, Ordering };
+use std::sync::Arc;
+
+/// A plain text email to a single recipient.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub enum MailError {
+    Address(String),
+    Transport(String),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for MailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MailError::Address(msg) => write!(f, "Invalid address: {}", msg),
+            MailError::Transport(msg) => write!(f, "Error sending mail: {}", msg),
+            MailError::Io(err) => write!(f, "Error writing mail: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for MailError {}
+
+impl From<std::io::Error> for MailError {
+    fn from(error: std::io::Error) -> Self {
+        MailError::Io(error)
+    }
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: Mail) -> Result<(), MailError>;
+}
+
+/// Builds the mailer described by the `[Mail]` config.
+pub fn from_config(config: &MailConfig) -> Result<Arc<dyn Mailer>, MailError> {
+    match &config.smtp {
+        Some(smtp) => Ok(Arc::new(SmtpMailer::new(&config.from, smtp)?)),
+        None => Ok(Arc::new(FileMailer::new(&config.from, &config.dir))),
+    }
+}
+
+/// Sends emails through an SMTP relay.
+pub struct SmtpMailer {
+    from: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpMailer {
+    pub fn new(from: &str, config: &SmtpConfig) -> Result<Self, MailError> {
+        let from = from.parse().map_err(|err| MailError::Address(format!("{}: {}", from, err)))?;
+        let mut builder = if config.starttls {
+            AsyncSmtpTransport::<Tokio1Executor>
+                ::starttls_relay(&config.host)
+                .map_err(|err| MailError::Transport(err.to_string()))?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host)
+        };
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(Self { from, transport: builder.port(config.port).build() })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        let to: Mailbox = mail.to
+            .parse()
+            .map_err(|err| MailError::Address(format!("{}: {}", mail.to, err)))?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(mail.subject)
+            .body(mail.body)
+            .map_err(|err| MailError::Transport(err.to_string()))?;
+
+        self.transport.send(message).await.map_err(|err| MailError::Transport(err.to_string()))?;
+
+        Ok(())
+    }
+}
+
+/// Writes every email to its own file in a directory instead of sending it, and logs it. Meant
+/// for development and tests.
+pub struct FileMailer {
+    from: String,
+    dir: PathBuf,
+    sent: AtomicU64,
+}
+
+impl FileMailer {
+    pub fn new(from: &str, dir: impl Into<PathBuf>) -> Self {
+        Self { from: from.to_string(), dir: dir.into(), sent: AtomicU64::new(0) }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        let name = format!(
+            "{}-{}-{}.eml",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.f"),
+            self.sent.fetch_add(1, Ordering::Relaxed),
+            mail.to
+        );
+        let path = self.dir.join(name);
+        let contents = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\n\r\n{}\r\n",
+            self.from,
+            mail.to,
+            mail.subject,
+            mail.body
+        );
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::write(&path, contents).await?;
+        info!("MAIL - {} | {} | {}", mail.to, mail.subject, path.display());
+
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -1,64 +1,67 @@
 use axum::Router;
-use server::constants::PROD_CONFIG_PATH;
+use clap::{ Parser, Subcommand };
 use server::db::{ DBConnector, Database, DatabaseError };
+use server::{ migrations, purge };
+use server::openapi::ApiDoc;
 use server::requests::routes::create_routes;
+use server::state::AppState;
 use server::toml_env::{ Config, DatabaseConfig };
 use std::net::SocketAddr;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
+#[derive(Parser)]
+#[command(version, about = "REST API for the RANS tech stack")]
+struct Cli {
+    /// Path to the config file. Defaults to $RANS_CONFIG, then the development config
+    #[arg(short, long, global = true)]
+    config: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Apply pending migrations and start the API (default)
+    Serve,
+    /// Manage database migrations
+    Migrate {
+        #[command(subcommand)]
+        action: Option<MigrateAction>,
+    },
+    /// Permanently remove items and orders deleted longer than the retention period ago
+    Purge,
+}
+
+#[derive(Subcommand)]
+enum MigrateAction {
+    /// Apply every pending migration (default)
+    Up,
+    /// Revert migrations above a version, or only the latest one
+    Down {
+        /// Version to revert to. `0` reverts everything
+        #[arg(long)]
+        to: Option<u32>,
+    },
+    /// List migrations and whether they have been applied
+    Status,
+}
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
-    #[derive(OpenApi)]
-    #[openapi(
-        paths(
-            server::requests::auth::handle_login,
-            server::requests::auth::handle_signup,
-            server::requests::jwt::refresh,
-            server::requests::items::get_item,
-            server::requests::items::get_items,
-            server::requests::items::add_item,
-            server::requests::items::edit_item,
-            server::requests::items::delete_item,
-            server::requests::orders::get_orders,
-            server::requests::orders::add_order,
-            server::requests::orders::delete_orders
-        ),
-        components(
-            schemas(
-                server::models::User,
-                server::models::Order,
-                server::models::Item,
-                server::models::Role,
-                server::api::ErrorResponse,
-                server::requests::auth::LoginParams,
-                server::requests::auth::AuthRes,
-                server::requests::auth::SignupParams,
-                server::requests::items::GetItemReq,
-                server::requests::items::AddItemReq,
-                server::requests::items::UpdateItemReq,
-                server::requests::items::DeleteItemReq,
-                server::requests::items::ItemUpdate,
-                server::requests::orders::AddOrderReq,
-                server::requests::orders::DeleteOrderReq,
-                server::requests::orders::ItemQuantityUpdate
-            )
-        ),
-        tags((name = "RANS API", description = "REST API for RANS tech stack"))
-    )]
-    struct ApiDoc;
-
-    let parsed_config = Config::parse(PROD_CONFIG_PATH);
+    let cli = Cli::parse();
+    let config_path = Config::path(cli.config);
+    let parsed_config = Config::parse(&config_path);
 
     let config = match parsed_config {
         Ok(config) => config,
         Err(err) => {
-            eprintln!("Error reading config file: {:?}", err);
+            eprintln!("Error reading config file {}: {:?}", config_path, err);
             return;
         }
     };
 
-    let db_result = get_db(config.db).await;
+    let db_result = get_db(&config.db).await;
 
     let db: Database = match db_result {
         Ok(db) => db,
@@ -70,22 +73,105 @@ async fn main() {
 
     println!("Successfully connected to database");
 
-    let app: Router = create_routes(db, config.log.path.as_str(), &config.server).await.merge(
+    if let Some(Command::Migrate { action }) = cli.command {
+        if let Err(e) = migrate(&db, action.unwrap_or(MigrateAction::Up)).await {
+            eprintln!("Error running migrations: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    match migrations::up(&db).await {
+        Ok(ran) if !ran.is_empty() => println!("Applied migrations {:?}", ran),
+        Ok(_) => (),
+        Err(e) => {
+            eprintln!("Error running migrations: {}", e);
+            return;
+        }
+    }
+
+    let addr: SocketAddr = config.server.socket_addr();
+    let state = match AppState::new(db, config) {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    if let Some(Command::Purge) = cli.command {
+        match purge::purge_deleted(&state, chrono::Local::now().naive_local()).await {
+            Ok(purged) => {
+                println!(
+                    "Purged {} items, {} orders and {} password resets",
+                    purged.items,
+                    purged.orders,
+                    purged.password_resets
+                );
+            }
+            Err(e) => {
+                eprintln!("Error purging deleted records: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    purge::spawn(state.clone());
+
+    let app: Router = create_routes(state).await.merge(
         SwaggerUi::new("/api/v1").url("/api-docs/openapi.json", ApiDoc::openapi())
     );
 
-    let addr: SocketAddr = SocketAddr::from(config.server.socket_addr());
     tracing::info!("listening on {}", addr);
     println!("Server listening on {}", addr);
-    axum::Server::bind(&addr).serve(app.into_make_service()).await.expect("Server failed to start");
+    axum::Server
+        ::bind(&addr)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>()).await
+        .expect("Server failed to start");
+}
+
+async fn migrate(db: &Database, action: MigrateAction) -> Result<(), DatabaseError> {
+    match action {
+        MigrateAction::Up => {
+            let ran = migrations::up(db).await?;
+            if ran.is_empty() {
+                println!("Database is up to date");
+            }
+            for version in ran {
+                println!("Applied migration {}", version);
+            }
+        }
+        MigrateAction::Down { to } => {
+            let reverted = migrations::down(db, to).await?;
+            if reverted.is_empty() {
+                println!("Nothing to revert");
+            }
+            for version in reverted {
+                println!("Reverted migration {}", version);
+            }
+        }
+        MigrateAction::Status => {
+            let applied = migrations::applied(db).await?;
+            for migration in migrations::MIGRATIONS {
+                let status = applied
+                    .iter()
+                    .find(|entry| entry.version == migration.version)
+                    .map_or("pending".to_string(), |entry| format!("applied {}", entry.applied_at));
+                println!("{:>4} {:<24} {}", migration.version, migration.name, status);
+            }
+        }
+    }
+
+    Ok(())
 }
 
-async fn get_db(config: DatabaseConfig) -> Result<Database, Box<DatabaseError>> {
+async fn get_db(config: &DatabaseConfig) -> Result<Database, Box<DatabaseError>> {
     let connector: DBConnector = DBConnector {
         db_url: config.get_url(),
-        db_name: config.name,
-        db_username: config.username,
-        db_password: config.password,
+        db_name: config.name.to_owned(),
+        db_username: config.username.to_owned(),
+        db_password: config.password.to_owned(),
     };
 
     Database::new(connector).await.map_err(|e| e.into())
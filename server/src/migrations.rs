@@ -0,0 +1,813 @@
+use crate::db::{ ArangoProvider, Database, DatabaseError };
+use crate::models::{ Currency, Money };
+use crate::search;
+use arangors::collection::options::CreateOptions;
+use arangors::index::{ Index, IndexSettings };
+use arangors::uclient::ClientExt;
+use arangors::{ ArangoError, ClientError };
+use chrono::{ NaiveDateTime, Utc };
+use serde::{ Deserialize, Serialize };
+use serde_json::{ json, Value };
+use std::collections::HashMap;
+
+/// System collection recording which migrations have been applied.
+pub const MIGRATIONS_COLLECTION: &str = "_migrations";
+
+/// A single reversible change to the database layout.
+pub enum Step {
+    /// Creates the collection with its schema rule. When the collection already exists (e.g. it
+    /// was created by hand) the schema is applied to it instead.
+    Collection {
+        name: &'static str,
+        schema: fn() -> Value,
+    },
+    /// Creates a persistent index over `fields`.
+    Index {
+        collection: &'static str,
+        fields: &'static [&'static str],
+        unique: bool,
+    },
+    /// Creates a TTL index that removes documents once the date in `field` has passed.
+    TtlIndex {
+        collection: &'static str,
+        field: &'static str,
+    },
+    /// Drops the persistent index over `fields`, recreating it when reverted.
+    DropIndex {
+        collection: &'static str,
+        fields: &'static [&'static str],
+        unique: bool,
+    },
+    /// Creates the ArangoSearch analyzers and view used by item search.
+    ItemSearch,
+    /// Replaces the schema rule of an existing collection, restoring `previous` when reverted.
+    Schema {
+        collection: &'static str,
+        schema: fn() -> Value,
+        previous: fn() -> Value,
+    },
+    /// Converts float `price` fields into `Money` in the default currency and swaps the schema.
+    /// Reverting converts them back to floats under the `previous` schema.
+    MoneyPrices {
+        collection: &'static str,
+        schema: fn() -> Value,
+        previous: fn() -> Value,
+    },
+}
+
+/// A numbered group of steps. Steps are applied in order on the way up and reverted in reverse
+/// order on the way down.
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub steps: &'static [Step],
+}
+
+/// Every migration known to this build, in ascending version order.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_collections",
+        steps: &[
+            Step::Collection { name: "User", schema: user_schema },
+            Step::Collection { name: "Item", schema: item_schema_v1 },
+            Step::Collection { name: "Order", schema: order_schema_v1 },
+            Step::Collection { name: "RefreshToken", schema: refresh_token_schema },
+        ],
+    },
+    Migration {
+        version: 2,
+        name: "create_indexes",
+        steps: &[
+            Step::Index { collection: "User", fields: &["email"], unique: true },
+            Step::Index { collection: "Item", fields: &["name"], unique: true },
+            Step::Index { collection: "RefreshToken", fields: &["token_hash"], unique: true },
+            Step::Index { collection: "RefreshToken", fields: &["family_id"], unique: false },
+        ],
+    },
+    Migration {
+        version: 3,
+        name: "create_item_search",
+        steps: &[Step::ItemSearch],
+    },
+    Migration {
+        version: 4,
+        name: "money_prices",
+        steps: &[
+            Step::MoneyPrices {
+                collection: "Item",
+                schema: item_schema_v4,
+                previous: item_schema_v1,
+            },
+            Step::MoneyPrices {
+                collection: "Order",
+                schema: order_schema_v4,
+                previous: order_schema_v1,
+            },
+        ],
+    },
+    Migration {
+        version: 5,
+        name: "carts_and_order_lines",
+        steps: &[
+            Step::Collection { name: "Cart", schema: cart_schema },
+            Step::Schema {
+                collection: "Order",
+                schema: order_schema_v5,
+                previous: order_schema_v4,
+            },
+        ],
+    },
+    Migration {
+        version: 6,
+        name: "order_status",
+        steps: &[
+            Step::Schema {
+                collection: "Order",
+                schema: order_schema_v6,
+                previous: order_schema_v5,
+            },
+        ],
+    },
+    Migration {
+        version: 7,
+        name: "soft_delete",
+        steps: &[
+            Step::Schema { collection: "User", schema: user_schema_v7, previous: user_schema },
+            Step::Schema { collection: "Item", schema: item_schema_v7, previous: item_schema_v4 },
+            Step::Schema {
+                collection: "Order",
+                schema: order_schema_v7,
+                previous: order_schema_v6,
+            },
+            // Deleted items keep their name, so only items that are not deleted must be unique.
+            Step::DropIndex { collection: "Item", fields: &["name"], unique: true },
+            Step::Index { collection: "Item", fields: &["name", "deleted_at"], unique: true },
+        ],
+    },
+    Migration {
+        version: 8,
+        name: "password_resets",
+        steps: &[
+            Step::Collection { name: "PasswordReset", schema: password_reset_schema },
+            Step::Index { collection: "PasswordReset", fields: &["token_hash"], unique: true },
+            Step::Index { collection: "PasswordReset", fields: &["email"], unique: false },
+        ],
+    },
+    Migration {
+        version: 9,
+        name: "email_verification",
+        steps: &[
+            Step::Schema { collection: "User", schema: user_schema_v9, previous: user_schema_v7 },
+        ],
+    },
+    Migration {
+        version: 10,
+        name: "login_lockout",
+        steps: &[
+            Step::Collection { name: "LoginAttempt", schema: login_attempt_schema },
+            Step::Index { collection: "LoginAttempt", fields: &["subject"], unique: false },
+            Step::TtlIndex { collection: "LoginAttempt", field: "expires_at" },
+            Step::Collection { name: "AuditLog", schema: audit_log_schema },
+            Step::Index { collection: "AuditLog", fields: &["subject"], unique: false },
+        ],
+    },
+    Migration {
+        version: 11,
+        name: "two_factor",
+        steps: &[
+            Step::Schema { collection: "User", schema: user_schema_v11, previous: user_schema_v9 },
+        ],
+    },
+    Migration {
+        version: 12,
+        name: "token_version",
+        steps: &[
+            Step::Schema { collection: "User", schema: user_schema_v12, previous: user_schema_v11 },
+        ],
+    },
+];
+
+/// Entry stored in `_migrations` for every applied migration.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AppliedMigration {
+    pub version: u32,
+    pub name: String,
+    pub applied_at: NaiveDateTime,
+}
+
+/// Latest version known to this build, or 0 when there are no migrations.
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+/// Migrations that have not been applied yet, in the order they should run.
+pub fn pending(applied: &[u32]) -> Vec<&'static Migration> {
+    MIGRATIONS.iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .collect()
+}
+
+/// Applied migrations above `target`, in the order they should be reverted.
+pub fn to_revert(applied: &[u32], target: u32) -> Vec<&'static Migration> {
+    MIGRATIONS.iter()
+        .rev()
+        .filter(|migration| migration.version > target && applied.contains(&migration.version))
+        .collect()
+}
+
+/// Lists the applied migrations, creating the `_migrations` collection on first use.
+pub async fn applied(database: &Database) -> Result<Vec<AppliedMigration>, DatabaseError> {
+    ensure_migrations_collection(database).await?;
+
+    let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+    bind_vars.insert("@migrations", MIGRATIONS_COLLECTION.into());
+
+    let applied: Vec<AppliedMigration> = database
+        .get_db()
+        .aql_bind_vars(
+            "FOR migration IN @@migrations SORT migration.version RETURN migration",
+            bind_vars
+        ).await?;
+
+    Ok(applied)
+}
+
+/// Applies every pending migration and returns the versions that ran.
+pub async fn up(database: &Database) -> Result<Vec<u32>, DatabaseError> {
+    let applied: Vec<u32> = applied(database).await?
+        .iter()
+        .map(|migration| migration.version)
+        .collect();
+
+    let mut ran = Vec::new();
+    for migration in pending(&applied) {
+        for step in migration.steps {
+            step.up(database).await?;
+        }
+        record(database, migration).await?;
+        ran.push(migration.version);
+    }
+
+    Ok(ran)
+}
+
+/// Reverts applied migrations until `target` is the latest one and returns the versions that
+/// were reverted. Without a target only the latest migration is reverted.
+pub async fn down(database: &Database, target: Option<u32>) -> Result<Vec<u32>, DatabaseError> {
+    let applied: Vec<u32> = applied(database).await?
+        .iter()
+        .map(|migration| migration.version)
+        .collect();
+
+    let target = match target {
+        Some(target) => target,
+        None => applied.iter().max().map_or(0, |latest| latest.saturating_sub(1)),
+    };
+
+    let mut reverted = Vec::new();
+    for migration in to_revert(&applied, target) {
+        for step in migration.steps.iter().rev() {
+            step.down(database).await?;
+        }
+        forget(database, migration).await?;
+        reverted.push(migration.version);
+    }
+
+    Ok(reverted)
+}
+
+impl Step {
+    async fn up(&self, database: &Database) -> Result<(), DatabaseError> {
+        let db = database.get_db();
+
+        match self {
+            Step::Collection { name, schema } => {
+                if collection_exists(database, name).await? {
+                    set_schema(database, name, schema()).await
+                } else {
+                    db.create_collection_with_options(
+                        CreateOptions::builder().name(name).schema(schema()).build(),
+                        Default::default()
+                    ).await?;
+                    Ok(())
+                }
+            }
+            Step::Index { collection, fields, unique } => {
+                create_index(database, collection, fields, persistent(*unique)).await
+            }
+            Step::TtlIndex { collection, field } => {
+                let ttl = IndexSettings::Ttl { expire_after: 0 };
+                create_index(database, collection, &[field], ttl).await
+            }
+            Step::DropIndex { collection, fields, .. } => {
+                drop_index(database, collection, fields).await
+            }
+            Step::ItemSearch => search::setup(database).await,
+            Step::Schema { collection, schema, .. } => {
+                set_schema(database, collection, schema()).await
+            }
+            Step::MoneyPrices { collection, schema, .. } => {
+                convert_prices(database, collection, schema(), |price| {
+                    let amount = price.as_f64()?;
+                    Some(Money::from_f64(amount, Currency::default()).map(|money| json!(money)))
+                }).await
+            }
+        }
+    }
+
+    async fn down(&self, database: &Database) -> Result<(), DatabaseError> {
+        let db = database.get_db();
+
+        match self {
+            Step::Collection { name, .. } => {
+                if collection_exists(database, name).await? {
+                    db.drop_collection(name).await?;
+                }
+                Ok(())
+            }
+            Step::Index { collection, fields, .. } => {
+                drop_index(database, collection, fields).await
+            }
+            Step::TtlIndex { collection, field } => {
+                drop_index(database, collection, &[field]).await
+            }
+            Step::DropIndex { collection, fields, unique } => {
+                create_index(database, collection, fields, persistent(*unique)).await
+            }
+            Step::ItemSearch => search::teardown(database).await,
+            Step::Schema { collection, previous, .. } => {
+                set_schema(database, collection, previous()).await
+            }
+            Step::MoneyPrices { collection, previous, .. } => {
+                convert_prices(database, collection, previous(), |price| {
+                    if !price.is_object() {
+                        return None;
+                    }
+                    let money = serde_json::from_value::<Money>(price.clone());
+                    Some(money.map(|money| json!(money.to_f64())))
+                }).await
+            }
+        }
+    }
+}
+
+fn persistent(unique: bool) -> IndexSettings {
+    IndexSettings::Persistent { unique, sparse: false, deduplicate: false }
+}
+
+async fn create_index(
+    database: &Database,
+    collection: &str,
+    fields: &[&str],
+    settings: IndexSettings
+) -> Result<(), DatabaseError> {
+    let name = index_name(collection, fields);
+    if find_index(database, collection, &name).await?.is_some() {
+        return Ok(());
+    }
+
+    let index = Index::builder()
+        .name(name)
+        .fields(fields.iter().map(|field| field.to_string()).collect())
+        .settings(settings)
+        .build();
+
+    database.get_db().create_index(collection, &index).await?;
+    Ok(())
+}
+
+async fn drop_index(
+    database: &Database,
+    collection: &str,
+    fields: &[&str]
+) -> Result<(), DatabaseError> {
+    let name = index_name(collection, fields);
+    if let Some(index) = find_index(database, collection, &name).await? {
+        database.get_db().delete_index(&index.id).await?;
+    }
+    Ok(())
+}
+
+fn index_name(collection: &str, fields: &[&str]) -> String {
+    format!("{}_{}", collection.to_lowercase(), fields.join("_"))
+}
+
+async fn find_index(
+    database: &Database,
+    collection: &str,
+    name: &str
+) -> Result<Option<Index>, DatabaseError> {
+    if !collection_exists(database, collection).await? {
+        return Ok(None);
+    }
+
+    let indexes = database.get_db().indexes(collection).await?;
+
+    Ok(indexes.indexes.into_iter().find(|index| index.name == name))
+}
+
+async fn collection_exists(database: &Database, name: &str) -> Result<bool, DatabaseError> {
+    let collections = database.get_db().accessible_collections().await?;
+
+    Ok(collections.iter().any(|collection| collection.name == name))
+}
+
+async fn ensure_migrations_collection(database: &Database) -> Result<(), DatabaseError> {
+    if collection_exists(database, MIGRATIONS_COLLECTION).await? {
+        return Ok(());
+    }
+
+    database
+        .get_db()
+        .create_collection_with_options(
+            CreateOptions::builder().name(MIGRATIONS_COLLECTION).is_system(true).build(),
+            Default::default()
+        ).await?;
+
+    Ok(())
+}
+
+/// arangors cannot change the schema of an existing collection, so the properties endpoint is
+/// called directly.
+async fn set_schema(database: &Database, name: &str, schema: Value) -> Result<(), DatabaseError> {
+    let db = database.get_db();
+    let url = db
+        .url()
+        .join(&format!("_api/collection/{}/properties", name))
+        .map_err(|err| DatabaseError::QueryError(err.to_string()))?;
+
+    let response = db
+        .session()
+        .put(url, json!({ "schema": schema }).to_string()).await
+        .map_err(ClientError::from)?;
+
+    let body: Value = serde_json::from_str(response.body()).map_err(ClientError::from)?;
+    if body["error"].as_bool().unwrap_or(false) {
+        let error: ArangoError = serde_json::from_value(body).map_err(ClientError::from)?;
+        return Err(DatabaseError::ArangoError(error));
+    }
+
+    Ok(())
+}
+
+/// Rewrites the `price` of every document `convert` returns a value for, then applies `schema`.
+/// Validation is switched off meanwhile, as documents only match the new schema once converted.
+async fn convert_prices<E: std::fmt::Display>(
+    database: &Database,
+    collection: &str,
+    schema: Value,
+    convert: impl Fn(&Value) -> Option<Result<Value, E>>
+) -> Result<(), DatabaseError> {
+    set_schema(database, collection, Value::Null).await?;
+
+    let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+    bind_vars.insert("@collection", collection.into());
+
+    let prices: Vec<Value> = database
+        .get_db()
+        .aql_bind_vars(
+            "FOR doc IN @@collection RETURN { _key: doc._key, price: doc.price }",
+            bind_vars
+        ).await?;
+
+    let mut changes = Vec::new();
+    for document in prices {
+        if let Some(price) = convert(&document["price"]) {
+            let price = price.map_err(|err| {
+                DatabaseError::QueryError(
+                    format!(
+                        "Cannot convert the price of {}/{}: {}",
+                        collection,
+                        document["_key"].as_str().unwrap_or_default(),
+                        err
+                    )
+                )
+            })?;
+            changes.push(json!({ "_key": document["_key"], "price": price }));
+        }
+    }
+
+    let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+    bind_vars.insert("@collection", collection.into());
+    bind_vars.insert("changes", changes.into());
+
+    let _: Vec<Value> = database
+        .get_db()
+        .aql_bind_vars("FOR change IN @changes UPDATE change IN @@collection", bind_vars).await?;
+
+    set_schema(database, collection, schema).await
+}
+
+async fn record(database: &Database, migration: &Migration) -> Result<(), DatabaseError> {
+    let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+    bind_vars.insert("@migrations", MIGRATIONS_COLLECTION.into());
+    bind_vars.insert(
+        "migration",
+        json!({
+            "_key": migration.version.to_string(),
+            "version": migration.version,
+            "name": migration.name,
+            "applied_at": Utc::now().naive_utc(),
+        })
+    );
+
+    let _: Vec<Value> = database
+        .get_db()
+        .aql_bind_vars("INSERT @migration INTO @@migrations", bind_vars).await?;
+
+    Ok(())
+}
+
+async fn forget(database: &Database, migration: &Migration) -> Result<(), DatabaseError> {
+    let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+    bind_vars.insert("@migrations", MIGRATIONS_COLLECTION.into());
+    bind_vars.insert("key", migration.version.to_string().into());
+
+    let _: Vec<Value> = database
+        .get_db()
+        .aql_bind_vars("REMOVE @key IN @@migrations", bind_vars).await?;
+
+    Ok(())
+}
+
+fn user_schema() -> Value {
+    json!({
+        "rule": {
+            "properties": {
+                "first_name": { "type": "string" },
+                "last_name": { "type": "string" },
+                "email": { "type": "string" },
+                "password": { "type": "string" },
+                "role": { "enum": ["CUSTOMER", "VENDOR"] },
+            },
+            "additionalProperties": false,
+            "required": ["first_name", "last_name", "email", "password", "role"],
+        },
+        "level": "moderate",
+        "message": "One or more user properties are missing or malformatted",
+    })
+}
+
+fn item_schema_v1() -> Value {
+    json!({
+        "rule": {
+            "properties": {
+                "name": { "type": "string" },
+                "user_id": { "type": "string" },
+                "description": { "type": "string" },
+                "quantity": { "type": "integer" },
+                "price": { "type": "number" },
+            },
+            "additionalProperties": false,
+            "required": ["name", "user_id", "description", "quantity", "price"],
+        },
+        "level": "moderate",
+        "message": "One or more item properties are missing or malformatted",
+    })
+}
+
+fn order_schema_v1() -> Value {
+    json!({
+        "rule": {
+            "properties": {
+                "user_id": { "type": "string" },
+                "item_id": { "type": "string" },
+                "item_name": { "type": "string" },
+                "quantity": { "type": "integer" },
+                "price": { "type": "number" },
+                "date": { "type": "string" },
+            },
+            "additionalProperties": false,
+            "required": ["date", "user_id", "item_id", "item_name", "quantity", "price"],
+        },
+        "level": "moderate",
+        "message": "One or more order properties are missing or malformatted",
+    })
+}
+
+fn refresh_token_schema() -> Value {
+    json!({
+        "rule": {
+            "properties": {
+                "user_id": { "type": "string" },
+                "family_id": { "type": "string" },
+                "token_hash": { "type": "string" },
+                "created_at": { "type": "string" },
+                "expires_at": { "type": "string" },
+                "revoked": { "type": "boolean" },
+            },
+            "additionalProperties": false,
+            "required": ["user_id", "family_id", "token_hash", "created_at", "expires_at", "revoked"],
+        },
+        "level": "moderate",
+        "message": "One or more refresh token properties are missing or malformatted",
+    })
+}
+
+fn money_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "amount": { "type": "string", "pattern": "^-?[0-9]+(\\.[0-9]+)?$" },
+            "currency": { "enum": Currency::ALL.map(|currency| currency.code()) },
+            "minor_units": { "type": "integer" },
+        },
+        "additionalProperties": false,
+        "required": ["amount", "currency", "minor_units"],
+    })
+}
+
+fn item_schema_v4() -> Value {
+    let mut schema = item_schema_v1();
+    schema["rule"]["properties"]["price"] = money_schema();
+    schema
+}
+
+fn order_schema_v4() -> Value {
+    let mut schema = order_schema_v1();
+    schema["rule"]["properties"]["price"] = money_schema();
+    schema
+}
+
+/// Orders hold `lines` and a `subtotal`; orders placed before keep their single top-level item.
+fn order_schema_v5() -> Value {
+    let mut schema = order_schema_v4();
+    let rule = &mut schema["rule"];
+
+    rule["properties"]["lines"] = json!({
+        "type": "array",
+        "minItems": 1,
+        "items": {
+            "type": "object",
+            "properties": {
+                "item_id": { "type": "string" },
+                "item_name": { "type": "string" },
+                "quantity": { "type": "integer" },
+                "unit_price": money_schema(),
+                "total": money_schema(),
+            },
+            "additionalProperties": false,
+            "required": ["item_id", "item_name", "quantity", "unit_price", "total"],
+        },
+    });
+    rule["properties"]["subtotal"] = money_schema();
+    rule["required"] = json!(["date", "user_id"]);
+    rule["anyOf"] = json!([
+        { "required": ["lines", "subtotal"] },
+        { "required": ["item_id", "item_name", "quantity", "price"] },
+    ]);
+
+    schema
+}
+
+/// Orders placed before statuses existed are read as pending, so neither field is required.
+fn order_schema_v6() -> Value {
+    let mut schema = order_schema_v5();
+    let statuses = json!(["PENDING", "PAID", "SHIPPED", "DELIVERED", "CANCELLED", "REFUNDED"]);
+    let rule = &mut schema["rule"];
+
+    rule["properties"]["status"] = json!({ "enum": statuses });
+    rule["properties"]["status_history"] = json!({
+        "type": "array",
+        "items": {
+            "type": "object",
+            "properties": {
+                "status": { "enum": statuses },
+                "changed_at": { "type": "string" },
+                "changed_by": { "type": "string" },
+            },
+            "additionalProperties": false,
+            "required": ["status", "changed_at", "changed_by"],
+        },
+    });
+
+    schema
+}
+
+fn user_schema_v7() -> Value {
+    let mut schema = user_schema();
+    schema["rule"]["properties"]["role"] = json!({ "enum": ["CUSTOMER", "VENDOR", "ADMIN"] });
+    schema
+}
+
+/// Users stored before this version have no `email_verified` and are read as verified.
+fn user_schema_v9() -> Value {
+    let mut schema = user_schema_v7();
+    schema["rule"]["properties"]["email_verified"] = json!({ "type": "boolean" });
+    schema
+}
+
+/// Users stored before this version have two-factor authentication disabled.
+fn user_schema_v11() -> Value {
+    let mut schema = user_schema_v9();
+    let properties = &mut schema["rule"]["properties"];
+    properties["totp_secret"] = json!({ "type": ["string", "null"] });
+    properties["totp_enabled"] = json!({ "type": "boolean" });
+    properties["totp_last_step"] = json!({ "type": "integer", "minimum": 0 });
+    properties["recovery_codes"] = json!({ "type": "array", "items": { "type": "string" } });
+    schema
+}
+
+/// Users stored before this version are at token version 0, like the tokens issued before.
+fn user_schema_v12() -> Value {
+    let mut schema = user_schema_v11();
+    schema["rule"]["properties"]["token_version"] = json!({ "type": "integer", "minimum": 0 });
+    schema
+}
+
+/// Adds the soft-delete fields, which are null again once a record is restored.
+fn with_deletion(mut schema: Value) -> Value {
+    let properties = &mut schema["rule"]["properties"];
+    properties["deleted_at"] = json!({ "type": ["string", "null"] });
+    properties["deleted_by"] = json!({ "type": ["string", "null"] });
+    schema
+}
+
+fn item_schema_v7() -> Value {
+    with_deletion(item_schema_v4())
+}
+
+fn order_schema_v7() -> Value {
+    with_deletion(order_schema_v6())
+}
+
+fn cart_schema() -> Value {
+    json!({
+        "rule": {
+            "properties": {
+                "user_id": { "type": "string" },
+                "lines": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "item_id": { "type": "string" },
+                            "quantity": { "type": "integer", "minimum": 1 },
+                        },
+                        "additionalProperties": false,
+                        "required": ["item_id", "quantity"],
+                    },
+                },
+                "updated_at": { "type": "string" },
+            },
+            "additionalProperties": false,
+            "required": ["user_id", "lines", "updated_at"],
+        },
+        "level": "moderate",
+        "message": "One or more cart properties are missing or malformatted",
+    })
+}
+
+fn password_reset_schema() -> Value {
+    json!({
+        "rule": {
+            "properties": {
+                "user_id": { "type": "string" },
+                "email": { "type": "string" },
+                "token_hash": { "type": "string" },
+                "created_at": { "type": "string" },
+                "expires_at": { "type": "string" },
+                "used_at": { "type": ["string", "null"] },
+            },
+            "additionalProperties": false,
+            "required": ["user_id", "email", "token_hash", "created_at", "expires_at"],
+        },
+        "level": "moderate",
+        "message": "One or more password reset properties are missing or malformatted",
+    })
+}
+
+fn login_attempt_schema() -> Value {
+    json!({
+        "rule": {
+            "properties": {
+                "subject": { "type": "string" },
+                "created_at": { "type": "string" },
+                "expires_at": { "type": "integer" },
+            },
+            "additionalProperties": false,
+            "required": ["subject", "created_at", "expires_at"],
+        },
+        "level": "moderate",
+        "message": "One or more login attempt properties are missing or malformatted",
+    })
+}
+
+fn audit_log_schema() -> Value {
+    json!({
+        "rule": {
+            "properties": {
+                "event": { "enum": ["login_lockout"] },
+                "subject": { "type": "string" },
+                "ip": { "type": ["string", "null"] },
+                "details": { "type": "object" },
+                "created_at": { "type": "string" },
+            },
+            "additionalProperties": false,
+            "required": ["event", "subject", "details", "created_at"],
+        },
+        "level": "moderate",
+        "message": "One or more audit entry properties are missing or malformatted",
+    })
+}
\ No newline at end of file
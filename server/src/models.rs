@@ -1,21 +1,25 @@
 use chrono::NaiveDateTime;
-use schemars::JsonSchema;
-use serde::{ Deserialize, Serialize };
+use schemars::{ gen::SchemaGenerator, schema::Schema, JsonSchema };
+use serde::de::{ self, IgnoredAny, MapAccess, Visitor };
+use serde::{ Deserialize, Deserializer, Serialize, Serializer };
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+use utoipa::openapi::{ RefOr, Schema as OpenApiSchema };
 use utoipa::ToSchema;
 
-#[derive(Deserialize, Serialize, Debug, Clone, JsonSchema, ToSchema)]
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq, JsonSchema, ToSchema)]
 pub enum Role {
+    #[default]
     CUSTOMER,
     VENDOR,
+    /// Can restore soft-deleted records. Cannot be chosen at signup.
+    ADMIN,
 }
 
-impl Default for Role {
-    fn default() -> Self {
-        Role::CUSTOMER
-    }
-}
-
-#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, ToSchema)]
+/// A user as stored in the database. Holds the password hash, so it is never sent in responses:
+/// convert it into a `UserProfile` instead.
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct User {
     pub _key: String,
@@ -27,20 +31,230 @@ pub struct User {
     pub password: String,
     #[serde(default)]
     pub role: Role,
+    /// Users created before email verification existed are read as verified.
+    #[serde(default = "verified_by_default")]
+    pub email_verified: bool,
+    /// Base32 TOTP secret. Set as soon as enrollment starts, but only asked for at login once
+    /// `totp_enabled` is.
+    #[serde(default)]
+    pub totp_secret: Option<String>,
+    #[serde(default)]
+    pub totp_enabled: bool,
+    /// Step of the last TOTP code accepted, so that a code cannot be replayed.
+    #[serde(default)]
+    pub totp_last_step: Option<u64>,
+    /// SHA-256 hashes of the unused recovery codes.
+    #[serde(default)]
+    pub recovery_codes: Vec<String>,
+    /// Bumped when the password changes, which revokes the access tokens issued before.
+    #[serde(default)]
+    pub token_version: u64,
+}
+
+fn verified_by_default() -> bool {
+    true
+}
+
+/// Public view of a user returned by the API.
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, PartialEq, ToSchema)]
+pub struct UserProfile {
+    pub _key: String,
+    pub _id: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub email: String,
+    pub role: Role,
+    pub email_verified: bool,
+    /// Logins ask for a TOTP code after the password.
+    pub totp_enabled: bool,
+}
+
+impl From<User> for UserProfile {
+    fn from(user: User) -> Self {
+        Self {
+            _key: user._key,
+            _id: user._id,
+            first_name: user.first_name,
+            last_name: user.last_name,
+            email: user.email,
+            role: user.role,
+            email_verified: user.email_verified,
+            totp_enabled: user.totp_enabled,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, ToSchema)]
-#[serde(deny_unknown_fields)]
+#[serde(try_from = "StoredOrder")]
 pub struct Order {
     pub _key: String,
     pub _rev: String,
     pub _id: String,
     pub date: NaiveDateTime,
     pub user_id: String,
+    pub lines: Vec<OrderLine>,
+    /// Sum of the line totals.
+    pub subtotal: Money,
+    pub status: OrderStatus,
+    /// Every status the order went through, oldest first.
+    pub status_history: Vec<StatusChange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<NaiveDateTime>,
+    /// Key of the user who deleted the order.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+}
+
+/// Marks a record as soft-deleted. Soft-deleted records are hidden from the API until an admin
+/// restores them, and purged once the configured retention has passed.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Deletion {
+    pub deleted_at: NaiveDateTime,
+    /// Key of the user who deleted the record.
+    pub deleted_by: String,
+}
+
+/// Fulfillment state of an order. Only the transitions listed in `OrderStatus::next` are allowed.
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+pub enum OrderStatus {
+    #[default]
+    PENDING,
+    PAID,
+    SHIPPED,
+    DELIVERED,
+    CANCELLED,
+    REFUNDED,
+}
+
+impl OrderStatus {
+    /// Statuses an order in this status can move to.
+    pub fn next(&self) -> &'static [OrderStatus] {
+        match self {
+            Self::PENDING => &[Self::PAID, Self::CANCELLED],
+            Self::PAID => &[Self::SHIPPED, Self::CANCELLED],
+            Self::SHIPPED => &[Self::DELIVERED],
+            Self::DELIVERED => &[Self::REFUNDED],
+            Self::CANCELLED | Self::REFUNDED => &[],
+        }
+    }
+
+    pub fn can_become(&self, status: OrderStatus) -> bool {
+        self.next().contains(&status)
+    }
+}
+
+impl fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// An entry of an order's status history.
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, PartialEq, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct StatusChange {
+    pub status: OrderStatus,
+    pub changed_at: NaiveDateTime,
+    /// Key of the user who made the change.
+    pub changed_by: String,
+}
+
+/// An item of an order, with its name and price snapshotted when the order was placed.
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, PartialEq, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct OrderLine {
     pub item_id: String,
     pub item_name: String,
     pub quantity: i64,
-    pub price: f64,
+    pub unit_price: Money,
+    /// Unit price times the quantity.
+    pub total: Money,
+}
+
+impl OrderLine {
+    pub fn for_item(item: &Item, quantity: i64) -> Result<Self, MoneyError> {
+        Ok(Self {
+            item_id: item._key.to_owned(),
+            item_name: item.name.to_owned(),
+            quantity,
+            unit_price: item.price,
+            total: item.price.checked_mul(quantity)?,
+        })
+    }
+}
+
+/// An order document as stored. Orders placed before multi-line orders hold a single item at the
+/// top level, with `price` being the order total, and orders placed before statuses have none.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StoredOrder {
+    _key: String,
+    _rev: String,
+    _id: String,
+    date: NaiveDateTime,
+    user_id: String,
+    lines: Option<Vec<OrderLine>>,
+    subtotal: Option<Money>,
+    item_id: Option<String>,
+    item_name: Option<String>,
+    quantity: Option<i64>,
+    price: Option<Money>,
+    status: Option<OrderStatus>,
+    status_history: Option<Vec<StatusChange>>,
+    deleted_at: Option<NaiveDateTime>,
+    deleted_by: Option<String>,
+}
+
+impl TryFrom<StoredOrder> for Order {
+    type Error = String;
+
+    fn try_from(stored: StoredOrder) -> Result<Self, Self::Error> {
+        let (lines, subtotal) = match (stored.lines, stored.subtotal) {
+            (Some(lines), Some(subtotal)) => (lines, subtotal),
+            _ => {
+                let (Some(item_id), Some(item_name), Some(quantity), Some(total)) = (
+                    stored.item_id,
+                    stored.item_name,
+                    stored.quantity,
+                    stored.price,
+                ) else {
+                    return Err(format!("order {} has neither lines nor an item", stored._key));
+                };
+
+                // Only the total was stored, and it was always the unit price times the quantity.
+                let unit_price = Money::from_minor_units(
+                    total.minor_units() / quantity.max(1),
+                    total.currency()
+                );
+                let line = OrderLine { item_id, item_name, quantity, unit_price, total };
+
+                (vec![line], total)
+            }
+        };
+
+        // Orders without a status were never fulfilled through the API, so they are still pending.
+        let status_history = stored.status_history.unwrap_or_else(|| {
+            vec![StatusChange {
+                status: OrderStatus::PENDING,
+                changed_at: stored.date,
+                changed_by: stored.user_id.to_owned(),
+            }]
+        });
+
+        Ok(Self {
+            _key: stored._key,
+            _rev: stored._rev,
+            _id: stored._id,
+            date: stored.date,
+            user_id: stored.user_id,
+            lines,
+            subtotal,
+            status: stored.status.unwrap_or_default(),
+            status_history,
+            deleted_at: stored.deleted_at,
+            deleted_by: stored.deleted_by,
+        })
+    }
 }
 
 #[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, ToSchema)]
@@ -52,6 +266,446 @@ pub struct Item {
     pub name: String,
     pub user_id: String,
     pub description: String,
-    pub price: f64,
+    pub price: Money,
     pub quantity: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<NaiveDateTime>,
+    /// Key of the user who deleted the item.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+}
+
+/// A user's shopping cart, keyed by the user's key. Prices are not stored: they are read from
+/// the items whenever the cart is shown or checked out.
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Cart {
+    pub _key: String,
+    pub _rev: String,
+    pub _id: String,
+    pub user_id: String,
+    pub lines: Vec<CartLine>,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, PartialEq, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CartLine {
+    pub item_id: String,
+    pub quantity: i64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RefreshToken {
+    pub _key: String,
+    pub _rev: String,
+    pub _id: String,
+    pub user_id: String,
+    pub family_id: String,
+    pub token_hash: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub revoked: bool,
+}
+
+/// A password reset requested by a user. Only the hash of the emailed token is stored, and the
+/// token stops working once `used_at` is set or `expires_at` has passed.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PasswordReset {
+    pub _key: String,
+    pub _rev: String,
+    pub _id: String,
+    pub user_id: String,
+    pub email: String,
+    pub token_hash: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+}
+
+/// A failed login, counted against an email address or a client IP (`subject`) until
+/// `expires_at`. That is stored in Unix seconds, as the TTL index ignores other date formats.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LoginAttempt {
+    pub _key: String,
+    pub _rev: String,
+    pub _id: String,
+    pub subject: String,
+    pub created_at: NaiveDateTime,
+    #[serde(with = "chrono::naive::serde::ts_seconds")]
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEvent {
+    /// Too many failed logins locked an email address or a client IP out.
+    LoginLockout,
+}
+
+/// A security-relevant event, kept for later investigation.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AuditEntry {
+    pub _key: String,
+    pub _rev: String,
+    pub _id: String,
+    pub event: AuditEvent,
+    /// What the event is about, e.g. `email:jane@doe.com` or `ip:10.0.0.1`.
+    pub subject: String,
+    /// Client IP of the request that caused the event, when known.
+    pub ip: Option<String>,
+    pub details: serde_json::Value,
+    pub created_at: NaiveDateTime,
+}
+
+/// ISO 4217 currencies prices can be listed in.
+#[derive(
+    Deserialize,
+    Serialize,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    JsonSchema,
+    ToSchema
+)]
+pub enum Currency {
+    #[default]
+    USD,
+    EUR,
+    GBP,
+    JPY,
+}
+
+impl Currency {
+    pub const ALL: [Currency; 4] = [Currency::USD, Currency::EUR, Currency::GBP, Currency::JPY];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Currency::USD => "USD",
+            Currency::EUR => "EUR",
+            Currency::GBP => "GBP",
+            Currency::JPY => "JPY",
+        }
+    }
+
+    /// Number of decimal places of the minor unit, e.g. 2 for cents.
+    pub fn exponent(&self) -> u32 {
+        match self {
+            Currency::JPY => 0,
+            _ => 2,
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|currency| currency.code() == code)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoneyError {
+    Invalid(String),
+    Overflow,
+    CurrencyMismatch(Currency, Currency),
+}
+
+impl fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoneyError::Invalid(amount) => write!(f, "invalid amount `{}`", amount),
+            MoneyError::Overflow => write!(f, "amount is too large"),
+            MoneyError::CurrencyMismatch(a, b) => {
+                write!(f, "cannot combine amounts in {} and {}", a.code(), b.code())
+            }
+        }
+    }
+}
+
+impl std::error::Error for MoneyError {}
+
+/// An amount of money held as a whole number of minor units (e.g. cents) of a currency, so
+/// prices and totals are exact.
+///
+/// Reads decimal strings (`"12.50"`, `"12.50 EUR"`), JSON numbers or `{ "amount", "currency" }`
+/// objects; amounts without a currency are in the default one. Digits past the currency's minor
+/// unit are rounded half to even. Writes `{ "amount": "12.50", "currency": "USD",
+/// "minor_units": 1250 }`, where `minor_units` lets queries compare prices numerically and is
+/// ignored on the way back in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Money {
+    minor_units: i64,
+    currency: Currency,
+}
+
+impl Money {
+    pub fn from_minor_units(minor_units: i64, currency: Currency) -> Self {
+        Self { minor_units, currency }
+    }
+
+    pub fn zero(currency: Currency) -> Self {
+        Self::from_minor_units(0, currency)
+    }
+
+    /// Parses a plain decimal amount such as `12.5` or `-0.99`.
+    pub fn parse(amount: &str, currency: Currency) -> Result<Self, MoneyError> {
+        let invalid = || MoneyError::Invalid(amount.to_string());
+
+        let (negative, digits) = match amount.trim().strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, amount.trim()),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+
+        let is_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+        if whole.is_empty() && fraction.is_empty() || !is_digits(whole) || !is_digits(fraction) {
+            return Err(invalid());
+        }
+
+        let exponent = currency.exponent() as usize;
+        let (kept, dropped) = fraction.split_at(fraction.len().min(exponent));
+        let digits = format!("{}{:0<width$}", whole, kept, width = exponent);
+
+        let significant = digits.trim_start_matches('0');
+        let mut minor_units = if significant.is_empty() {
+            0
+        } else {
+            significant.parse::<i64>().map_err(|_| MoneyError::Overflow)?
+        };
+
+        if round_up(minor_units, dropped) {
+            minor_units = minor_units.checked_add(1).ok_or(MoneyError::Overflow)?;
+        }
+
+        Ok(Self::from_minor_units(if negative { -minor_units } else { minor_units }, currency))
+    }
+
+    /// Converts a float through its shortest decimal representation, so `0.1` becomes exactly
+    /// ten cents rather than the nearest binary fraction.
+    pub fn from_f64(amount: f64, currency: Currency) -> Result<Self, MoneyError> {
+        if !amount.is_finite() {
+            return Err(MoneyError::Invalid(amount.to_string()));
+        }
+
+        Self::parse(&amount.to_string(), currency)
+    }
+
+    pub fn minor_units(&self) -> i64 {
+        self.minor_units
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.minor_units < 0
+    }
+
+    /// The amount as a decimal string with exactly as many decimals as the currency uses.
+    pub fn amount(&self) -> String {
+        let exponent = self.currency.exponent() as usize;
+        let digits = format!("{:0>width$}", self.minor_units.unsigned_abs(), width = exponent + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - exponent);
+        let sign = if self.is_negative() { "-" } else { "" };
+
+        if fraction.is_empty() {
+            format!("{}{}", sign, whole)
+        } else {
+            format!("{}{}.{}", sign, whole, fraction)
+        }
+    }
+
+    /// Lossy conversion for storage formats that only know floats.
+    pub fn to_f64(&self) -> f64 {
+        self.amount().parse().unwrap_or_default()
+    }
+
+    pub fn checked_add(&self, other: &Money) -> Result<Money, MoneyError> {
+        if self.currency != other.currency {
+            return Err(MoneyError::CurrencyMismatch(self.currency, other.currency));
+        }
+
+        self.minor_units
+            .checked_add(other.minor_units)
+            .map(|minor_units| Self::from_minor_units(minor_units, self.currency))
+            .ok_or(MoneyError::Overflow)
+    }
+
+    /// Price of `quantity` units at this unit price, e.g. an order line total.
+    pub fn checked_mul(&self, quantity: i64) -> Result<Money, MoneyError> {
+        self.minor_units
+            .checked_mul(quantity)
+            .map(|minor_units| Self::from_minor_units(minor_units, self.currency))
+            .ok_or(MoneyError::Overflow)
+    }
+
+    /// Adds up amounts that must all be in `currency`, e.g. the lines of an order.
+    pub fn sum<'a>(
+        amounts: impl IntoIterator<Item = &'a Money>,
+        currency: Currency
+    ) -> Result<Money, MoneyError> {
+        amounts
+            .into_iter()
+            .try_fold(Self::zero(currency), |total, amount| total.checked_add(amount))
+    }
+}
+
+/// Rounds half to even: a dropped remainder above one half rounds away from zero, exactly one
+/// half only when the kept digits are odd.
+fn round_up(kept: i64, dropped: &str) -> bool {
+    let mut digits = dropped.bytes();
+    match digits.next() {
+        Some(b'6'..=b'9') => true,
+        Some(b'5') => digits.any(|digit| digit != b'0') || kept % 2 == 1,
+        _ => false,
+    }
+}
+
+/// Amounts in different currencies are not comparable.
+impl PartialOrd for Money {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if self.currency != other.currency {
+            return None;
+        }
+
+        Some(self.minor_units.cmp(&other.minor_units))
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.amount(), self.currency.code())
+    }
+}
+
+/// Parses `"12.50"` in the default currency or `"12.50 EUR"`.
+impl FromStr for Money {
+    type Err = MoneyError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().split_once(' ') {
+            Some((amount, code)) => {
+                let currency = Currency::from_code(code.trim()).ok_or_else(|| {
+                    MoneyError::Invalid(value.to_string())
+                })?;
+                Self::parse(amount, currency)
+            }
+            None => Self::parse(value, Currency::default()),
+        }
+    }
+}
+
+/// Serialized shape of `Money`.
+#[derive(Serialize, JsonSchema, ToSchema)]
+struct MoneyRepr {
+    /// Decimal amount, e.g. `"12.50"`
+    amount: String,
+    currency: Currency,
+    /// Amount in the currency's minor unit, e.g. cents
+    minor_units: i64,
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MoneyRepr {
+            amount: self.amount(),
+            currency: self.currency,
+            minor_units: self.minor_units,
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(MoneyVisitor)
+    }
+}
+
+const MONEY_FIELDS: &[&str] = &["amount", "currency", "minor_units"];
+
+struct MoneyVisitor;
+
+impl<'de> Visitor<'de> for MoneyVisitor {
+    type Value = Money;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a decimal amount or an object with an amount and a currency")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Money, E> {
+        value.parse().map_err(E::custom)
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Money, E> {
+        self.visit_str(&value.to_string())
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Money, E> {
+        self.visit_str(&value.to_string())
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Money, E> {
+        Money::from_f64(value, Currency::default()).map_err(E::custom)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Money, A::Error> {
+        let mut amount: Option<AmountInput> = None;
+        let mut currency: Option<Currency> = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "amount" => {
+                    amount = Some(map.next_value()?);
+                }
+                "currency" => {
+                    currency = Some(map.next_value()?);
+                }
+                "minor_units" => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+                other => {
+                    return Err(de::Error::unknown_field(other, MONEY_FIELDS));
+                }
+            }
+        }
+
+        let amount = amount.ok_or_else(|| de::Error::missing_field("amount"))?;
+        let currency = currency.unwrap_or_default();
+        let money = match amount {
+            AmountInput::Text(amount) => Money::parse(&amount, currency),
+            AmountInput::Number(amount) => Money::from_f64(amount, currency),
+        };
+
+        money.map_err(de::Error::custom)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AmountInput {
+    Text(String),
+    Number(f64),
+}
+
+impl JsonSchema for Money {
+    fn schema_name() -> String {
+        "Money".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        MoneyRepr::json_schema(gen)
+    }
+}
+
+impl<'s> ToSchema<'s> for Money {
+    fn schema() -> (&'s str, RefOr<OpenApiSchema>) {
+        ("Money", MoneyRepr::schema().1)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,95 @@
+use utoipa::OpenApi;
+
+/// OpenAPI document served by the Swagger UI.
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        crate::requests::auth::handle_login,
+        crate::requests::two_factor::complete_login,
+        crate::requests::auth::handle_signup,
+        crate::requests::auth::forgot_password,
+        crate::requests::auth::reset_password,
+        crate::requests::auth::verify_email,
+        crate::requests::auth::resend_verification,
+        crate::requests::jwt::refresh,
+        crate::requests::jwt::logout,
+        crate::requests::jwt::jwks,
+        crate::requests::users::get_profile,
+        crate::requests::users::update_profile,
+        crate::requests::users::change_password,
+        crate::requests::users::delete_account,
+        crate::requests::two_factor::start_enrollment,
+        crate::requests::two_factor::confirm_enrollment,
+        crate::requests::two_factor::disable,
+        crate::requests::items::get_item,
+        crate::requests::items::get_items,
+        crate::requests::items::get_item_by_key,
+        crate::requests::items::search_items,
+        crate::requests::items::add_item,
+        crate::requests::items::edit_item,
+        crate::requests::items::delete_item,
+        crate::requests::orders::get_orders,
+        crate::requests::orders::add_order,
+        crate::requests::orders::delete_orders,
+        crate::requests::orders::update_order_status,
+        crate::requests::admin::restore_item,
+        crate::requests::admin::restore_order,
+        crate::requests::carts::get_cart,
+        crate::requests::carts::add_line,
+        crate::requests::carts::update_line,
+        crate::requests::carts::remove_line,
+        crate::requests::carts::checkout
+    ),
+    components(
+        schemas(
+            crate::models::UserProfile,
+            crate::models::Order,
+            crate::models::OrderLine,
+            crate::models::OrderStatus,
+            crate::models::StatusChange,
+            crate::models::Cart,
+            crate::models::CartLine,
+            crate::models::Item,
+            crate::models::Role,
+            crate::models::Money,
+            crate::models::Currency,
+            crate::api::ErrorResponse,
+            crate::api::FieldError,
+            crate::api::ItemsPage,
+            crate::requests::auth::LoginParams,
+            crate::requests::auth::AuthRes,
+            crate::requests::auth::LoginRes,
+            crate::requests::auth::SignupParams,
+            crate::requests::auth::SignupRole,
+            crate::requests::auth::ForgotPasswordParams,
+            crate::requests::auth::ResetPasswordParams,
+            crate::requests::auth::SignupRes,
+            crate::requests::auth::VerifyEmailParams,
+            crate::requests::jwt::RefreshReq,
+            crate::requests::users::UpdateProfileReq,
+            crate::requests::users::ChangePasswordReq,
+            crate::requests::users::DeleteAccountReq,
+            crate::requests::two_factor::StartEnrollmentReq,
+            crate::requests::two_factor::EnrollmentRes,
+            crate::requests::two_factor::ConfirmEnrollmentReq,
+            crate::requests::two_factor::RecoveryCodesRes,
+            crate::requests::two_factor::DisableReq,
+            crate::requests::two_factor::CompleteLoginReq,
+            crate::requests::items::GetItemReq,
+            crate::requests::items::AddItemReq,
+            crate::requests::items::UpdateItemReq,
+            crate::requests::items::DeleteItemReq,
+            crate::requests::items::SearchHit,
+            crate::requests::items::ItemHighlights,
+            crate::repositories::items::ItemUpdate,
+            crate::requests::orders::AddOrderReq,
+            crate::requests::orders::DeleteOrderReq,
+            crate::requests::orders::UpdateOrderStatusReq,
+            crate::requests::carts::AddCartLineReq,
+            crate::requests::carts::UpdateCartLineReq,
+            crate::requests::carts::CartRes
+        )
+    ),
+    tags((name = "RANS API", description = "REST API for RANS tech stack"))
+)]
+pub struct ApiDoc;
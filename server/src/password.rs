@@ -0,0 +1,296 @@
+use crate::api::FieldError;
+use crate::toml_env::{ HashAlgorithm, PasswordConfig, PasswordPolicy };
+use argon2::password_hash::{ PasswordHash, PasswordHasher as _, PasswordVerifier, SaltString };
+use argon2::{ Algorithm, Argon2, Params, Version };
+use bcrypt::HashParts;
+use rand::RngCore;
+use std::fmt;
+use std::sync::Arc;
+
+/// Passwords rejected by `reject_common`, compared case-insensitively.
+const COMMON_PASSWORDS: &[&str] = &[
+    "00000000",
+    "11111111",
+    "12345678",
+    "123456789",
+    "1234567890",
+    "1q2w3e4r",
+    "1qaz2wsx",
+    "aa123456",
+    "abc12345",
+    "abcd1234",
+    "admin123",
+    "asdfghjk",
+    "baseball",
+    "changeme",
+    "football",
+    "iloveyou",
+    "letmein1",
+    "p@ssw0rd",
+    "passw0rd",
+    "password",
+    "password1",
+    "password12",
+    "password123",
+    "princess",
+    "qwerty123",
+    "qwertyui",
+    "qwertyuiop",
+    "starwars",
+    "sunshine",
+    "superman",
+    "trustno1",
+    "welcome1",
+    "whatever",
+    "zaq12wsx",
+];
+
+#[derive(Debug)]
+pub struct PasswordError(pub String);
+
+impl fmt::Display for PasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for PasswordError {}
+
+/// One password hashing algorithm, with the parameters new hashes are made with.
+pub trait PasswordHasher: Send + Sync {
+    /// Whether `hash` was made by this algorithm, with any parameters.
+    fn recognizes(&self, hash: &str) -> bool;
+    /// Whether `hash` was made by this algorithm with the configured parameters.
+    fn is_current(&self, hash: &str) -> bool;
+    fn hash(&self, password: &str) -> Result<String, PasswordError>;
+    /// Checks `password` against a `hash` this algorithm recognizes, using the parameters stored
+    /// in the hash.
+    fn verify(&self, password: &str, hash: &str) -> bool;
+}
+
+pub struct BcryptHasher {
+    cost: u32,
+}
+
+impl BcryptHasher {
+    pub fn new(cost: u32) -> Result<Self, PasswordError> {
+        if !(4..=31).contains(&cost) {
+            return Err(PasswordError(format!("bcrypt_cost {} is not between 4 and 31", cost)));
+        }
+
+        Ok(Self { cost })
+    }
+}
+
+impl PasswordHasher for BcryptHasher {
+    fn recognizes(&self, hash: &str) -> bool {
+        hash.parse::<HashParts>().is_ok()
+    }
+
+    fn is_current(&self, hash: &str) -> bool {
+        hash.parse::<HashParts>().is_ok_and(|parts| parts.get_cost() == self.cost)
+    }
+
+    fn hash(&self, password: &str) -> Result<String, PasswordError> {
+        bcrypt::hash(password, self.cost).map_err(|err| PasswordError(err.to_string()))
+    }
+
+    fn verify(&self, password: &str, hash: &str) -> bool {
+        bcrypt::verify(password, hash).unwrap_or(false)
+    }
+}
+
+pub struct Argon2Hasher {
+    params: Params,
+}
+
+impl Argon2Hasher {
+    pub fn new(memory_kib: u32, iterations: u32, parallelism: u32) -> Result<Self, PasswordError> {
+        let params = Params::new(memory_kib, iterations, parallelism, None).map_err(|err| {
+            PasswordError(format!("invalid Argon2 parameters: {}", err))
+        })?;
+
+        Ok(Self { params })
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+}
+
+impl PasswordHasher for Argon2Hasher {
+    fn recognizes(&self, hash: &str) -> bool {
+        PasswordHash::new(hash).is_ok_and(|parsed| parsed.algorithm == Algorithm::Argon2id.ident())
+    }
+
+    fn is_current(&self, hash: &str) -> bool {
+        let Ok(parsed) = PasswordHash::new(hash) else {
+            return false;
+        };
+
+        parsed.algorithm == Algorithm::Argon2id.ident() &&
+            parsed.version == Some(Version::V0x13.into()) &&
+            Params::try_from(&parsed).is_ok_and(|params| {
+                params.m_cost() == self.params.m_cost() &&
+                    params.t_cost() == self.params.t_cost() &&
+                    params.p_cost() == self.params.p_cost()
+            })
+    }
+
+    fn hash(&self, password: &str) -> Result<String, PasswordError> {
+        let mut salt = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let salt = SaltString::encode_b64(&salt).map_err(|err| PasswordError(err.to_string()))?;
+
+        self.argon2()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|err| PasswordError(err.to_string()))
+    }
+
+    fn verify(&self, password: &str, hash: &str) -> bool {
+        PasswordHash::new(hash).is_ok_and(|parsed| {
+            self.argon2().verify_password(password.as_bytes(), &parsed).is_ok()
+        })
+    }
+}
+
+/// Hashes new passwords with the configured algorithm, and verifies hashes made by any supported
+/// one, so that changing the algorithm locks nobody out.
+pub struct Passwords {
+    current: Box<dyn PasswordHasher>,
+    /// Every supported algorithm other than the current one.
+    legacy: Vec<Box<dyn PasswordHasher>>,
+    policy: PasswordPolicy,
+    /// Hash of a random password made with the current algorithm, for logins of unknown users.
+    dummy_hash: String,
+}
+
+impl Passwords {
+    pub fn from_config(config: &PasswordConfig) -> Result<Self, PasswordError> {
+        let bcrypt: Box<dyn PasswordHasher> = Box::new(BcryptHasher::new(config.bcrypt_cost)?);
+        let argon2: Box<dyn PasswordHasher> = Box::new(
+            Argon2Hasher::new(
+                config.argon2_memory_kib,
+                config.argon2_iterations,
+                config.argon2_parallelism
+            )?
+        );
+
+        let (current, legacy) = match config.algorithm {
+            HashAlgorithm::Bcrypt => (bcrypt, argon2),
+            HashAlgorithm::Argon2id => (argon2, bcrypt),
+        };
+
+        let dummy_hash = current.hash(&format!("{:x}", rand::random::<u128>()))?;
+
+        Ok(Self { current, legacy: vec![legacy], policy: config.policy.clone(), dummy_hash })
+    }
+
+    pub fn hash(&self, password: &str) -> Result<String, PasswordError> {
+        self.current.hash(password)
+    }
+
+    /// Checks `password` against `hash`, whichever supported algorithm made it.
+    pub fn verify(&self, password: &str, hash: &str) -> bool {
+        std::iter::once(&self.current)
+            .chain(&self.legacy)
+            .find(|hasher| hasher.recognizes(hash))
+            .is_some_and(|hasher| hasher.verify(password, hash))
+    }
+
+    /// Takes as long as `verify` takes on a hash made with the current algorithm, so that logins of
+    /// unknown users cannot be told apart from wrong passwords by their response time.
+    pub fn verify_unknown_user(&self, password: &str) {
+        self.current.verify(password, &self.dummy_hash);
+    }
+
+    /// `hash` on the blocking thread pool, since slow hashes would stall the async runtime.
+    pub async fn hash_blocking(self: &Arc<Self>, password: &str) -> Result<String, PasswordError> {
+        let password = password.to_owned();
+        self.run_blocking(move |passwords| passwords.hash(&password)).await?
+    }
+
+    /// `verify` on the blocking thread pool.
+    pub async fn verify_blocking(self: &Arc<Self>, password: &str, hash: &str) -> bool {
+        let (password, hash) = (password.to_owned(), hash.to_owned());
+        self.run_blocking(move |passwords| passwords.verify(&password, &hash)).await
+            .unwrap_or(false)
+    }
+
+    /// `verify_unknown_user` on the blocking thread pool.
+    pub async fn verify_unknown_user_blocking(self: &Arc<Self>, password: &str) {
+        let password = password.to_owned();
+        let _ = self.run_blocking(move |passwords| passwords.verify_unknown_user(&password)).await;
+    }
+
+    async fn run_blocking<T: Send + 'static>(
+        self: &Arc<Self>,
+        task: impl FnOnce(&Passwords) -> T + Send + 'static
+    ) -> Result<T, PasswordError> {
+        let passwords = Arc::clone(self);
+        tokio::task
+            ::spawn_blocking(move || task(&passwords)).await
+            .map_err(|err| PasswordError(format!("Password task failed: {}", err)))
+    }
+
+    /// Whether `hash` should be replaced by one made with the current algorithm and parameters.
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        !self.current.is_current(hash)
+    }
+
+    /// Checks a new password against the policy. `personal` holds the names and email address of
+    /// the user, which the password must not contain. Violations are reported against `field`.
+    pub fn check_policy(
+        &self,
+        field: &str,
+        password: &str,
+        personal: &[&str]
+    ) -> Result<(), FieldError> {
+        let policy = &self.policy;
+        let violation = |code: &str, message: String| FieldError {
+            field: field.to_string(),
+            code: code.to_string(),
+            message,
+        };
+
+        if password.chars().count() < policy.min_length {
+            let msg = format!("must be at least {} characters long", policy.min_length);
+            return Err(violation("length", msg));
+        }
+
+        let classes = [
+            password.chars().any(char::is_lowercase),
+            password.chars().any(char::is_uppercase),
+            password.chars().any(|c| c.is_ascii_digit()),
+            password.chars().any(|c| !c.is_alphanumeric()),
+        ];
+        if classes.iter().filter(|class| **class).count() < policy.min_character_classes {
+            let msg = format!(
+                "must mix at least {} of lowercase letters, uppercase letters, digits and symbols",
+                policy.min_character_classes
+            );
+            return Err(violation("character_classes", msg));
+        }
+
+        let password = password.to_lowercase();
+        if policy.reject_common && COMMON_PASSWORDS.contains(&password.as_str()) {
+            return Err(violation("common", "is too common".to_string()));
+        }
+
+        // Only the local part of email addresses: domains such as `gmail` are shared by too many
+        // users to say anything about this one.
+        let mut words = personal
+            .iter()
+            .map(|value| value.split('@').next().unwrap_or_default())
+            .flat_map(|value| value.split(|c: char| !c.is_alphanumeric()))
+            .filter(|word| word.chars().count() >= 3)
+            .map(str::to_lowercase);
+        if policy.reject_personal && words.any(|word| password.contains(&word)) {
+            let msg = "cannot contain your name or email address".to_string();
+            return Err(violation("personal", msg));
+        }
+
+        Ok(())
+    }
+}
\ No newline at end of file
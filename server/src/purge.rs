@@ -0,0 +1,57 @@
+use crate::db::DatabaseError;
+use crate::state::AppState;
+use chrono::{ Local, NaiveDateTime, Utc };
+use log::{ error, info };
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Number of records permanently removed by a purge run.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Purged {
+    pub items: u64,
+    pub orders: u64,
+    pub password_resets: u64,
+}
+
+/// Permanently removes the items and orders that were soft-deleted longer than the configured
+/// retention before `now`, and the password resets that have expired.
+pub async fn purge_deleted(state: &AppState, now: NaiveDateTime) -> Result<Purged, DatabaseError> {
+    let before = state.config.retention.purge_before(now);
+
+    Ok(Purged {
+        items: state.items.purge(before).await?,
+        orders: state.orders.purge(before).await?,
+        // Reset expiries are in UTC, unlike deletion times.
+        password_resets: state.resets.purge_expired(Utc::now().naive_utc()).await?,
+    })
+}
+
+/// Runs `purge_deleted` in the background every `purge_interval_minutes`, starting right away.
+/// Returns `None` when the interval is zero.
+pub fn spawn(state: AppState) -> Option<JoinHandle<()>> {
+    let minutes = state.config.retention.purge_interval_minutes;
+    if minutes == 0 {
+        return None;
+    }
+
+    let handle = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(minutes * 60));
+        loop {
+            interval.tick().await;
+            match purge_deleted(&state, Local::now().naive_local()).await {
+                Ok(purged) if purged != Purged::default() => {
+                    info!(
+                        "Purged {} deleted items, {} deleted orders and {} expired password resets",
+                        purged.items,
+                        purged.orders,
+                        purged.password_resets
+                    );
+                }
+                Ok(_) => (),
+                Err(err) => error!("Error purging deleted records: {}", err),
+            }
+        }
+    });
+
+    Some(handle)
+}
\ No newline at end of file
@@ -0,0 +1,143 @@
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{ Mutex, MutexGuard };
+
+/// Buckets the memory store holds before it drops the ones that have refilled.
+const MEMORY_STORE_SOFT_LIMIT: usize = 10_000;
+
+/// `requests` a client can make per `per_seconds`. The bucket starts full, so the whole budget can
+/// be spent in one burst, and refills steadily over the period.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub struct Budget {
+    /// `0` lifts the limit.
+    pub requests: u32,
+    pub per_seconds: u64,
+}
+
+impl Budget {
+    fn tokens_per_second(&self) -> f64 {
+        self.requests as f64 / self.per_seconds.max(1) as f64
+    }
+
+    fn seconds_to(&self, tokens: f64) -> u64 {
+        (tokens.max(0.0) / self.tokens_per_second()).ceil() as u64
+    }
+}
+
+/// Outcome of taking a token, reported to clients in the `X-RateLimit-*` headers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the bucket is full again.
+    pub reset: u64,
+    /// Seconds until the next token, when the request was refused.
+    pub retry_after: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct RateLimitError(pub String);
+
+impl fmt::Display for RateLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error reading rate limit: {}", self.0)
+    }
+}
+
+impl std::error::Error for RateLimitError {}
+
+/// Where token buckets are kept. Servers behind a load balancer need a shared store so that a
+/// client's budget is not multiplied by the number of servers.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Takes one token from the bucket `key`, which holds `budget`, at `now`.
+    async fn take(
+        &self,
+        key: &str,
+        budget: &Budget,
+        now: NaiveDateTime
+    ) -> Result<RateLimit, RateLimitError>;
+    /// Puts back a token taken from the bucket `key`, for a request another bucket refused.
+    async fn refund(&self, key: &str, budget: &Budget) -> Result<(), RateLimitError>;
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated_at: NaiveDateTime,
+}
+
+impl Bucket {
+    fn refill(&mut self, budget: &Budget, now: NaiveDateTime) {
+        let elapsed = (now - self.updated_at).num_milliseconds().max(0) as f64 / 1000.0;
+        self.tokens = (self.tokens + elapsed * budget.tokens_per_second()).min(
+            budget.requests as f64
+        );
+        self.updated_at = now;
+    }
+}
+
+/// Keeps the buckets in the memory of this process, which only suits a single server.
+#[derive(Default)]
+pub struct MemoryRateLimitStore {
+    buckets: Mutex<HashMap<String, (Budget, Bucket)>>,
+}
+
+impl MemoryRateLimitStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, (Budget, Bucket)>> {
+        self.buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn take(
+        &self,
+        key: &str,
+        budget: &Budget,
+        now: NaiveDateTime
+    ) -> Result<RateLimit, RateLimitError> {
+        let mut buckets = self.lock();
+        if buckets.len() >= MEMORY_STORE_SOFT_LIMIT {
+            // A full bucket is no different from a missing one.
+            buckets.retain(|_, (budget, bucket)| {
+                bucket.refill(budget, now);
+                bucket.tokens < budget.requests as f64
+            });
+        }
+
+        let full = Bucket { tokens: budget.requests as f64, updated_at: now };
+        let (stored, bucket) = buckets.entry(key.to_string()).or_insert((*budget, full));
+        *stored = *budget;
+        bucket.refill(budget, now);
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        Ok(RateLimit {
+            allowed,
+            limit: budget.requests,
+            remaining: bucket.tokens.floor() as u32,
+            reset: budget.seconds_to(budget.requests as f64 - bucket.tokens),
+            retry_after: (!allowed).then(|| budget.seconds_to(1.0 - bucket.tokens).max(1)),
+        })
+    }
+
+    async fn refund(&self, key: &str, budget: &Budget) -> Result<(), RateLimitError> {
+        if let Some((_, bucket)) = self.lock().get_mut(key) {
+            bucket.tokens = (bucket.tokens + 1.0).min(budget.requests as f64);
+        }
+
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,57 @@
+use crate::db::{ ArangoProvider, Database, DatabaseError };
+use crate::models::{ AuditEntry, AuditEvent };
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use serde_json::{ json, Value };
+use std::collections::HashMap;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct NewAuditEntry {
+    pub event: AuditEvent,
+    pub subject: String,
+    pub ip: Option<String>,
+    pub details: Value,
+    pub created_at: NaiveDateTime,
+}
+
+#[async_trait]
+pub trait AuditRepository: Send + Sync {
+    async fn insert(&self, entry: NewAuditEntry) -> Result<AuditEntry, DatabaseError>;
+    /// Every entry about `subject`, e.g. `email:jane@doe.com`.
+    async fn find_by_subject(&self, subject: &str) -> Result<Vec<AuditEntry>, DatabaseError>;
+}
+
+#[async_trait]
+impl AuditRepository for Database {
+    async fn insert(&self, entry: NewAuditEntry) -> Result<AuditEntry, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("entry", json!(&entry));
+
+        let mut entries: Vec<AuditEntry> = self
+            .get_db()
+            .aql_bind_vars("INSERT @entry INTO AuditLog RETURN NEW", bind_vars).await?;
+
+        entries
+            .pop()
+            .ok_or_else(|| DatabaseError::QueryError("Error storing audit entry".to_string()))
+    }
+
+    async fn find_by_subject(&self, subject: &str) -> Result<Vec<AuditEntry>, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("subject", subject.into());
+
+        let entries: Vec<AuditEntry> = self
+            .get_db()
+            .aql_bind_vars(
+                "
+    FOR entry IN AuditLog
+        FILTER entry.subject == @subject
+        RETURN entry
+    ",
+                bind_vars
+            ).await?;
+
+        Ok(entries)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,75 @@
+use crate::db::{ ArangoProvider, Database, DatabaseError, DOCUMENT_NOT_FOUND };
+use crate::models::{ Cart, CartLine };
+use arangors::ClientError;
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use serde_json::{ json, Value };
+use std::collections::HashMap;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct NewCart {
+    pub _key: String,
+    pub user_id: String,
+    pub lines: Vec<CartLine>,
+    pub updated_at: NaiveDateTime,
+}
+
+impl NewCart {
+    pub fn new(user_id: &str, lines: Vec<CartLine>, updated_at: NaiveDateTime) -> Self {
+        Self {
+            _key: user_id.to_string(),
+            user_id: user_id.to_string(),
+            lines,
+            updated_at,
+        }
+    }
+}
+
+#[async_trait]
+pub trait CartRepository: Send + Sync {
+    async fn find(&self, user_id: &str) -> Result<Option<Cart>, DatabaseError>;
+    /// Replaces the user's cart, creating it on first use.
+    async fn save(&self, cart: NewCart) -> Result<Cart, DatabaseError>;
+    async fn remove(&self, user_id: &str) -> Result<(), DatabaseError>;
+}
+
+#[async_trait]
+impl CartRepository for Database {
+    async fn find(&self, user_id: &str) -> Result<Option<Cart>, DatabaseError> {
+        let collection = self.get_db().collection("Cart").await?;
+        match collection.document::<Cart>(user_id).await {
+            Ok(cart) => Ok(Some(cart.document)),
+            Err(ClientError::Arango(err)) if err.error_num() == DOCUMENT_NOT_FOUND => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn save(&self, cart: NewCart) -> Result<Cart, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("cart", json!(&cart));
+
+        let mut carts: Vec<Cart> = self
+            .get_db()
+            .aql_bind_vars(
+                "UPSERT { _key: @cart._key } INSERT @cart REPLACE @cart IN Cart RETURN NEW",
+                bind_vars
+            ).await?;
+
+        carts.pop().ok_or_else(|| DatabaseError::QueryError("Error saving cart".to_string()))
+    }
+
+    async fn remove(&self, user_id: &str) -> Result<(), DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("key", user_id.into());
+
+        let _: Vec<Value> = self
+            .get_db()
+            .aql_bind_vars(
+                "FOR cart IN Cart FILTER cart._key == @key REMOVE cart IN Cart",
+                bind_vars
+            ).await?;
+
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,409 @@
+use crate::db::{ ArangoProvider, Database, DatabaseError, DOCUMENT_NOT_FOUND, PRECONDITION_FAILED };
+use crate::models::{ Deletion, Item, Money };
+use crate::search::{ ITEM_SEARCH_VIEW, NGRAM_ANALYZER, NGRAM_THRESHOLD, TEXT_ANALYZER };
+use arangors::document::options::UpdateOptions;
+use arangors::ClientError;
+use async_trait::async_trait;
+use base64::{ engine::general_purpose::URL_SAFE_NO_PAD, Engine };
+use chrono::NaiveDateTime;
+use serde::{ Deserialize, Serialize };
+use serde_json::{ json, Value };
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct NewItem {
+    pub name: String,
+    pub user_id: String,
+    pub description: String,
+    pub price: Money,
+    pub quantity: i64,
+}
+
+#[derive(Debug, Serialize, Clone, Default, ToSchema)]
+pub struct ItemUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<Money>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<i64>,
+    /// Revision the update is based on. When set, the update fails if the item changed since.
+    #[serde(rename = "_rev", skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemSort {
+    #[default]
+    Name,
+    Price,
+    Quantity,
+}
+
+impl ItemSort {
+    /// Attribute path of the sort field. Prices sort by their minor units, so items listed in
+    /// different currencies are not ranked against each other meaningfully.
+    pub fn field(&self) -> &'static [&'static str] {
+        match self {
+            ItemSort::Name => &["name"],
+            ItemSort::Price => &["price", "minor_units"],
+            ItemSort::Quantity => &["quantity"],
+        }
+    }
+
+    pub fn value(&self, item: &Item) -> Value {
+        match self {
+            ItemSort::Name => json!(item.name),
+            ItemSort::Price => json!(item.price.minor_units()),
+            ItemSort::Quantity => json!(item.quantity),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortDirection {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            SortDirection::Asc => "ASC",
+            SortDirection::Desc => "DESC",
+        }
+    }
+
+    /// Comparison operator selecting the documents that come after a cursor in this direction.
+    pub fn after_operator(&self) -> &'static str {
+        match self {
+            SortDirection::Asc => ">",
+            SortDirection::Desc => "<",
+        }
+    }
+}
+
+/// Keyset position of the last item on a page: its sort value, with the key as tie-breaker.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ItemCursor {
+    pub value: Value,
+    pub key: String,
+}
+
+impl ItemCursor {
+    pub fn after(item: &Item, sort: ItemSort) -> Self {
+        Self {
+            value: sort.value(item),
+            key: item._key.to_owned(),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(json!(self).to_string())
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let bytes = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ItemQuery {
+    pub min_price: Option<Money>,
+    pub max_price: Option<Money>,
+    pub user_id: Option<String>,
+    pub in_stock: bool,
+    /// Also list soft-deleted items.
+    pub include_deleted: bool,
+    pub sort: ItemSort,
+    pub direction: SortDirection,
+    pub after: Option<ItemCursor>,
+    pub offset: u64,
+    pub limit: u64,
+}
+
+#[derive(Debug)]
+pub struct ItemPage {
+    pub items: Vec<Item>,
+    /// Number of items matching the filters, regardless of pagination.
+    pub total: u64,
+    pub next: Option<ItemCursor>,
+}
+
+impl ItemPage {
+    /// Builds a page from a window fetched with `query.limit + 1` items, where the extra item only
+    /// signals that another page exists.
+    pub(crate) fn from_window(mut items: Vec<Item>, total: u64, query: &ItemQuery) -> Self {
+        let next = if items.len() as u64 > query.limit {
+            items.truncate(query.limit as usize);
+            items.last().map(|item| ItemCursor::after(item, query.sort))
+        } else {
+            None
+        };
+
+        Self { items, total, next }
+    }
+}
+
+/// Search result with its relevance score; higher scores rank first.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ScoredItem {
+    pub item: Item,
+    pub score: f64,
+}
+
+#[derive(Deserialize)]
+struct ItemWindow {
+    items: Vec<Item>,
+    total: u64,
+}
+
+#[async_trait]
+pub trait ItemRepository: Send + Sync {
+    async fn search(&self, query: &str, limit: u64) -> Result<Vec<ScoredItem>, DatabaseError>;
+    async fn find_page(&self, query: &ItemQuery) -> Result<ItemPage, DatabaseError>;
+    async fn find_by_key(&self, key: &str) -> Result<Item, DatabaseError>;
+    async fn insert(&self, item: NewItem) -> Result<Item, DatabaseError>;
+    /// Applies `update`, failing with `PreconditionFailed` if `update.rev` is set and no longer
+    /// matches the stored item.
+    async fn update(&self, key: &str, update: ItemUpdate) -> Result<Item, DatabaseError>;
+    /// Soft-deletes the item. Deleted items are left out of searches and listings, and
+    /// `find_by_key` reports them as not found.
+    async fn remove(&self, key: &str, deletion: Deletion) -> Result<Item, DatabaseError>;
+    /// Soft-deletes every item listed by a vendor and returns how many.
+    async fn remove_by_user(&self, user_id: &str, deletion: Deletion) -> Result<u64, DatabaseError>;
+    /// Undoes `remove`. Fails with a conflict if another item took the name in the meantime.
+    async fn restore(&self, key: &str) -> Result<Item, DatabaseError>;
+    /// Permanently removes the items deleted before `deleted_before` and returns how many.
+    async fn purge(&self, deleted_before: NaiveDateTime) -> Result<u64, DatabaseError>;
+}
+
+#[async_trait]
+impl ItemRepository for Database {
+    async fn search(&self, query: &str, limit: u64) -> Result<Vec<ScoredItem>, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("@view", json!(ITEM_SEARCH_VIEW));
+        bind_vars.insert("query", json!(query));
+        bind_vars.insert("text", json!(TEXT_ANALYZER));
+        bind_vars.insert("ngram", json!(NGRAM_ANALYZER));
+        bind_vars.insert("threshold", json!(NGRAM_THRESHOLD));
+        bind_vars.insert("limit", json!(limit));
+
+        // Name matches are boosted over description matches; n-gram matching catches typos
+        // that the stemmed tokens miss.
+        let hits: Vec<ScoredItem> = self
+            .get_db()
+            .aql_bind_vars(
+                "FOR item IN @@view
+                    SEARCH ANALYZER(
+                        BOOST(item.name IN TOKENS(@query, @text), 2) OR
+                            item.description IN TOKENS(@query, @text),
+                        @text
+                    ) OR
+                    NGRAM_MATCH(item.name, @query, @threshold, @ngram) OR
+                    NGRAM_MATCH(item.description, @query, @threshold, @ngram)
+                    FILTER item.deleted_at == null
+                    LET score = BM25(item)
+                    SORT score DESC, item.name ASC
+                    LIMIT @limit
+                    RETURN { item, score }",
+                bind_vars
+            ).await?;
+
+        Ok(hits)
+    }
+
+    async fn find_page(&self, query: &ItemQuery) -> Result<ItemPage, DatabaseError> {
+        let (after_value, after_key) = match &query.after {
+            Some(cursor) => (cursor.value.clone(), Value::String(cursor.key.to_owned())),
+            None => (Value::Null, Value::Null),
+        };
+
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("min_price", json!(query.min_price));
+        bind_vars.insert("max_price", json!(query.max_price));
+        bind_vars.insert("user_id", json!(query.user_id));
+        bind_vars.insert("in_stock", json!(query.in_stock));
+        bind_vars.insert("include_deleted", json!(query.include_deleted));
+        bind_vars.insert("sort", json!(query.sort.field()));
+        bind_vars.insert("after_value", after_value);
+        bind_vars.insert("after_key", after_key);
+        bind_vars.insert("offset", json!(query.offset));
+        bind_vars.insert("limit", json!(query.limit + 1));
+
+        let filters =
+            "FILTER @min_price == null OR (
+                item.price.currency == @min_price.currency AND
+                    item.price.minor_units >= @min_price.minor_units
+            )
+            FILTER @max_price == null OR (
+                item.price.currency == @max_price.currency AND
+                    item.price.minor_units <= @max_price.minor_units
+            )
+            FILTER @user_id == null OR item.user_id == @user_id
+            FILTER !@in_stock OR item.quantity > 0
+            FILTER @include_deleted OR item.deleted_at == null";
+
+        // The total ignores the cursor, so it is counted apart rather than with `fullCount`, and
+        // without materializing the matching items. Sort direction and comparison operators
+        // cannot be bound, so they come from the enums.
+        let aql = format!(
+            "RETURN {{
+                total: FIRST(
+                    FOR item IN Item
+                        {filters}
+                        COLLECT WITH COUNT INTO total
+                        RETURN total
+                ),
+                items: (
+                    FOR item IN Item
+                        {filters}
+                        FILTER @after_key == null OR item.@sort {op} @after_value OR
+                            (item.@sort == @after_value AND item._key {op} @after_key)
+                        SORT item.@sort {dir}, item._key {dir}
+                        LIMIT @offset, @limit
+                        RETURN item
+                )
+            }}",
+            filters = filters,
+            op = query.direction.after_operator(),
+            dir = query.direction.keyword()
+        );
+
+        let mut windows: Vec<ItemWindow> = self.get_db().aql_bind_vars(&aql, bind_vars).await?;
+        let window = windows
+            .pop()
+            .ok_or_else(|| DatabaseError::QueryError("Error listing items".to_string()))?;
+
+        Ok(ItemPage::from_window(window.items, window.total, query))
+    }
+
+    async fn find_by_key(&self, key: &str) -> Result<Item, DatabaseError> {
+        let collection = self.get_db().collection("Item").await?;
+        match collection.document::<Item>(key).await {
+            Ok(item) if item.document.deleted_at.is_none() => Ok(item.document),
+            Ok(_) => Err(DatabaseError::NotFound(format!("Item {} not found", key))),
+            Err(ClientError::Arango(err)) if err.error_num() == DOCUMENT_NOT_FOUND => {
+                Err(DatabaseError::NotFound(format!("Item {} not found", key)))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn insert(&self, item: NewItem) -> Result<Item, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("item", json!(&item));
+
+        let mut items: Vec<Item> = self
+            .get_db()
+            .aql_bind_vars("INSERT @item INTO Item RETURN NEW", bind_vars).await?;
+
+        items.pop().ok_or_else(|| DatabaseError::QueryError("Error creating item".to_string()))
+    }
+
+    async fn update(&self, key: &str, update: ItemUpdate) -> Result<Item, DatabaseError> {
+        let collection = self.get_db().collection("Item").await?;
+        let options = UpdateOptions::builder()
+            .return_new(true)
+            .ignore_revs(update.rev.is_none())
+            .build();
+        let response = match collection.update_document(key, json!(&update), options).await {
+            Ok(response) => response,
+            Err(ClientError::Arango(err)) if err.code() == PRECONDITION_FAILED => {
+                let msg = format!("Item {} changed since it was read", key);
+                return Err(DatabaseError::PreconditionFailed(msg));
+            }
+            Err(ClientError::Arango(err)) if err.error_num() == DOCUMENT_NOT_FOUND => {
+                return Err(DatabaseError::NotFound(format!("Item {} not found", key)));
+            }
+            Err(err) => {
+                return Err(err.into());
+            }
+        };
+
+        match response.new_doc() {
+            Some(doc) =>
+                serde_json
+                    ::from_value(doc.clone())
+                    .map_err(|err| DatabaseError::ClientError(err.into())),
+            None => Err(DatabaseError::NotFound(format!("Item {} not found", key))),
+        }
+    }
+
+    async fn remove(&self, key: &str, deletion: Deletion) -> Result<Item, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("key", key.into());
+        bind_vars.insert("deleted_at", json!(deletion.deleted_at));
+        bind_vars.insert("deleted_by", deletion.deleted_by.into());
+
+        let mut items: Vec<Item> = self
+            .get_db()
+            .aql_bind_vars(
+                "FOR item IN Item FILTER item._key == @key AND item.deleted_at == null
+                    UPDATE item WITH { deleted_at: @deleted_at, deleted_by: @deleted_by } IN Item
+                    RETURN NEW",
+                bind_vars
+            ).await?;
+
+        items.pop().ok_or_else(|| DatabaseError::NotFound(format!("Item {} not found", key)))
+    }
+
+    async fn remove_by_user(&self, user_id: &str, deletion: Deletion) -> Result<u64, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("user_id", user_id.into());
+        bind_vars.insert("deleted_at", json!(deletion.deleted_at));
+        bind_vars.insert("deleted_by", deletion.deleted_by.into());
+
+        let removed: Vec<Value> = self
+            .get_db()
+            .aql_bind_vars(
+                "FOR item IN Item FILTER item.user_id == @user_id AND item.deleted_at == null
+                    UPDATE item WITH { deleted_at: @deleted_at, deleted_by: @deleted_by } IN Item
+                    RETURN NEW._key",
+                bind_vars
+            ).await?;
+
+        Ok(removed.len() as u64)
+    }
+
+    async fn restore(&self, key: &str) -> Result<Item, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("key", key.into());
+
+        let mut items: Vec<Item> = self
+            .get_db()
+            .aql_bind_vars(
+                "FOR item IN Item FILTER item._key == @key AND item.deleted_at != null
+                    UPDATE item WITH { deleted_at: null, deleted_by: null } IN Item
+                    RETURN NEW",
+                bind_vars
+            ).await?;
+
+        items
+            .pop()
+            .ok_or_else(|| DatabaseError::NotFound(format!("No deleted item {} found", key)))
+    }
+
+    async fn purge(&self, deleted_before: NaiveDateTime) -> Result<u64, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("before", json!(deleted_before));
+
+        let purged: Vec<Value> = self
+            .get_db()
+            .aql_bind_vars(
+                "FOR item IN Item FILTER item.deleted_at != null AND item.deleted_at < @before
+                    REMOVE item IN Item
+                    RETURN OLD._key",
+                bind_vars
+            ).await?;
+
+        Ok(purged.len() as u64)
+    }
+}
\ No newline at end of file
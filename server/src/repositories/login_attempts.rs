@@ -0,0 +1,86 @@
+use crate::db::{ ArangoProvider, Database, DatabaseError };
+use crate::models::LoginAttempt;
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use serde_json::{ json, Value };
+use std::collections::HashMap;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct NewLoginAttempt {
+    pub subject: String,
+    pub created_at: NaiveDateTime,
+    #[serde(with = "chrono::naive::serde::ts_seconds")]
+    pub expires_at: NaiveDateTime,
+}
+
+#[async_trait]
+pub trait LoginAttemptRepository: Send + Sync {
+    async fn insert(&self, attempt: NewLoginAttempt) -> Result<LoginAttempt, DatabaseError>;
+    /// Failed attempts counted against `subject` that have not expired at `now`. Arango removes
+    /// expired attempts in the background, so they are filtered out here as well.
+    async fn find_active(
+        &self,
+        subject: &str,
+        now: NaiveDateTime
+    ) -> Result<Vec<LoginAttempt>, DatabaseError>;
+    /// Forgets every failed attempt counted against `subject`.
+    async fn clear(&self, subject: &str) -> Result<(), DatabaseError>;
+}
+
+#[async_trait]
+impl LoginAttemptRepository for Database {
+    async fn insert(&self, attempt: NewLoginAttempt) -> Result<LoginAttempt, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("attempt", json!(&attempt));
+
+        let mut attempts: Vec<LoginAttempt> = self
+            .get_db()
+            .aql_bind_vars("INSERT @attempt INTO LoginAttempt RETURN NEW", bind_vars).await?;
+
+        attempts
+            .pop()
+            .ok_or_else(|| DatabaseError::QueryError("Error storing login attempt".to_string()))
+    }
+
+    async fn find_active(
+        &self,
+        subject: &str,
+        now: NaiveDateTime
+    ) -> Result<Vec<LoginAttempt>, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("subject", subject.into());
+        bind_vars.insert("now", now.timestamp().into());
+
+        let attempts: Vec<LoginAttempt> = self
+            .get_db()
+            .aql_bind_vars(
+                "
+    FOR attempt IN LoginAttempt
+        FILTER attempt.subject == @subject AND @now < attempt.expires_at
+        RETURN attempt
+    ",
+                bind_vars
+            ).await?;
+
+        Ok(attempts)
+    }
+
+    async fn clear(&self, subject: &str) -> Result<(), DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("subject", subject.into());
+
+        let _: Vec<Value> = self
+            .get_db()
+            .aql_bind_vars(
+                "
+    FOR attempt IN LoginAttempt
+        FILTER attempt.subject == @subject
+        REMOVE attempt IN LoginAttempt
+    ",
+                bind_vars
+            ).await?;
+
+        Ok(())
+    }
+}
\ No newline at end of file
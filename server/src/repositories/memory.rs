@@ -0,0 +1,847 @@
+use crate::constants::DELETED_USER_ID;
+use crate::db::DatabaseError;
+use crate::models::{
+    AuditEntry,
+    Cart,
+    Deletion,
+    Item,
+    LoginAttempt,
+    Order,
+    OrderStatus,
+    PasswordReset,
+    RefreshToken,
+    StatusChange,
+    User,
+};
+use crate::repositories::audit::{ AuditRepository, NewAuditEntry };
+use crate::repositories::carts::{ CartRepository, NewCart };
+use crate::repositories::items::{
+    ItemPage,
+    ItemQuery,
+    ItemRepository,
+    ItemUpdate,
+    NewItem,
+    ScoredItem,
+    SortDirection,
+};
+use crate::repositories::login_attempts::{ LoginAttemptRepository, NewLoginAttempt };
+use crate::repositories::orders::{
+    check_stock,
+    NewOrder,
+    OrderDocument,
+    OrderRepository,
+    StatusPatch,
+};
+use crate::repositories::password_resets::{ NewPasswordReset, PasswordResetRepository };
+use crate::repositories::tokens::{ NewRefreshToken, TokenRepository };
+use crate::repositories::users::{ NewUser, UserRepository, UserUpdate };
+use crate::search::{ terms, NGRAM_THRESHOLD };
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use serde::{ de::DeserializeOwned, Serialize };
+use serde_json::{ json, Value };
+use std::cmp::Ordering;
+use std::collections::{ BTreeMap, HashSet };
+use std::sync::{ Mutex, MutexGuard };
+
+/// In-memory stand-in for the Arango collections, used to exercise handlers without a database.
+#[derive(Default)]
+pub struct MemoryDatabase {
+    collections: Mutex<Collections>,
+}
+
+#[derive(Default)]
+struct Collections {
+    users: BTreeMap<String, User>,
+    items: BTreeMap<String, Item>,
+    orders: BTreeMap<String, Order>,
+    carts: BTreeMap<String, Cart>,
+    tokens: BTreeMap<String, RefreshToken>,
+    resets: BTreeMap<String, PasswordReset>,
+    login_attempts: BTreeMap<String, LoginAttempt>,
+    audit: BTreeMap<String, AuditEntry>,
+    last_key: u64,
+    last_rev: u64,
+}
+
+impl Collections {
+    fn next_key(&mut self) -> String {
+        self.last_key += 1;
+        self.last_key.to_string()
+    }
+
+    fn next_rev(&mut self) -> String {
+        self.last_rev += 1;
+        format!("_rev{}", self.last_rev)
+    }
+}
+
+impl MemoryDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Collections> {
+        self.collections.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn to_document<T, B>(collection: &str, key: &str, rev: &str, body: &B) -> Result<T, DatabaseError>
+    where T: DeserializeOwned, B: Serialize
+{
+    let mut doc = json!(body);
+    if let Value::Object(fields) = &mut doc {
+        fields.insert("_key".to_string(), key.into());
+        fields.insert("_id".to_string(), format!("{}/{}", collection, key).into());
+        fields.insert("_rev".to_string(), rev.into());
+    }
+
+    serde_json::from_value(doc).map_err(|err| DatabaseError::QueryError(err.to_string()))
+}
+
+/// Orders JSON scalars the way the AQL `SORT` used by the Arango repositories does for numbers and
+/// strings.
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => {
+            a.as_f64().partial_cmp(&b.as_f64()).unwrap_or(Ordering::Equal)
+        }
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    }
+}
+
+fn trigrams(word: &str) -> HashSet<Vec<char>> {
+    let chars: Vec<char> = word.chars().collect();
+    chars
+        .windows(3)
+        .map(|window| window.to_vec())
+        .collect()
+}
+
+/// Rough stand-in for the `ItemSearch` view: exact term matches weigh twice as much in the name as
+/// in the description, and misspelled terms still score through trigram overlap.
+fn search_score(item: &Item, terms: &[String]) -> f64 {
+    let name = item.name.to_lowercase();
+    let description = item.description.to_lowercase();
+    let words: Vec<String> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .chain(description.split(|c: char| !c.is_alphanumeric()))
+        .map(str::to_string)
+        .collect();
+
+    terms
+        .iter()
+        .map(|term| {
+            let exact = (if name.contains(term.as_str()) { 2.0 } else { 0.0 }) +
+                (if description.contains(term.as_str()) { 1.0 } else { 0.0 });
+            if exact > 0.0 {
+                return exact;
+            }
+
+            let expected = trigrams(term);
+            if expected.is_empty() {
+                return 0.0;
+            }
+            words
+                .iter()
+                .map(|word| {
+                    (expected.intersection(&trigrams(word)).count() as f64) /
+                        (expected.len() as f64)
+                })
+                .filter(|similarity| *similarity >= NGRAM_THRESHOLD)
+                .fold(0.0, f64::max)
+        })
+        .sum()
+}
+
+#[async_trait]
+impl ItemRepository for MemoryDatabase {
+    async fn search(&self, query: &str, limit: u64) -> Result<Vec<ScoredItem>, DatabaseError> {
+        let terms = terms(query);
+        let mut hits: Vec<ScoredItem> = self
+            .lock()
+            .items.values()
+            .filter(|item| item.deleted_at.is_none())
+            .map(|item| ScoredItem { item: item.clone(), score: search_score(item, &terms) })
+            .filter(|hit| hit.score > 0.0)
+            .collect();
+
+        hits.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(Ordering::Equal)
+                .then(a.item.name.cmp(&b.item.name))
+        });
+        hits.truncate(limit as usize);
+
+        Ok(hits)
+    }
+
+    async fn find_page(&self, query: &ItemQuery) -> Result<ItemPage, DatabaseError> {
+        let position = |item: &Item, value: &Value, key: &str| {
+            let ordering = compare_values(&query.sort.value(item), value).then(
+                item._key.as_str().cmp(key)
+            );
+            match query.direction {
+                SortDirection::Asc => ordering,
+                SortDirection::Desc => ordering.reverse(),
+            }
+        };
+
+        let mut items: Vec<Item> = self
+            .lock()
+            .items.values()
+            .filter(|item| query.min_price.is_none_or(|min| item.price >= min))
+            .filter(|item| query.max_price.is_none_or(|max| item.price <= max))
+            .filter(|item| query.user_id.as_ref().is_none_or(|user_id| &item.user_id == user_id))
+            .filter(|item| !query.in_stock || item.quantity > 0)
+            .filter(|item| query.include_deleted || item.deleted_at.is_none())
+            .cloned()
+            .collect();
+        let total = items.len() as u64;
+
+        items.sort_by(|a, b| position(a, &query.sort.value(b), &b._key));
+
+        let window = items
+            .into_iter()
+            .filter(|item| {
+                query.after.as_ref().is_none_or(|cursor| {
+                    position(item, &cursor.value, &cursor.key) == Ordering::Greater
+                })
+            })
+            .skip(query.offset as usize)
+            .take((query.limit + 1) as usize)
+            .collect();
+
+        Ok(ItemPage::from_window(window, total, query))
+    }
+
+    async fn find_by_key(&self, key: &str) -> Result<Item, DatabaseError> {
+        self.lock()
+            .items.get(key)
+            .filter(|item| item.deleted_at.is_none())
+            .cloned()
+            .ok_or_else(|| DatabaseError::NotFound(format!("Item {} not found", key)))
+    }
+
+    async fn insert(&self, item: NewItem) -> Result<Item, DatabaseError> {
+        let mut collections = self.lock();
+        let taken = collections.items
+            .values()
+            .any(|existing| existing.name == item.name && existing.deleted_at.is_none());
+        if taken {
+            return Err(DatabaseError::Conflict(format!("Item name {} already used", item.name)));
+        }
+
+        let key = collections.next_key();
+        let rev = collections.next_rev();
+        let item: Item = to_document("Item", &key, &rev, &item)?;
+        collections.items.insert(key, item.clone());
+        Ok(item)
+    }
+
+    async fn update(&self, key: &str, update: ItemUpdate) -> Result<Item, DatabaseError> {
+        let mut collections = self.lock();
+        let rev = collections.next_rev();
+        let item = collections.items
+            .get_mut(key)
+            .ok_or_else(|| DatabaseError::NotFound(format!("Item {} not found", key)))?;
+
+        if update.rev.as_ref().is_some_and(|rev| rev != &item._rev) {
+            return Err(
+                DatabaseError::PreconditionFailed(format!("Item {} changed since it was read", key))
+            );
+        }
+        if let Some(name) = update.name {
+            item.name = name;
+        }
+        if let Some(description) = update.description {
+            item.description = description;
+        }
+        if let Some(price) = update.price {
+            item.price = price;
+        }
+        if let Some(quantity) = update.quantity {
+            item.quantity = quantity;
+        }
+        item._rev = rev;
+
+        Ok(item.clone())
+    }
+
+    async fn remove(&self, key: &str, deletion: Deletion) -> Result<Item, DatabaseError> {
+        let mut collections = self.lock();
+        let rev = collections.next_rev();
+        let item = collections.items
+            .get_mut(key)
+            .filter(|item| item.deleted_at.is_none())
+            .ok_or_else(|| DatabaseError::NotFound(format!("Item {} not found", key)))?;
+
+        item.deleted_at = Some(deletion.deleted_at);
+        item.deleted_by = Some(deletion.deleted_by);
+        item._rev = rev;
+
+        Ok(item.clone())
+    }
+
+    async fn remove_by_user(&self, user_id: &str, deletion: Deletion) -> Result<u64, DatabaseError> {
+        let mut collections = self.lock();
+        let keys: Vec<String> = collections.items
+            .values()
+            .filter(|item| item.user_id == user_id && item.deleted_at.is_none())
+            .map(|item| item._key.to_owned())
+            .collect();
+
+        for key in &keys {
+            let rev = collections.next_rev();
+            let item = collections.items.get_mut(key).unwrap();
+            item.deleted_at = Some(deletion.deleted_at);
+            item.deleted_by = Some(deletion.deleted_by.to_owned());
+            item._rev = rev;
+        }
+
+        Ok(keys.len() as u64)
+    }
+
+    async fn restore(&self, key: &str) -> Result<Item, DatabaseError> {
+        let mut collections = self.lock();
+        let rev = collections.next_rev();
+        let deleted = collections.items
+            .get(key)
+            .filter(|item| item.deleted_at.is_some())
+            .cloned()
+            .ok_or_else(|| DatabaseError::NotFound(format!("No deleted item {} found", key)))?;
+
+        let taken = collections.items
+            .values()
+            .any(|item| item.name == deleted.name && item.deleted_at.is_none());
+        if taken {
+            return Err(
+                DatabaseError::Conflict(format!("Item name {} already used", deleted.name))
+            );
+        }
+
+        let item = collections.items.get_mut(key).unwrap();
+        item.deleted_at = None;
+        item.deleted_by = None;
+        item._rev = rev;
+
+        Ok(item.clone())
+    }
+
+    async fn purge(&self, deleted_before: NaiveDateTime) -> Result<u64, DatabaseError> {
+        let mut collections = self.lock();
+        let before = collections.items.len();
+        collections.items.retain(|_, item| item.deleted_at.is_none_or(|at| at >= deleted_before));
+
+        Ok((before - collections.items.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl OrderRepository for MemoryDatabase {
+    async fn find_by_user(
+        &self,
+        user_id: &str,
+        include_deleted: bool
+    ) -> Result<Vec<Order>, DatabaseError> {
+        Ok(
+            self
+                .lock()
+                .orders.values()
+                .filter(|order| order.user_id == user_id)
+                .filter(|order| include_deleted || order.deleted_at.is_none())
+                .cloned()
+                .collect()
+        )
+    }
+
+    async fn find_by_key(&self, key: &str) -> Result<Order, DatabaseError> {
+        self.lock()
+            .orders.get(key)
+            .filter(|order| order.deleted_at.is_none())
+            .cloned()
+            .ok_or_else(|| DatabaseError::NotFound(format!("Order {} not found", key)))
+    }
+
+    async fn place(&self, order: NewOrder) -> Result<Order, DatabaseError> {
+        let mut collections = self.lock();
+        if order.lines.is_empty() {
+            return Err(DatabaseError::Conflict("An order needs at least one line".to_string()));
+        }
+        if let Some(cart_rev) = &order.cart_rev {
+            let current = collections.carts.get(&order.user_id).map(|cart| &cart._rev);
+            if current != Some(cart_rev) {
+                return Err(DatabaseError::Conflict("The cart changed, try again".to_string()));
+            }
+        }
+
+        // Stock changes are staged so a line failing leaves every item untouched.
+        let mut staged: BTreeMap<String, Item> = BTreeMap::new();
+        let mut items = Vec::with_capacity(order.lines.len());
+        for line in &order.lines {
+            let item = staged
+                .get(&line.item_id)
+                .or_else(|| collections.items.get(&line.item_id))
+                .filter(|item| item.deleted_at.is_none())
+                .cloned()
+                .ok_or_else(|| {
+                    DatabaseError::NotFound(format!("Item {} not found", line.item_id))
+                })?;
+            check_stock(line, &item)?;
+
+            let mut updated = item.clone();
+            updated.quantity -= line.quantity;
+            updated._rev = collections.next_rev();
+            staged.insert(line.item_id.to_owned(), updated);
+            items.push(item);
+        }
+
+        let document = OrderDocument::new(&order, &items)?;
+        let key = collections.next_key();
+        let rev = collections.next_rev();
+        let placed: Order = to_document("Order", &key, &rev, &document)?;
+
+        collections.items.extend(staged);
+        collections.orders.insert(key, placed.clone());
+        if order.cart_rev.is_some() {
+            collections.carts.remove(&order.user_id);
+        }
+
+        Ok(placed)
+    }
+
+    async fn remove_by_user(
+        &self,
+        user_id: &str,
+        deletion: Deletion
+    ) -> Result<Vec<Order>, DatabaseError> {
+        let mut collections = self.lock();
+        let mut removed = Vec::new();
+        let keys: Vec<String> = collections.orders
+            .values()
+            .filter(|order| order.user_id == user_id && order.deleted_at.is_none())
+            .map(|order| order._key.to_owned())
+            .collect();
+
+        for key in keys {
+            let rev = collections.next_rev();
+            let order = collections.orders.get_mut(&key).unwrap();
+            order.deleted_at = Some(deletion.deleted_at);
+            order.deleted_by = Some(deletion.deleted_by.to_owned());
+            order._rev = rev;
+            removed.push(order.clone());
+        }
+
+        Ok(removed)
+    }
+
+    async fn anonymize_user(&self, user_id: &str) -> Result<u64, DatabaseError> {
+        let mut collections = self.lock();
+        let keys: Vec<String> = collections.orders
+            .values()
+            .filter(|order| order.user_id == user_id)
+            .map(|order| order._key.to_owned())
+            .collect();
+
+        for key in &keys {
+            let rev = collections.next_rev();
+            let order = collections.orders.get_mut(key).unwrap();
+            order.user_id = DELETED_USER_ID.to_string();
+            order._rev = rev;
+        }
+
+        Ok(keys.len() as u64)
+    }
+
+    async fn restore(&self, key: &str) -> Result<Order, DatabaseError> {
+        let mut collections = self.lock();
+        let rev = collections.next_rev();
+        let order = collections.orders
+            .get_mut(key)
+            .filter(|order| order.deleted_at.is_some())
+            .ok_or_else(|| DatabaseError::NotFound(format!("No deleted order {} found", key)))?;
+
+        order.deleted_at = None;
+        order.deleted_by = None;
+        order._rev = rev;
+
+        Ok(order.clone())
+    }
+
+    async fn purge(&self, deleted_before: NaiveDateTime) -> Result<u64, DatabaseError> {
+        let mut collections = self.lock();
+        let before = collections.orders.len();
+        collections.orders.retain(|_, order| {
+            order.deleted_at.is_none_or(|at| at >= deleted_before)
+        });
+
+        Ok((before - collections.orders.len()) as u64)
+    }
+
+    async fn change_status(
+        &self,
+        order: &Order,
+        change: StatusChange
+    ) -> Result<Order, DatabaseError> {
+        let mut collections = self.lock();
+        let current = collections.orders
+            .get(&order._key)
+            .ok_or_else(|| DatabaseError::NotFound(format!("Order {} not found", order._key)))?;
+        if current._rev != order._rev {
+            return Err(DatabaseError::Conflict(format!("Order {} changed, try again", order._key)));
+        }
+
+        let patch = StatusPatch::new(order, change);
+        if patch.status == OrderStatus::CANCELLED {
+            for line in &order.lines {
+                let rev = collections.next_rev();
+                if let Some(item) = collections.items.get_mut(&line.item_id) {
+                    item.quantity += line.quantity;
+                    item._rev = rev;
+                }
+            }
+        }
+
+        let mut updated = order.clone();
+        updated.status = patch.status;
+        updated.status_history = patch.status_history;
+        updated._rev = collections.next_rev();
+        collections.orders.insert(updated._key.to_owned(), updated.clone());
+
+        Ok(updated)
+    }
+}
+
+#[async_trait]
+impl CartRepository for MemoryDatabase {
+    async fn find(&self, user_id: &str) -> Result<Option<Cart>, DatabaseError> {
+        Ok(self.lock().carts.get(user_id).cloned())
+    }
+
+    async fn save(&self, cart: NewCart) -> Result<Cart, DatabaseError> {
+        let mut collections = self.lock();
+        let rev = collections.next_rev();
+        let cart: Cart = to_document("Cart", &cart._key, &rev, &cart)?;
+        collections.carts.insert(cart._key.to_owned(), cart.clone());
+        Ok(cart)
+    }
+
+    async fn remove(&self, user_id: &str) -> Result<(), DatabaseError> {
+        self.lock().carts.remove(user_id);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserRepository for MemoryDatabase {
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, DatabaseError> {
+        Ok(
+            self
+                .lock()
+                .users.values()
+                .find(|user| user.email == email)
+                .cloned()
+        )
+    }
+
+    async fn find_by_key(&self, key: &str) -> Result<Option<User>, DatabaseError> {
+        Ok(self.lock().users.get(key).cloned())
+    }
+
+    async fn insert(&self, user: NewUser) -> Result<User, DatabaseError> {
+        let mut collections = self.lock();
+        if collections.users.values().any(|existing| existing.email == user.email) {
+            return Err(DatabaseError::Conflict(format!("Email {} already used", user.email)));
+        }
+
+        let key = collections.next_key();
+        let rev = collections.next_rev();
+        let user: User = to_document("User", &key, &rev, &user)?;
+        collections.users.insert(key, user.clone());
+        Ok(user)
+    }
+
+    async fn update(&self, key: &str, update: UserUpdate) -> Result<User, DatabaseError> {
+        let mut collections = self.lock();
+        if let Some(email) = &update.email {
+            let taken = collections.users
+                .values()
+                .any(|existing| &existing.email == email && existing._key != key);
+            if taken {
+                return Err(DatabaseError::Conflict(format!("Email {} already used", email)));
+            }
+        }
+
+        let rev = collections.next_rev();
+        let user = collections.users
+            .get_mut(key)
+            .ok_or_else(|| DatabaseError::NotFound(format!("User {} not found", key)))?;
+
+        if let Some(first_name) = update.first_name {
+            user.first_name = first_name;
+        }
+        if let Some(last_name) = update.last_name {
+            user.last_name = last_name;
+        }
+        if let Some(email) = update.email {
+            user.email = email;
+        }
+        if let Some(password) = update.password {
+            user.password = password;
+        }
+        if let Some(email_verified) = update.email_verified {
+            user.email_verified = email_verified;
+        }
+        if let Some(totp_secret) = update.totp_secret {
+            user.totp_secret = totp_secret;
+        }
+        if let Some(totp_enabled) = update.totp_enabled {
+            user.totp_enabled = totp_enabled;
+        }
+        if let Some(totp_last_step) = update.totp_last_step {
+            user.totp_last_step = Some(totp_last_step);
+        }
+        if let Some(recovery_codes) = update.recovery_codes {
+            user.recovery_codes = recovery_codes;
+        }
+        if let Some(token_version) = update.token_version {
+            user.token_version = token_version;
+        }
+        user._rev = rev;
+
+        Ok(user.clone())
+    }
+
+    async fn claim_totp_step(&self, key: &str, step: u64) -> Result<bool, DatabaseError> {
+        let mut collections = self.lock();
+        let rev = collections.next_rev();
+        let Some(user) = collections.users.get_mut(key) else {
+            return Ok(false);
+        };
+        if user.totp_last_step.is_some_and(|last| last >= step) {
+            return Ok(false);
+        }
+
+        user.totp_last_step = Some(step);
+        user._rev = rev;
+        Ok(true)
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        key: &str,
+        current: &[String],
+        remaining: Vec<String>
+    ) -> Result<bool, DatabaseError> {
+        let mut collections = self.lock();
+        let rev = collections.next_rev();
+        let Some(user) = collections.users.get_mut(key) else {
+            return Ok(false);
+        };
+        if user.recovery_codes != current {
+            return Ok(false);
+        }
+
+        user.recovery_codes = remaining;
+        user._rev = rev;
+        Ok(true)
+    }
+
+    async fn remove(&self, key: &str) -> Result<(), DatabaseError> {
+        self.lock().users.remove(key);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TokenRepository for MemoryDatabase {
+    async fn insert(&self, token: NewRefreshToken) -> Result<RefreshToken, DatabaseError> {
+        let mut collections = self.lock();
+        let key = collections.next_key();
+        let rev = collections.next_rev();
+        let token: RefreshToken = to_document("RefreshToken", &key, &rev, &token)?;
+        collections.tokens.insert(key, token.clone());
+        Ok(token)
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<RefreshToken>, DatabaseError> {
+        Ok(
+            self
+                .lock()
+                .tokens.values()
+                .find(|token| token.token_hash == token_hash)
+                .cloned()
+        )
+    }
+
+    async fn revoke(&self, key: &str) -> Result<bool, DatabaseError> {
+        match self.lock().tokens.get_mut(key) {
+            Some(token) if !token.revoked => {
+                token.revoked = true;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn revoke_family(&self, family_id: &str) -> Result<(), DatabaseError> {
+        self.lock()
+            .tokens.values_mut()
+            .filter(|token| token.family_id == family_id)
+            .for_each(|token| {
+                token.revoked = true;
+            });
+        Ok(())
+    }
+
+    async fn revoke_user(&self, user_id: &str) -> Result<(), DatabaseError> {
+        self.lock()
+            .tokens.values_mut()
+            .filter(|token| token.user_id == user_id)
+            .for_each(|token| {
+                token.revoked = true;
+            });
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PasswordResetRepository for MemoryDatabase {
+    async fn insert(&self, reset: NewPasswordReset) -> Result<PasswordReset, DatabaseError> {
+        let mut collections = self.lock();
+        let key = collections.next_key();
+        let rev = collections.next_rev();
+        let reset: PasswordReset = to_document("PasswordReset", &key, &rev, &reset)?;
+        collections.resets.insert(key, reset.clone());
+        Ok(reset)
+    }
+
+    async fn count_since(&self, email: &str, since: NaiveDateTime) -> Result<u64, DatabaseError> {
+        Ok(
+            self
+                .lock()
+                .resets.values()
+                .filter(|reset| reset.email == email && reset.created_at > since)
+                .count() as u64
+        )
+    }
+
+    async fn find_valid(
+        &self,
+        token_hash: &str,
+        now: NaiveDateTime
+    ) -> Result<Option<PasswordReset>, DatabaseError> {
+        Ok(
+            self
+                .lock()
+                .resets.values()
+                .find(|reset| {
+                    reset.token_hash == token_hash &&
+                        reset.used_at.is_none() &&
+                        reset.expires_at > now
+                })
+                .cloned()
+        )
+    }
+
+    async fn redeem(
+        &self,
+        reset: &PasswordReset,
+        now: NaiveDateTime,
+        password: &str,
+        token_version: u64
+    ) -> Result<bool, DatabaseError> {
+        let mut collections = self.lock();
+        let unused = collections.resets
+            .get(&reset._key)
+            .is_some_and(|stored| stored.used_at.is_none());
+        if !unused {
+            return Ok(false);
+        }
+
+        let rev = collections.next_rev();
+        let user = collections.users
+            .get_mut(&reset.user_id)
+            .ok_or_else(|| DatabaseError::NotFound(format!("User {} not found", reset.user_id)))?;
+        user.password = password.to_string();
+        user.token_version = token_version;
+        user._rev = rev;
+
+        collections.resets
+            .values_mut()
+            .filter(|stored| stored.user_id == reset.user_id && stored.used_at.is_none())
+            .for_each(|stored| {
+                stored.used_at = Some(now);
+            });
+
+        Ok(true)
+    }
+
+    async fn purge_expired(&self, now: NaiveDateTime) -> Result<u64, DatabaseError> {
+        let mut collections = self.lock();
+        let before = collections.resets.len();
+        collections.resets.retain(|_, reset| reset.expires_at >= now);
+
+        Ok((before - collections.resets.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl LoginAttemptRepository for MemoryDatabase {
+    async fn insert(&self, attempt: NewLoginAttempt) -> Result<LoginAttempt, DatabaseError> {
+        let mut collections = self.lock();
+        // Nothing expires attempts in the background here, so they are dropped as new ones come.
+        collections.login_attempts.retain(|_, stored| stored.expires_at > attempt.created_at);
+
+        let key = collections.next_key();
+        let rev = collections.next_rev();
+        let attempt: LoginAttempt = to_document("LoginAttempt", &key, &rev, &attempt)?;
+        collections.login_attempts.insert(key, attempt.clone());
+        Ok(attempt)
+    }
+
+    async fn find_active(
+        &self,
+        subject: &str,
+        now: NaiveDateTime
+    ) -> Result<Vec<LoginAttempt>, DatabaseError> {
+        Ok(
+            self
+                .lock()
+                .login_attempts.values()
+                .filter(|attempt| attempt.subject == subject && attempt.expires_at > now)
+                .cloned()
+                .collect()
+        )
+    }
+
+    async fn clear(&self, subject: &str) -> Result<(), DatabaseError> {
+        self.lock().login_attempts.retain(|_, attempt| attempt.subject != subject);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AuditRepository for MemoryDatabase {
+    async fn insert(&self, entry: NewAuditEntry) -> Result<AuditEntry, DatabaseError> {
+        let mut collections = self.lock();
+        let key = collections.next_key();
+        let rev = collections.next_rev();
+        let entry: AuditEntry = to_document("AuditLog", &key, &rev, &entry)?;
+        collections.audit.insert(key, entry.clone());
+        Ok(entry)
+    }
+
+    async fn find_by_subject(&self, subject: &str) -> Result<Vec<AuditEntry>, DatabaseError> {
+        Ok(
+            self
+                .lock()
+                .audit.values()
+                .filter(|entry| entry.subject == subject)
+                .cloned()
+                .collect()
+        )
+    }
+}
\ No newline at end of file
@@ -0,0 +1,440 @@
+use crate::constants::DELETED_USER_ID;
+use crate::db::{ finish, ArangoProvider, Database, DatabaseError, WRITE_CONFLICT };
+use crate::models::{ Deletion, Item, Money, Order, OrderLine, OrderStatus, StatusChange };
+use arangors::transaction::{ Transaction, TransactionCollections, TransactionSettings };
+use arangors::uclient::surf::SurfClient;
+use arangors::ClientError;
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use serde_json::{ json, Value };
+use std::collections::HashMap;
+
+/// Number of times an order write is retried when another transaction modified an item
+/// concurrently.
+const MAX_WRITE_ATTEMPTS: usize = 5;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct NewOrder {
+    pub user_id: String,
+    pub lines: Vec<NewOrderLine>,
+    pub date: NaiveDateTime,
+    /// Revision of the user's cart when the order comes from a checkout. The cart is emptied in
+    /// the same transaction, and the order fails if the cart changed in the meantime.
+    pub cart_rev: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct NewOrderLine {
+    pub item_id: String,
+    pub quantity: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct OrderDocument<'a> {
+    user_id: &'a str,
+    lines: Vec<OrderLine>,
+    subtotal: Money,
+    date: NaiveDateTime,
+    status: OrderStatus,
+    status_history: Vec<StatusChange>,
+}
+
+impl<'a> OrderDocument<'a> {
+    /// Snapshots the item names and prices so later edits to the listings don't alter the order.
+    pub(crate) fn new(order: &'a NewOrder, items: &[Item]) -> Result<Self, DatabaseError> {
+        let total_error = |err| DatabaseError::Conflict(format!("Cannot total the order: {}", err));
+
+        let lines = order.lines
+            .iter()
+            .zip(items)
+            .map(|(line, item)| OrderLine::for_item(item, line.quantity))
+            .collect::<Result<Vec<OrderLine>, _>>()
+            .map_err(total_error)?;
+
+        let currency = items.first().map(|item| item.price.currency()).unwrap_or_default();
+        let subtotal = Money::sum(lines.iter().map(|line| &line.total), currency).map_err(
+            total_error
+        )?;
+
+        Ok(Self {
+            user_id: &order.user_id,
+            lines,
+            subtotal,
+            date: order.date,
+            status: OrderStatus::PENDING,
+            status_history: vec![StatusChange {
+                status: OrderStatus::PENDING,
+                changed_at: order.date,
+                changed_by: order.user_id.to_owned(),
+            }],
+        })
+    }
+}
+
+/// Checks that `item` holds enough stock for `line`.
+pub(crate) fn check_stock(line: &NewOrderLine, item: &Item) -> Result<(), DatabaseError> {
+    if line.quantity > item.quantity {
+        return Err(
+            DatabaseError::Conflict(
+                format!("Order quantity exceeds the quantity of item {}", item._key)
+            )
+        );
+    }
+
+    Ok(())
+}
+
+/// The fields written when an order changes status.
+#[derive(Debug, Serialize)]
+pub(crate) struct StatusPatch {
+    pub(crate) status: OrderStatus,
+    pub(crate) status_history: Vec<StatusChange>,
+}
+
+impl StatusPatch {
+    pub(crate) fn new(order: &Order, change: StatusChange) -> Self {
+        let mut status_history = order.status_history.to_owned();
+        let status = change.status;
+        status_history.push(change);
+
+        Self { status, status_history }
+    }
+}
+
+#[async_trait]
+pub trait OrderRepository: Send + Sync {
+    /// Orders of a user, leaving out soft-deleted ones unless `include_deleted` is set.
+    async fn find_by_user(
+        &self,
+        user_id: &str,
+        include_deleted: bool
+    ) -> Result<Vec<Order>, DatabaseError>;
+    /// Finds an order that has not been soft-deleted.
+    async fn find_by_key(&self, key: &str) -> Result<Order, DatabaseError>;
+    /// Atomically checks the stock of every line, decrements the item quantities and stores the
+    /// order, emptying the cart it came from if any.
+    async fn place(&self, order: NewOrder) -> Result<Order, DatabaseError>;
+    /// Soft-deletes every order of a user and returns them.
+    async fn remove_by_user(
+        &self,
+        user_id: &str,
+        deletion: Deletion
+    ) -> Result<Vec<Order>, DatabaseError>;
+    async fn restore(&self, key: &str) -> Result<Order, DatabaseError>;
+    /// Detaches every order of a user from them by setting `user_id` to `DELETED_USER_ID`, so
+    /// sales history is kept once the account is gone. Returns how many orders were changed.
+    async fn anonymize_user(&self, user_id: &str) -> Result<u64, DatabaseError>;
+    /// Permanently removes the orders deleted before `deleted_before` and returns how many.
+    async fn purge(&self, deleted_before: NaiveDateTime) -> Result<u64, DatabaseError>;
+    /// Appends `change` to the history of `order`, returning the stock of every line to its item
+    /// when the order is cancelled. Fails with a conflict if the order changed since it was read.
+    async fn change_status(
+        &self,
+        order: &Order,
+        change: StatusChange
+    ) -> Result<Order, DatabaseError>;
+}
+
+#[async_trait]
+impl OrderRepository for Database {
+    async fn find_by_user(
+        &self,
+        user_id: &str,
+        include_deleted: bool
+    ) -> Result<Vec<Order>, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("user_id", user_id.into());
+        bind_vars.insert("include_deleted", include_deleted.into());
+
+        let orders: Vec<Order> = self
+            .get_db()
+            .aql_bind_vars(
+                "FOR order IN Order
+                    FILTER order.user_id == @user_id
+                    FILTER @include_deleted OR order.deleted_at == null
+                    RETURN order",
+                bind_vars
+            ).await?;
+
+        Ok(orders)
+    }
+
+    async fn find_by_key(&self, key: &str) -> Result<Order, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("key", key.into());
+
+        let mut found: Vec<Option<Order>> = self
+            .get_db()
+            .aql_bind_vars("RETURN DOCUMENT(Order, @key)", bind_vars).await?;
+
+        found
+            .pop()
+            .flatten()
+            .filter(|order| order.deleted_at.is_none())
+            .ok_or_else(|| DatabaseError::NotFound(format!("Order {} not found", key)))
+    }
+
+    async fn place(&self, order: NewOrder) -> Result<Order, DatabaseError> {
+        for _ in 0..MAX_WRITE_ATTEMPTS {
+            let transaction = self.begin_order_transaction().await?;
+            let result = place_in_transaction(&transaction, &order).await;
+
+            match finish(transaction, result).await {
+                Err(err) if is_write_conflict(&err) => {
+                    continue;
+                }
+                result => {
+                    return result;
+                }
+            }
+        }
+
+        Err(
+            DatabaseError::Conflict(
+                "The items or the cart are being changed concurrently, try again".to_string()
+            )
+        )
+    }
+
+    async fn change_status(
+        &self,
+        order: &Order,
+        change: StatusChange
+    ) -> Result<Order, DatabaseError> {
+        let patch = StatusPatch::new(order, change);
+
+        for _ in 0..MAX_WRITE_ATTEMPTS {
+            let transaction = self.begin_order_transaction().await?;
+            let result = change_status_in_transaction(&transaction, order, &patch).await;
+
+            match finish(transaction, result).await {
+                Err(err) if is_write_conflict(&err) => {
+                    continue;
+                }
+                result => {
+                    return result;
+                }
+            }
+        }
+
+        Err(
+            DatabaseError::Conflict(
+                "The items of the order are being changed concurrently, try again".to_string()
+            )
+        )
+    }
+
+    async fn remove_by_user(
+        &self,
+        user_id: &str,
+        deletion: Deletion
+    ) -> Result<Vec<Order>, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("user_id", user_id.into());
+        bind_vars.insert("deleted_at", json!(deletion.deleted_at));
+        bind_vars.insert("deleted_by", deletion.deleted_by.into());
+
+        let orders: Vec<Order> = self
+            .get_db()
+            .aql_bind_vars(
+                "FOR order IN Order FILTER order.user_id == @user_id AND order.deleted_at == null
+                    UPDATE order WITH { deleted_at: @deleted_at, deleted_by: @deleted_by } IN Order
+                    RETURN NEW",
+                bind_vars
+            ).await?;
+
+        Ok(orders)
+    }
+
+    async fn anonymize_user(&self, user_id: &str) -> Result<u64, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("user_id", user_id.into());
+        bind_vars.insert("anonymous", DELETED_USER_ID.into());
+
+        let anonymized: Vec<Value> = self
+            .get_db()
+            .aql_bind_vars(
+                "FOR order IN Order FILTER order.user_id == @user_id
+                    UPDATE order WITH { user_id: @anonymous } IN Order
+                    RETURN NEW._key",
+                bind_vars
+            ).await?;
+
+        Ok(anonymized.len() as u64)
+    }
+
+    async fn restore(&self, key: &str) -> Result<Order, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("key", key.into());
+
+        let mut orders: Vec<Order> = self
+            .get_db()
+            .aql_bind_vars(
+                "FOR order IN Order FILTER order._key == @key AND order.deleted_at != null
+                    UPDATE order WITH { deleted_at: null, deleted_by: null } IN Order
+                    RETURN NEW",
+                bind_vars
+            ).await?;
+
+        orders
+            .pop()
+            .ok_or_else(|| DatabaseError::NotFound(format!("No deleted order {} found", key)))
+    }
+
+    async fn purge(&self, deleted_before: NaiveDateTime) -> Result<u64, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("before", json!(deleted_before));
+
+        let purged: Vec<Value> = self
+            .get_db()
+            .aql_bind_vars(
+                "FOR order IN Order FILTER order.deleted_at != null AND order.deleted_at < @before
+                    REMOVE order IN Order
+                    RETURN OLD._key",
+                bind_vars
+            ).await?;
+
+        Ok(purged.len() as u64)
+    }
+}
+
+impl Database {
+    /// Starts a stream transaction that can write orders, the stock of items and carts.
+    async fn begin_order_transaction(&self) -> Result<Transaction<SurfClient>, DatabaseError> {
+        let settings = TransactionSettings::builder()
+            .collections(
+                TransactionCollections::builder()
+                    .write(vec!["Item".to_string(), "Order".to_string(), "Cart".to_string()])
+                    .build()
+            )
+            .build();
+
+        Ok(self.get_db().begin_transaction(settings).await?)
+    }
+}
+
+fn is_write_conflict(err: &DatabaseError) -> bool {
+    matches!(
+        err,
+        DatabaseError::ClientError(ClientError::Arango(err)) if err.error_num() == WRITE_CONFLICT
+    )
+}
+
+async fn place_in_transaction(
+    transaction: &Transaction<SurfClient>,
+    order: &NewOrder
+) -> Result<Order, DatabaseError> {
+    if order.lines.is_empty() {
+        return Err(DatabaseError::Conflict("An order needs at least one line".to_string()));
+    }
+
+    let mut items = Vec::with_capacity(order.lines.len());
+    for line in &order.lines {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("item_id", line.item_id.as_str().into());
+
+        let mut found: Vec<Option<Item>> = transaction.aql_bind_vars(
+            "RETURN DOCUMENT(Item, @item_id)",
+            bind_vars
+        ).await?;
+
+        let item = found
+            .pop()
+            .flatten()
+            .filter(|item| item.deleted_at.is_none())
+            .ok_or_else(|| DatabaseError::NotFound(format!("Item {} not found", line.item_id)))?;
+        check_stock(line, &item)?;
+
+        // Matching on `_rev` makes a concurrent stock change fail with a write conflict instead
+        // of silently overwriting it.
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("item", json!({ "_key": item._key, "_rev": item._rev }));
+        bind_vars.insert("quantity", (item.quantity - line.quantity).into());
+
+        let _: Vec<Value> = transaction.aql_bind_vars(
+            "UPDATE @item WITH { quantity: @quantity } IN Item OPTIONS { ignoreRevs: false }",
+            bind_vars
+        ).await?;
+
+        items.push(item);
+    }
+
+    let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+    bind_vars.insert("order", json!(OrderDocument::new(order, &items)?));
+
+    let mut orders: Vec<Order> = transaction.aql_bind_vars(
+        "INSERT @order INTO Order RETURN NEW",
+        bind_vars
+    ).await?;
+
+    if let Some(cart_rev) = &order.cart_rev {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("cart", json!({ "_key": order.user_id, "_rev": cart_rev }));
+
+        let _: Vec<Value> = transaction.aql_bind_vars(
+            "REMOVE @cart IN Cart OPTIONS { ignoreRevs: false }",
+            bind_vars
+        ).await?;
+    }
+
+    orders.pop().ok_or_else(|| DatabaseError::QueryError("Error creating order".to_string()))
+}
+
+async fn change_status_in_transaction(
+    transaction: &Transaction<SurfClient>,
+    order: &Order,
+    patch: &StatusPatch
+) -> Result<Order, DatabaseError> {
+    let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+    bind_vars.insert("order", json!({ "_key": order._key, "_rev": order._rev }));
+    bind_vars.insert("status", json!(patch.status));
+    bind_vars.insert("status_history", json!(patch.status_history));
+
+    // A revision mismatch on the order itself is not worth retrying: the caller checked the
+    // transition against a status that is no longer current.
+    let mut updated: Vec<Order> = transaction
+        .aql_bind_vars(
+            concat!(
+                "UPDATE @order WITH { status: @status, status_history: @status_history } ",
+                "IN Order OPTIONS { ignoreRevs: false } RETURN NEW"
+            ),
+            bind_vars
+        ).await
+        .map_err(|err| {
+            let err = DatabaseError::from(err);
+            if is_write_conflict(&err) {
+                DatabaseError::Conflict(format!("Order {} changed, try again", order._key))
+            } else {
+                err
+            }
+        })?;
+
+    if patch.status == OrderStatus::CANCELLED {
+        for line in &order.lines {
+            let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+            bind_vars.insert("item_id", line.item_id.as_str().into());
+
+            let mut found: Vec<Option<Item>> = transaction.aql_bind_vars(
+                "RETURN DOCUMENT(Item, @item_id)",
+                bind_vars
+            ).await?;
+
+            // Stock of items removed since the order was placed has nowhere to go back to.
+            let Some(item) = found.pop().flatten() else {
+                continue;
+            };
+
+            let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+            bind_vars.insert("item", json!({ "_key": item._key, "_rev": item._rev }));
+            bind_vars.insert("quantity", (item.quantity + line.quantity).into());
+
+            let _: Vec<Value> = transaction.aql_bind_vars(
+                "UPDATE @item WITH { quantity: @quantity } IN Item OPTIONS { ignoreRevs: false }",
+                bind_vars
+            ).await?;
+        }
+    }
+
+    updated.pop().ok_or_else(|| DatabaseError::QueryError("Error updating order".to_string()))
+}
\ No newline at end of file
@@ -0,0 +1,196 @@
+use crate::db::{ finish, ArangoProvider, Database, DatabaseError };
+use crate::models::PasswordReset;
+use arangors::transaction::{ Transaction, TransactionCollections, TransactionSettings };
+use arangors::uclient::surf::SurfClient;
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use serde_json::{ json, Value };
+use std::collections::HashMap;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct NewPasswordReset {
+    pub user_id: String,
+    pub email: String,
+    pub token_hash: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+}
+
+#[async_trait]
+pub trait PasswordResetRepository: Send + Sync {
+    async fn insert(&self, reset: NewPasswordReset) -> Result<PasswordReset, DatabaseError>;
+    /// Counts the resets requested for `email` after `since`.
+    async fn count_since(&self, email: &str, since: NaiveDateTime) -> Result<u64, DatabaseError>;
+    /// Returns the reset matching `token_hash` if it is still unused and not expired at `now`.
+    async fn find_valid(
+        &self,
+        token_hash: &str,
+        now: NaiveDateTime
+    ) -> Result<Option<PasswordReset>, DatabaseError>;
+    /// In one transaction, marks `reset` as used, sets the password hash and token version of its
+    /// user, and marks the other unused resets of the user as used, so older emails stop working.
+    /// Returns false, changing nothing, if the reset was used in the meantime.
+    async fn redeem(
+        &self,
+        reset: &PasswordReset,
+        now: NaiveDateTime,
+        password: &str,
+        token_version: u64
+    ) -> Result<bool, DatabaseError>;
+    /// Removes the resets that expired before `now` and returns how many.
+    async fn purge_expired(&self, now: NaiveDateTime) -> Result<u64, DatabaseError>;
+}
+
+#[async_trait]
+impl PasswordResetRepository for Database {
+    async fn insert(&self, reset: NewPasswordReset) -> Result<PasswordReset, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("reset", json!(&reset));
+
+        let mut resets: Vec<PasswordReset> = self
+            .get_db()
+            .aql_bind_vars("INSERT @reset INTO PasswordReset RETURN NEW", bind_vars).await?;
+
+        resets
+            .pop()
+            .ok_or_else(|| DatabaseError::QueryError("Error storing password reset".to_string()))
+    }
+
+    async fn count_since(&self, email: &str, since: NaiveDateTime) -> Result<u64, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("email", email.into());
+        bind_vars.insert("since", json!(since));
+
+        let resets: Vec<Value> = self
+            .get_db()
+            .aql_bind_vars(
+                "
+    FOR reset IN PasswordReset
+        FILTER reset.email == @email AND @since < reset.created_at
+        RETURN reset
+    ",
+                bind_vars
+            ).await?;
+
+        Ok(resets.len() as u64)
+    }
+
+    async fn find_valid(
+        &self,
+        token_hash: &str,
+        now: NaiveDateTime
+    ) -> Result<Option<PasswordReset>, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("token_hash", token_hash.into());
+        bind_vars.insert("now", json!(now));
+
+        let mut resets: Vec<PasswordReset> = self
+            .get_db()
+            .aql_bind_vars(
+                "
+    FOR reset IN PasswordReset
+        FILTER reset.token_hash == @token_hash AND reset.used_at == null AND @now < reset.expires_at
+        RETURN reset
+    ",
+                bind_vars
+            ).await?;
+
+        Ok(resets.pop())
+    }
+
+    async fn redeem(
+        &self,
+        reset: &PasswordReset,
+        now: NaiveDateTime,
+        password: &str,
+        token_version: u64
+    ) -> Result<bool, DatabaseError> {
+        let settings = TransactionSettings::builder()
+            .collections(
+                TransactionCollections::builder()
+                    .write(vec!["PasswordReset".to_string(), "User".to_string()])
+                    .build()
+            )
+            .build();
+        let transaction = self.get_db().begin_transaction(settings).await?;
+        let result = redeem_in_transaction(&transaction, reset, now, password, token_version).await;
+
+        finish(transaction, result).await
+    }
+
+    async fn purge_expired(&self, now: NaiveDateTime) -> Result<u64, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("now", json!(now));
+
+        let purged: Vec<Value> = self
+            .get_db()
+            .aql_bind_vars(
+                "FOR reset IN PasswordReset FILTER reset.expires_at < @now
+                    REMOVE reset IN PasswordReset
+                    RETURN OLD._key",
+                bind_vars
+            ).await?;
+
+        Ok(purged.len() as u64)
+    }
+}
+
+async fn redeem_in_transaction(
+    transaction: &Transaction<SurfClient>,
+    reset: &PasswordReset,
+    now: NaiveDateTime,
+    password: &str,
+    token_version: u64
+) -> Result<bool, DatabaseError> {
+    let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+    bind_vars.insert("key", reset._key.as_str().into());
+    bind_vars.insert("now", json!(now));
+
+    let used: Vec<Value> = transaction.aql_bind_vars(
+        "
+    FOR reset IN PasswordReset
+        FILTER reset._key == @key AND reset.used_at == null
+        UPDATE reset WITH { used_at: @now } IN PasswordReset
+        RETURN NEW._key
+    ",
+        bind_vars
+    ).await?;
+    if used.is_empty() {
+        return Ok(false);
+    }
+
+    let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+    bind_vars.insert("user_id", reset.user_id.as_str().into());
+    bind_vars.insert("password", password.into());
+    bind_vars.insert("token_version", json!(token_version));
+
+    let updated: Vec<Value> = transaction.aql_bind_vars(
+        "
+    FOR user IN User
+        FILTER user._key == @user_id
+        UPDATE user WITH { password: @password, token_version: @token_version } IN User
+        RETURN NEW._key
+    ",
+        bind_vars
+    ).await?;
+    if updated.is_empty() {
+        return Err(DatabaseError::NotFound(format!("User {} not found", reset.user_id)));
+    }
+
+    let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+    bind_vars.insert("user_id", reset.user_id.as_str().into());
+    bind_vars.insert("now", json!(now));
+
+    let _: Vec<Value> = transaction.aql_bind_vars(
+        "
+    FOR reset IN PasswordReset
+        FILTER reset.user_id == @user_id AND reset.used_at == null
+        UPDATE reset WITH { used_at: @now } IN PasswordReset
+    ",
+        bind_vars
+    ).await?;
+
+    Ok(true)
+}
\ No newline at end of file
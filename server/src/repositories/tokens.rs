@@ -0,0 +1,111 @@
+use crate::db::{ ArangoProvider, Database, DatabaseError };
+use crate::models::RefreshToken;
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use serde_json::{ json, Value };
+use std::collections::HashMap;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct NewRefreshToken {
+    pub user_id: String,
+    pub family_id: String,
+    pub token_hash: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub revoked: bool,
+}
+
+#[async_trait]
+pub trait TokenRepository: Send + Sync {
+    async fn insert(&self, token: NewRefreshToken) -> Result<RefreshToken, DatabaseError>;
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<RefreshToken>, DatabaseError>;
+    /// Revokes a single token, returning `false` if it had already been revoked.
+    async fn revoke(&self, key: &str) -> Result<bool, DatabaseError>;
+    async fn revoke_family(&self, family_id: &str) -> Result<(), DatabaseError>;
+    /// Revokes every refresh token of a user, logging them out of all sessions.
+    async fn revoke_user(&self, user_id: &str) -> Result<(), DatabaseError>;
+}
+
+#[async_trait]
+impl TokenRepository for Database {
+    async fn insert(&self, token: NewRefreshToken) -> Result<RefreshToken, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("token", json!(&token));
+
+        let mut tokens: Vec<RefreshToken> = self
+            .get_db()
+            .aql_bind_vars("INSERT @token INTO RefreshToken RETURN NEW", bind_vars).await?;
+
+        tokens.pop().ok_or_else(|| DatabaseError::QueryError("Error storing token".to_string()))
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<RefreshToken>, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("token_hash", token_hash.into());
+
+        let mut tokens: Vec<RefreshToken> = self
+            .get_db()
+            .aql_bind_vars(
+                "FOR token IN RefreshToken FILTER token.token_hash == @token_hash RETURN token",
+                bind_vars
+            ).await?;
+
+        Ok(tokens.pop())
+    }
+
+    async fn revoke(&self, key: &str) -> Result<bool, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("key", key.into());
+
+        let revoked: Vec<Value> = self
+            .get_db()
+            .aql_bind_vars(
+                "
+    FOR token IN RefreshToken
+        FILTER token._key == @key AND token.revoked == false
+        UPDATE token WITH { revoked: true } IN RefreshToken
+        RETURN NEW._key
+    ",
+                bind_vars
+            ).await?;
+
+        Ok(!revoked.is_empty())
+    }
+
+    async fn revoke_family(&self, family_id: &str) -> Result<(), DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("family_id", family_id.into());
+
+        let _: Vec<Value> = self
+            .get_db()
+            .aql_bind_vars(
+                "
+    FOR token IN RefreshToken
+        FILTER token.family_id == @family_id AND token.revoked == false
+        UPDATE token WITH { revoked: true } IN RefreshToken
+    ",
+                bind_vars
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn revoke_user(&self, user_id: &str) -> Result<(), DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("user_id", user_id.into());
+
+        let _: Vec<Value> = self
+            .get_db()
+            .aql_bind_vars(
+                "
+    FOR token IN RefreshToken
+        FILTER token.user_id == @user_id AND token.revoked == false
+        UPDATE token WITH { revoked: true } IN RefreshToken
+    ",
+                bind_vars
+            ).await?;
+
+        Ok(())
+    }
+}
\ No newline at end of file
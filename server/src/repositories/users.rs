@@ -0,0 +1,187 @@
+use crate::db::{ ArangoProvider, Database, DatabaseError, DOCUMENT_NOT_FOUND };
+use crate::models::{ Role, User };
+use arangors::document::options::UpdateOptions;
+use arangors::ClientError;
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::{ json, Value };
+use std::collections::HashMap;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct NewUser {
+    pub first_name: String,
+    pub last_name: String,
+    pub email: String,
+    pub password: String,
+    pub role: Role,
+    pub email_verified: bool,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct UserUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// Hash of the new password.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+    /// `Some(None)` removes the secret.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub totp_secret: Option<Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub totp_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub totp_last_step: Option<u64>,
+    /// Hashes of the recovery codes, replacing the stored ones.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovery_codes: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_version: Option<u64>,
+}
+
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, DatabaseError>;
+    async fn find_by_key(&self, key: &str) -> Result<Option<User>, DatabaseError>;
+    async fn insert(&self, user: NewUser) -> Result<User, DatabaseError>;
+    /// Fails with a conflict if the new email is already used by another user.
+    async fn update(&self, key: &str, update: UserUpdate) -> Result<User, DatabaseError>;
+    /// Records `step` as the last TOTP step the user logged in with, unless that step or a later
+    /// one already is. Returns whether it did.
+    async fn claim_totp_step(&self, key: &str, step: u64) -> Result<bool, DatabaseError>;
+    /// Replaces the recovery codes of the user with `remaining`, unless they are no longer
+    /// `current`. Returns whether it did.
+    async fn replace_recovery_codes(
+        &self,
+        key: &str,
+        current: &[String],
+        remaining: Vec<String>
+    ) -> Result<bool, DatabaseError>;
+    /// Permanently removes the user.
+    async fn remove(&self, key: &str) -> Result<(), DatabaseError>;
+}
+
+#[async_trait]
+impl UserRepository for Database {
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("email", email.into());
+
+        let mut users: Vec<User> = self
+            .get_db()
+            .aql_bind_vars("FOR user IN User FILTER user.email == @email RETURN user", bind_vars).await?;
+
+        Ok(users.pop())
+    }
+
+    async fn find_by_key(&self, key: &str) -> Result<Option<User>, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("key", key.into());
+
+        let mut users: Vec<Option<User>> = self
+            .get_db()
+            .aql_bind_vars("RETURN DOCUMENT(User, @key)", bind_vars).await?;
+
+        Ok(users.pop().flatten())
+    }
+
+    async fn insert(&self, user: NewUser) -> Result<User, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("user", json!(&user));
+
+        let mut users: Vec<User> = self
+            .get_db()
+            .aql_bind_vars("INSERT @user INTO User RETURN NEW", bind_vars).await?;
+
+        users.pop().ok_or_else(|| DatabaseError::QueryError("Error creating user".to_string()))
+    }
+
+    async fn update(&self, key: &str, update: UserUpdate) -> Result<User, DatabaseError> {
+        let collection = self.get_db().collection("User").await?;
+        let response = match
+            collection.update_document(
+                key,
+                json!(&update),
+                UpdateOptions::builder().return_new(true).build()
+            ).await
+        {
+            Ok(response) => response,
+            Err(ClientError::Arango(err)) if err.error_num() == DOCUMENT_NOT_FOUND => {
+                return Err(DatabaseError::NotFound(format!("User {} not found", key)));
+            }
+            Err(err) => {
+                return Err(err.into());
+            }
+        };
+
+        match response.new_doc() {
+            Some(doc) =>
+                serde_json
+                    ::from_value(doc.clone())
+                    .map_err(|err| DatabaseError::ClientError(err.into())),
+            None => Err(DatabaseError::NotFound(format!("User {} not found", key))),
+        }
+    }
+
+    async fn claim_totp_step(&self, key: &str, step: u64) -> Result<bool, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("key", key.into());
+        bind_vars.insert("step", step.into());
+
+        // `null` sorts before numbers, so a user without a last step can claim any.
+        let claimed: Vec<Value> = self.get_db().aql_bind_vars(
+            "
+    FOR user IN User
+        FILTER user._key == @key AND user.totp_last_step < @step
+        UPDATE user WITH { totp_last_step: @step } IN User
+        RETURN NEW._key
+    ",
+            bind_vars
+        ).await?;
+
+        Ok(!claimed.is_empty())
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        key: &str,
+        current: &[String],
+        remaining: Vec<String>
+    ) -> Result<bool, DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("key", key.into());
+        bind_vars.insert("current", json!(current));
+        bind_vars.insert("remaining", json!(remaining));
+
+        let replaced: Vec<Value> = self.get_db().aql_bind_vars(
+            "
+    FOR user IN User
+        FILTER user._key == @key AND user.recovery_codes == @current
+        UPDATE user WITH { recovery_codes: @remaining } IN User
+        RETURN NEW._key
+    ",
+            bind_vars
+        ).await?;
+
+        Ok(!replaced.is_empty())
+    }
+
+    async fn remove(&self, key: &str) -> Result<(), DatabaseError> {
+        let mut bind_vars: HashMap<&str, Value> = HashMap::new();
+        bind_vars.insert("key", key.into());
+
+        let _: Vec<Value> = self
+            .get_db()
+            .aql_bind_vars(
+                "FOR user IN User FILTER user._key == @key REMOVE user IN User",
+                bind_vars
+            ).await?;
+
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,54 @@
+use crate::api::{ ApiResponse, ApiResult };
+use crate::models::{ Item, Order };
+use crate::state::AppState;
+use axum::extract::{ Path, State };
+use axum::Json;
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/items/{id}/restore",
+    params(
+        ("id" = String, Path, description = "Key of the deleted item")
+    ),
+    responses(
+        (status = 200, description = "Return the restored item", body = Item),
+        (status = 403, description = "Only admins can restore items", body = ErrorResponse),
+        (status = 404, description = "No deleted item with that key", body = ErrorResponse),
+        (
+            status = 409,
+            description = "Another item was listed under the same name since",
+            body = ErrorResponse,
+        ),
+        (status = 500, description = "Error in the database query", body = ErrorResponse)
+    )
+)]
+pub async fn restore_item(
+    State(state): State<AppState>,
+    Path(id): Path<String>
+) -> ApiResult<Item> {
+    let item = state.items.restore(&id).await?;
+
+    Ok(Json(ApiResponse::Success(item)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/orders/{id}/restore",
+    params(
+        ("id" = String, Path, description = "Key of the deleted order")
+    ),
+    responses(
+        (status = 200, description = "Return the restored order", body = Order),
+        (status = 403, description = "Only admins can restore orders", body = ErrorResponse),
+        (status = 404, description = "No deleted order with that key", body = ErrorResponse),
+        (status = 500, description = "Error in the database query", body = ErrorResponse)
+    )
+)]
+pub async fn restore_order(
+    State(state): State<AppState>,
+    Path(id): Path<String>
+) -> ApiResult<Order> {
+    let order = state.orders.restore(&id).await?;
+
+    Ok(Json(ApiResponse::Success(order)))
+}
\ No newline at end of file
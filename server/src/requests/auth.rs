@@ -1,41 +1,152 @@
-use crate::api::{ generate_error, ApiResponse };
-use crate::db::Database;
-use crate::models::User;
-use axum::Extension;
-use axum::{ http::StatusCode, Json };
-use bcrypt::{ hash, verify, DEFAULT_COST };
+use crate::api::{ ApiError, ApiResponse, ApiResult };
+use crate::constants::{
+    EMAIL_VERIFICATION_TTL_HOURS,
+    PASSWORD_RESET_LIMIT,
+    PASSWORD_RESET_TTL_MINUTES,
+    PASSWORD_RESET_WINDOW_MINUTES,
+};
+use crate::mail::Mail;
+use crate::models::{ Role, User, UserProfile };
+use crate::repositories::password_resets::NewPasswordReset;
+use crate::repositories::users::{ NewUser, UserUpdate };
+use crate::state::AppState;
+use axum::extract::State;
+use axum::Json;
+use chrono::Duration;
+use log::{ error, info, warn };
 use serde::{ Deserialize, Serialize };
-use serde_json::Value;
-use std::collections::HashMap;
 use utoipa::ToSchema;
+use validator::Validate;
 
-use super::jwt::generate_jwt;
+use super::jwt::{
+    check_login_allowed,
+    generate_challenge_token,
+    generate_token,
+    generate_verification_token,
+    hash_token,
+    issue_tokens,
+    validate_verification_token,
+};
+use super::security::{ self, ClientIp };
+use super::validation::{ not_blank, Valid };
 
-#[derive(Deserialize, ToSchema)]
+#[derive(Deserialize, ToSchema, Validate)]
 pub struct LoginParams {
+    #[validate(email(message = "must be a valid email address"))]
     email: String,
+    #[validate(length(min = 1, message = "cannot be empty"))]
     password: String,
 }
 
 #[derive(Serialize, ToSchema)]
 pub struct AuthRes {
-    user: User,
+    user: UserProfile,
     token: String,
+    refresh_token: String,
 }
 
 impl AuthRes {
-    pub fn new(user: User, token: String) -> Self {
-        Self { user, token }
+    pub fn new(user: User, token: String, refresh_token: String) -> Self {
+        Self { user: user.into(), token, refresh_token }
     }
 }
 
-#[derive(Deserialize, ToSchema)]
+/// Answer to a login. Users with two-factor authentication enabled get an `mfa_token` instead of
+/// their profile and tokens, to exchange at `/api/auth/login/2fa` together with a code.
+#[derive(Serialize, ToSchema)]
+pub struct LoginRes {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<UserProfile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refresh_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mfa_token: Option<String>,
+}
+
+impl From<AuthRes> for LoginRes {
+    fn from(auth: AuthRes) -> Self {
+        Self {
+            user: Some(auth.user),
+            token: Some(auth.token),
+            refresh_token: Some(auth.refresh_token),
+            mfa_token: None,
+        }
+    }
+}
+
+/// Answer to a signup. Tokens are left out when users must verify their email before logging in.
+#[derive(Serialize, ToSchema)]
+pub struct SignupRes {
+    user: UserProfile,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refresh_token: Option<String>,
+}
+
+impl From<AuthRes> for SignupRes {
+    fn from(auth: AuthRes) -> Self {
+        Self { user: auth.user, token: Some(auth.token), refresh_token: Some(auth.refresh_token) }
+    }
+}
+
+#[derive(Deserialize, ToSchema, Validate)]
 pub struct SignupParams {
+    #[validate(
+        custom = "not_blank",
+        length(max = 64, message = "must be at most 64 characters long")
+    )]
     first_name: String,
+    #[validate(
+        custom = "not_blank",
+        length(max = 64, message = "must be at most 64 characters long")
+    )]
     last_name: String,
+    #[validate(email(message = "must be a valid email address"))]
     email: String,
+    #[validate(length(max = 128, message = "must be at most 128 characters long"))]
     password: String,
-    role: String,
+    role: SignupRole,
+}
+
+/// Roles users can pick for themselves. Admins are promoted directly in the database.
+#[derive(Deserialize, ToSchema)]
+pub enum SignupRole {
+    CUSTOMER,
+    VENDOR,
+}
+
+#[derive(Deserialize, ToSchema, Validate)]
+pub struct VerifyEmailParams {
+    /// Token from the verification email
+    #[validate(length(min = 1, message = "cannot be empty"))]
+    token: String,
+}
+
+#[derive(Deserialize, ToSchema, Validate)]
+pub struct ForgotPasswordParams {
+    #[validate(email(message = "must be a valid email address"))]
+    email: String,
+}
+
+#[derive(Deserialize, ToSchema, Validate)]
+pub struct ResetPasswordParams {
+    /// Token from the reset email
+    #[validate(length(min = 1, message = "cannot be empty"))]
+    token: String,
+    #[validate(length(max = 128, message = "must be at most 128 characters long"))]
+    password: String,
+}
+
+impl From<SignupRole> for Role {
+    fn from(role: SignupRole) -> Self {
+        match role {
+            SignupRole::CUSTOMER => Role::CUSTOMER,
+            SignupRole::VENDOR => Role::VENDOR,
+        }
+    }
 }
 
 #[utoipa::path(
@@ -43,37 +154,42 @@ pub struct SignupParams {
     path = "/api/auth/login",
     request_body = LoginParams,
     responses(
-        (status = 200, description = "Return authenticated user", body = AuthRes),
-        (status = 400, description = "Credentials are wrong", body = ErrorResponse)
+        (
+            status = 200,
+            description = "Return authenticated user, or a challenge for users with two-factor authentication",
+            body = LoginRes,
+        ),
+        (status = 400, description = "Credentials are wrong", body = ErrorResponse),
+        (status = 403, description = "Email address must be verified first", body = ErrorResponse),
+        (status = 422, description = "Email or password is malformed", body = ErrorResponse),
+        (status = 429, description = "Too many failed logins for the email or from the client IP", body = ErrorResponse),
+        (status = 500, description = "Error during query/token generation", body = ErrorResponse)
     )
 )]
 pub async fn handle_login(
-    Extension(database): Extension<Database>,
-    Extension(secret): Extension<String>,
-    Json(payload): Json<LoginParams>
-) -> (StatusCode, Json<ApiResponse<AuthRes>>) {
-    let email: String = payload.email;
-    let password: String = payload.password;
-
-    let mut bind_vars: HashMap<&str, Value> = HashMap::new();
-    bind_vars.insert("email", email.to_owned().into());
-
-    let users: Vec<User> = database.arango_db
-        .aql_bind_vars("FOR user IN User FILTER user.email == @email RETURN user", bind_vars).await
-        .unwrap();
-
-    if users.is_empty() {
-        (StatusCode::BAD_REQUEST, generate_error("Email and/or password are wrong"))
-    } else {
-        let user = users[0].clone();
-
-        if verify(password, &user.password).unwrap_or(false) {
-            let token = generate_jwt(&email, &secret).unwrap();
-            (StatusCode::OK, Json(ApiResponse::Success(AuthRes::new(user, token))))
-        } else {
-            (StatusCode::BAD_REQUEST, generate_error("Email and/or password are wrong"))
-        }
+    State(state): State<AppState>,
+    ip: ClientIp,
+    Valid(payload): Valid<LoginParams>
+) -> ApiResult<LoginRes> {
+    let user = check_credentials(&state, ip, &payload).await?;
+    check_login_allowed(&state, &user)?;
+
+    if user.totp_enabled {
+        let mfa_token = generate_challenge_token(&user, &state.keys).map_err(|err| {
+            ApiError::Internal(format!("Error generating token: {}", err))
+        })?;
+        let challenge = LoginRes {
+            user: None,
+            token: None,
+            refresh_token: None,
+            mfa_token: Some(mfa_token),
+        };
+        return Ok(Json(ApiResponse::Success(challenge)));
     }
+
+    let auth = issue_tokens(&state, user, None).await?;
+
+    Ok(Json(ApiResponse::Success(auth.into())))
 }
 
 #[utoipa::path(
@@ -81,68 +197,313 @@ pub async fn handle_login(
     path = "/api/auth/signup",
     request_body = SignupParams,
     responses(
-        (status = 200, description = "Return authenticated user", body = AuthRes),
-        (status = 400, description = "Credentials are wrong", body = ErrorResponse),
+        (
+            status = 200,
+            description = "Return the new user, and tokens unless the email must be verified first",
+            body = SignupRes,
+        ),
+        (status = 409, description = "Email is already associated with another user", body = ErrorResponse),
+        (status = 422, description = "One or more fields are invalid, or the password is too weak", body = ErrorResponse),
         (status = 500, description = "Error during query/hashing", body = ErrorResponse)
     )
 )]
 pub async fn handle_signup(
-    Extension(database): Extension<Database>,
-    Extension(secret): Extension<String>,
-    Json(payload): Json<SignupParams>
-) -> (StatusCode, Json<ApiResponse<AuthRes>>) {
-    let first_name: String = payload.first_name;
-    let last_name: String = payload.last_name;
-    let email: String = payload.email;
-    let password: String = payload.password;
-    let role: String = payload.role;
-
-    let hashed_password = match hash(password, DEFAULT_COST) {
-        Ok(h) => h,
-        Err(err) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                generate_error(format!("Error hashing password: {:?}", { err }).as_str()),
-            );
+    State(state): State<AppState>,
+    Valid(payload): Valid<SignupParams>
+) -> ApiResult<SignupRes> {
+    let personal = [payload.first_name.as_str(), &payload.last_name, &payload.email];
+    state.passwords
+        .check_policy("password", &payload.password, &personal)
+        .map_err(|violation| ApiError::InvalidFields(vec![violation]))?;
+
+    let hashed_password = state.passwords
+        .hash_blocking(&payload.password).await
+        .map_err(|err| ApiError::Internal(format!("Error hashing password: {}", err)))?;
+
+    let user = NewUser {
+        first_name: payload.first_name,
+        last_name: payload.last_name,
+        email: payload.email,
+        password: hashed_password,
+        role: payload.role.into(),
+        email_verified: false,
+    };
+
+    let user = state.users.insert(user).await.map_err(|err| {
+        match ApiError::from(err) {
+            ApiError::Conflict(_) => {
+                ApiError::Conflict("Email is already associated with another user".to_string())
+            }
+            err => err,
+        }
+    })?;
+
+    // The account exists either way, so a failed email only means the user has to ask again.
+    if let Err(err) = send_verification(&state, &user).await {
+        error!("Error sending verification email to user {}: {}", user._key, err);
+    }
+
+    if state.config.server.require_verified_email.blocks_login() {
+        let signup = SignupRes { user: user.into(), token: None, refresh_token: None };
+        return Ok(Json(ApiResponse::Success(signup)));
+    }
+
+    let auth = issue_tokens(&state, user, None).await?;
+
+    Ok(Json(ApiResponse::Success(auth.into())))
+}
+
+/// Emails `user` a signed link that verifies their current address.
+pub async fn send_verification(state: &AppState, user: &User) -> Result<(), ApiError> {
+    let token = generate_verification_token(user, &state.keys).map_err(|err| {
+        ApiError::Internal(format!("Error generating token: {}", err))
+    })?;
+
+    let mail = Mail {
+        to: user.email.to_owned(),
+        subject: "Verify your RANS email address".to_string(),
+        body: format!(
+            "Hi {},\n\nOpen this link to verify your email address: {}?token={}\n\n\
+            The link expires in {} hours.",
+            user.first_name,
+            state.config.mail.verify_url,
+            token,
+            EMAIL_VERIFICATION_TTL_HOURS
+        ),
+    };
+
+    state.mailer.send(mail).await.map_err(|err| ApiError::Internal(err.to_string()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/verify",
+    request_body = VerifyEmailParams,
+    responses(
+        (status = 200, description = "Return the user with a verified email", body = UserProfile),
+        (status = 400, description = "Verification link is invalid or expired", body = ErrorResponse),
+        (status = 422, description = "Token is empty", body = ErrorResponse),
+        (status = 500, description = "Error in the database query", body = ErrorResponse)
+    )
+)]
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Valid(payload): Valid<VerifyEmailParams>
+) -> ApiResult<UserProfile> {
+    let invalid = || ApiError::Validation("Verification link is invalid or expired".to_string());
+
+    let claims = validate_verification_token(&payload.token, &state.keys).map_err(|_| invalid())?;
+    let user = state.users
+        .find_by_key(&claims.sub).await?
+        .filter(|user| user.email == claims.email)
+        .ok_or_else(invalid)?;
+
+    if user.email_verified {
+        return Ok(Json(ApiResponse::Success(user.into())));
+    }
+
+    let update = UserUpdate { email_verified: Some(true), ..Default::default() };
+    let user = state.users.update(&user._key, update).await?;
+
+    Ok(Json(ApiResponse::Success(user.into())))
+}
+
+/// Finds the user the credentials belong to. Wrong credentials count towards a lockout of the
+/// email and the client IP, and are refused without a lookup while either is locked out. Users
+/// with two-factor authentication keep their failures until they also give a right code. Password
+/// hashes made with an outdated algorithm or cost are upgraded on the way.
+async fn check_credentials(
+    state: &AppState,
+    ip: ClientIp,
+    credentials: &LoginParams
+) -> Result<User, ApiError> {
+    security::check_lockout(state, &credentials.email, ip).await?;
+
+    let user = state.users.find_by_email(&credentials.email).await?;
+    let verified = match &user {
+        Some(user) => state.passwords.verify_blocking(&credentials.password, &user.password).await,
+        None => {
+            state.passwords.verify_unknown_user_blocking(&credentials.password).await;
+            false
         }
     };
 
-    let query =
-        "
-    INSERT {
-        first_name: @first_name,
-        last_name: @last_name,
-        email: @email,
-        password: @hashed_password,
-        role: @role
-    } INTO User
-    RETURN NEW
-    ";
-
-    let mut bind_vars = HashMap::new();
-    bind_vars.insert("first_name", first_name.into());
-    bind_vars.insert("last_name", last_name.into());
-    bind_vars.insert("email", email.clone().into());
-    bind_vars.insert("hashed_password", hashed_password.into());
-    bind_vars.insert("role", role.into());
-
-    let result = database.arango_db.aql_bind_vars(query, bind_vars).await;
-
-    match result {
-        Ok(mut users) => {
-            if let Some(user) = users.pop() {
-                let token = generate_jwt(&email, &secret).unwrap();
-                (StatusCode::OK, Json(ApiResponse::Success(AuthRes { user, token })))
-            } else {
-                (StatusCode::INTERNAL_SERVER_ERROR, generate_error("Error creating user"))
+    match user {
+        Some(user) if verified => {
+            if !user.totp_enabled {
+                security::clear_failures(state, &credentials.email).await?;
+            }
+            if state.passwords.needs_rehash(&user.password) {
+                upgrade_hash(state, &user, &credentials.password).await;
             }
+            Ok(user)
         }
+        _ => {
+            security::record_failure(state, &credentials.email, ip).await?;
+            Err(ApiError::Validation("Email and/or password are wrong".to_string()))
+        }
+    }
+}
+
+/// Rehashes the password of `user` with the current algorithm and cost. A failure only leaves the
+/// old hash in place until the next login.
+async fn upgrade_hash(state: &AppState, user: &User, password: &str) {
+    let hashed_password = match state.passwords.hash_blocking(password).await {
+        Ok(hashed_password) => hashed_password,
         Err(err) => {
-            eprintln!("Error creating user: {:?}", err);
-            (
-                StatusCode::BAD_REQUEST,
-                generate_error("Email is already associated with another user"),
-            )
+            error!("Error rehashing the password of user {}: {}", user._key, err);
+            return;
+        }
+    };
+
+    let update = UserUpdate { password: Some(hashed_password), ..Default::default() };
+    match state.users.update(&user._key, update).await {
+        Ok(_) => info!("Upgraded the password hash of user {}", user._key),
+        Err(err) => error!("Error storing the rehashed password of user {}: {}", user._key, err),
+    }
+}
+
+/// Takes the credentials rather than a token, since users may not be allowed to log in yet.
+#[utoipa::path(
+    post,
+    path = "/api/auth/verify/resend",
+    request_body = LoginParams,
+    responses(
+        (status = 200, description = "A new verification email was sent", body = bool),
+        (status = 400, description = "Credentials are wrong or email is verified", body = ErrorResponse),
+        (status = 422, description = "Email or password is malformed", body = ErrorResponse),
+        (status = 429, description = "Too many failed logins for the email or from the client IP", body = ErrorResponse),
+        (status = 500, description = "Error during query/sending the email", body = ErrorResponse)
+    )
+)]
+pub async fn resend_verification(
+    State(state): State<AppState>,
+    ip: ClientIp,
+    Valid(payload): Valid<LoginParams>
+) -> ApiResult<bool> {
+    let user = check_credentials(&state, ip, &payload).await?;
+
+    if user.email_verified {
+        return Err(ApiError::Validation("Email is already verified".to_string()));
+    }
+    send_verification(&state, &user).await?;
+
+    Ok(Json(ApiResponse::Success(true)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/forgot_password",
+    request_body = ForgotPasswordParams,
+    responses(
+        (status = 200, description = "Reset email sent if the email is known", body = bool),
+        (status = 422, description = "Email is malformed", body = ErrorResponse)
+    )
+)]
+pub async fn forgot_password(
+    State(state): State<AppState>,
+    Valid(payload): Valid<ForgotPasswordParams>
+) -> ApiResult<bool> {
+    // Known and unknown emails get the same answer just as fast, so the endpoint cannot be used
+    // to find users: the lookup and the email happen in the background, and errors are logged.
+    tokio::spawn(async move {
+        if let Err(err) = send_password_reset(&state, &payload.email).await {
+            error!("Error handling a password reset request: {}", err);
         }
+    });
+
+    Ok(Json(ApiResponse::Success(true)))
+}
+
+/// Mails a reset link to the user of `email`, if there is one and they did not ask for too many.
+async fn send_password_reset(state: &AppState, email: &str) -> Result<(), ApiError> {
+    let Some(user) = state.users.find_by_email(email).await? else {
+        return Ok(());
+    };
+
+    let now = chrono::Utc::now().naive_utc();
+    let since = now - Duration::minutes(PASSWORD_RESET_WINDOW_MINUTES);
+    if state.resets.count_since(&user.email, since).await? >= PASSWORD_RESET_LIMIT {
+        warn!("Too many password resets requested for user {}", user._key);
+        return Ok(());
+    }
+
+    let token = generate_token();
+    let reset = NewPasswordReset {
+        user_id: user._key.to_owned(),
+        email: user.email.to_owned(),
+        token_hash: hash_token(&token),
+        created_at: now,
+        expires_at: now + Duration::minutes(PASSWORD_RESET_TTL_MINUTES),
+        used_at: None,
+    };
+    state.resets.insert(reset).await?;
+
+    let mail = Mail {
+        to: user.email,
+        subject: "Reset your RANS password".to_string(),
+        body: format!(
+            "Hi {},\n\nOpen this link to choose a new password: {}?token={}\n\n\
+            The link expires in {} minutes. If you did not ask to reset your password, \
+            you can ignore this email.",
+            user.first_name,
+            state.config.mail.reset_url,
+            token,
+            PASSWORD_RESET_TTL_MINUTES
+        ),
+    };
+    if let Err(err) = state.mailer.send(mail).await {
+        error!("Error sending password reset email to user {}: {}", user._key, err);
     }
+
+    Ok(())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/reset_password",
+    request_body = ResetPasswordParams,
+    responses(
+        (status = 200, description = "Password changed and every session logged out", body = bool),
+        (status = 400, description = "Reset token is invalid or expired", body = ErrorResponse),
+        (status = 422, description = "Password is malformed or too weak", body = ErrorResponse),
+        (status = 500, description = "Error during query/hashing", body = ErrorResponse)
+    )
+)]
+pub async fn reset_password(
+    State(state): State<AppState>,
+    Valid(payload): Valid<ResetPasswordParams>
+) -> ApiResult<bool> {
+    let invalid = || ApiError::Validation("Reset token is invalid or expired".to_string());
+    let now = chrono::Utc::now().naive_utc();
+
+    // Nothing is written until the password is accepted and hashed, so the link keeps working
+    // when any step fails.
+    let token_hash = hash_token(&payload.token);
+    let reset = state.resets.find_valid(&token_hash, now).await?.ok_or_else(invalid)?;
+    let user = state.users.find_by_key(&reset.user_id).await?.ok_or_else(invalid)?;
+
+    let personal = [user.first_name.as_str(), &user.last_name, &user.email];
+    state.passwords
+        .check_policy("password", &payload.password, &personal)
+        .map_err(|violation| ApiError::InvalidFields(vec![violation]))?;
+
+    let hashed_password = state.passwords
+        .hash_blocking(&payload.password).await
+        .map_err(|err| ApiError::Internal(format!("Error hashing password: {}", err)))?;
+
+    let redeemed = state.resets
+        .redeem(&reset, now, &hashed_password, user.token_version + 1).await
+        .map_err(|err| {
+            match ApiError::from(err) {
+                ApiError::NotFound(_) => invalid(),
+                err => err,
+            }
+        })?;
+    if !redeemed {
+        return Err(invalid());
+    }
+    state.tokens.revoke_user(&reset.user_id).await?;
+
+    Ok(Json(ApiResponse::Success(true)))
 }
\ No newline at end of file
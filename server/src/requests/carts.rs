@@ -0,0 +1,256 @@
+use crate::api::{ ApiError, ApiResponse, ApiResult };
+use crate::models::{ CartLine, Currency, Item, Money, Order, OrderLine };
+use crate::repositories::carts::NewCart;
+use crate::repositories::orders::{ NewOrder, NewOrderLine };
+use crate::state::AppState;
+use super::jwt::Claims;
+use super::validation::{ not_blank, Valid };
+use axum::{ extract::{ Path, State }, Json };
+use chrono::Local;
+use serde::{ Deserialize, Serialize };
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Deserialize, Debug, Serialize, ToSchema, Validate)]
+pub struct AddCartLineReq {
+    #[validate(custom = "not_blank")]
+    item_id: String,
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    quantity: i64,
+}
+
+#[derive(Deserialize, Debug, Serialize, ToSchema, Validate)]
+pub struct UpdateCartLineReq {
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    quantity: i64,
+}
+
+/// The cart priced at the current item prices. Lines whose item was deleted are left out.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct CartRes {
+    lines: Vec<OrderLine>,
+    subtotal: Money,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/cart",
+    responses(
+        (status = 200, description = "Return the cart of the logged in user", body = CartRes),
+        (status = 500, description = "Error in the database query", body = ErrorResponse)
+    )
+)]
+pub async fn get_cart(State(state): State<AppState>, claims: Claims) -> ApiResult<CartRes> {
+    let lines = cart_lines(&state, &claims.key).await?;
+
+    Ok(Json(ApiResponse::Success(price_cart(&state, &lines).await?)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/cart/lines",
+    request_body = AddCartLineReq,
+    responses(
+        (status = 200, description = "Return the updated cart", body = CartRes),
+        (status = 404, description = "Item not found", body = ErrorResponse),
+        (
+            status = 409,
+            description = "Not enough stock, or the item is priced in another currency than the cart",
+            body = ErrorResponse,
+        ),
+        (status = 422, description = "Quantity must be at least 1", body = ErrorResponse),
+        (status = 500, description = "Error in the database query", body = ErrorResponse)
+    )
+)]
+pub async fn add_line(
+    State(state): State<AppState>,
+    claims: Claims,
+    Valid(payload): Valid<AddCartLineReq>
+) -> ApiResult<CartRes> {
+    let item = state.items.find_by_key(&payload.item_id).await?;
+    let mut lines = cart_lines(&state, &claims.key).await?;
+
+    let cart = price_cart(&state, &lines).await?;
+    if !cart.lines.is_empty() && cart.subtotal.currency() != item.price.currency() {
+        return Err(
+            ApiError::Conflict(
+                format!(
+                    "The cart holds items priced in {}, {} is priced in {}",
+                    cart.subtotal.currency().code(),
+                    item.name,
+                    item.price.currency().code()
+                )
+            )
+        );
+    }
+
+    match lines.iter_mut().find(|line| line.item_id == item._key) {
+        Some(line) => {
+            line.quantity += payload.quantity;
+        }
+        None => lines.push(CartLine { item_id: item._key.to_owned(), quantity: payload.quantity }),
+    }
+
+    save_lines(&state, &claims.key, lines, &item).await
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/cart/lines/{item_id}",
+    params(
+        ("item_id" = String, Path, description = "Item of the cart line")
+    ),
+    request_body = UpdateCartLineReq,
+    responses(
+        (status = 200, description = "Return the updated cart", body = CartRes),
+        (status = 404, description = "Item is not in the cart", body = ErrorResponse),
+        (status = 409, description = "Not enough stock", body = ErrorResponse),
+        (status = 422, description = "Quantity must be at least 1", body = ErrorResponse),
+        (status = 500, description = "Error in the database query", body = ErrorResponse)
+    )
+)]
+pub async fn update_line(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(item_id): Path<String>,
+    Valid(payload): Valid<UpdateCartLineReq>
+) -> ApiResult<CartRes> {
+    let mut lines = cart_lines(&state, &claims.key).await?;
+    let line = lines
+        .iter_mut()
+        .find(|line| line.item_id == item_id)
+        .ok_or_else(|| ApiError::NotFound(format!("Item {} is not in the cart", item_id)))?;
+    line.quantity = payload.quantity;
+
+    let item = state.items.find_by_key(&item_id).await?;
+
+    save_lines(&state, &claims.key, lines, &item).await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/cart/lines/{item_id}",
+    params(
+        ("item_id" = String, Path, description = "Item of the cart line")
+    ),
+    responses(
+        (status = 200, description = "Return the updated cart", body = CartRes),
+        (status = 404, description = "Item is not in the cart", body = ErrorResponse),
+        (status = 500, description = "Error in the database query", body = ErrorResponse)
+    )
+)]
+pub async fn remove_line(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(item_id): Path<String>
+) -> ApiResult<CartRes> {
+    let mut lines = cart_lines(&state, &claims.key).await?;
+    let count = lines.len();
+    lines.retain(|line| line.item_id != item_id);
+
+    if lines.len() == count {
+        return Err(ApiError::NotFound(format!("Item {} is not in the cart", item_id)));
+    }
+
+    let cart = NewCart::new(&claims.key, lines, Local::now().naive_local());
+    let cart = state.carts.save(cart).await?;
+
+    Ok(Json(ApiResponse::Success(price_cart(&state, &cart.lines).await?)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/cart/checkout",
+    responses(
+        (status = 200, description = "Return the order placed from the cart", body = Order),
+        (status = 400, description = "The cart is empty", body = ErrorResponse),
+        (status = 403, description = "Email address must be verified first", body = ErrorResponse),
+        (status = 404, description = "An item in the cart no longer exists", body = ErrorResponse),
+        (
+            status = 409,
+            description = "Not enough stock for a line, or the cart changed during checkout",
+            body = ErrorResponse,
+        ),
+        (status = 500, description = "Error in the database query", body = ErrorResponse)
+    )
+)]
+pub async fn checkout(State(state): State<AppState>, claims: Claims) -> ApiResult<Order> {
+    let cart = state.carts
+        .find(&claims.key).await?
+        .filter(|cart| !cart.lines.is_empty())
+        .ok_or_else(|| ApiError::Validation("The cart is empty".to_string()))?;
+
+    let order = NewOrder {
+        user_id: claims.key,
+        lines: cart.lines
+            .into_iter()
+            .map(|line| NewOrderLine { item_id: line.item_id, quantity: line.quantity })
+            .collect(),
+        date: Local::now().naive_local(),
+        cart_rev: Some(cart._rev),
+    };
+
+    let order = state.orders.place(order).await?;
+
+    Ok(Json(ApiResponse::Success(order)))
+}
+
+async fn cart_lines(state: &AppState, user_id: &str) -> Result<Vec<CartLine>, ApiError> {
+    Ok(
+        state.carts
+            .find(user_id).await?
+            .map(|cart| cart.lines)
+            .unwrap_or_default()
+    )
+}
+
+/// Stores the lines after checking that `item`, the one that changed, has enough stock.
+async fn save_lines(
+    state: &AppState,
+    user_id: &str,
+    lines: Vec<CartLine>,
+    item: &Item
+) -> ApiResult<CartRes> {
+    let wanted = lines
+        .iter()
+        .find(|line| line.item_id == item._key)
+        .map_or(0, |line| line.quantity);
+
+    if wanted > item.quantity {
+        return Err(
+            ApiError::Conflict(format!("Only {} of {} left in stock", item.quantity, item.name))
+        );
+    }
+
+    let cart = state.carts.save(NewCart::new(user_id, lines, Local::now().naive_local())).await?;
+
+    Ok(Json(ApiResponse::Success(price_cart(state, &cart.lines).await?)))
+}
+
+async fn price_cart(state: &AppState, lines: &[CartLine]) -> Result<CartRes, ApiError> {
+    let mut priced = Vec::with_capacity(lines.len());
+    for line in lines {
+        let item = match state.items.find_by_key(&line.item_id).await.map_err(ApiError::from) {
+            Ok(item) => item,
+            Err(ApiError::NotFound(_)) => {
+                continue;
+            }
+            Err(err) => {
+                return Err(err);
+            }
+        };
+
+        priced.push(
+            OrderLine::for_item(&item, line.quantity).map_err(|err| {
+                ApiError::Conflict(format!("Cannot total the cart: {}", err))
+            })?
+        );
+    }
+
+    let currency = priced.first().map_or(Currency::default(), |line| line.unit_price.currency());
+    let subtotal = Money::sum(priced.iter().map(|line| &line.total), currency).map_err(|err| {
+        ApiError::Conflict(format!("Cannot total the cart: {}", err))
+    })?;
+
+    Ok(CartRes { lines: priced, subtotal })
+}
\ No newline at end of file
@@ -1,37 +1,145 @@
-use crate::api::{ generate_error, ApiResponse };
-use crate::db::Database;
-use crate::models::Item;
-use arangors::document::options::{ RemoveOptions, UpdateOptions };
-use axum::extract::Path;
-use axum::Extension;
-use axum::{ http::StatusCode, Json };
+use crate::api::{ expected_rev, ApiError, ApiResponse, ApiResult, Page, Tagged, TaggedResult };
+use crate::constants::{ DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE };
+use crate::db::DatabaseError;
+use crate::models::{ Deletion, Item, Money };
+use crate::repositories::items::{
+    ItemCursor,
+    ItemQuery,
+    ItemSort,
+    ItemUpdate,
+    NewItem,
+    SortDirection,
+};
+use crate::search::{ highlight, terms };
+use crate::state::AppState;
+use super::jwt::Claims;
+use super::validation::{ not_blank, valid_price, Valid };
+use axum::extract::{ Path, Query, State };
+use axum::http::HeaderMap;
+use axum::Json;
+use chrono::Local;
 use serde::{ Deserialize, Serialize };
-use serde_json::{ json, Number, Value };
-use std::collections::HashMap;
+use serde_json::{ json, Value };
+use std::cmp::Ordering;
 use urlencoding::decode;
-use utoipa::ToSchema;
+use utoipa::{ IntoParams, ToSchema };
+use validator::Validate;
 
 #[derive(Deserialize, Debug, Serialize, ToSchema)]
 pub struct GetItemReq {
     id: String,
 }
 
-#[derive(Deserialize, Debug, Serialize, ToSchema)]
+#[derive(Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetItemsParams {
+    /// Maximum number of items to return, between 1 and 100. Defaults to 20
+    limit: Option<u64>,
+    /// Number of items to skip, applied after `cursor`
+    offset: Option<u64>,
+    /// Opaque `next_cursor` returned with the previous page
+    cursor: Option<String>,
+    /// Only return items priced at or above this amount, e.g. `12.50` or `12.50 EUR`. Items in
+    /// other currencies are left out
+    #[param(value_type = Option<String>)]
+    min_price: Option<Money>,
+    /// Only return items priced at or below this amount, e.g. `12.50` or `12.50 EUR`. Items in
+    /// other currencies are left out
+    #[param(value_type = Option<String>)]
+    max_price: Option<Money>,
+    /// Only return items listed by this vendor
+    user_id: Option<String>,
+    /// Only return items with a quantity above zero
+    in_stock: Option<bool>,
+    /// Field to sort by. Defaults to `name`
+    #[param(inline)]
+    sort: Option<ItemSort>,
+    /// Sort direction. Defaults to `asc`
+    #[param(inline)]
+    order: Option<SortDirection>,
+    /// Also list soft-deleted items. Admins only
+    include_deleted: Option<bool>,
+}
+
+impl GetItemsParams {
+    fn into_query(self) -> Result<ItemQuery, ApiError> {
+        if let (Some(min), Some(max)) = (self.min_price, self.max_price) {
+            match min.partial_cmp(&max) {
+                None => {
+                    return Err(
+                        ApiError::Validation(
+                            "min_price and max_price must use the same currency".to_string()
+                        )
+                    );
+                }
+                Some(Ordering::Greater) => {
+                    return Err(
+                        ApiError::Validation(
+                            "min_price cannot be greater than max_price".to_string()
+                        )
+                    );
+                }
+                _ => (),
+            }
+        }
+
+        let after = self.cursor
+            .map(|cursor| {
+                ItemCursor::decode(&cursor).ok_or_else(|| {
+                    ApiError::Validation("Invalid pagination cursor".to_string())
+                })
+            })
+            .transpose()?;
+
+        Ok(ItemQuery {
+            min_price: self.min_price,
+            max_price: self.max_price,
+            user_id: self.user_id,
+            in_stock: self.in_stock.unwrap_or(false),
+            include_deleted: self.include_deleted.unwrap_or(false),
+            sort: self.sort.unwrap_or_default(),
+            direction: self.order.unwrap_or_default(),
+            after,
+            offset: self.offset.unwrap_or(0),
+            limit: self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE),
+        })
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize, ToSchema, Validate)]
 pub struct AddItemReq {
+    #[validate(
+        custom = "not_blank",
+        length(max = 100, message = "must be at most 100 characters long")
+    )]
     name: String,
-    user_id: String,
+    #[validate(length(max = 2000, message = "must be at most 2000 characters long"))]
     description: String,
-    price: f64,
+    #[validate(custom = "valid_price")]
+    price: Money,
+    #[validate(range(min = 0, message = "cannot be negative"))]
     quantity: i64,
 }
 
-#[derive(Deserialize, Debug, Serialize, Clone, ToSchema)]
+#[derive(Deserialize, Debug, Serialize, Clone, ToSchema, Validate)]
 pub struct UpdateItemReq {
+    #[validate(custom = "not_blank")]
     id: String,
+    #[validate(
+        custom = "not_blank",
+        length(max = 100, message = "must be at most 100 characters long")
+    )]
     name: Option<String>,
+    #[validate(length(max = 2000, message = "must be at most 2000 characters long"))]
     description: Option<String>,
-    price: Option<f64>,
+    #[validate(custom = "valid_price")]
+    price: Option<Money>,
+    #[validate(range(min = 0, message = "cannot be negative"))]
     quantity: Option<i64>,
+    /// Revision the edit is based on, as read from `_rev` or the `ETag` header. The `If-Match`
+    /// header can be sent instead
+    #[serde(rename = "_rev")]
+    rev: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Serialize, ToSchema)]
@@ -39,16 +147,30 @@ pub struct DeleteItemReq {
     id: String,
 }
 
-#[derive(Debug, Serialize, ToSchema)]
-pub struct ItemUpdate {
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchItemsParams {
+    /// Free-text query matched against item names and descriptions
+    q: String,
+    /// Maximum number of results, between 1 and 100. Defaults to 20
+    limit: Option<u64>,
+}
+
+/// Item fields with the words matching the query wrapped in `<mark>` tags. Fields that did not
+/// match are omitted.
+#[derive(Serialize, Debug, Default, ToSchema)]
+pub struct ItemHighlights {
     #[serde(skip_serializing_if = "Option::is_none")]
     name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     description: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    price: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    quantity: Option<i64>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct SearchHit {
+    item: Item,
+    score: f64,
+    highlights: ItemHighlights,
 }
 
 #[utoipa::path(
@@ -58,62 +180,126 @@ pub struct ItemUpdate {
         ("name" = String, Path, description = "Item Name")
     ),
     responses(
-        (status = 200, description = "Return list of items that loosely match the name", body = Vec<Item>),
+        (status = 200, description = "Return items matching the name, most relevant first", body = Vec<Item>),
+        (status = 400, description = "Name is not valid UTF-8", body = ErrorResponse),
         (status = 404, description = "No results found", body = ErrorResponse),
         (status = 500, description = "Error in the database query", body = ErrorResponse)
     )
 )]
 pub async fn get_item(
-    Extension(database): Extension<Database>,
+    State(state): State<AppState>,
     Path(name): Path<String>
-) -> (StatusCode, Json<ApiResponse<Vec<Item>>>) {
-    let decoded_name = decode(name.as_str()).expect("UTF-8");
-
-    let mut bind_vars: HashMap<&str, Value> = HashMap::new();
-    bind_vars.insert("name", Value::String(decoded_name.into_owned()));
-
-    match
-        database.arango_db.aql_bind_vars(
-            "FOR item IN Item FILTER LOWER(item.name) LIKE CONCAT('%', LOWER(@name), '%') RETURN item",
-            bind_vars
-        ).await
-    {
-        Ok(items) => {
-            if items.is_empty() {
-                (StatusCode::NOT_FOUND, generate_error("No Item Matches Provided Name"))
-            } else {
-                (StatusCode::OK, Json(ApiResponse::Success(items)))
-            }
-        }
-        Err(e) => {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                generate_error(format!("Error getting item: {}", e.to_string()).as_str()),
-            )
-        }
+) -> ApiResult<Vec<Item>> {
+    let decoded_name = decode(name.as_str()).map_err(|_| {
+        ApiError::Validation("Item name must be valid UTF-8".to_string())
+    })?;
+
+    let items: Vec<Item> = state.items
+        .search(&decoded_name, MAX_PAGE_SIZE).await?
+        .into_iter()
+        .map(|hit| hit.item)
+        .collect();
+
+    if items.is_empty() {
+        return Err(ApiError::NotFound("No Item Matches Provided Name".to_string()));
     }
+
+    Ok(Json(ApiResponse::Success(items)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/items/{id}",
+    params(
+        ("id" = String, Path, description = "Item key")
+    ),
+    responses(
+        (
+            status = 200,
+            description = "Return the item, with its revision in the ETag header",
+            body = Item,
+        ),
+        (status = 404, description = "Item does not exist in database", body = ErrorResponse),
+        (status = 500, description = "Error in the database query", body = ErrorResponse)
+    )
+)]
+pub async fn get_item_by_key(
+    State(state): State<AppState>,
+    Path(id): Path<String>
+) -> TaggedResult<Item> {
+    let item = state.items.find_by_key(&id).await?;
+
+    Ok(Tagged::new(item._rev.clone(), item))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/search_items",
+    params(SearchItemsParams),
+    responses(
+        (status = 200, description = "Return ranked matches with highlighted fields", body = Vec<SearchHit>),
+        (status = 400, description = "Search query is empty", body = ErrorResponse),
+        (status = 500, description = "Error in the database query", body = ErrorResponse)
+    )
+)]
+pub async fn search_items(
+    State(state): State<AppState>,
+    Query(params): Query<SearchItemsParams>
+) -> ApiResult<Vec<SearchHit>> {
+    let terms = terms(&params.q);
+    if terms.is_empty() {
+        return Err(ApiError::Validation("Search query cannot be empty".to_string()));
+    }
+
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let hits = state.items
+        .search(&params.q, limit).await?
+        .into_iter()
+        .map(|hit| SearchHit {
+            highlights: ItemHighlights {
+                name: highlight(&hit.item.name, &terms),
+                description: highlight(&hit.item.description, &terms),
+            },
+            item: hit.item,
+            score: hit.score,
+        })
+        .collect();
+
+    Ok(Json(ApiResponse::Success(hits)))
 }
 
 #[utoipa::path(
     get,
     path = "/api/get_items",
+    params(GetItemsParams),
     responses(
-        (status = 200, description = "Return all items in the database", body = Vec<Item>),
+        (status = 200, description = "Return one page of items matching the filters", body = ItemsPage),
+        (status = 400, description = "Invalid cursor or price range", body = ErrorResponse),
+        (status = 403, description = "Only admins can list deleted items", body = ErrorResponse),
         (status = 500, description = "Error in the database query", body = ErrorResponse)
     )
 )]
-pub async fn get_items(Extension(database): Extension<Database>) -> (
-    StatusCode,
-    Json<ApiResponse<Vec<Item>>>,
-) {
-    match database.arango_db.aql_str("FOR item IN Item RETURN item").await {
-        Ok(items) => (StatusCode::OK, Json(ApiResponse::Success(items))),
-        Err(e) =>
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                generate_error(format!("Error getting items: {}", e.to_string()).as_str()),
-            ),
+pub async fn get_items(
+    State(state): State<AppState>,
+    claims: Option<Claims>,
+    Query(params): Query<GetItemsParams>
+) -> ApiResult<Page<Item>> {
+    let query = params.into_query()?;
+    if query.include_deleted && !claims.is_some_and(|claims| claims.is_admin()) {
+        return Err(ApiError::Forbidden("Only admins can list deleted items".to_string()));
     }
+
+    let page = state.items.find_page(&query).await?;
+
+    Ok(
+        Json(
+            ApiResponse::Success(Page {
+                items: page.items,
+                total: page.total,
+                next_cursor: page.next.map(|cursor| cursor.encode()),
+            })
+        )
+    )
 }
 
 #[utoipa::path(
@@ -121,123 +307,110 @@ pub async fn get_items(Extension(database): Extension<Database>) -> (
     path = "/api/add_item",
     request_body = AddItemReq,
     responses(
-        (status = 200, description = "Return created item", body = Item),
         (
-            status = 500,
-            description = "Error parsing request body. Missing or malformatted attributes",
-            body = ErrorResponse,
+            status = 200,
+            description = "Return created item, with its revision in the ETag header",
+            body = Item,
         ),
+        (status = 403, description = "Only vendors can list items", body = ErrorResponse),
+        (status = 409, description = "Item name is already used", body = ErrorResponse),
+        (status = 422, description = "One or more fields are invalid", body = ErrorResponse),
         (status = 500, description = "Error in the database query", body = ErrorResponse)
     )
 )]
 pub async fn add_item(
-    Extension(database): Extension<Database>,
-    Json(payload): Json<AddItemReq>
-) -> (StatusCode, Json<ApiResponse<Item>>) {
+    State(state): State<AppState>,
+    claims: Claims,
+    Valid(payload): Valid<AddItemReq>
+) -> TaggedResult<Item> {
     let name: String = payload.name;
-    let user_id: String = payload.user_id;
-    let description: String = payload.description;
-    let price: f64 = payload.price;
-    let quantity: i64 = payload.quantity;
-
-    let mut bind_vars: HashMap<&str, Value> = HashMap::new();
-    bind_vars.insert("name", Value::String(name.clone()));
-    bind_vars.insert("user_id", Value::String(user_id));
-    bind_vars.insert("description", Value::String(description));
-    bind_vars.insert("price", Value::Number(Number::from_f64(price).unwrap()));
-    bind_vars.insert("quantity", Value::Number(Number::from(quantity)));
-
-    let query =
-        "
-    INSERT {
-        name: @name,
-        user_id: @user_id,
-        description: @description,
-        price: @price,
-        quantity: @quantity
-    } INTO Item
-    RETURN NEW
-    ";
-
-    let result: Result<Vec<Item>, arangors::ClientError> = database.arango_db.aql_bind_vars(
-        query,
-        bind_vars
-    ).await;
-
-    match result {
-        Ok(items) => {
-            if let Some(item) = items.first() {
-                (StatusCode::OK, Json(ApiResponse::Success(item.clone())))
-            } else {
-                (StatusCode::INTERNAL_SERVER_ERROR, generate_error("Error creating item"))
+
+    let item = NewItem {
+        name: name.clone(),
+        user_id: claims.key,
+        description: payload.description,
+        price: payload.price,
+        quantity: payload.quantity,
+    };
+
+    let item = state.items.insert(item).await.map_err(|err| {
+        match ApiError::from(err) {
+            ApiError::Conflict(_) => {
+                ApiError::Conflict(format!("Error creating item: Name {} already used", name))
             }
+            err => err,
         }
-        Err(e) => {
-            eprintln!("{:?}", e.to_string());
-            (
-                StatusCode::BAD_REQUEST,
-                generate_error(format!("Error creating item: Name {} already used", name).as_str()),
-            )
-        }
-    }
+    })?;
+
+    Ok(Tagged::new(item._rev.clone(), item))
 }
 
 #[utoipa::path(
     put,
     path = "/api/edit_item",
     request_body = UpdateItemReq,
+    params(
+        (
+            "If-Match" = Option<String>,
+            Header,
+            description = "Quoted revision the edit is based on, as returned in the ETag header",
+        )
+    ),
     responses(
-        (status = 200, description = "Return created item", body = Item),
+        (
+            status = 200,
+            description = "Return edited item, with its new revision in the ETag header",
+            body = Item,
+        ),
+        (
+            status = 400,
+            description = "If-Match is malformed or disagrees with _rev",
+            body = ErrorResponse,
+        ),
+        (status = 403, description = "Item is listed by another vendor", body = ErrorResponse),
         (
             status = 404,
             description = "Error editing item. Item does not exist in database",
             body = ErrorResponse,
         ),
+        (status = 409, description = "Item name is already used", body = ErrorResponse),
+        (
+            status = 412,
+            description = "Item changed since the given revision. Returns the current item and its ETag",
+            body = ErrorResponse,
+        ),
+        (status = 422, description = "One or more fields are invalid", body = ErrorResponse),
         (status = 500, description = "Error in the database query", body = ErrorResponse)
     )
 )]
 pub async fn edit_item(
-    Extension(database): Extension<Database>,
-    Json(payload): Json<UpdateItemReq>
-) -> (StatusCode, Json<ApiResponse<Value>>) {
+    State(state): State<AppState>,
+    claims: Claims,
+    headers: HeaderMap,
+    Valid(payload): Valid<UpdateItemReq>
+) -> TaggedResult<Item> {
     let id = payload.id;
-    let name = payload.name;
-    let description = payload.description;
-    let price = payload.price;
-    let quantity = payload.quantity;
+    let rev = expected_rev(&headers, payload.rev)?;
+
+    ensure_owner(&state, &claims, &id).await?;
 
     let params = ItemUpdate {
-        name,
-        description,
-        price,
-        quantity,
+        name: payload.name,
+        description: payload.description,
+        price: payload.price,
+        quantity: payload.quantity,
+        rev,
     };
 
-    let patch = json!(&params);
-
-    let collection = database.arango_db.collection("Item").await.unwrap();
-    let response = collection.update_document(
-        id.to_owned().as_str(),
-        patch,
-        UpdateOptions::builder().return_new(true).build()
-    ).await;
-
-    match response {
-        Ok(response) => {
-            if let Some(item) = response.new_doc() {
-                (StatusCode::OK, Json(ApiResponse::Success(item.clone())))
-            } else {
-                (StatusCode::INTERNAL_SERVER_ERROR, generate_error("Error updating item"))
-            }
+    let item = match state.items.update(&id, params).await {
+        Err(DatabaseError::PreconditionFailed(message)) => {
+            let current = state.items.find_by_key(&id).await?;
+            return Err(ApiError::PreconditionFailed { message, current: Some(json!(current)) });
         }
-        Err(e) => {
-            eprintln!("Error updating item: {}", e.to_string());
-            (
-                StatusCode::NOT_FOUND,
-                generate_error(format!("Error updating item: id {} not found", id).as_str()),
-            )
-        }
-    }
+        result => result?,
+    };
+
+    Ok(Tagged::new(item._rev.clone(), item))
 }
 
 #[utoipa::path(
@@ -245,7 +418,12 @@ pub async fn edit_item(
     path = "/api/delete_item",
     request_body = DeleteItemReq,
     responses(
-        (status = 200, description = "Return deleted item name", body = String),
+        (
+            status = 200,
+            description = "Return deleted item name. The item is soft-deleted: orders keep referencing it and an admin can restore it until it is purged",
+            body = String,
+        ),
+        (status = 403, description = "Item is listed by another vendor", body = ErrorResponse),
         (
             status = 404,
             description = "Error deleting item. Item does not exist in database",
@@ -255,31 +433,34 @@ pub async fn edit_item(
     )
 )]
 pub async fn delete_item(
-    Extension(database): Extension<Database>,
+    State(state): State<AppState>,
+    claims: Claims,
     Json(payload): Json<DeleteItemReq>
-) -> (StatusCode, Json<ApiResponse<Value>>) {
-    let id = payload.id;
-    let collection = database.arango_db.collection("Item").await.unwrap();
-
-    match
-        collection.remove_document(
-            id.to_owned().as_str(),
-            RemoveOptions::builder().return_old(true).build(),
-            None
-        ).await
-    {
-        Ok(res) => {
-            if let Some(old_doc) = res.old_doc() {
-                let item: &Item = old_doc;
-                (StatusCode::OK, Json(ApiResponse::Success(json!({ "name": item.name }))))
-            } else {
-                (StatusCode::NOT_FOUND, generate_error("Item to delete not found"))
-            }
+) -> ApiResult<Value> {
+    ensure_owner(&state, &claims, &payload.id).await?;
+
+    let deletion = Deletion {
+        deleted_at: Local::now().naive_local(),
+        deleted_by: claims.key,
+    };
+    let item = state.items.remove(&payload.id, deletion).await?;
+
+    Ok(Json(ApiResponse::Success(json!({ "name": item.name }))))
+}
+
+async fn ensure_owner(state: &AppState, claims: &Claims, id: &str) -> Result<(), ApiError> {
+    let item = state.items.find_by_key(id).await.map_err(|err| {
+        match ApiError::from(err) {
+            ApiError::NotFound(_) => ApiError::NotFound(format!("Item {} not found", id)),
+            err => err,
         }
-        Err(e) =>
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                generate_error(format!("Error deleting item: {}", e.to_string()).as_str()),
-            ),
+    })?;
+
+    if item.user_id != claims.key {
+        return Err(
+            ApiError::Forbidden("Only the vendor who listed the item can modify it".to_string())
+        );
     }
+
+    Ok(())
 }
\ No newline at end of file
@@ -1,56 +1,360 @@
 use crate::{
-    api::{ generate_error, ApiResponse },
-    constants::PROD_CONFIG_PATH,
-    db::Database,
-    models::User,
-    toml_env::Config,
+    api::{ ApiError, ApiResponse, ApiResult },
+    constants::{
+        ACCESS_TOKEN_TTL_MINUTES,
+        EMAIL_VERIFICATION_TTL_HOURS,
+        MFA_CHALLENGE_TTL_MINUTES,
+        REFRESH_TOKEN_TTL_DAYS,
+    },
+    models::{ Role, User },
+    repositories::tokens::NewRefreshToken,
+    state::AppState,
+    toml_env::{ Config, JwtKeyConfig },
 };
 use axum::{
-    extract::Path,
-    http::{ Request, StatusCode },
+    async_trait,
+    extract::{ FromRequestParts, Path, State },
+    http::{ request::Parts, Request },
     middleware::Next,
     response::Response,
-    Extension,
     Json,
 };
 use jsonwebtoken::{
     decode,
+    decode_header,
     encode,
     errors::{ Error, ErrorKind },
+    jwk::{
+        AlgorithmParameters,
+        CommonParameters,
+        EllipticCurve,
+        Jwk,
+        JwkSet,
+        OctetKeyPairParameters,
+        OctetKeyPairType,
+        PublicKeyUse,
+        RSAKeyParameters,
+        RSAKeyType,
+    },
+    Algorithm,
     DecodingKey,
     EncodingKey,
     Header,
     Validation,
 };
-use serde::{ Deserialize, Serialize };
-use std::collections::HashMap;
+use base64::{ engine::general_purpose::URL_SAFE_NO_PAD, Engine };
+use rand::RngCore;
+use serde::{ de::DeserializeOwned, Deserialize, Serialize };
+use simple_asn1::ASN1Block;
+use sha2::{ Digest, Sha256 };
 use utoipa::ToSchema;
 
 use super::auth::AuthRes;
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct Claims {
-    sub: String,
-    iat: usize,
-    exp: usize,
+/// Audience of email verification tokens, so they cannot pass for any other token.
+const VERIFY_EMAIL_AUDIENCE: &str = "verify_email";
+/// Audience of the challenges of logins waiting for a TOTP code.
+const MFA_CHALLENGE_AUDIENCE: &str = "mfa_challenge";
+
+/// Object identifiers of the public key algorithms `[[Jwt.Keys]]` can hold.
+const RSA_OID: [u64; 7] = [1, 2, 840, 113549, 1, 1, 1];
+const ED25519_OID: [u64; 4] = [1, 3, 101, 112];
+
+/// A key tokens are verified with. Only keys with a private half can sign.
+struct JwtKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    encoding: Option<EncodingKey>,
+    decoding: DecodingKey,
+}
+
+/// Signing and verification keys loaded once at startup: either the configured secret, or the
+/// `[[Jwt.Keys]]` key pairs. Tokens signed with a key pair carry its `kid`.
+pub struct JwtKeys {
+    keys: Vec<JwtKey>,
+    active: usize,
+    jwks: JwkSet,
+}
+
+impl JwtKeys {
+    pub fn from_secret(secret: &str) -> Self {
+        let key = JwtKey {
+            kid: None,
+            algorithm: Algorithm::HS256,
+            encoding: Some(EncodingKey::from_secret(secret.as_ref())),
+            decoding: DecodingKey::from_secret(secret.as_ref()),
+        };
+
+        Self { keys: vec![key], active: 0, jwks: JwkSet { keys: Vec::new() } }
+    }
+
+    /// Loads the `[[Jwt.Keys]]` key pairs, falling back to the server secret when there are none.
+    pub fn from_config(config: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        if config.jwt.keys.is_empty() {
+            return Ok(Self::from_secret(&config.server.secret));
+        }
+
+        let active = match &config.jwt.active {
+            Some(kid) =>
+                config.jwt.keys
+                    .iter()
+                    .position(|key| &key.kid == kid)
+                    .ok_or_else(|| format!("active key {} is not configured", kid))?,
+            None => 0,
+        };
+
+        let mut keys = Vec::new();
+        let mut jwks = Vec::new();
+        for (index, config) in config.jwt.keys.iter().enumerate() {
+            if jwks.iter().any(|jwk: &Jwk| jwk.common.key_id.as_ref() == Some(&config.kid)) {
+                return Err(format!("key {} is configured twice", config.kid).into());
+            }
+            if index == active && config.private_key.is_none() {
+                return Err(format!("active key {} has no private_key", config.kid).into());
+            }
+
+            let (key, jwk) = load_key(config).map_err(|err| {
+                format!("key {}: {}", config.kid, err)
+            })?;
+            keys.push(key);
+            jwks.push(jwk);
+        }
+
+        Ok(Self { keys, active, jwks: JwkSet { keys: jwks } })
+    }
+
+    /// Public keys of the key pairs, for services that verify tokens themselves. Empty when
+    /// tokens are signed with the secret, which must never be published.
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+
+    /// Signs `claims` with the active key.
+    pub fn encode<T: Serialize>(&self, claims: &T) -> Result<String, Error> {
+        let key = &self.keys[self.active];
+        let mut header = Header::new(key.algorithm);
+        header.kid = key.kid.clone();
+
+        encode(&header, claims, key.encoding.as_ref().expect("active key can sign"))
+    }
+
+    /// Verifies `token` with the key named by its `kid`, which must sign with the algorithm the
+    /// key is configured for.
+    pub fn decode<T: DeserializeOwned>(
+        &self,
+        token: &str,
+        audience: Option<&str>
+    ) -> Result<T, Error> {
+        let header = decode_header(token)?;
+        let key = self.keys
+            .iter()
+            .find(|key| key.kid == header.kid)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidToken))?;
+
+        let mut validation = Validation::new(key.algorithm);
+        if let Some(audience) = audience {
+            validation.set_audience(&[audience]);
+        }
+
+        decode::<T>(token, &key.decoding, &validation).map(|data| data.claims)
+    }
 }
 
-pub fn generate_jwt(sub: &String, secret: &String) -> Result<String, jsonwebtoken::errors::Error> {
-    let header = Header::default();
-    let claims = Claims {
-        sub: sub.to_string(),
-        iat: chrono::Utc::now().timestamp() as usize,
-        exp: (chrono::Utc::now() + chrono::Duration::minutes(15)).timestamp() as usize,
+/// Reads a configured key pair, checking that its private key belongs to its public key.
+fn load_key(config: &JwtKeyConfig) -> Result<(JwtKey, Jwk), Box<dyn std::error::Error>> {
+    let read = |path: &str| std::fs::read(path).map_err(|err| format!("{}: {}", path, err));
+
+    let jwk = public_jwk(config, &read(&config.public_key)?)?;
+    let key = JwtKey {
+        kid: Some(config.kid.clone()),
+        algorithm: config.algorithm,
+        encoding: None,
+        decoding: DecodingKey::from_jwk(&jwk)?,
     };
-    encode(&header, &claims, &EncodingKey::from_secret(secret.as_ref()))
+
+    let encoding = match &config.private_key {
+        Some(path) if config.algorithm == Algorithm::EdDSA => {
+            EncodingKey::from_ed_pem(&read(path)?)?
+        }
+        Some(path) => EncodingKey::from_rsa_pem(&read(path)?)?,
+        None => {
+            return Ok((key, jwk));
+        }
+    };
+
+    let claims = serde_json::json!({ "sub": config.kid, "exp": u64::MAX });
+    let probe = encode(&Header::new(key.algorithm), &claims, &encoding)?;
+    decode::<serde_json::Value>(&probe, &key.decoding, &Validation::new(key.algorithm)).map_err(
+        |_| "private_key does not belong to public_key"
+    )?;
+
+    Ok((JwtKey { encoding: Some(encoding), ..key }, jwk))
 }
 
-pub fn validate_jwt(token: &String, secret: &String) -> Result<bool, Error> {
-    let validation = Validation::new(jsonwebtoken::Algorithm::HS256);
-    let result = decode::<Claims>(&token, &DecodingKey::from_secret(secret.as_ref()), &validation);
+/// Builds the JWK of a PEM encoded SubjectPublicKeyInfo, checking that the key can sign with the
+/// configured algorithm.
+fn public_jwk(config: &JwtKeyConfig, pem: &[u8]) -> Result<Jwk, Box<dyn std::error::Error>> {
+    let invalid = || format!("{} is not a PEM encoded public key", config.public_key);
+
+    let blocks = simple_asn1::from_der(&pem::parse(pem)?.contents)?;
+    let (oid, key) = match blocks.as_slice() {
+        [ASN1Block::Sequence(_, spki)] =>
+            match spki.as_slice() {
+                [ASN1Block::Sequence(_, id), ASN1Block::BitString(_, _, key)] =>
+                    match id.first() {
+                        Some(ASN1Block::ObjectIdentifier(_, oid)) => (oid.as_vec::<u64>()?, key),
+                        _ => {
+                            return Err(invalid().into());
+                        }
+                    }
+                _ => {
+                    return Err(invalid().into());
+                }
+            }
+        _ => {
+            return Err(invalid().into());
+        }
+    };
+
+    let algorithm = match config.algorithm {
+        | Algorithm::RS256
+        | Algorithm::RS384
+        | Algorithm::RS512
+        | Algorithm::PS256
+        | Algorithm::PS384
+        | Algorithm::PS512 if oid == RSA_OID => {
+            let (n, e) = match simple_asn1::from_der(key)?.as_slice() {
+                [ASN1Block::Sequence(_, parts)] =>
+                    match parts.as_slice() {
+                        [ASN1Block::Integer(_, n), ASN1Block::Integer(_, e)] => {
+                            (n.clone(), e.clone())
+                        }
+                        _ => {
+                            return Err(invalid().into());
+                        }
+                    }
+                _ => {
+                    return Err(invalid().into());
+                }
+            };
+
+            AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: URL_SAFE_NO_PAD.encode(n.to_bytes_be().1),
+                e: URL_SAFE_NO_PAD.encode(e.to_bytes_be().1),
+            })
+        }
+        Algorithm::EdDSA if oid == ED25519_OID => {
+            AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(key),
+            })
+        }
+        algorithm => {
+            let msg = format!("{:?} cannot be used with {}", algorithm, config.public_key);
+            return Err(msg.into());
+        }
+    };
+
+    Ok(Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            algorithm: Some(config.algorithm),
+            key_id: Some(config.kid.clone()),
+            ..Default::default()
+        },
+        algorithm,
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RefreshReq {
+    refresh_token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Claims {
+    pub sub: String,
+    pub key: String,
+    pub role: Role,
+    pub iat: usize,
+    pub exp: usize,
+    /// Token version of the user when the token was issued.
+    #[serde(default)]
+    pub ver: u64,
+}
+
+impl Claims {
+    pub fn new(user: &User) -> Self {
+        Self {
+            sub: user.email.to_owned(),
+            key: user._key.to_owned(),
+            role: user.role.to_owned(),
+            iat: chrono::Utc::now().timestamp() as usize,
+            exp: (
+                chrono::Utc::now() + chrono::Duration::minutes(ACCESS_TOKEN_TTL_MINUTES)
+            ).timestamp() as usize,
+            ver: user.token_version,
+        }
+    }
+
+    pub fn is_vendor(&self) -> bool {
+        self.role == Role::VENDOR
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.role == Role::ADMIN
+    }
+}
+
+/// Claims of the signed links that verify an email address. The address is part of the token, so
+/// links stop working once the user changes it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationClaims {
+    /// Key of the user.
+    pub sub: String,
+    pub email: String,
+    pub aud: String,
+    pub exp: usize,
+}
+
+/// Claims of the challenge a login hands out instead of tokens when the user has two-factor
+/// authentication enabled. It proves the password was right, and is exchanged for tokens together
+/// with a TOTP or recovery code.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChallengeClaims {
+    /// Key of the user.
+    pub sub: String,
+    pub aud: String,
+    pub exp: usize,
+    /// Token version of the user when the challenge was issued.
+    #[serde(default)]
+    pub ver: u64,
+}
+
+/// Extracts the claims inserted by `jwt_middleware`, rejecting requests that carry none.
+#[async_trait]
+impl<S> FromRequestParts<S> for Claims where S: Send + Sync {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts.extensions
+            .get::<Claims>()
+            .cloned()
+            .ok_or_else(|| ApiError::Unauthorized("Authentication required".to_string()))
+    }
+}
+
+pub fn generate_jwt(user: &User, keys: &JwtKeys) -> Result<String, jsonwebtoken::errors::Error> {
+    keys.encode(&Claims::new(user))
+}
+
+pub fn validate_jwt(token: &str, keys: &JwtKeys) -> Result<Claims, Error> {
+    let result = keys.decode::<Claims>(token, None);
 
     match result {
-        Ok(_) => Ok(true),
+        Ok(claims) => Ok(claims),
         Err(e) => {
             if e.kind() == &ErrorKind::ExpiredSignature {
                 Err(ErrorKind::ExpiredSignature.into())
@@ -61,106 +365,276 @@ pub fn validate_jwt(token: &String, secret: &String) -> Result<bool, Error> {
     }
 }
 
+/// Validates `token`, then checks that it was not revoked since it was issued: access tokens of
+/// deleted users, and those issued before the last password change, are refused.
+pub async fn authenticate(state: &AppState, token: &str) -> Result<Claims, ApiError> {
+    let claims = validate_jwt(token, &state.keys).map_err(|err| {
+        eprintln!("Error validating JWT token: {:?}", err.to_string());
+        ApiError::Unauthorized("Invalid JWT Token".to_string())
+    })?;
+
+    match state.users.find_by_key(&claims.key).await? {
+        Some(user) if user.token_version == claims.ver => Ok(claims),
+        _ => Err(ApiError::Unauthorized("JWT Token was revoked".to_string())),
+    }
+}
+
+pub fn generate_verification_token(user: &User, keys: &JwtKeys) -> Result<String, Error> {
+    let claims = VerificationClaims {
+        sub: user._key.to_owned(),
+        email: user.email.to_owned(),
+        aud: VERIFY_EMAIL_AUDIENCE.to_string(),
+        exp: (
+            chrono::Utc::now() + chrono::Duration::hours(EMAIL_VERIFICATION_TTL_HOURS)
+        ).timestamp() as usize,
+    };
+    keys.encode(&claims)
+}
+
+pub fn validate_verification_token(
+    token: &str,
+    keys: &JwtKeys
+) -> Result<VerificationClaims, Error> {
+    keys.decode::<VerificationClaims>(token, Some(VERIFY_EMAIL_AUDIENCE))
+}
+
+pub fn generate_challenge_token(user: &User, keys: &JwtKeys) -> Result<String, Error> {
+    let claims = ChallengeClaims {
+        sub: user._key.to_owned(),
+        aud: MFA_CHALLENGE_AUDIENCE.to_string(),
+        exp: (
+            chrono::Utc::now() + chrono::Duration::minutes(MFA_CHALLENGE_TTL_MINUTES)
+        ).timestamp() as usize,
+        ver: user.token_version,
+    };
+    keys.encode(&claims)
+}
+
+pub fn validate_challenge_token(token: &str, keys: &JwtKeys) -> Result<ChallengeClaims, Error> {
+    keys.decode::<ChallengeClaims>(token, Some(MFA_CHALLENGE_AUDIENCE))
+}
+
+/// Keeps users who have not verified their email from getting tokens when the server requires
+/// it before login.
+pub fn check_login_allowed(state: &AppState, user: &User) -> Result<(), ApiError> {
+    if state.config.server.require_verified_email.blocks_login() && !user.email_verified {
+        return Err(ApiError::Forbidden("Verify your email address to log in".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Generates an opaque, URL-safe token for refresh and password reset links. Only its hash is ever
+/// persisted.
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Issues an access token and a refresh token for `user`. Rotated tokens keep the family of the
+/// token they replace so that reuse of any ancestor can revoke the whole chain.
+pub async fn issue_tokens(
+    state: &AppState,
+    user: User,
+    family_id: Option<String>
+) -> Result<AuthRes, ApiError> {
+    let token = generate_jwt(&user, &state.keys).map_err(|err| {
+        ApiError::Internal(format!("Error generating token: {}", err))
+    })?;
+    let refresh_token = generate_token();
+    let now = chrono::Utc::now().naive_utc();
+
+    let stored = NewRefreshToken {
+        user_id: user._key.to_owned(),
+        family_id: family_id.unwrap_or_else(generate_token),
+        token_hash: hash_token(&refresh_token),
+        created_at: now,
+        expires_at: now + chrono::Duration::days(REFRESH_TOKEN_TTL_DAYS),
+        revoked: false,
+    };
+
+    state.tokens.insert(stored).await?;
+
+    Ok(AuthRes::new(user, token, refresh_token))
+}
+
 #[utoipa::path(
-    get,
+    post,
     path = "/api/auth/refresh",
-    params(("email" = String, Path, description = "user email")),
+    request_body = RefreshReq,
     responses(
-        (status = 200, description = "Return authenticated user", body = AuthRes),
-        (status = 500, description = "Error generating jwt", body = ErrorResponse)
+        (status = 200, description = "Return authenticated user with rotated tokens", body = AuthRes),
+        (status = 401, description = "Refresh token is invalid, expired or reused", body = ErrorResponse),
+        (status = 403, description = "Email address must be verified first", body = ErrorResponse),
+        (status = 500, description = "Error generating tokens", body = ErrorResponse)
     )
 )]
 pub async fn refresh(
-    Extension(database): Extension<Database>,
-    Extension(secret): Extension<String>,
-    Path(email): Path<String>
-) -> (StatusCode, Json<ApiResponse<AuthRes>>) {
-    let users: Vec<User> = database.arango_db
-        .aql_bind_vars(
-            "FOR user IN User FILTER user.email == @email RETURN user",
-            HashMap::from([("email", email.to_owned().into())])
-        ).await
-        .unwrap();
-
-    let token = generate_jwt(&email, &secret);
-
-    let response = token
-        .map(|jwt| {
-            users.first().map_or_else(
-                || {
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        generate_error("Error generating token: no user found"),
-                    )
-                },
-                |user| {
-                    (StatusCode::OK, Json(ApiResponse::Success(AuthRes::new(user.to_owned(), jwt))))
-                }
-            )
-        })
-        .unwrap_or_else(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                generate_error(format!("Error generating token: {}", e.to_string()).as_str()),
-            )
-        });
+    State(state): State<AppState>,
+    Json(payload): Json<RefreshReq>
+) -> ApiResult<AuthRes> {
+    let invalid = || ApiError::Unauthorized("Invalid refresh token".to_string());
+
+    let stored = state.tokens
+        .find_by_hash(&hash_token(&payload.refresh_token)).await?
+        .ok_or_else(invalid)?;
+
+    if !state.tokens.revoke(&stored._key).await? {
+        eprintln!("Refresh token reuse detected for user {}", stored.user_id);
+        if let Err(e) = state.tokens.revoke_family(&stored.family_id).await {
+            eprintln!("Error revoking token family {}: {}", stored.family_id, e);
+        }
+        let msg = "Refresh token was already used, please log in again";
+        return Err(ApiError::Unauthorized(msg.to_string()));
+    }
 
-    response
+    if stored.expires_at < chrono::Utc::now().naive_utc() {
+        return Err(ApiError::Unauthorized("Refresh token expired".to_string()));
+    }
+
+    let user = state.users.find_by_key(&stored.user_id).await?.ok_or_else(invalid)?;
+    check_login_allowed(&state, &user)?;
+    let auth = issue_tokens(&state, user, Some(stored.family_id)).await?;
+
+    Ok(Json(ApiResponse::Success(auth)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/logout",
+    request_body = RefreshReq,
+    responses(
+        (status = 200, description = "Refresh token and its rotations are revoked", body = bool),
+        (status = 500, description = "Error revoking tokens", body = ErrorResponse)
+    )
+)]
+pub async fn logout(
+    State(state): State<AppState>,
+    Json(payload): Json<RefreshReq>
+) -> ApiResult<bool> {
+    if let Some(stored) = state.tokens.find_by_hash(&hash_token(&payload.refresh_token)).await? {
+        state.tokens.revoke_family(&stored.family_id).await?;
+    }
+
+    Ok(Json(ApiResponse::Success(true)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    responses(
+        (status = 200, description = "JWK Set of the public keys tokens are signed with. Empty when tokens are signed with the server secret", body = Object)
+    )
+)]
+pub async fn jwks(State(state): State<AppState>) -> Json<JwkSet> {
+    Json(state.keys.jwks().clone())
 }
 
 pub async fn validate_jwt_route(
-    Extension(secret): Extension<String>,
+    State(state): State<AppState>,
     Path(token): Path<String>
-) -> (StatusCode, Json<ApiResponse<bool>>) {
-    match validate_jwt(&token, &secret) {
-        Ok(_) => (StatusCode::OK, Json(ApiResponse::Success(true))),
-        Err(e) => {
-            eprintln!("Error validating JWT token: {:?}", e.to_string());
-            (StatusCode::UNAUTHORIZED, generate_error("Invalid JWT Token"))
+) -> ApiResult<bool> {
+    authenticate(&state, &token).await?;
+
+    Ok(Json(ApiResponse::Success(true)))
+}
+
+pub async fn jwt_middleware<B>(
+    State(state): State<AppState>,
+    mut req: Request<B>,
+    next: Next<B>
+) -> Result<Response, ApiError> {
+    let token = req
+        .headers()
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| header.split_whitespace().nth(1));
+
+    let claims = match token {
+        Some(token) => Some(authenticate(&state, token).await),
+        None => None,
+    };
+
+    match claims {
+        Some(Ok(claims)) => {
+            req.extensions_mut().insert(claims);
+            Ok(next.run(req).await)
+        }
+        Some(Err(ApiError::Unauthorized(_))) | None if state.env.is_dev() => {
+            Ok(next.run(req).await)
+        }
+        Some(Err(err)) => Err(err),
+        None => {
+            eprintln!("Error validating token. Bearer token missing in request");
+            Err(ApiError::Unauthorized("Bearer token missing".to_string()))
         }
     }
 }
 
-pub async fn jwt_middleware<B>(req: Request<B>, next: Next<B>) -> Result<Response, StatusCode> {
-    let parsed_config = match Config::parse(PROD_CONFIG_PATH) {
-        Ok(config) => config,
-        Err(err) => {
-            eprintln!("Error parsing configuration: {:?}", err);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+/// Route guard that only lets authenticated vendors through, once they have enabled two-factor
+/// authentication when the server requires it. Must run after `jwt_middleware`.
+pub async fn vendor_guard<B>(
+    State(state): State<AppState>,
+    req: Request<B>,
+    next: Next<B>
+) -> Result<Response, ApiError> {
+    let unauthorized = || ApiError::Unauthorized("Authentication required".to_string());
+    let claims = match req.extensions().get::<Claims>() {
+        Some(claims) if claims.is_vendor() => claims,
+        Some(_) => {
+            return Err(ApiError::Forbidden("Only vendors can perform this action".to_string()));
+        }
+        None => {
+            return Err(unauthorized());
         }
     };
 
-    if parsed_config.server.env.is_dev() {
-        return Ok(next.run(req).await);
+    if state.config.two_factor.require_for_vendors {
+        let user = state.users.find_by_key(&claims.key).await?.ok_or_else(unauthorized)?;
+        if !user.totp_enabled {
+            let msg = "Enable two-factor authentication to manage items";
+            return Err(ApiError::Forbidden(msg.to_string()));
+        }
     }
 
-    let secret = parsed_config.server.secret;
+    Ok(next.run(req).await)
+}
 
-    let auth_header = req
-        .headers()
-        .get(axum::http::header::AUTHORIZATION)
-        .and_then(|header| header.to_str().ok());
+/// Route guard that only lets authenticated admins through. Must run after `jwt_middleware`.
+pub async fn admin_guard<B>(req: Request<B>, next: Next<B>) -> Result<Response, ApiError> {
+    match req.extensions().get::<Claims>() {
+        Some(claims) if claims.is_admin() => Ok(next.run(req).await),
+        Some(_) => Err(ApiError::Forbidden("Only admins can perform this action".to_string())),
+        None => Err(ApiError::Unauthorized("Authentication required".to_string())),
+    }
+}
 
-    let token = if let Some(auth_header) = auth_header {
-        auth_header.split_whitespace().nth(1)
-    } else {
-        eprintln!("Error validating token. Bearer token missing in request");
-        return Err(StatusCode::UNAUTHORIZED);
-    };
+/// Route guard that keeps users who have not verified their email from ordering, when the server
+/// requires it. Must run after `jwt_middleware`.
+pub async fn verified_guard<B>(
+    State(state): State<AppState>,
+    req: Request<B>,
+    next: Next<B>
+) -> Result<Response, ApiError> {
+    if !state.config.server.require_verified_email.blocks_ordering() {
+        return Ok(next.run(req).await);
+    }
 
-    match token {
-        Some(tok) =>
-            match validate_jwt(&tok.to_string(), &secret) {
-                Ok(_) => {
-                    return Ok(next.run(req).await);
-                }
-                Err(e) => {
-                    eprintln!("Error validating JWT token: {:?}", e.to_string());
-                    return Err(StatusCode::UNAUTHORIZED);
-                }
-            }
-        None => {
-            return Err(StatusCode::UNAUTHORIZED);
-        }
+    let unauthorized = || ApiError::Unauthorized("Authentication required".to_string());
+    let claims = req.extensions().get::<Claims>().ok_or_else(unauthorized)?;
+    let user = state.users.find_by_key(&claims.key).await?.ok_or_else(unauthorized)?;
+
+    if !user.email_verified {
+        return Err(ApiError::Forbidden("Verify your email address to place orders".to_string()));
     }
+
+    Ok(next.run(req).await)
 }
\ No newline at end of file
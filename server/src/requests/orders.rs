@@ -1,23 +1,29 @@
-use crate::api::{ generate_error, ApiResponse };
-use crate::db::Database;
-use crate::models::{ Item, Order };
-use arangors::document::options::UpdateOptions;
-use arangors::Document;
-use axum::{ extract::Path, http::StatusCode, Extension, Json };
+use crate::api::{ ApiError, ApiResponse, ApiResult };
+use crate::db::DatabaseError;
+use crate::models::{ Deletion, Order, OrderStatus, StatusChange };
+use crate::repositories::orders::{ NewOrder, NewOrderLine };
+use crate::state::AppState;
+use super::jwt::Claims;
+use super::validation::{ not_blank, Valid };
+use axum::{ extract::{ Path, Query, State }, Json };
 use chrono::{ Local, NaiveDateTime };
 use serde::{ Deserialize, Serialize };
-use serde_json::{ to_value, Value };
-use std::collections::HashMap;
-use utoipa::ToSchema;
+use utoipa::{ IntoParams, ToSchema };
+use validator::Validate;
 
-#[derive(Deserialize, Debug, Serialize, ToSchema)]
+#[derive(Deserialize, Debug, Serialize, ToSchema, Validate)]
 pub struct AddOrderReq {
-    user_id: String,
+    #[validate(custom = "not_blank")]
     item_id: String,
-    item_name: String,
+    #[validate(range(min = 1, message = "must be at least 1"))]
     quantity: i64,
-    price: f64,
-    quantity_diff: i64,
+}
+
+#[derive(Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetOrdersParams {
+    /// Also list soft-deleted orders. Admins only
+    include_deleted: Option<bool>,
 }
 
 #[derive(Deserialize, Debug, Serialize, ToSchema)]
@@ -25,49 +31,54 @@ pub struct DeleteOrderReq {
     user_id: String,
 }
 
-#[derive(Deserialize, Debug, Serialize, ToSchema)]
-pub struct ItemQuantityUpdate {
-    quantity: i64,
+#[derive(Deserialize, Debug, Serialize, ToSchema, Validate)]
+pub struct UpdateOrderStatusReq {
+    status: OrderStatus,
 }
 
 #[utoipa::path(
     get,
     path = "/api/get_orders/{user_id}",
     params(
-        ("user_id" = String, Path, description = "User ID associated with the order")
+        ("user_id" = String, Path, description = "User ID associated with the order"),
+        GetOrdersParams
     ),
     responses(
-        (status = 200, description = "Return list of orders based on user id", body = Vec<Order>),
+        (
+            status = 200,
+            description = "Return list of orders based on user id. Orders placed before multi-line orders are returned with a single line",
+            body = Vec<Order>,
+        ),
+        (
+            status = 403,
+            description = "Users can only list their own orders, and only admins can list deleted orders",
+            body = ErrorResponse,
+        ),
         (status = 404, description = "No orders found", body = ErrorResponse),
         (status = 500, description = "Error in the database query", body = ErrorResponse)
     )
 )]
 pub async fn get_orders(
-    Extension(database): Extension<Database>,
-    Path(user_id): Path<String>
-) -> (StatusCode, Json<ApiResponse<Vec<Order>>>) {
-    let mut bind_vars: HashMap<&str, Value> = HashMap::new();
-    bind_vars.insert("user_id", user_id.to_owned().into());
-
-    match
-        database.arango_db.aql_bind_vars(
-            "FOR order IN Order FILTER order.user_id == @user_id RETURN order",
-            bind_vars
-        ).await
-    {
-        Ok(orders) => {
-            if orders.is_empty() {
-                (StatusCode::NOT_FOUND, generate_error("No orders found"))
-            } else {
-                (StatusCode::OK, Json(ApiResponse::Success(orders)))
-            }
-        }
-        Err(e) =>
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                generate_error(format!("Error getting item: {}", e.to_string()).as_str()),
-            ),
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(user_id): Path<String>,
+    Query(params): Query<GetOrdersParams>
+) -> ApiResult<Vec<Order>> {
+    if user_id != claims.key && !claims.is_admin() {
+        return Err(ApiError::Forbidden("Users can only list their own orders".to_string()));
+    }
+    let include_deleted = params.include_deleted.unwrap_or(false);
+    if include_deleted && !claims.is_admin() {
+        return Err(ApiError::Forbidden("Only admins can list deleted orders".to_string()));
+    }
+
+    let orders = state.orders.find_by_user(&user_id, include_deleted).await?;
+
+    if orders.is_empty() {
+        return Err(ApiError::NotFound("No orders found".to_string()));
     }
+
+    Ok(Json(ApiResponse::Success(orders)))
 }
 
 #[utoipa::path(
@@ -75,108 +86,35 @@ pub async fn get_orders(
     path = "/api/add_order",
     request_body = AddOrderReq,
     responses(
-        (status = 200, description = "Return created order", body = Order),
+        (status = 200, description = "Return created single-line order", body = Order),
+        (status = 403, description = "Email address must be verified first", body = ErrorResponse),
+        (status = 404, description = "Item to order not found", body = ErrorResponse),
         (
-            status = 400,
-            description = "Error creating order. Missing or malformatted attributes",
+            status = 409,
+            description = "Not enough stock or the item is being ordered concurrently",
             body = ErrorResponse,
         ),
+        (status = 422, description = "Order quantity must be at least 1", body = ErrorResponse),
         (status = 500, description = "Error querying the database", body = ErrorResponse)
     )
 )]
 pub async fn add_order(
-    Extension(database): Extension<Database>,
-    Json(payload): Json<AddOrderReq>
-) -> (StatusCode, Json<ApiResponse<Order>>) {
-    let user_id: String = payload.user_id;
-    let item_id: String = payload.item_id;
-    let item_name: String = payload.item_name;
-    let quantity: i64 = payload.quantity;
-    let price: f64 = payload.price;
-    let diff: i64 = payload.quantity_diff;
+    State(state): State<AppState>,
+    claims: Claims,
+    Valid(payload): Valid<AddOrderReq>
+) -> ApiResult<Order> {
     let date: NaiveDateTime = Local::now().naive_local();
 
-    let collection = database.arango_db.collection("Item").await.unwrap();
-
-    let item: Result<Document<Item>, arangors::ClientError> = collection.document(
-        &item_id.to_owned()
-    ).await;
+    let order = NewOrder {
+        user_id: claims.key,
+        lines: vec![NewOrderLine { item_id: payload.item_id, quantity: payload.quantity }],
+        date,
+        cart_rev: None,
+    };
 
-    match item {
-        Ok(item) => {
-            if quantity > item.quantity {
-                return (
-                    StatusCode::BAD_REQUEST,
-                    generate_error("Order quantity exceeds item quantity"),
-                );
-            }
-        }
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                generate_error(format!("Error retrieving item: {}", e.to_string()).as_str()),
-            );
-        }
-    }
+    let order = state.orders.place(order).await?;
 
-    let mut bind_vars: HashMap<&str, Value> = HashMap::new();
-    bind_vars.insert("user_id", user_id.to_owned().into());
-    bind_vars.insert("item_id", item_id.to_owned().into());
-    bind_vars.insert("quantity", quantity.to_owned().into());
-    bind_vars.insert("price", price.to_owned().into());
-    bind_vars.insert("date", to_value(&date).unwrap());
-    bind_vars.insert("item_name", item_name.to_owned().into());
-
-    let query: &str =
-        "
-    INSERT {
-        user_id: @user_id,
-        item_id: @item_id,
-        item_name: @item_name,
-        quantity: @quantity,
-        price: @price,
-        date: @date
-    } INTO Order
-    RETURN NEW
-    ";
-
-    let result: Result<Vec<Order>, arangors::ClientError> = database.arango_db.aql_bind_vars(
-        query,
-        bind_vars
-    ).await;
-
-    match result {
-        Ok(orders) => {
-            if let Some(order) = orders.first() {
-                let collection = database.arango_db.collection("Item").await.unwrap();
-                let response = collection.update_document(
-                    item_id.to_owned().as_str(),
-                    ItemQuantityUpdate { quantity: diff },
-                    UpdateOptions::builder().return_new(true).build()
-                ).await;
-
-                match response {
-                    Ok(_) => (StatusCode::OK, Json(ApiResponse::Success(order.clone()))),
-                    Err(e) =>
-                        (
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            generate_error(
-                                format!("Error creating order: {}", e.to_string()).as_str()
-                            ),
-                        ),
-                }
-            } else {
-                (StatusCode::INTERNAL_SERVER_ERROR, generate_error("Error creating order"))
-            }
-        }
-        Err(e) => {
-            eprintln!("{:?}", e.to_string());
-            (
-                StatusCode::BAD_REQUEST,
-                generate_error(format!("Error creating order: {}", e.to_string()).as_str()),
-            )
-        }
-    }
+    Ok(Json(ApiResponse::Success(order)))
 }
 
 #[utoipa::path(
@@ -184,27 +122,139 @@ pub async fn add_order(
     path = "/api/delete_orders",
     request_body = DeleteOrderReq,
     responses(
-        (status = 200, description = "Returns remaining orders", body = Order),
+        (
+            status = 200,
+            description = "Returns the soft-deleted orders. An admin can restore them until they are purged",
+            body = Vec<Order>,
+        ),
+        (status = 403, description = "Users can only delete their own orders", body = ErrorResponse),
         (status = 500, description = "Error querying the database", body = ErrorResponse)
     )
 )]
 pub async fn delete_orders(
-    Extension(database): Extension<Database>,
+    State(state): State<AppState>,
+    claims: Claims,
     Json(payload): Json<DeleteOrderReq>
-) -> (StatusCode, Json<ApiResponse<Vec<Order>>>) {
-    let user_id: String = payload.user_id;
+) -> ApiResult<Vec<Order>> {
+    if payload.user_id != claims.key && !claims.is_admin() {
+        return Err(ApiError::Forbidden("Users can only delete their own orders".to_string()));
+    }
 
-    let mut bind_vars: HashMap<&str, Value> = HashMap::new();
-    bind_vars.insert("user_id", user_id.to_owned().into());
+    let deletion = Deletion {
+        deleted_at: Local::now().naive_local(),
+        deleted_by: claims.key,
+    };
+    let orders = state.orders.remove_by_user(&payload.user_id, deletion).await?;
 
-    let query: &str = "FOR order IN Order FILTER order.user_id == @user_id REMOVE order IN Order";
+    Ok(Json(ApiResponse::Success(orders)))
+}
 
-    match database.arango_db.aql_bind_vars(query, bind_vars).await {
-        Ok(orders) => (StatusCode::OK, Json(ApiResponse::Success(orders))),
-        Err(e) =>
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                generate_error(format!("Error getting item: {}", e.to_string()).as_str()),
-            ),
+#[utoipa::path(
+    patch,
+    path = "/api/orders/{id}/status",
+    request_body = UpdateOrderStatusReq,
+    params(
+        ("id" = String, Path, description = "Key of the order")
+    ),
+    responses(
+        (
+            status = 200,
+            description = "Return the order with the new status appended to its history. Cancelled orders return their stock to the items",
+            body = Order,
+        ),
+        (
+            status = 403,
+            description = "Customers can only cancel their own orders, and vendors can only ship and deliver orders made only of their items. Refunds are left to admins",
+            body = ErrorResponse,
+        ),
+        (status = 404, description = "Order not found", body = ErrorResponse),
+        (
+            status = 409,
+            description = "The order cannot move to the status from its current one, or it changed concurrently",
+            body = ErrorResponse,
+        ),
+        (status = 500, description = "Error querying the database", body = ErrorResponse)
+    )
+)]
+pub async fn update_order_status(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+    Valid(payload): Valid<UpdateOrderStatusReq>
+) -> ApiResult<Order> {
+    let order = state.orders.find_by_key(&id).await?;
+    ensure_can_change(&state, &claims, &order, payload.status).await?;
+
+    if !order.status.can_become(payload.status) {
+        return Err(
+            ApiError::Conflict(
+                format!("An order cannot go from {} to {}", order.status, payload.status)
+            )
+        );
     }
+
+    let change = StatusChange {
+        status: payload.status,
+        changed_at: Local::now().naive_local(),
+        changed_by: claims.key,
+    };
+    let order = state.orders.change_status(&order, change).await?;
+
+    Ok(Json(ApiResponse::Success(order)))
+}
+
+/// Admins may make any allowed transition and customers may cancel their own orders. Vendors may
+/// only move orders along fulfilment, and only when every line is one of their items: cancelling
+/// or refunding affects the other vendors' lines and the customer's payment.
+async fn ensure_can_change(
+    state: &AppState,
+    claims: &Claims,
+    order: &Order,
+    status: OrderStatus
+) -> Result<(), ApiError> {
+    if claims.is_admin() || (order.user_id == claims.key && status == OrderStatus::CANCELLED) {
+        return Ok(());
+    }
+
+    let fulfilment = matches!(
+        status,
+        OrderStatus::PAID | OrderStatus::SHIPPED | OrderStatus::DELIVERED
+    );
+    if claims.is_vendor() && fulfilment && sells_every_line(state, claims, order).await? {
+        return Ok(());
+    }
+
+    let message = if order.user_id == claims.key {
+        "Customers can only cancel their orders"
+    } else if claims.is_vendor() && fulfilment {
+        "Vendors can only change orders made only of their items"
+    } else if claims.is_vendor() {
+        "Only the customer or an admin can cancel or refund an order"
+    } else {
+        "Only the customer, the vendor of its items or an admin can change an order"
+    };
+
+    Err(ApiError::Forbidden(message.to_string()))
+}
+
+/// Whether every line of `order` is an item listed by the vendor of `claims`. Lines of deleted
+/// items belong to nobody.
+async fn sells_every_line(
+    state: &AppState,
+    claims: &Claims,
+    order: &Order
+) -> Result<bool, ApiError> {
+    for line in &order.lines {
+        match state.items.find_by_key(&line.item_id).await {
+            Ok(item) if item.user_id == claims.key => (),
+            Ok(_) | Err(DatabaseError::NotFound(_)) => {
+                return Ok(false);
+            }
+            Err(err) => {
+                return Err(err.into());
+            }
+        }
+    }
+
+    Ok(true)
 }
\ No newline at end of file
@@ -1,15 +1,14 @@
 use std::time::Duration;
 use crate::logs::set_log;
-use crate::requests::{ auth, items, jwt, orders };
-use crate::{ db::Database, toml_env::{ Environment, ServerConfig } };
+use crate::requests::{ admin, auth, carts, items, jwt, orders, security, two_factor, users };
+use crate::{ state::AppState, toml_env::Environment };
 use axum::http::header;
 use axum::{
     body::{ Body, Bytes },
     http::{ HeaderMap, HeaderName, Request, Method },
     middleware,
     response::Response,
-    routing::{ delete, get, post, put },
-    Extension,
+    routing::{ delete, get, patch, post, put },
     Router,
 };
 use log::{ debug, error, info, LevelFilter };
@@ -23,55 +22,128 @@ use tower_http::{
 };
 use tracing::Span;
 
-pub async fn create_routes(database: Database, path: &str, server: &ServerConfig) -> Router {
-    set_log(path, LevelFilter::Info);
+pub async fn create_routes(state: AppState) -> Router {
+    let server = &state.config.server;
+    set_log(state.config.log.path.as_str(), LevelFilter::Info);
 
     let cors = if server.allow_origins().is_none() || server.env == Environment::DEV {
         CorsLayer::permissive()
     } else {
         CorsLayer::new()
             .allow_origin(server.allow_origins().unwrap())
-            .allow_headers(vec![header::AUTHORIZATION])
+            .allow_headers(vec![header::AUTHORIZATION, header::IF_MATCH])
+            .expose_headers(
+                vec![
+                    header::ETAG,
+                    header::RETRY_AFTER,
+                    security::X_RATELIMIT_LIMIT,
+                    security::X_RATELIMIT_REMAINING,
+                    security::X_RATELIMIT_RESET
+                ]
+            )
     };
 
+    let authenticated = middleware::from_fn_with_state(state.clone(), jwt::jwt_middleware);
+    let verified = middleware::from_fn_with_state(state.clone(), jwt::verified_guard);
+    let vendor = middleware::from_fn_with_state(state.clone(), jwt::vendor_guard);
+    let rate_limit = middleware::from_fn_with_state(state.clone(), security::rate_limit);
+
     Router::new()
         .route("/api/auth/login", post(auth::handle_login))
+        .route("/api/auth/login/2fa", post(two_factor::complete_login))
         .route("/api/auth/signup", post(auth::handle_signup))
-        .route("/api/auth/refresh/:email", get(jwt::refresh))
+        .route("/api/auth/refresh", post(jwt::refresh))
+        .route("/api/auth/logout", post(jwt::logout))
+        .route("/api/auth/forgot_password", post(auth::forgot_password))
+        .route("/api/auth/reset_password", post(auth::reset_password))
+        .route("/api/auth/verify", post(auth::verify_email))
+        .route("/api/auth/verify/resend", post(auth::resend_verification))
+        .route("/.well-known/jwks.json", get(jwt::jwks))
+        .route(
+            "/api/users/me",
+            get(users::get_profile)
+                .patch(users::update_profile)
+                .delete(users::delete_account)
+                .route_layer(authenticated.clone())
+        )
+        .route(
+            "/api/users/me/password",
+            post(users::change_password).route_layer(authenticated.clone())
+        )
         .route(
-            "/api/get_item/:name",
-            get(items::get_item).route_layer(middleware::from_fn(jwt::jwt_middleware))
+            "/api/users/me/2fa",
+            post(two_factor::start_enrollment)
+                .delete(two_factor::disable)
+                .route_layer(authenticated.clone())
         )
         .route(
-            "/api/get_items",
-            get(items::get_items).route_layer(middleware::from_fn(jwt::jwt_middleware))
+            "/api/users/me/2fa/confirm",
+            post(two_factor::confirm_enrollment).route_layer(authenticated.clone())
         )
+        .route("/api/get_item/:name", get(items::get_item).route_layer(authenticated.clone()))
+        .route("/api/get_items", get(items::get_items).route_layer(authenticated.clone()))
+        .route("/api/items/:id", get(items::get_item_by_key).route_layer(authenticated.clone()))
+        .route("/api/search_items", get(items::search_items).route_layer(authenticated.clone()))
         .route(
             "/api/add_item",
-            post(items::add_item).route_layer(middleware::from_fn(jwt::jwt_middleware))
+            post(items::add_item)
+                .route_layer(vendor.clone())
+                .route_layer(authenticated.clone())
         )
         .route(
             "/api/edit_item",
-            put(items::edit_item).route_layer(middleware::from_fn(jwt::jwt_middleware))
+            put(items::edit_item)
+                .route_layer(vendor.clone())
+                .route_layer(authenticated.clone())
         )
         .route(
             "/api/delete_item",
-            delete(items::delete_item).route_layer(middleware::from_fn(jwt::jwt_middleware))
+            delete(items::delete_item)
+                .route_layer(vendor)
+                .route_layer(authenticated.clone())
         )
         .route(
             "/api/get_orders/:user_id",
-            get(orders::get_orders).route_layer(middleware::from_fn(jwt::jwt_middleware))
+            get(orders::get_orders).route_layer(authenticated.clone())
         )
         .route(
             "/api/add_order",
-            post(orders::add_order).route_layer(middleware::from_fn(jwt::jwt_middleware))
+            post(orders::add_order)
+                .route_layer(verified.clone())
+                .route_layer(authenticated.clone())
         )
         .route(
             "/api/delete_orders",
-            delete(orders::delete_orders).route_layer(middleware::from_fn(jwt::jwt_middleware))
+            delete(orders::delete_orders).route_layer(authenticated.clone())
+        )
+        .route(
+            "/api/orders/:id/status",
+            patch(orders::update_order_status).route_layer(authenticated.clone())
+        )
+        .route("/api/cart", get(carts::get_cart).route_layer(authenticated.clone()))
+        .route("/api/cart/lines", post(carts::add_line).route_layer(authenticated.clone()))
+        .route(
+            "/api/cart/lines/:item_id",
+            put(carts::update_line).delete(carts::remove_line).route_layer(authenticated.clone())
+        )
+        .route(
+            "/api/cart/checkout",
+            post(carts::checkout).route_layer(verified).route_layer(authenticated.clone())
+        )
+        .route(
+            "/api/admin/items/:id/restore",
+            post(admin::restore_item)
+                .route_layer(middleware::from_fn(jwt::admin_guard))
+                .route_layer(authenticated.clone())
+        )
+        .route(
+            "/api/admin/orders/:id/restore",
+            post(admin::restore_order)
+                .route_layer(middleware::from_fn(jwt::admin_guard))
+                .route_layer(authenticated)
         )
-        .layer(Extension(database))
-        .layer(Extension(server.secret.clone()))
+        // Route layers see the matched route, which per-route budgets are keyed by.
+        .route_layer(rate_limit)
         .layer(CompressionLayer::new())
         .layer(PropagateHeaderLayer::new(HeaderName::from_static("x-request-id")))
         .layer(ValidateRequestHeaderLayer::accept("application/json"))
@@ -130,4 +202,5 @@ pub async fn create_routes(database: Database, path: &str, server: &ServerConfig
                     error!("FAILURE - {:?} | {}ms", error, latency.as_millis());
                 })
         )
+        .with_state(state)
 }
\ No newline at end of file
@@ -0,0 +1,243 @@
+use crate::api::ApiError;
+use crate::models::AuditEvent;
+use crate::rate_limit::RateLimit;
+use crate::repositories::audit::NewAuditEntry;
+use crate::repositories::login_attempts::NewLoginAttempt;
+use crate::state::AppState;
+use axum::{
+    async_trait,
+    extract::{ ConnectInfo, FromRequestParts, MatchedPath, State },
+    http::{ header, request::Parts, HeaderName, HeaderValue, Request },
+    middleware::Next,
+    response::{ IntoResponse, Response },
+};
+use chrono::NaiveDateTime;
+use log::{ error, warn };
+use serde_json::json;
+use std::convert::Infallible;
+use std::net::{ IpAddr, SocketAddr };
+
+use super::jwt::validate_jwt;
+
+pub const X_RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+pub const X_RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+pub const X_RATELIMIT_RESET: HeaderName = HeaderName::from_static("x-ratelimit-reset");
+
+/// IP address of the client: the last `X-Forwarded-For` entry when `[Security]
+/// trust_forwarded_for` is set, else the peer of the connection. `None` when neither is known.
+/// Only the last entry was written by the proxy; the ones before it come from the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ClientIp(pub Option<IpAddr>);
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState
+    ) -> Result<Self, Self::Rejection> {
+        let forwarded = parts.headers
+            .get("x-forwarded-for")
+            .filter(|_| state.config.security.trust_forwarded_for)
+            .and_then(|header| header.to_str().ok())
+            .and_then(|header| header.rsplit(',').next())
+            .and_then(|ip| ip.trim().parse().ok());
+        let peer = parts.extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        Ok(ClientIp(forwarded.or(peer)))
+    }
+}
+
+fn email_subject(email: &str) -> String {
+    format!("email:{}", email.to_lowercase())
+}
+
+/// What failed logins for `email` from `ip` count against, with the failures that lock each out.
+fn subjects(state: &AppState, email: &str, ip: ClientIp) -> Vec<(String, u32)> {
+    let security = &state.config.security;
+    let mut subjects = vec![(email_subject(email), security.max_failures)];
+    if let ClientIp(Some(ip)) = ip {
+        subjects.push((format!("ip:{}", ip), security.max_ip_failures));
+    }
+    subjects.retain(|(_, limit)| *limit > 0);
+
+    subjects
+}
+
+/// The recent failures of `subject` and the end of its lockout, if it is locked out at `now`.
+async fn lockout(
+    state: &AppState,
+    subject: &str,
+    limit: u32,
+    now: NaiveDateTime
+) -> Result<Option<(u32, NaiveDateTime)>, ApiError> {
+    let attempts = state.attempts.find_active(subject, now).await?;
+    let failures = attempts.len() as u32;
+    let last = attempts.iter().map(|attempt| attempt.created_at).max();
+
+    let until = last.zip(state.config.security.lockout(failures, limit)).map(|(last, lockout)| {
+        last + lockout
+    });
+
+    Ok(until.filter(|until| *until > now).map(|until| (failures, until)))
+}
+
+fn locked_out(until: NaiveDateTime, now: NaiveDateTime) -> ApiError {
+    let milliseconds = (until - now).num_milliseconds().max(0) as u64;
+
+    ApiError::TooManyRequests {
+        message: "Too many failed logins, try again later".to_string(),
+        retry_after: milliseconds.div_ceil(1000).max(1),
+    }
+}
+
+/// Rejects logins for `email` or from `ip` while either is locked out.
+pub async fn check_lockout(state: &AppState, email: &str, ip: ClientIp) -> Result<(), ApiError> {
+    let now = chrono::Utc::now().naive_utc();
+
+    for (subject, limit) in subjects(state, email, ip) {
+        if let Some((_, until)) = lockout(state, &subject, limit, now).await? {
+            return Err(locked_out(until, now));
+        }
+    }
+
+    Ok(())
+}
+
+/// Counts a failed login against `email` and `ip`. When that locks either out, writes an audit
+/// entry for it and returns the lockout error.
+pub async fn record_failure(state: &AppState, email: &str, ip: ClientIp) -> Result<(), ApiError> {
+    let now = chrono::Utc::now().naive_utc();
+    let mut locked_until = None;
+
+    for (subject, limit) in subjects(state, email, ip) {
+        let attempt = NewLoginAttempt {
+            subject: subject.clone(),
+            created_at: now,
+            expires_at: now + state.config.security.failure_window(),
+        };
+        state.attempts.insert(attempt).await?;
+
+        let Some((failures, until)) = lockout(state, &subject, limit, now).await? else {
+            continue;
+        };
+        warn!("{} locked out until {} after {} failed logins", subject, until, failures);
+
+        let entry = NewAuditEntry {
+            event: AuditEvent::LoginLockout,
+            subject,
+            ip: ip.0.map(|ip| ip.to_string()),
+            details: json!({ "failures": failures, "locked_until": until }),
+            created_at: now,
+        };
+        state.audit.insert(entry).await?;
+        locked_until = locked_until.max(Some(until));
+    }
+
+    match locked_until {
+        Some(until) => Err(locked_out(until, now)),
+        None => Ok(()),
+    }
+}
+
+/// Forgets the failed logins of `email` once its password is given. Failures of the client IP
+/// are kept, so that one known password does not unlock guessing the others.
+pub async fn clear_failures(state: &AppState, email: &str) -> Result<(), ApiError> {
+    state.attempts.clear(&email_subject(email)).await?;
+
+    Ok(())
+}
+
+/// Who a request is counted against: the user of a valid access token, else the client IP.
+fn rate_limit_client<B>(state: &AppState, req: &Request<B>, ip: ClientIp) -> Option<String> {
+    let user = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| header.split_whitespace().nth(1))
+        .and_then(|token| validate_jwt(token, &state.keys).ok())
+        .map(|claims| format!("user:{}", claims.key));
+
+    user.or_else(|| ip.0.map(|ip| format!("ip:{}", ip)))
+}
+
+/// Spends a request from the global budget of the client and from the budget of the route, if
+/// one is configured, refusing it once either is empty. A refused request gives back what it took
+/// from the other budget. The tighter of the two is reported in the `X-RateLimit-*` headers.
+/// Requests that cannot be tied to a client are let through.
+pub async fn rate_limit<B>(
+    State(state): State<AppState>,
+    ip: ClientIp,
+    route: Option<MatchedPath>,
+    req: Request<B>,
+    next: Next<B>
+) -> Response {
+    let config = &state.config.rate_limit;
+    let client = rate_limit_client(&state, &req, ip).filter(|_| config.enabled);
+    let Some(client) = client else {
+        return next.run(req).await;
+    };
+
+    let mut budgets = vec![("*".to_string(), config.budget())];
+    let route = route.and_then(|route| config.route(req.method().as_str(), route.as_str()));
+    if let Some(route) = route {
+        let method = route.method.as_deref().unwrap_or("*").to_uppercase();
+        budgets.push((format!("{} {}", method, route.path), route.budget()));
+    }
+
+    let now = chrono::Utc::now().naive_utc();
+    let mut tightest: Option<RateLimit> = None;
+    let mut taken = Vec::new();
+    for (name, budget) in budgets.iter().filter(|(_, budget)| budget.requests > 0) {
+        let key = format!("{}|{}", name, client);
+        let limit = match state.limiter.take(&key, budget, now).await {
+            Ok(limit) => limit,
+            Err(err) => {
+                // A broken store must not take the whole API down with it.
+                error!("{}", err);
+                continue;
+            }
+        };
+        if limit.allowed {
+            taken.push((key, budget));
+        }
+
+        let tighter = match tightest {
+            None => true,
+            Some(tightest) if limit.allowed != tightest.allowed => !limit.allowed,
+            Some(tightest) => limit.remaining < tightest.remaining,
+        };
+        if tighter {
+            tightest = Some(limit);
+        }
+    }
+
+    let Some(limit) = tightest else {
+        return next.run(req).await;
+    };
+    if !limit.allowed {
+        for (key, budget) in taken {
+            if let Err(err) = state.limiter.refund(&key, budget).await {
+                error!("{}", err);
+            }
+        }
+    }
+
+    let mut response = match limit.retry_after {
+        Some(retry_after) if !limit.allowed => {
+            let message = "Too many requests, slow down".to_string();
+            ApiError::TooManyRequests { message, retry_after }.into_response()
+        }
+        _ => next.run(req).await,
+    };
+
+    let headers = response.headers_mut();
+    headers.insert(X_RATELIMIT_LIMIT, HeaderValue::from(limit.limit));
+    headers.insert(X_RATELIMIT_REMAINING, HeaderValue::from(limit.remaining));
+    headers.insert(X_RATELIMIT_RESET, HeaderValue::from(limit.reset));
+
+    response
+}
\ No newline at end of file
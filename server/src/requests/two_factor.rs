@@ -0,0 +1,265 @@
+use crate::api::{ ApiError, ApiResponse, ApiResult };
+use crate::models::User;
+use crate::repositories::users::UserUpdate;
+use crate::state::AppState;
+use crate::totp;
+use axum::extract::State;
+use axum::Json;
+use log::{ info, warn };
+use serde::{ Deserialize, Serialize };
+use utoipa::ToSchema;
+use validator::Validate;
+
+use super::auth::AuthRes;
+use super::jwt::{ check_login_allowed, hash_token, issue_tokens, validate_challenge_token, Claims };
+use super::security::{ self, ClientIp };
+use super::users::{ check_password, current_user };
+use super::validation::Valid;
+
+#[derive(Deserialize, ToSchema, Validate)]
+pub struct StartEnrollmentReq {
+    #[validate(length(min = 1, message = "cannot be empty"))]
+    password: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct EnrollmentRes {
+    /// Base32 secret, for authenticator apps that cannot scan the URI
+    secret: String,
+    /// `otpauth://` URI to show as a QR code
+    otpauth_uri: String,
+}
+
+#[derive(Deserialize, ToSchema, Validate)]
+pub struct ConfirmEnrollmentReq {
+    /// Current code of the authenticator app
+    #[validate(length(min = 1, message = "cannot be empty"))]
+    code: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RecoveryCodesRes {
+    /// Single-use codes that replace the authenticator app. They are only shown once.
+    recovery_codes: Vec<String>,
+}
+
+#[derive(Deserialize, ToSchema, Validate)]
+pub struct DisableReq {
+    #[validate(length(min = 1, message = "cannot be empty"))]
+    password: String,
+    /// Code of the authenticator app or an unused recovery code
+    #[validate(length(min = 1, message = "cannot be empty"))]
+    code: String,
+}
+
+#[derive(Deserialize, ToSchema, Validate)]
+pub struct CompleteLoginReq {
+    /// Challenge returned by `/api/auth/login`
+    #[validate(length(min = 1, message = "cannot be empty"))]
+    mfa_token: String,
+    /// Code of the authenticator app or an unused recovery code
+    #[validate(length(min = 1, message = "cannot be empty"))]
+    code: String,
+}
+
+/// Starts over when an enrollment was never confirmed.
+#[utoipa::path(
+    post,
+    path = "/api/users/me/2fa",
+    request_body = StartEnrollmentReq,
+    responses(
+        (status = 200, description = "Return the secret to add to an authenticator app", body = EnrollmentRes),
+        (status = 400, description = "Password is wrong", body = ErrorResponse),
+        (status = 404, description = "The user was deleted", body = ErrorResponse),
+        (status = 409, description = "Two-factor authentication is already enabled", body = ErrorResponse),
+        (status = 429, description = "Too many wrong passwords for the user", body = ErrorResponse),
+        (status = 500, description = "Error in the database query", body = ErrorResponse)
+    )
+)]
+pub async fn start_enrollment(
+    State(state): State<AppState>,
+    claims: Claims,
+    Valid(payload): Valid<StartEnrollmentReq>
+) -> ApiResult<EnrollmentRes> {
+    let user = current_user(&state, &claims).await?;
+    check_password(&state, &payload.password, &user).await?;
+
+    if user.totp_enabled {
+        let msg = "Two-factor authentication is already enabled";
+        return Err(ApiError::Conflict(msg.to_string()));
+    }
+
+    let secret = totp::generate_secret();
+    let update = UserUpdate { totp_secret: Some(Some(secret.clone())), ..Default::default() };
+    let user = state.users.update(&user._key, update).await?;
+
+    let otpauth_uri = totp::otpauth_uri(&state.config.two_factor.issuer, &user.email, &secret);
+
+    Ok(Json(ApiResponse::Success(EnrollmentRes { secret, otpauth_uri })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/users/me/2fa/confirm",
+    request_body = ConfirmEnrollmentReq,
+    responses(
+        (status = 200, description = "Two-factor authentication is enabled. Return the recovery codes", body = RecoveryCodesRes),
+        (status = 400, description = "Code is wrong or no enrollment was started", body = ErrorResponse),
+        (status = 404, description = "The user was deleted", body = ErrorResponse),
+        (status = 409, description = "Two-factor authentication is already enabled", body = ErrorResponse),
+        (status = 500, description = "Error in the database query", body = ErrorResponse)
+    )
+)]
+pub async fn confirm_enrollment(
+    State(state): State<AppState>,
+    claims: Claims,
+    Valid(payload): Valid<ConfirmEnrollmentReq>
+) -> ApiResult<RecoveryCodesRes> {
+    let user = current_user(&state, &claims).await?;
+
+    if user.totp_enabled {
+        let msg = "Two-factor authentication is already enabled";
+        return Err(ApiError::Conflict(msg.to_string()));
+    }
+    let secret = user.totp_secret.as_ref().ok_or_else(|| {
+        ApiError::Validation("Start enrolling before confirming a code".to_string())
+    })?;
+
+    let config = &state.config.two_factor;
+    let now = chrono::Utc::now().timestamp() as u64;
+    let step = totp::verify(secret, &payload.code, now, config.skew_steps, None).ok_or_else(|| {
+        ApiError::Validation("Code is wrong".to_string())
+    })?;
+
+    let recovery_codes = totp::generate_recovery_codes(config.recovery_codes);
+    let update = UserUpdate {
+        totp_enabled: Some(true),
+        totp_last_step: Some(step),
+        recovery_codes: Some(
+            recovery_codes
+                .iter()
+                .map(|code| hash_token(&totp::normalize_recovery_code(code)))
+                .collect()
+        ),
+        ..Default::default()
+    };
+    state.users.update(&user._key, update).await?;
+    info!("User {} enabled two-factor authentication", user._key);
+
+    Ok(Json(ApiResponse::Success(RecoveryCodesRes { recovery_codes })))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/users/me/2fa",
+    request_body = DisableReq,
+    responses(
+        (status = 200, description = "Two-factor authentication is disabled", body = bool),
+        (status = 400, description = "Password or code is wrong, or two-factor authentication is not enabled", body = ErrorResponse),
+        (status = 404, description = "The user was deleted", body = ErrorResponse),
+        (status = 429, description = "Too many wrong passwords for the user", body = ErrorResponse),
+        (status = 500, description = "Error in the database query", body = ErrorResponse)
+    )
+)]
+pub async fn disable(
+    State(state): State<AppState>,
+    claims: Claims,
+    Valid(payload): Valid<DisableReq>
+) -> ApiResult<bool> {
+    let user = current_user(&state, &claims).await?;
+    check_password(&state, &payload.password, &user).await?;
+
+    if !user.totp_enabled {
+        let msg = "Two-factor authentication is not enabled";
+        return Err(ApiError::Validation(msg.to_string()));
+    }
+    if !accept_code(&state, &user, &payload.code).await? {
+        return Err(ApiError::Validation("Code is wrong".to_string()));
+    }
+
+    let update = UserUpdate {
+        totp_secret: Some(None),
+        totp_enabled: Some(false),
+        recovery_codes: Some(Vec::new()),
+        ..Default::default()
+    };
+    state.users.update(&user._key, update).await?;
+    info!("User {} disabled two-factor authentication", user._key);
+
+    Ok(Json(ApiResponse::Success(true)))
+}
+
+/// Second step of the login of users with two-factor authentication. Wrong codes count towards
+/// the same lockout as wrong passwords.
+#[utoipa::path(
+    post,
+    path = "/api/auth/login/2fa",
+    request_body = CompleteLoginReq,
+    responses(
+        (status = 200, description = "Return authenticated user", body = AuthRes),
+        (status = 400, description = "Code is wrong", body = ErrorResponse),
+        (status = 401, description = "Challenge is invalid, expired or older than a password change", body = ErrorResponse),
+        (status = 403, description = "Email address must be verified first", body = ErrorResponse),
+        (status = 422, description = "Challenge or code is empty", body = ErrorResponse),
+        (status = 429, description = "Too many failed logins for the email or from the client IP", body = ErrorResponse),
+        (status = 500, description = "Error during query/token generation", body = ErrorResponse)
+    )
+)]
+pub async fn complete_login(
+    State(state): State<AppState>,
+    ip: ClientIp,
+    Valid(payload): Valid<CompleteLoginReq>
+) -> ApiResult<AuthRes> {
+    let invalid = || ApiError::Unauthorized("Login challenge is invalid or expired".to_string());
+
+    let claims = validate_challenge_token(&payload.mfa_token, &state.keys).map_err(|_| invalid())?;
+    let user = state.users
+        .find_by_key(&claims.sub).await?
+        .filter(|user| user.totp_enabled && user.token_version == claims.ver)
+        .ok_or_else(invalid)?;
+
+    // Codes are spent when accepted, so users who cannot log in yet must not burn one.
+    check_login_allowed(&state, &user)?;
+    security::check_lockout(&state, &user.email, ip).await?;
+    if !accept_code(&state, &user, &payload.code).await? {
+        security::record_failure(&state, &user.email, ip).await?;
+        return Err(ApiError::Validation("Code is wrong".to_string()));
+    }
+    security::clear_failures(&state, &user.email).await?;
+
+    let auth = issue_tokens(&state, user, None).await?;
+
+    Ok(Json(ApiResponse::Success(auth)))
+}
+
+/// Checks `code` against the authenticator app of `user`, then against their unused recovery
+/// codes. Accepted codes cannot be used again: when concurrent requests send the same code, only
+/// the first one to record it is accepted.
+async fn accept_code(state: &AppState, user: &User, code: &str) -> Result<bool, ApiError> {
+    let now = chrono::Utc::now().timestamp() as u64;
+    let skew = state.config.two_factor.skew_steps;
+
+    if let Some(secret) = &user.totp_secret {
+        if let Some(step) = totp::verify(secret, code, now, skew, user.totp_last_step) {
+            return Ok(state.users.claim_totp_step(&user._key, step).await?);
+        }
+    }
+
+    let hash = hash_token(&totp::normalize_recovery_code(code));
+    if !user.recovery_codes.contains(&hash) {
+        return Ok(false);
+    }
+
+    let remaining: Vec<String> = user.recovery_codes
+        .iter()
+        .filter(|stored| **stored != hash)
+        .cloned()
+        .collect();
+    let left = remaining.len();
+    if !state.users.replace_recovery_codes(&user._key, &user.recovery_codes, remaining).await? {
+        return Ok(false);
+    }
+    warn!("User {} used a recovery code, {} left", user._key, left);
+
+    Ok(true)
+}
\ No newline at end of file
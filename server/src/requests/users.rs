@@ -0,0 +1,230 @@
+use crate::api::{ ApiError, ApiResponse, ApiResult };
+use crate::models::{ Deletion, User, UserProfile };
+use crate::repositories::users::UserUpdate;
+use crate::state::AppState;
+use super::auth::{ send_verification, AuthRes };
+use super::jwt::{ issue_tokens, Claims };
+use super::security::{ self, ClientIp };
+use super::validation::{ not_blank, Valid };
+use axum::extract::State;
+use axum::Json;
+use chrono::Local;
+use log::error;
+use serde::Deserialize;
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Deserialize, ToSchema, Validate)]
+pub struct UpdateProfileReq {
+    #[validate(
+        custom = "not_blank",
+        length(max = 64, message = "must be at most 64 characters long")
+    )]
+    first_name: Option<String>,
+    #[validate(
+        custom = "not_blank",
+        length(max = 64, message = "must be at most 64 characters long")
+    )]
+    last_name: Option<String>,
+    #[validate(email(message = "must be a valid email address"))]
+    email: Option<String>,
+    /// Required to change the email, so a stolen access token cannot take over the account
+    current_password: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema, Validate)]
+pub struct ChangePasswordReq {
+    #[validate(length(min = 1, message = "cannot be empty"))]
+    current_password: String,
+    #[validate(length(max = 128, message = "must be at most 128 characters long"))]
+    new_password: String,
+}
+
+#[derive(Deserialize, ToSchema, Validate)]
+pub struct DeleteAccountReq {
+    #[validate(length(min = 1, message = "cannot be empty"))]
+    password: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/users/me",
+    responses(
+        (status = 200, description = "Return the profile of the logged in user", body = UserProfile),
+        (status = 404, description = "The user was deleted", body = ErrorResponse),
+        (status = 500, description = "Error in the database query", body = ErrorResponse)
+    )
+)]
+pub async fn get_profile(State(state): State<AppState>, claims: Claims) -> ApiResult<UserProfile> {
+    let user = current_user(&state, &claims).await?;
+
+    Ok(Json(ApiResponse::Success(user.into())))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/users/me",
+    request_body = UpdateProfileReq,
+    responses(
+        (status = 200, description = "Return the updated profile", body = UserProfile),
+        (
+            status = 400,
+            description = "Current password is missing or wrong while changing the email",
+            body = ErrorResponse,
+        ),
+        (status = 404, description = "The user was deleted", body = ErrorResponse),
+        (status = 409, description = "Email is already associated with another user", body = ErrorResponse),
+        (status = 422, description = "One or more fields are invalid", body = ErrorResponse),
+        (status = 429, description = "Too many wrong passwords for the user", body = ErrorResponse),
+        (status = 500, description = "Error in the database query", body = ErrorResponse)
+    )
+)]
+pub async fn update_profile(
+    State(state): State<AppState>,
+    claims: Claims,
+    Valid(payload): Valid<UpdateProfileReq>
+) -> ApiResult<UserProfile> {
+    let user = current_user(&state, &claims).await?;
+
+    let email = payload.email.filter(|email| email != &user.email);
+    if email.is_some() {
+        let password = payload.current_password.unwrap_or_default();
+        check_password(&state, &password, &user).await?;
+    }
+
+    let email_changed = email.is_some();
+    let update = UserUpdate {
+        first_name: payload.first_name,
+        last_name: payload.last_name,
+        email,
+        email_verified: email_changed.then_some(false),
+        ..Default::default()
+    };
+
+    let user = state.users.update(&user._key, update).await.map_err(|err| {
+        match ApiError::from(err) {
+            ApiError::Conflict(_) => {
+                ApiError::Conflict("Email is already associated with another user".to_string())
+            }
+            err => err,
+        }
+    })?;
+
+    if email_changed {
+        if let Err(err) = send_verification(&state, &user).await {
+            error!("Error sending verification email to user {}: {}", user._key, err);
+        }
+    }
+
+    Ok(Json(ApiResponse::Success(user.into())))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/users/me/password",
+    request_body = ChangePasswordReq,
+    responses(
+        (
+            status = 200,
+            description = "Return new tokens. Every access and refresh token issued before is revoked",
+            body = AuthRes,
+        ),
+        (status = 400, description = "Current password is wrong", body = ErrorResponse),
+        (status = 404, description = "The user was deleted", body = ErrorResponse),
+        (status = 422, description = "New password is too short, too long or too weak", body = ErrorResponse),
+        (status = 429, description = "Too many wrong passwords for the user", body = ErrorResponse),
+        (status = 500, description = "Error during query/hashing", body = ErrorResponse)
+    )
+)]
+pub async fn change_password(
+    State(state): State<AppState>,
+    claims: Claims,
+    Valid(payload): Valid<ChangePasswordReq>
+) -> ApiResult<AuthRes> {
+    let user = current_user(&state, &claims).await?;
+    check_password(&state, &payload.current_password, &user).await?;
+
+    let personal = [user.first_name.as_str(), &user.last_name, &user.email];
+    state.passwords
+        .check_policy("new_password", &payload.new_password, &personal)
+        .map_err(|violation| ApiError::InvalidFields(vec![violation]))?;
+
+    let hashed_password = state.passwords
+        .hash_blocking(&payload.new_password).await
+        .map_err(|err| ApiError::Internal(format!("Error hashing password: {}", err)))?;
+    let update = UserUpdate {
+        password: Some(hashed_password),
+        token_version: Some(user.token_version + 1),
+        ..Default::default()
+    };
+    let user = state.users.update(&user._key, update).await?;
+
+    state.tokens.revoke_user(&user._key).await?;
+    let auth = issue_tokens(&state, user, None).await?;
+
+    Ok(Json(ApiResponse::Success(auth)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/users/me",
+    request_body = DeleteAccountReq,
+    responses(
+        (
+            status = 200,
+            description = "Account deleted, its orders are anonymized, its listings soft-deleted and its tokens revoked",
+            body = bool,
+        ),
+        (status = 400, description = "Password is wrong", body = ErrorResponse),
+        (status = 404, description = "The user was already deleted", body = ErrorResponse),
+        (status = 429, description = "Too many wrong passwords for the user", body = ErrorResponse),
+        (status = 500, description = "Error in the database query", body = ErrorResponse)
+    )
+)]
+pub async fn delete_account(
+    State(state): State<AppState>,
+    claims: Claims,
+    Valid(payload): Valid<DeleteAccountReq>
+) -> ApiResult<bool> {
+    let user = current_user(&state, &claims).await?;
+    check_password(&state, &payload.password, &user).await?;
+
+    // The user goes last, so a request that fails halfway can simply be retried.
+    state.tokens.revoke_user(&user._key).await?;
+    let deletion = Deletion {
+        deleted_at: Local::now().naive_local(),
+        deleted_by: user._key.to_owned(),
+    };
+    state.items.remove_by_user(&user._key, deletion).await?;
+    state.orders.anonymize_user(&user._key).await?;
+    state.carts.remove(&user._key).await?;
+    state.users.remove(&user._key).await?;
+
+    Ok(Json(ApiResponse::Success(true)))
+}
+
+pub async fn current_user(state: &AppState, claims: &Claims) -> Result<User, ApiError> {
+    state.users
+        .find_by_key(&claims.key).await?
+        .ok_or_else(|| ApiError::NotFound("User not found".to_string()))
+}
+
+/// Checks the password of a signed-in user. Wrong passwords count towards the lockout of their
+/// email like failed logins do, so a stolen access token cannot be used to guess the password.
+pub async fn check_password(
+    state: &AppState,
+    password: &str,
+    user: &User
+) -> Result<(), ApiError> {
+    // The token already names the account, so the IP of the client is not counted.
+    let ip = ClientIp::default();
+    security::check_lockout(state, &user.email, ip).await?;
+
+    if !state.passwords.verify_blocking(password, &user.password).await {
+        security::record_failure(state, &user.email, ip).await?;
+        return Err(ApiError::Validation("Current password is wrong".to_string()));
+    }
+    security::clear_failures(state, &user.email).await?;
+
+    Ok(())
+}
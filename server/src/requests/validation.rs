@@ -0,0 +1,137 @@
+use crate::api::{ ApiError, FieldError };
+use crate::models::Money;
+use axum::{ async_trait, body::{ Bytes, HttpBody }, extract::FromRequest, http::Request, BoxError };
+use serde::de::DeserializeOwned;
+use serde_json::error::Category;
+use validator::{ Validate, ValidationError, ValidationErrors, ValidationErrorsKind };
+
+/// JSON body extractor that runs the payload's `Validate` rules. Values of the wrong type and
+/// broken rules are rejected with a 422 listing every offending field.
+#[derive(Debug, Clone)]
+pub struct Valid<T>(pub T);
+
+#[async_trait]
+impl<T, S, B> FromRequest<S, B>
+    for Valid<T>
+    where
+        T: DeserializeOwned + Validate,
+        S: Send + Sync,
+        B: HttpBody + Send + 'static,
+        B::Data: Send,
+        B::Error: Into<BoxError>
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let bytes = Bytes::from_request(req, state).await.map_err(|err| {
+            ApiError::Validation(err.body_text())
+        })?;
+
+        let deserializer = &mut serde_json::Deserializer::from_slice(&bytes);
+        let payload: T = serde_path_to_error::deserialize(deserializer).map_err(deserialize_error)?;
+
+        payload.validate().map_err(|errors| ApiError::InvalidFields(field_errors(&errors)))?;
+
+        Ok(Valid(payload))
+    }
+}
+
+/// Rejects strings that are empty or only contain whitespace.
+pub fn not_blank(value: &str) -> Result<(), ValidationError> {
+    if value.trim().is_empty() {
+        return Err(rule_error("not_blank", "cannot be blank"));
+    }
+
+    Ok(())
+}
+
+/// Rejects negative prices. Malformed amounts are already rejected while deserializing.
+pub fn valid_price(price: &Money) -> Result<(), ValidationError> {
+    if price.is_negative() {
+        return Err(rule_error("price", "cannot be negative"));
+    }
+
+    Ok(())
+}
+
+fn rule_error(code: &'static str, message: &'static str) -> ValidationError {
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.into());
+    error
+}
+
+fn deserialize_error(error: serde_path_to_error::Error<serde_json::Error>) -> ApiError {
+    let path = error.path().to_string();
+    let error = error.into_inner();
+
+    if error.classify() != Category::Data {
+        return ApiError::Validation(format!("Malformed JSON body: {}", error));
+    }
+
+    // serde appends the position of the error, which means nothing to API clients.
+    let message = error.to_string();
+    let message = message.split(" at line ").next().unwrap_or_default();
+
+    let missing = message
+        .strip_prefix("missing field `")
+        .and_then(|field| field.split('`').next());
+
+    let field_error = match missing {
+        Some(field) =>
+            FieldError {
+                field: join_path(&path, field),
+                code: "required".to_string(),
+                message: "is required".to_string(),
+            },
+        None =>
+            FieldError {
+                field: path,
+                code: "invalid_type".to_string(),
+                message: message.to_string(),
+            },
+    };
+
+    ApiError::InvalidFields(vec![field_error])
+}
+
+/// Flattens nested validation errors into one entry per broken rule, ordered by field.
+pub fn field_errors(errors: &ValidationErrors) -> Vec<FieldError> {
+    let mut fields = Vec::new();
+    collect_errors(".", errors, &mut fields);
+    fields.sort_by(|a, b| a.field.cmp(&b.field));
+    fields
+}
+
+fn collect_errors(path: &str, errors: &ValidationErrors, fields: &mut Vec<FieldError>) {
+    for (field, kind) in errors.errors() {
+        let path = join_path(path, field);
+
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                fields.extend(
+                    errors.iter().map(|error| FieldError {
+                        field: path.clone(),
+                        code: error.code.to_string(),
+                        message: error.message
+                            .as_ref()
+                            .map(|message| message.to_string())
+                            .unwrap_or_else(|| format!("failed the {} rule", error.code)),
+                    })
+                );
+            }
+            ValidationErrorsKind::Struct(errors) => collect_errors(&path, errors, fields),
+            ValidationErrorsKind::List(errors) => {
+                for (index, errors) in errors {
+                    collect_errors(&format!("{}[{}]", path, index), errors, fields);
+                }
+            }
+        }
+    }
+}
+
+fn join_path(parent: &str, field: &str) -> String {
+    match parent {
+        "." | "" => field.to_string(),
+        parent => format!("{}.{}", parent, field),
+    }
+}
\ No newline at end of file
@@ -0,0 +1,179 @@
+use crate::db::{ ArangoProvider, Database, DatabaseError };
+use arangors::analyzer::{
+    AnalyzerCase,
+    AnalyzerFeature,
+    AnalyzerInfo,
+    NgramAnalyzerProperties,
+    NgramStreamType,
+    TextAnalyzerProperties,
+};
+use arangors::view::{ ArangoSearchViewLink, ArangoSearchViewPropertiesOptions, ViewOptions };
+use std::collections::HashMap;
+
+/// ArangoSearch view indexing `Item.name` and `Item.description`.
+pub const ITEM_SEARCH_VIEW: &str = "ItemSearch";
+/// Tokenizing, stemming analyzer used for relevance ranking.
+pub const TEXT_ANALYZER: &str = "item_text";
+/// Trigram analyzer used for typo-tolerant matching.
+pub const NGRAM_ANALYZER: &str = "item_ngram";
+/// Minimum share of matching trigrams for `NGRAM_MATCH` to accept a misspelled term.
+pub const NGRAM_THRESHOLD: f64 = 0.6;
+
+const SEARCHABLE_FIELDS: [&str; 2] = ["name", "description"];
+
+/// Creates the search analyzers and the `ItemSearch` view when they do not exist yet.
+/// Safe to run on every startup.
+pub async fn setup(database: &Database) -> Result<(), DatabaseError> {
+    let db = database.get_db();
+    // BM25 needs frequencies and norms, NGRAM_MATCH needs positions.
+    let features = || {
+        vec![AnalyzerFeature::Frequency, AnalyzerFeature::Norm, AnalyzerFeature::Position]
+    };
+
+    let existing: Vec<String> = db
+        .list_analyzers().await?
+        .iter()
+        .filter_map(analyzer_name)
+        .collect();
+
+    if !existing.iter().any(|name| is_analyzer(name, TEXT_ANALYZER)) {
+        db.create_analyzer(AnalyzerInfo::Text {
+            name: TEXT_ANALYZER.to_string(),
+            features: Some(features()),
+            properties: Some(
+                TextAnalyzerProperties::builder()
+                    .locale("en".to_string())
+                    .case(AnalyzerCase::Lower)
+                    .accent(false)
+                    .stemming(true)
+                    .stopwords(Vec::new())
+                    .build()
+            ),
+        }).await?;
+    }
+
+    if !existing.iter().any(|name| is_analyzer(name, NGRAM_ANALYZER)) {
+        db.create_analyzer(AnalyzerInfo::Ngram {
+            name: NGRAM_ANALYZER.to_string(),
+            features: Some(features()),
+            properties: Some(
+                NgramAnalyzerProperties::builder()
+                    .min(3)
+                    .max(3)
+                    .preserve_original(false)
+                    .stream_type(NgramStreamType::Utf8)
+                    .build()
+            ),
+        }).await?;
+    }
+
+    let views = db.list_views().await?;
+    if views.iter().any(|view| view.name == ITEM_SEARCH_VIEW) {
+        return Ok(());
+    }
+
+    let fields: HashMap<String, ArangoSearchViewLink> = SEARCHABLE_FIELDS.iter()
+        .map(|field| (field.to_string(), ArangoSearchViewLink::builder().build()))
+        .collect();
+
+    let link = ArangoSearchViewLink::builder()
+        .analyzers(vec![TEXT_ANALYZER.to_string(), NGRAM_ANALYZER.to_string()])
+        .fields(fields)
+        .include_all_fields(false)
+        .build();
+
+    db.create_view(
+        ViewOptions::builder()
+            .name(ITEM_SEARCH_VIEW.to_string())
+            .properties(
+                ArangoSearchViewPropertiesOptions::builder()
+                    .links(HashMap::from([("Item".to_string(), link)]))
+                    .build()
+            )
+            .build()
+    ).await?;
+
+    Ok(())
+}
+
+/// Drops the `ItemSearch` view and its analyzers. Analyzers can only be removed once no view
+/// references them, so the view goes first.
+pub async fn teardown(database: &Database) -> Result<(), DatabaseError> {
+    let db = database.get_db();
+
+    let views = db.list_views().await?;
+    if views.iter().any(|view| view.name == ITEM_SEARCH_VIEW) {
+        db.drop_view(ITEM_SEARCH_VIEW).await?;
+    }
+
+    let existing: Vec<String> = db
+        .list_analyzers().await?
+        .iter()
+        .filter_map(analyzer_name)
+        .collect();
+
+    for analyzer in [TEXT_ANALYZER, NGRAM_ANALYZER] {
+        if existing.iter().any(|name| is_analyzer(name, analyzer)) {
+            db.drop_analyzer(analyzer).await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn analyzer_name(info: &AnalyzerInfo) -> Option<String> {
+    match info {
+        AnalyzerInfo::Text { name, .. } | AnalyzerInfo::Ngram { name, .. } => Some(name.to_owned()),
+        _ => None,
+    }
+}
+
+/// Custom analyzers are listed with a `<database>::` prefix.
+fn is_analyzer(listed: &str, name: &str) -> bool {
+    listed == name || listed.rsplit("::").next() == Some(name)
+}
+
+/// Lower-cased alphanumeric terms of a search query.
+pub fn terms(query: &str) -> Vec<String> {
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| term.to_lowercase())
+        .collect()
+}
+
+/// Wraps every word of `text` containing one of `terms` in `<mark>` tags. Returns `None` when
+/// nothing matched, so callers can skip fields that did not contribute to the hit.
+pub fn highlight(text: &str, terms: &[String]) -> Option<String> {
+    let mut highlighted = String::with_capacity(text.len());
+    let mut matched = false;
+    let mut word = String::new();
+
+    let mut flush = |word: &mut String, highlighted: &mut String| {
+        if word.is_empty() {
+            return;
+        }
+        let lower = word.to_lowercase();
+        if terms.iter().any(|term| lower.contains(term.as_str())) {
+            matched = true;
+            highlighted.push_str("<mark>");
+            highlighted.push_str(word);
+            highlighted.push_str("</mark>");
+        } else {
+            highlighted.push_str(word);
+        }
+        word.clear();
+    };
+
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            word.push(c);
+        } else {
+            flush(&mut word, &mut highlighted);
+            highlighted.push(c);
+        }
+    }
+    flush(&mut word, &mut highlighted);
+
+    matched.then_some(highlighted)
+}
\ No newline at end of file
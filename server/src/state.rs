@@ -0,0 +1,91 @@
+use crate::db::Database;
+use crate::mail::{ self, Mailer };
+use crate::password::Passwords;
+use crate::rate_limit::{ MemoryRateLimitStore, RateLimitStore };
+use crate::repositories::audit::AuditRepository;
+use crate::repositories::carts::CartRepository;
+use crate::repositories::items::ItemRepository;
+use crate::repositories::login_attempts::LoginAttemptRepository;
+use crate::repositories::memory::MemoryDatabase;
+use crate::repositories::orders::OrderRepository;
+use crate::repositories::password_resets::PasswordResetRepository;
+use crate::repositories::tokens::TokenRepository;
+use crate::repositories::users::UserRepository;
+use crate::requests::jwt::JwtKeys;
+use crate::toml_env::{ AttemptStore, Config, Environment };
+use std::error::Error;
+use std::sync::Arc;
+
+/// Everything a handler or middleware needs, built once in `main` and shared through axum `State`.
+#[derive(Clone)]
+pub struct AppState {
+    pub items: Arc<dyn ItemRepository>,
+    pub orders: Arc<dyn OrderRepository>,
+    pub carts: Arc<dyn CartRepository>,
+    pub users: Arc<dyn UserRepository>,
+    pub tokens: Arc<dyn TokenRepository>,
+    pub resets: Arc<dyn PasswordResetRepository>,
+    pub attempts: Arc<dyn LoginAttemptRepository>,
+    pub audit: Arc<dyn AuditRepository>,
+    pub mailer: Arc<dyn Mailer>,
+    pub limiter: Arc<dyn RateLimitStore>,
+    pub config: Arc<Config>,
+    pub keys: Arc<JwtKeys>,
+    pub passwords: Arc<Passwords>,
+    pub env: Environment,
+}
+
+impl AppState {
+    pub fn new(database: Database, config: Config) -> Result<Self, Box<dyn Error>> {
+        Self::from_repository(Arc::new(database), config)
+    }
+
+    pub fn in_memory(config: Config) -> Result<Self, Box<dyn Error>> {
+        Self::from_repository(Arc::new(MemoryDatabase::new()), config)
+    }
+
+    /// Fails when the `[Mail]`, `[Jwt]` or `[Passwords]` config cannot be used.
+    pub fn from_repository<R>(repository: Arc<R>, config: Config) -> Result<Self, Box<dyn Error>>
+        where
+            R: ItemRepository +
+                OrderRepository +
+                CartRepository +
+                UserRepository +
+                TokenRepository +
+                PasswordResetRepository +
+                LoginAttemptRepository +
+                AuditRepository +
+                'static
+    {
+        let attempts: Arc<dyn LoginAttemptRepository> = match config.security.attempt_store {
+            AttemptStore::Database => repository.clone(),
+            AttemptStore::Memory => Arc::new(MemoryDatabase::new()),
+        };
+        let mailer = mail
+            ::from_config(&config.mail)
+            .map_err(|err| format!("Invalid [Mail] config: {}", err))?;
+        let keys = JwtKeys::from_config(&config).map_err(|err| {
+            format!("Invalid [Jwt] config: {}", err)
+        })?;
+        let passwords = Passwords::from_config(&config.passwords).map_err(|err| {
+            format!("Invalid [Passwords] config: {}", err)
+        })?;
+
+        Ok(Self {
+            items: repository.clone(),
+            orders: repository.clone(),
+            carts: repository.clone(),
+            users: repository.clone(),
+            tokens: repository.clone(),
+            resets: repository.clone(),
+            attempts,
+            audit: repository,
+            mailer,
+            limiter: Arc::new(MemoryRateLimitStore::new()),
+            keys: Arc::new(keys),
+            passwords: Arc::new(passwords),
+            env: config.server.env,
+            config: Arc::new(config),
+        })
+    }
+}
\ No newline at end of file
@@ -1,3 +1,7 @@
+use crate::constants::{ CONFIG_PATH_ENV, DEV_CONFIG_PATH };
+use crate::rate_limit::Budget;
+use chrono::{ Duration, NaiveDateTime };
+use jsonwebtoken::Algorithm;
 use log::LevelFilter;
 use serde::{ Deserialize, Deserializer };
 use std::{ error::Error, net::{ IpAddr, Ipv4Addr, SocketAddr } };
@@ -11,15 +15,42 @@ pub struct Config {
     pub log: LogConfig,
     #[serde(rename = "Server")]
     pub server: ServerConfig,
+    #[serde(rename = "Retention", default)]
+    pub retention: RetentionConfig,
+    #[serde(rename = "Mail", default)]
+    pub mail: MailConfig,
+    #[serde(rename = "Jwt", default)]
+    pub jwt: JwtConfig,
+    #[serde(rename = "Security", default)]
+    pub security: SecurityConfig,
+    #[serde(rename = "RateLimit", default)]
+    pub rate_limit: RateLimitConfig,
+    #[serde(rename = "TwoFactor", default)]
+    pub two_factor: TwoFactorConfig,
+    #[serde(rename = "Passwords", default)]
+    pub passwords: PasswordConfig,
 }
 
 impl Config {
     pub fn parse(path: &str) -> Result<Self, Box<dyn Error>> {
         let contents = std::fs::read_to_string(path)?;
-        let config: Self = toml::from_str(&contents)?;
+
+        Self::from_toml(&contents)
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Self, Box<dyn Error>> {
+        let config: Self = toml::from_str(contents)?;
 
         Ok(config)
     }
+
+    /// Resolves the config file from the `--config` CLI flag, then the `RANS_CONFIG` env var,
+    /// falling back to the development config.
+    pub fn path(flag: Option<String>) -> String {
+        flag
+            .or_else(|| std::env::var(CONFIG_PATH_ENV).ok())
+            .unwrap_or_else(|| DEV_CONFIG_PATH.to_string())
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -61,6 +92,304 @@ fn deserialize_log_level<'de, D>(deserializer: D) -> Result<LevelFilter, D::Erro
     Ok(level)
 }
 
+/// How long soft-deleted records are kept before the purge job removes them for good.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Days a soft-deleted item or order can still be restored.
+    pub deleted_days: u32,
+    /// Minutes between two runs of the purge job. `0` disables the job.
+    pub purge_interval_minutes: u64,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self { deleted_days: 30, purge_interval_minutes: 60 }
+    }
+}
+
+impl RetentionConfig {
+    /// Records deleted before this moment are due to be purged.
+    pub fn purge_before(&self, now: NaiveDateTime) -> NaiveDateTime {
+        now - Duration::days(self.deleted_days.into())
+    }
+}
+
+/// How emails are sent. Without a `[Mail.Smtp]` section they are written to files in `dir`.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct MailConfig {
+    /// Sender of every email, e.g. `RANS <no-reply@rans.com>`.
+    pub from: String,
+    /// Directory the file mailer writes emails to.
+    pub dir: String,
+    /// Client page that sets the new password. Reset emails link to it with a `token` parameter.
+    pub reset_url: String,
+    /// Client page that verifies email addresses. Verification emails link to it with a `token`
+    /// parameter.
+    pub verify_url: String,
+    #[serde(rename = "Smtp")]
+    pub smtp: Option<SmtpConfig>,
+}
+
+impl Default for MailConfig {
+    fn default() -> Self {
+        Self {
+            from: "RANS <no-reply@rans.com>".to_string(),
+            dir: "mail".to_string(),
+            reset_url: "http://localhost:8080/reset_password".to_string(),
+            verify_url: "http://localhost:8080/verify_email".to_string(),
+            smtp: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SmtpConfig {
+    pub host: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Upgrades the connection with STARTTLS. Only disable it for local relays.
+    #[serde(default = "default_starttls")]
+    pub starttls: bool,
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+fn default_starttls() -> bool {
+    true
+}
+
+/// Lockout of email addresses and client IPs after repeated failed logins.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct SecurityConfig {
+    /// Failed logins for one email address before it is locked out. `0` disables the lockout.
+    pub max_failures: u32,
+    /// Failed logins from one client IP, whatever the email, before it is locked out. `0`
+    /// disables the lockout. Behind a proxy whose `X-Forwarded-For` is not trusted every client
+    /// shares the proxy's IP, so this lockout then locks everyone out at once.
+    pub max_ip_failures: u32,
+    /// Minutes a failed login keeps counting towards a lockout.
+    pub failure_window_minutes: u64,
+    /// Seconds the first lockout lasts. Every failure after it doubles the next one.
+    pub lockout_seconds: u64,
+    /// Longest a lockout can last, in seconds.
+    pub max_lockout_seconds: u64,
+    /// Where failed logins are counted.
+    pub attempt_store: AttemptStore,
+    /// Takes the client IP from the last `X-Forwarded-For` entry, the one the proxy appended. Only
+    /// enable it behind a single proxy that appends to the header, like the shipped nginx.conf;
+    /// clients that connect directly could otherwise pick their IP.
+    pub trust_forwarded_for: bool,
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            max_failures: 5,
+            max_ip_failures: 50,
+            failure_window_minutes: 60,
+            lockout_seconds: 30,
+            max_lockout_seconds: 900,
+            attempt_store: AttemptStore::default(),
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+impl SecurityConfig {
+    pub fn failure_window(&self) -> Duration {
+        Duration::minutes(self.failure_window_minutes as i64)
+    }
+
+    /// How long `failures` recent failed logins lock a subject out, given its `limit`. `None`
+    /// while the limit is not reached.
+    pub fn lockout(&self, failures: u32, limit: u32) -> Option<Duration> {
+        if limit == 0 || failures < limit {
+            return None;
+        }
+
+        let doublings = (failures - limit).min(32);
+        let seconds = self.lockout_seconds
+            .saturating_mul(1 << doublings)
+            .min(self.max_lockout_seconds);
+
+        Some(Duration::seconds(seconds as i64))
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AttemptStore {
+    /// The `LoginAttempt` collection, shared by every server and emptied by a TTL index.
+    #[default]
+    Database,
+    /// The memory of this process. Only suits a single server, and restarts forget attempts.
+    Memory,
+}
+
+/// How passwords are hashed, and what makes a new password strong enough. Stored hashes made with
+/// another algorithm or other parameters are upgraded the next time their user logs in.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct PasswordConfig {
+    pub algorithm: HashAlgorithm,
+    /// Work factor of bcrypt, between 4 and 31. Every step doubles the time a hash takes.
+    pub bcrypt_cost: u32,
+    /// Memory one Argon2id hash uses, in KiB.
+    pub argon2_memory_kib: u32,
+    pub argon2_iterations: u32,
+    pub argon2_parallelism: u32,
+    #[serde(rename = "Policy")]
+    pub policy: PasswordPolicy,
+}
+
+impl Default for PasswordConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: HashAlgorithm::default(),
+            bcrypt_cost: bcrypt::DEFAULT_COST,
+            argon2_memory_kib: 19456,
+            argon2_iterations: 2,
+            argon2_parallelism: 1,
+            policy: PasswordPolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    #[default]
+    Bcrypt,
+    Argon2id,
+}
+
+/// Rules new passwords must follow at signup, password change and password reset.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct PasswordPolicy {
+    /// Shortest password accepted. Requests never take fewer than 8 or more than 128 characters.
+    pub min_length: usize,
+    /// How many of lowercase letters, uppercase letters, digits and symbols a password must mix.
+    pub min_character_classes: usize,
+    /// Rejects well-known passwords such as `password1`.
+    pub reject_common: bool,
+    /// Rejects passwords containing the name or email address of the user.
+    pub reject_personal: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self { min_length: 8, min_character_classes: 1, reject_common: true, reject_personal: true }
+    }
+}
+
+/// Two-factor authentication with time-based one-time passwords (TOTP).
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct TwoFactorConfig {
+    /// Name authenticator apps show next to the codes.
+    pub issuer: String,
+    /// Keeps vendors from managing items until they enable two-factor authentication. They can
+    /// still log in to enroll.
+    pub require_for_vendors: bool,
+    /// Steps before and after the current one whose codes are still accepted, to allow for clock
+    /// drift.
+    pub skew_steps: u64,
+    /// Recovery codes handed out when two-factor authentication is enabled.
+    pub recovery_codes: usize,
+}
+
+impl Default for TwoFactorConfig {
+    fn default() -> Self {
+        Self {
+            issuer: "RANS".to_string(),
+            require_for_vendors: false,
+            skew_steps: 1,
+            recovery_codes: 10,
+        }
+    }
+}
+
+/// Request budgets of every client, who is the user of a valid access token or else the client
+/// IP. Each request spends from the global budget and from the budget of its route, if any.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Requests a client can make to all routes per `per_seconds`.
+    pub requests: u32,
+    pub per_seconds: u64,
+    #[serde(rename = "Routes")]
+    pub routes: Vec<RouteLimitConfig>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self { enabled: true, requests: 300, per_seconds: 60, routes: Vec::new() }
+    }
+}
+
+impl RateLimitConfig {
+    pub fn budget(&self) -> Budget {
+        Budget { requests: self.requests, per_seconds: self.per_seconds }
+    }
+
+    /// The budget of the route matched by `path` (e.g. `/api/get_item/:name`) and `method`.
+    pub fn route(&self, method: &str, path: &str) -> Option<&RouteLimitConfig> {
+        self.routes.iter().find(|route| {
+            route.path == path &&
+                route.method.as_ref().is_none_or(|expected| expected.eq_ignore_ascii_case(method))
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RouteLimitConfig {
+    /// Route as declared in the router, e.g. `/api/get_item/:name`.
+    pub path: String,
+    /// Only limits requests with this method. Defaults to every method.
+    pub method: Option<String>,
+    pub requests: u32,
+    pub per_seconds: u64,
+}
+
+impl RouteLimitConfig {
+    pub fn budget(&self) -> Budget {
+        Budget { requests: self.requests, per_seconds: self.per_seconds }
+    }
+}
+
+/// Key pairs access and verification tokens are signed with. Without any `[[Jwt.Keys]]` tokens are
+/// signed with the server `secret` (HS256).
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct JwtConfig {
+    /// `kid` of the key new tokens are signed with. Defaults to the first key.
+    pub active: Option<String>,
+    /// Every key tokens are accepted from. Keep a rotated-out key here until the tokens it signed
+    /// have expired.
+    #[serde(rename = "Keys")]
+    pub keys: Vec<JwtKeyConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JwtKeyConfig {
+    pub kid: String,
+    /// `RS256`, `RS384`, `RS512`, `PS256`, `PS384`, `PS512` for RSA keys or `EdDSA` for Ed25519.
+    pub algorithm: Algorithm,
+    /// Path to the PEM encoded public key (SubjectPublicKeyInfo).
+    pub public_key: String,
+    /// Path to the PEM encoded PKCS#8 private key. Only the active key needs one.
+    pub private_key: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ServerConfig {
     #[serde(deserialize_with = "deserialize_env")]
@@ -70,6 +399,31 @@ pub struct ServerConfig {
     pub port: u16,
     pub secret: String,
     pub origins: Option<Vec<String>>,
+    /// What users cannot do until they verify their email address.
+    #[serde(default)]
+    pub require_verified_email: VerifiedEmailGate,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum VerifiedEmailGate {
+    /// Unverified users can use the whole API.
+    #[default]
+    Off,
+    /// Unverified users cannot log in, refresh their session or order.
+    Login,
+    /// Unverified users can log in and browse, but cannot order.
+    Ordering,
+}
+
+impl VerifiedEmailGate {
+    pub fn blocks_login(&self) -> bool {
+        self == &VerifiedEmailGate::Login
+    }
+
+    pub fn blocks_ordering(&self) -> bool {
+        self != &VerifiedEmailGate::Off
+    }
 }
 
 impl ServerConfig {
@@ -84,7 +438,7 @@ impl ServerConfig {
                 for value in values {
                     origins.push(value.parse().unwrap());
                 }
-                return Some(AllowOrigin::list(origins));
+                Some(AllowOrigin::list(origins))
             }
             None => None,
         }
@@ -113,7 +467,7 @@ fn deserialize_env<'de, D>(deserializer: D) -> Result<Environment, D::Error>
     Ok(env)
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Deserialize, PartialEq, Clone, Copy)]
 pub enum Environment {
     DEV,
     PROD,
@@ -121,18 +475,18 @@ pub enum Environment {
 
 impl Environment {
     pub fn is_dev(&self) -> bool {
-        return self == &Environment::DEV;
+        self == &Environment::DEV
     }
 
     pub fn is_prod(&self) -> bool {
-        return self == &Environment::PROD;
+        self == &Environment::PROD
     }
 
     pub fn is_equal(&self, compare_with: &Environment) -> bool {
-        return self == compare_with;
+        self == compare_with
     }
 
     pub fn compare(env: &Environment, compare_with: &Environment) -> bool {
-        return env == compare_with;
+        env == compare_with
     }
 }
\ No newline at end of file
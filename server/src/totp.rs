@@ -0,0 +1,134 @@
+use rand::RngCore;
+use ring::{ constant_time, hmac };
+
+/// Seconds each code is valid for, as assumed by authenticator apps.
+pub const STEP_SECONDS: u64 = 30;
+pub const DIGITS: u32 = 6;
+/// Length of the shared secret, as recommended by RFC 4226.
+const SECRET_BYTES: usize = 20;
+/// Length of a recovery code, split in two groups for readability.
+const RECOVERY_CODE_CHARS: usize = 10;
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Generates a random shared secret, base32 encoded for authenticator apps.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; SECRET_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    base32_encode(&bytes)
+}
+
+/// `otpauth://` URI of `secret`, which authenticator apps import from a QR code.
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &str) -> String {
+    let issuer = urlencoding::encode(issuer);
+
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        issuer,
+        urlencoding::encode(account),
+        secret,
+        issuer,
+        DIGITS,
+        STEP_SECONDS
+    )
+}
+
+/// Time step `timestamp` (seconds since the epoch) falls in.
+pub fn step(timestamp: u64) -> u64 {
+    timestamp / STEP_SECONDS
+}
+
+/// Code of `secret` for `step` (RFC 6238 with HMAC-SHA1). `None` when the secret is not base32.
+pub fn code(secret: &str, step: u64) -> Option<String> {
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, &base32_decode(secret)?);
+    let digest = hmac::sign(&key, &step.to_be_bytes());
+    let digest = digest.as_ref();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let bytes = [digest[offset], digest[offset + 1], digest[offset + 2], digest[offset + 3]];
+    let value = (u32::from_be_bytes(bytes) & 0x7fff_ffff) % 10u32.pow(DIGITS);
+
+    Some(format!("{:0width$}", value, width = DIGITS as usize))
+}
+
+/// Step of the code of `secret` matching `code`, looking up to `skew` steps around `timestamp` to
+/// allow for clock drift. Steps up to `last_step` are skipped, so that a code works only once.
+pub fn verify(
+    secret: &str,
+    code: &str,
+    timestamp: u64,
+    skew: u64,
+    last_step: Option<u64>
+) -> Option<u64> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    let current = step(timestamp);
+
+    (current.saturating_sub(skew)..=current + skew)
+        .filter(|step| last_step.is_none_or(|last| *step > last))
+        .find(|step| {
+            self::code(secret, *step).is_some_and(|expected| {
+                constant_time::verify_slices_are_equal(expected.as_bytes(), code.as_bytes()).is_ok()
+            })
+        })
+}
+
+/// Generates `count` single-use recovery codes, formatted like `abcde-fghij`.
+pub fn generate_recovery_codes(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|_| {
+            let mut bytes = [0u8; RECOVERY_CODE_CHARS * 5 / 8];
+            rand::thread_rng().fill_bytes(&mut bytes);
+            let code = base32_encode(&bytes).to_lowercase();
+            let (first, second) = code.split_at(RECOVERY_CODE_CHARS / 2);
+
+            format!("{}-{}", first, second)
+        })
+        .collect()
+}
+
+/// Recovery code as it is hashed, ignoring case, dashes and spaces.
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// RFC 4648 base32, without padding.
+pub fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    encoded
+}
+
+/// Decodes RFC 4648 base32, ignoring case, spaces and padding. `None` on any other character.
+pub fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(encoded.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for c in encoded.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let value = BASE32_ALPHABET.iter().position(|a| *a as char == c.to_ascii_uppercase())?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(bytes)
+}
\ No newline at end of file
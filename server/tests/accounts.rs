@@ -0,0 +1,184 @@
+mod common;
+
+use axum::{ extract::State, http::StatusCode, Json };
+use chrono::Local;
+use serde::de::DeserializeOwned;
+use serde_json::{ json, Value };
+use server::api::ApiError;
+use server::models::Role;
+use server::repositories::items::{ ItemRepository, NewItem };
+use server::repositories::memory::MemoryDatabase;
+use server::repositories::orders::{ NewOrder, NewOrderLine, OrderRepository };
+use server::repositories::users::{ NewUser, UserRepository };
+use server::requests::jwt::Claims;
+use server::requests::security::ClientIp;
+use server::requests::validation::Valid;
+use server::requests::{ auth, jwt, users };
+use server::state::AppState;
+use std::sync::Arc;
+
+fn valid<T: DeserializeOwned>(value: Value) -> Valid<T> {
+    Valid(serde_json::from_value(value).unwrap())
+}
+
+async fn seed_user(database: &MemoryDatabase, email: &str, role: Role) -> Claims {
+    let user = UserRepository::insert(database, NewUser {
+        email: email.to_string(),
+        role: role.clone(),
+        ..common::user_fixture()
+    }).await.unwrap();
+
+    Claims {
+        sub: user.email,
+        key: user._key,
+        role,
+        iat: 0,
+        exp: usize::MAX,
+        ver: 0,
+    }
+}
+
+async fn login(state: &AppState, email: &str, password: &str) -> (StatusCode, Value) {
+    common::respond(
+        auth::handle_login(
+            State(state.clone()),
+            ClientIp::default(),
+            valid(json!({ "email": email, "password": password }))
+        ).await
+    )
+}
+
+#[tokio::test]
+async fn users_read_and_update_their_profile() {
+    let database = Arc::new(MemoryDatabase::new());
+    let state = common::state_with(database.clone());
+    let jane = seed_user(&database, "jane@doe.com", Role::CUSTOMER).await;
+    seed_user(&database, "john@doe.com", Role::CUSTOMER).await;
+
+    let profile = users::get_profile(State(state.clone()), jane.clone()).await;
+    let (status, profile) = common::respond(profile);
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(profile["email"], "jane@doe.com");
+    assert!(profile.get("password").is_none());
+
+    let update = |payload: Value| {
+        users::update_profile(State(state.clone()), jane.clone(), valid(payload))
+    };
+
+    let (status, profile) = common::respond(update(json!({ "first_name": "Janet" })).await);
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(profile["first_name"], "Janet");
+    assert_eq!(profile["last_name"], "Doe");
+
+    // Changing the email asks for the password again.
+    let (status, _) = common::respond(update(json!({ "email": "janet@doe.com" })).await);
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let payload = json!({ "email": "john@doe.com", "current_password": "Password.1" });
+    let (status, _) = common::respond(update(payload).await);
+    assert_eq!(status, StatusCode::CONFLICT);
+    let payload = json!({ "email": "janet@doe.com", "current_password": "Password.1" });
+    let (status, profile) = common::respond(update(payload).await);
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(profile["email"], "janet@doe.com");
+
+    assert_eq!(login(&state, "janet@doe.com", "Password.1").await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn changing_the_password_revokes_refresh_tokens() {
+    let database = Arc::new(MemoryDatabase::new());
+    let state = common::state_with(database.clone());
+    let jane = seed_user(&database, "jane@doe.com", Role::CUSTOMER).await;
+    let (_, session) = login(&state, "jane@doe.com", "Password.1").await;
+
+    let change = |current: &str, new: &str| {
+        let payload = valid(json!({ "current_password": current, "new_password": new }));
+        users::change_password(State(state.clone()), jane.clone(), payload)
+    };
+    let (status, _) = common::respond(change("wrong", "Password.2").await);
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, auth) = common::respond(change("Password.1", "Password.2").await);
+    assert_eq!(status, StatusCode::OK);
+
+    let refresh = |token: &Value| {
+        let payload = Json(serde_json::from_value(json!({ "refresh_token": token })).unwrap());
+        jwt::refresh(State(state.clone()), payload)
+    };
+    let (status, _) = common::respond(refresh(&session["refresh_token"]).await);
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(common::respond(refresh(&auth["refresh_token"]).await).0, StatusCode::OK);
+
+    // Access tokens issued before the change stop working too.
+    let old = jwt::authenticate(&state, session["token"].as_str().unwrap()).await;
+    assert!(matches!(old, Err(ApiError::Unauthorized(_))));
+    assert!(jwt::authenticate(&state, auth["token"].as_str().unwrap()).await.is_ok());
+
+    assert_eq!(login(&state, "jane@doe.com", "Password.1").await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(login(&state, "jane@doe.com", "Password.2").await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn deleting_an_account_removes_listings_and_anonymizes_orders() {
+    let database = Arc::new(MemoryDatabase::new());
+    let state = common::state_with(database.clone());
+    let vendor = seed_user(&database, "vendor@doe.com", Role::VENDOR).await;
+    let customer = seed_user(&database, "customer@doe.com", Role::CUSTOMER).await;
+    let item = ItemRepository::insert(&*database, NewItem {
+        name: "Nutella Jar".to_string(),
+        user_id: vendor.key.to_owned(),
+        description: "Hazelnut spread".to_string(),
+        price: "3.50".parse().unwrap(),
+        quantity: 5,
+    }).await.unwrap();
+    database
+        .place(NewOrder {
+            user_id: customer.key.to_owned(),
+            lines: vec![NewOrderLine { item_id: item._key.to_owned(), quantity: 1 }],
+            date: Local::now().naive_local(),
+            cart_rev: None,
+        }).await
+        .unwrap();
+
+    let (_, session) = login(&state, "customer@doe.com", "Password.1").await;
+    let delete = |claims: &Claims, password: &str| {
+        let payload = valid(json!({ "password": password }));
+        users::delete_account(State(state.clone()), claims.clone(), payload)
+    };
+    assert_eq!(common::respond(delete(&vendor, "wrong").await).0, StatusCode::BAD_REQUEST);
+    assert_eq!(common::respond(delete(&vendor, "Password.1").await).0, StatusCode::OK);
+    assert_eq!(common::respond(delete(&customer, "Password.1").await).0, StatusCode::OK);
+    assert_eq!(common::respond(delete(&customer, "Password.1").await).0, StatusCode::NOT_FOUND);
+
+    assert!(ItemRepository::find_by_key(&*database, &item._key).await.is_err());
+    assert!(database.find_by_user(&customer.key, true).await.unwrap().is_empty());
+    let orders = database.find_by_user("deleted", false).await.unwrap();
+    assert_eq!(orders[0].lines[0].item_name, "Nutella Jar");
+    assert!(database.find_by_email("customer@doe.com").await.unwrap().is_none());
+    assert_eq!(login(&state, "customer@doe.com", "Password.1").await.0, StatusCode::BAD_REQUEST);
+    let token = session["token"].as_str().unwrap();
+    assert!(matches!(jwt::authenticate(&state, token).await, Err(ApiError::Unauthorized(_))));
+}
+
+#[tokio::test]
+async fn wrong_current_passwords_count_towards_the_lockout() {
+    let database = Arc::new(MemoryDatabase::new());
+    let mut config = common::config();
+    config.security.max_failures = 3;
+    let state = AppState::from_repository(database.clone(), config).unwrap();
+    let jane = seed_user(&database, "jane@doe.com", Role::CUSTOMER).await;
+    let delete = |password: &str| {
+        let payload = valid(json!({ "password": password }));
+        users::delete_account(State(state.clone()), jane.clone(), payload)
+    };
+
+    for _ in 0..2 {
+        assert_eq!(common::respond(delete("Password.2").await).0, StatusCode::BAD_REQUEST);
+    }
+    let change = json!({ "current_password": "Password.2", "new_password": "Password.3" });
+    let changed = users::change_password(State(state.clone()), jane.clone(), valid(change)).await;
+    assert_eq!(common::respond(changed).0, StatusCode::TOO_MANY_REQUESTS);
+
+    // The right password does not get through either, and neither does a login.
+    assert_eq!(common::respond(delete("Password.1").await).0, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(login(&state, "jane@doe.com", "Password.1").await.0, StatusCode::TOO_MANY_REQUESTS);
+}
\ No newline at end of file
@@ -0,0 +1,139 @@
+mod common;
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{ HeaderMap, Request, StatusCode },
+    middleware,
+    routing::post,
+    Extension,
+    Json,
+    Router,
+};
+use jsonwebtoken::{ encode, EncodingKey, Header };
+use serde_json::json;
+use server::models::Role;
+use server::requests::{ items, jwt::{ self, Claims }, validation::Valid };
+use server::state::AppState;
+use tower::ServiceExt;
+
+fn claims(key: &str, role: Role) -> Claims {
+    Claims {
+        sub: format!("{}@rans.com", key),
+        key: key.to_string(),
+        role,
+        iat: 0,
+        exp: usize::MAX,
+        ver: 0,
+    }
+}
+
+async fn guarded_status(claims: Option<Claims>) -> StatusCode {
+    let mut app = Router::new().route(
+        "/",
+        post(|| async { "ok" }).route_layer(
+            middleware::from_fn_with_state(common::state(), jwt::vendor_guard)
+        )
+    );
+
+    if let Some(claims) = claims {
+        app = app.layer(Extension(claims));
+    }
+
+    app.oneshot(Request::post("/").body(Body::empty()).unwrap()).await.unwrap().status()
+}
+
+fn sign(secret: &str, claims: &Claims) -> String {
+    let key = EncodingKey::from_secret(secret.as_ref());
+    encode(&Header::default(), claims, &key).unwrap()
+}
+
+async fn authenticated_status(state: AppState, token: Option<&str>) -> StatusCode {
+    let app = Router::new().route(
+        "/",
+        post(|claims: Claims| async move { claims.key }).route_layer(
+            middleware::from_fn_with_state(state, jwt::jwt_middleware)
+        )
+    );
+
+    let mut request = Request::post("/");
+    if let Some(token) = token {
+        request = request.header("Authorization", format!("Bearer {}", token));
+    }
+
+    app.oneshot(request.body(Body::empty()).unwrap()).await.unwrap().status()
+}
+
+#[tokio::test]
+async fn jwt_middleware_verifies_tokens_with_the_configured_secret() {
+    let state = common::state();
+    let user = state.users.insert(common::user_fixture()).await.unwrap();
+    let valid = sign(common::SECRET, &claims(&user._key, Role::CUSTOMER));
+    let forged = sign("not the secret", &claims(&user._key, Role::CUSTOMER));
+    let stranger = sign(common::SECRET, &claims("missing", Role::CUSTOMER));
+
+    assert_eq!(authenticated_status(state.clone(), Some(&valid)).await, StatusCode::OK);
+    assert_eq!(
+        authenticated_status(state.clone(), Some(&forged)).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        authenticated_status(state.clone(), Some(&stranger)).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(authenticated_status(state, None).await, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn vendor_guard_only_admits_vendors() {
+    assert_eq!(guarded_status(Some(claims("1", Role::VENDOR))).await, StatusCode::OK);
+    assert_eq!(guarded_status(Some(claims("2", Role::CUSTOMER))).await, StatusCode::FORBIDDEN);
+    assert_eq!(guarded_status(None).await, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn vendors_can_only_modify_their_own_items() {
+    let state = common::state();
+    let owner = claims("1", Role::VENDOR);
+    let other = claims("2", Role::VENDOR);
+
+    let (_, item) = common::respond(items::add_item(
+        State(state.clone()),
+        owner.clone(),
+        Valid(
+            serde_json::from_value(
+                json!({
+                    "name": "Nutella Jar",
+                    "description": "Hazelnut spread",
+                    "price": 4.0,
+                    "quantity": 3
+                })
+            ).unwrap()
+        )
+    ).await);
+    assert_eq!(item["user_id"], "1");
+
+    let key = item["_key"].as_str().unwrap();
+
+    let (status, _) = common::respond(items::edit_item(
+        State(state.clone()),
+        other.clone(),
+        HeaderMap::new(),
+        Valid(serde_json::from_value(json!({ "id": key, "price": 1.0 })).unwrap())
+    ).await);
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = common::respond(items::delete_item(
+        State(state.clone()),
+        other,
+        Json(serde_json::from_value(json!({ "id": key })).unwrap())
+    ).await);
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = common::respond(items::delete_item(
+        State(state),
+        owner,
+        Json(serde_json::from_value(json!({ "id": key })).unwrap())
+    ).await);
+    assert_eq!(status, StatusCode::OK);
+}
\ No newline at end of file
@@ -0,0 +1,140 @@
+mod common;
+
+use axum::{ extract::{ Path, State }, http::StatusCode };
+use serde_json::{ json, Value };
+use server::models::Role;
+use server::repositories::items::{ ItemRepository, NewItem };
+use server::repositories::memory::MemoryDatabase;
+use server::requests::carts;
+use server::requests::jwt::Claims;
+use server::requests::validation::Valid;
+use server::state::AppState;
+use std::sync::Arc;
+
+fn claims(key: &str) -> Claims {
+    Claims {
+        sub: format!("{}@rans.com", key),
+        key: key.to_string(),
+        role: Role::CUSTOMER,
+        iat: 0,
+        exp: usize::MAX,
+        ver: 0,
+    }
+}
+
+async fn seeded() -> (AppState, Arc<MemoryDatabase>, String, String) {
+    let database = Arc::new(MemoryDatabase::new());
+    let mut keys = Vec::new();
+    for (name, price, quantity) in [("Nutella Jar", "3.50", 5), ("Baba Cake", "12.00", 2)] {
+        let item = database
+            .insert(NewItem {
+                name: name.to_string(),
+                user_id: "883".to_string(),
+                description: format!("Fresh {}", name),
+                price: price.parse().unwrap(),
+                quantity,
+            }).await
+            .unwrap();
+        keys.push(item._key);
+    }
+
+    let cake = keys.pop().unwrap();
+    let jar = keys.pop().unwrap();
+    (common::state_with(database.clone()), database, jar, cake)
+}
+
+async fn add(state: &AppState, user: &str, item_id: &str, quantity: i64) -> (StatusCode, Value) {
+    let payload = serde_json::from_value(json!({ "item_id": item_id, "quantity": quantity }));
+    common::respond(
+        carts::add_line(State(state.clone()), claims(user), Valid(payload.unwrap())).await
+    )
+}
+
+#[tokio::test]
+async fn lines_are_merged_and_priced_at_current_prices() {
+    let (state, _, jar, cake) = seeded().await;
+
+    add(&state, "750", &jar, 2).await;
+    add(&state, "750", &cake, 1).await;
+    let (status, cart) = add(&state, "750", &jar, 1).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(cart["lines"].as_array().unwrap().len(), 2);
+    assert_eq!(cart["lines"][0]["quantity"], 3);
+    assert_eq!(cart["lines"][0]["total"]["amount"], "10.50");
+    assert_eq!(cart["subtotal"]["amount"], "22.50");
+
+    let payload = serde_json::from_value(json!({ "quantity": 1 })).unwrap();
+    let (status, cart) = common::respond(
+        carts::update_line(State(state.clone()), claims("750"), Path(jar.clone()), Valid(payload)).await
+    );
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(cart["subtotal"]["amount"], "15.50");
+
+    let (status, cart) = common::respond(
+        carts::remove_line(State(state.clone()), claims("750"), Path(cake.clone())).await
+    );
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(cart["subtotal"]["amount"], "3.50");
+
+    let (_, other) = common::respond(carts::get_cart(State(state), claims("751")).await);
+    assert_eq!(other["lines"], json!([]));
+    assert_eq!(other["subtotal"]["amount"], "0.00");
+}
+
+#[tokio::test]
+async fn lines_are_checked_against_stock_and_the_cart_currency() {
+    let (state, database, jar, _) = seeded().await;
+
+    let (status, _) = add(&state, "750", &jar, 6).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, _) = add(&state, "750", "missing", 1).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    add(&state, "750", &jar, 1).await;
+    let euros = database
+        .insert(NewItem {
+            name: "Croissant".to_string(),
+            user_id: "883".to_string(),
+            description: "Butter croissant".to_string(),
+            price: "2.00 EUR".parse().unwrap(),
+            quantity: 10,
+        }).await
+        .unwrap();
+    let (status, body) = add(&state, "750", &euros._key, 1).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert!(body["error_msg"].as_str().unwrap().contains("USD"));
+
+    let (status, _) = common::respond(
+        carts::remove_line(State(state), claims("750"), Path(euros._key)).await
+    );
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn checkout_places_one_order_and_empties_the_cart() {
+    let (state, database, jar, cake) = seeded().await;
+
+    let (status, _) = common::respond(carts::checkout(State(state.clone()), claims("750")).await);
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    add(&state, "750", &jar, 2).await;
+    add(&state, "750", &cake, 2).await;
+
+    let (status, order) = common::respond(
+        carts::checkout(State(state.clone()), claims("750")).await
+    );
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(order["lines"].as_array().unwrap().len(), 2);
+    assert_eq!(order["subtotal"]["amount"], "31.00");
+    assert_eq!(database.find_by_key(&jar).await.unwrap().quantity, 3);
+    assert_eq!(database.find_by_key(&cake).await.unwrap().quantity, 0);
+
+    let (_, cart) = common::respond(carts::get_cart(State(state.clone()), claims("750")).await);
+    assert_eq!(cart["lines"], json!([]));
+
+    // The cake sold out, so it cannot go back into the cart.
+    let (status, _) = add(&state, "750", &cake, 1).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
\ No newline at end of file
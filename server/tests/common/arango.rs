@@ -0,0 +1,798 @@
+//! In-process stand-in for the parts of ArangoDB's HTTP API the repositories use: database and
+//! collection lookups, document reads and writes, stream transactions and the cursor endpoint.
+//!
+//! Documents live in in-memory maps. AQL is not parsed in general; the cursor endpoint recognises
+//! the query shapes issued by `server::repositories` and answers anything else with an error
+//! naming the query, so a new repository query fails loudly until it is taught here.
+
+use axum::{
+    async_trait,
+    body::{ Bytes, HttpBody },
+    extract::{ FromRequest, Path, Query, State },
+    http::{ header::SERVER, HeaderMap, HeaderValue, Request, StatusCode },
+    response::{ IntoResponse, Response },
+    routing::{ get, post, put },
+    Json,
+    BoxError,
+    Router,
+};
+use regex::Regex;
+use serde_json::{ json, Map, Value };
+use server::db::{
+    DBConnector,
+    Database,
+    DOCUMENT_NOT_FOUND,
+    UNIQUE_CONSTRAINT_VIOLATED,
+    WRITE_CONFLICT,
+};
+use server::migrations::{ Step, MIGRATIONS };
+use std::cmp::Ordering;
+use std::collections::{ BTreeMap, HashMap };
+use std::net::SocketAddr;
+use std::sync::{ Arc, Mutex };
+use tower_http::set_header::SetResponseHeaderLayer;
+
+/// `ERROR_QUERY_PARSE`, returned for queries the fake does not understand.
+const QUERY_PARSE: u16 = 1501;
+/// `ERROR_ARANGO_DATA_SOURCE_NOT_FOUND`.
+const COLLECTION_NOT_FOUND: u16 = 1203;
+/// `ERROR_TRANSACTION_NOT_FOUND`.
+const TRANSACTION_NOT_FOUND: u16 = 1655;
+
+pub const DATABASE: &str = "rans_test";
+
+type Collections = BTreeMap<String, BTreeMap<String, Value>>;
+type Rows = Result<Vec<Value>, ArangoFailure>;
+
+#[derive(Default)]
+struct Store {
+    collections: Collections,
+    /// `(collection, fields)` pairs covered by a unique index.
+    unique: Vec<(String, Vec<String>)>,
+    /// Collection state at the start of every open transaction, restored on abort.
+    transactions: HashMap<String, Collections>,
+    sequence: u64,
+}
+
+#[derive(Debug)]
+struct ArangoFailure {
+    status: StatusCode,
+    error_num: u16,
+    message: String,
+}
+
+impl ArangoFailure {
+    fn new(status: StatusCode, error_num: u16, message: impl Into<String>) -> Self {
+        Self { status, error_num, message: message.into() }
+    }
+
+    fn not_found(collection: &str, key: &str) -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            DOCUMENT_NOT_FOUND,
+            format!("document not found: {}/{}", collection, key)
+        )
+    }
+}
+
+impl IntoResponse for ArangoFailure {
+    fn into_response(self) -> Response {
+        let body = json!({
+            "error": true,
+            "code": self.status.as_u16(),
+            "errorNum": self.error_num,
+            "errorMessage": self.message,
+        });
+
+        (self.status, Json(body)).into_response()
+    }
+}
+
+/// Handle to a running fake server. The server lives until the test's runtime shuts down.
+#[derive(Clone)]
+pub struct FakeArango {
+    pub url: String,
+    store: Arc<Mutex<Store>>,
+}
+
+impl FakeArango {
+    /// Binds the fake to a random local port, with the collections and unique indexes declared
+    /// by the migrations already in place.
+    pub async fn start() -> Self {
+        let mut store = Store::default();
+        for step in MIGRATIONS.iter().flat_map(|migration| migration.steps.iter()) {
+            match step {
+                Step::Collection { name, .. } => {
+                    store.collections.insert(name.to_string(), BTreeMap::new());
+                }
+                Step::Index { collection, fields, unique: true } => {
+                    let fields = fields.iter().map(|field| field.to_string()).collect();
+                    store.unique.push((collection.to_string(), fields));
+                }
+                Step::DropIndex { collection, fields, unique: true } => {
+                    store.unique.retain(|(name, indexed)| name != collection || indexed != fields);
+                }
+                _ => (),
+            }
+        }
+
+        let store = Arc::new(Mutex::new(store));
+        let app = Router::new()
+            .route("/", get(|| async { Json(json!({ "server": "arango" })) }))
+            .route("/_db/:db/_api/database/current", get(current_database))
+            .route("/_db/:db/_api/collection", get(list_collections))
+            .route("/_db/:db/_api/collection/:name", get(collection_info))
+            .route("/_db/:db/_api/document/:collection", post(insert_document))
+            .route(
+                "/_db/:db/_api/document/:collection/:key",
+                get(read_document).patch(update_document).delete(remove_document)
+            )
+            .route("/_db/:db/_api/cursor", post(cursor))
+            .route("/_db/:db/_api/transaction/begin", post(begin_transaction))
+            .route(
+                "/_db/:db/_api/transaction/:id",
+                put(commit_transaction).delete(abort_transaction)
+            )
+            .fallback(|| async {
+                ArangoFailure::new(StatusCode::NOT_FOUND, 404, "unknown path")
+            })
+            .layer(SetResponseHeaderLayer::overriding(SERVER, HeaderValue::from_static("ArangoDB")))
+            .with_state(store.clone());
+
+        let server = axum::Server
+            ::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .serve(app.into_make_service());
+        let url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+
+        Self { url, store }
+    }
+
+    /// Connects a real `Database` to the fake, the same way `main` connects to ArangoDB.
+    pub async fn database(&self) -> Database {
+        Database::new(DBConnector {
+            db_url: self.url.clone(),
+            db_name: DATABASE.to_string(),
+            db_username: "root".to_string(),
+            db_password: "root".to_string(),
+        }).await.unwrap()
+    }
+
+    /// Current documents of a collection, ordered by key.
+    pub fn documents(&self, collection: &str) -> Vec<Value> {
+        let store = self.store.lock().unwrap();
+        store.collections
+            .get(collection)
+            .map(|documents| documents.values().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+type SharedStore = State<Arc<Mutex<Store>>>;
+
+/// JSON request body. The driver does not send a `Content-Type`, so axum's `Json` would reject it.
+struct Body(Value);
+
+#[async_trait]
+impl<S, B> FromRequest<S, B>
+    for Body
+    where S: Send + Sync, B: HttpBody + Send + 'static, B::Data: Send, B::Error: Into<BoxError>
+{
+    type Rejection = ArangoFailure;
+
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let bytes = Bytes::from_request(req, state).await.unwrap_or_default();
+        let value = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes).map_err(|err| {
+                ArangoFailure::new(StatusCode::BAD_REQUEST, 600, err.to_string())
+            })?
+        };
+
+        Ok(Body(value))
+    }
+}
+
+async fn current_database(Path(db): Path<String>) -> Json<Value> {
+    Json(
+        json!({
+            "error": false,
+            "code": 200,
+            "result": { "name": db, "id": "1", "path": "", "isSystem": false }
+        })
+    )
+}
+
+fn collection_json(name: &str, count: usize) -> Value {
+    json!({
+        "id": name,
+        "name": name,
+        "globallyUniqueId": name,
+        "isSystem": name.starts_with('_'),
+        "status": 3,
+        "type": 2,
+        "count": count,
+    })
+}
+
+async fn list_collections(State(store): SharedStore) -> Json<Value> {
+    let store = store.lock().unwrap();
+    let collections: Vec<Value> = store.collections
+        .iter()
+        .map(|(name, documents)| collection_json(name, documents.len()))
+        .collect();
+
+    Json(json!({ "error": false, "code": 200, "result": collections }))
+}
+
+async fn collection_info(
+    State(store): SharedStore,
+    Path((_, name)): Path<(String, String)>
+) -> Result<Json<Value>, ArangoFailure> {
+    let store = store.lock().unwrap();
+    let documents = store.collections
+        .get(&name)
+        .ok_or_else(|| {
+            ArangoFailure::new(StatusCode::NOT_FOUND, COLLECTION_NOT_FOUND, "collection not found")
+        })?;
+
+    let mut info = collection_json(&name, documents.len());
+    info["error"] = json!(false);
+    info["code"] = json!(200);
+
+    Ok(Json(info))
+}
+
+async fn insert_document(
+    State(store): SharedStore,
+    Path((_, collection)): Path<(String, String)>,
+    Body(document): Body
+) -> Result<(StatusCode, Json<Value>), ArangoFailure> {
+    let mut store = store.lock().unwrap();
+    let document = store.insert(&collection, document)?;
+
+    let body = json!({
+        "_id": document["_id"],
+        "_key": document["_key"],
+        "_rev": document["_rev"],
+        "new": document,
+    });
+
+    Ok((StatusCode::CREATED, Json(body)))
+}
+
+async fn read_document(
+    State(store): SharedStore,
+    Path((_, collection, key)): Path<(String, String, String)>
+) -> Result<Json<Value>, ArangoFailure> {
+    let store = store.lock().unwrap();
+
+    store.document(&collection, &key).cloned().map(Json)
+}
+
+async fn update_document(
+    State(store): SharedStore,
+    Path((_, collection, key)): Path<(String, String, String)>,
+    Query(options): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    Body(patch): Body
+) -> Result<(StatusCode, Json<Value>), ArangoFailure> {
+    let mut store = store.lock().unwrap();
+    // Like ArangoDB, a `_rev` in the patch is only a precondition when `ignoreRevs` is false.
+    let expected_rev = headers
+        .get("if-match")
+        .and_then(|rev| rev.to_str().ok())
+        .or_else(|| {
+            let checked = options.get("ignoreRevs").is_some_and(|value| value == "false");
+            patch["_rev"].as_str().filter(|_| checked)
+        });
+
+    let old = store.document(&collection, &key)?.clone();
+    let new = store.update(&collection, &key, &patch, expected_rev)?;
+
+    let mut body = json!({
+        "_id": new["_id"],
+        "_key": new["_key"],
+        "_rev": new["_rev"],
+        "_oldRev": old["_rev"],
+    });
+    if options.get("returnNew").is_some_and(|value| value == "true") {
+        body["new"] = new;
+    }
+    if options.get("returnOld").is_some_and(|value| value == "true") {
+        body["old"] = old;
+    }
+
+    Ok((StatusCode::CREATED, Json(body)))
+}
+
+async fn remove_document(
+    State(store): SharedStore,
+    Path((_, collection, key)): Path<(String, String, String)>,
+    Query(options): Query<HashMap<String, String>>,
+    headers: HeaderMap
+) -> Result<Json<Value>, ArangoFailure> {
+    let mut store = store.lock().unwrap();
+    let expected_rev = headers.get("if-match").and_then(|rev| rev.to_str().ok());
+
+    let old = store.remove(&collection, &key, expected_rev)?;
+
+    let mut body = json!({ "_id": old["_id"], "_key": old["_key"], "_rev": old["_rev"] });
+    if options.get("returnOld").is_some_and(|value| value == "true") {
+        body["old"] = old;
+    }
+
+    Ok(Json(body))
+}
+
+async fn begin_transaction(State(store): SharedStore) -> Json<Value> {
+    let mut store = store.lock().unwrap();
+    let id = store.next_sequence().to_string();
+    let snapshot = store.collections.clone();
+    store.transactions.insert(id.clone(), snapshot);
+
+    Json(json!({ "error": false, "code": 201, "result": { "id": id, "status": "running" } }))
+}
+
+async fn commit_transaction(
+    State(store): SharedStore,
+    Path((_, id)): Path<(String, String)>
+) -> Result<Json<Value>, ArangoFailure> {
+    let mut store = store.lock().unwrap();
+    store.transactions.remove(&id).ok_or_else(|| transaction_not_found(&id))?;
+
+    Ok(Json(json!({ "error": false, "code": 200, "result": { "id": id, "status": "committed" } })))
+}
+
+async fn abort_transaction(
+    State(store): SharedStore,
+    Path((_, id)): Path<(String, String)>
+) -> Result<Json<Value>, ArangoFailure> {
+    let mut store = store.lock().unwrap();
+    let snapshot = store.transactions.remove(&id).ok_or_else(|| transaction_not_found(&id))?;
+    store.collections = snapshot;
+
+    Ok(Json(json!({ "error": false, "code": 200, "result": { "id": id, "status": "aborted" } })))
+}
+
+fn transaction_not_found(id: &str) -> ArangoFailure {
+    ArangoFailure::new(
+        StatusCode::NOT_FOUND,
+        TRANSACTION_NOT_FOUND,
+        format!("transaction {} not found", id)
+    )
+}
+
+async fn cursor(
+    State(store): SharedStore,
+    Body(request): Body
+) -> Result<(StatusCode, Json<Value>), ArangoFailure> {
+    let query = request["query"].as_str().unwrap_or_default();
+    let query = query.split_whitespace().collect::<Vec<_>>().join(" ");
+    let empty = Map::new();
+    let bind_vars = request["bindVars"].as_object().unwrap_or(&empty);
+
+    let mut store = store.lock().unwrap();
+    let result = store.run(&query, bind_vars)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(
+            json!({
+                "error": false,
+                "code": 201,
+                "result": result,
+                "hasMore": false,
+                "cached": false,
+                "extra": {},
+            })
+        ),
+    ))
+}
+
+impl Store {
+    fn next_sequence(&mut self) -> u64 {
+        self.sequence += 1;
+        self.sequence
+    }
+
+    fn collection(&self, name: &str) -> Result<&BTreeMap<String, Value>, ArangoFailure> {
+        self.collections
+            .get(name)
+            .ok_or_else(|| {
+                ArangoFailure::new(
+                    StatusCode::NOT_FOUND,
+                    COLLECTION_NOT_FOUND,
+                    format!("collection or view not found: {}", name)
+                )
+            })
+    }
+
+    fn collection_mut(
+        &mut self,
+        name: &str
+    ) -> Result<&mut BTreeMap<String, Value>, ArangoFailure> {
+        self.collection(name)?;
+        Ok(self.collections.get_mut(name).unwrap())
+    }
+
+    fn document(&self, collection: &str, key: &str) -> Result<&Value, ArangoFailure> {
+        self.collection(collection)?
+            .get(key)
+            .ok_or_else(|| ArangoFailure::not_found(collection, key))
+    }
+
+    fn check_unique(&self, collection: &str, document: &Value) -> Result<(), ArangoFailure> {
+        let documents = self.collection(collection)?;
+
+        for (_, fields) in self.unique.iter().filter(|(name, _)| name == collection) {
+            let duplicate = documents.values().any(|other| {
+                other["_key"] != document["_key"] &&
+                    fields.iter().all(|field| other[field] == document[field])
+            });
+
+            if duplicate && fields.iter().any(|field| !document[field].is_null()) {
+                return Err(
+                    ArangoFailure::new(
+                        StatusCode::CONFLICT,
+                        UNIQUE_CONSTRAINT_VIOLATED,
+                        format!(
+                            "unique constraint violated - in index {}_{}",
+                            collection,
+                            fields.join("_")
+                        )
+                    )
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn insert(&mut self, collection: &str, mut document: Value) -> Result<Value, ArangoFailure> {
+        let key = match document["_key"].as_str() {
+            Some(key) => key.to_string(),
+            None => self.next_sequence().to_string(),
+        };
+        let rev = format!("_r{}", self.next_sequence());
+
+        document["_key"] = json!(key);
+        document["_id"] = json!(format!("{}/{}", collection, key));
+        document["_rev"] = json!(rev);
+
+        if self.collection(collection)?.contains_key(&key) {
+            return Err(
+                ArangoFailure::new(
+                    StatusCode::CONFLICT,
+                    UNIQUE_CONSTRAINT_VIOLATED,
+                    format!("unique constraint violated - in index primary of {}", collection)
+                )
+            );
+        }
+        self.check_unique(collection, &document)?;
+
+        self.collection_mut(collection)?.insert(key, document.clone());
+
+        Ok(document)
+    }
+
+    fn update(
+        &mut self,
+        collection: &str,
+        key: &str,
+        patch: &Value,
+        expected_rev: Option<&str>
+    ) -> Result<Value, ArangoFailure> {
+        let mut document = self.document(collection, key)?.clone();
+
+        if let Some(expected) = expected_rev {
+            if document["_rev"] != expected {
+                return Err(
+                    ArangoFailure::new(StatusCode::PRECONDITION_FAILED, WRITE_CONFLICT, "conflict")
+                );
+            }
+        }
+
+        for (field, value) in patch.as_object().into_iter().flatten() {
+            if !field.starts_with('_') {
+                document[field] = value.clone();
+            }
+        }
+        document["_rev"] = json!(format!("_r{}", self.next_sequence()));
+
+        self.check_unique(collection, &document)?;
+        self.collection_mut(collection)?.insert(key.to_string(), document.clone());
+
+        Ok(document)
+    }
+
+    fn remove(
+        &mut self,
+        collection: &str,
+        key: &str,
+        expected_rev: Option<&str>
+    ) -> Result<Value, ArangoFailure> {
+        let document = self.document(collection, key)?;
+
+        if let Some(expected) = expected_rev {
+            if document["_rev"] != expected {
+                return Err(
+                    ArangoFailure::new(StatusCode::PRECONDITION_FAILED, WRITE_CONFLICT, "conflict")
+                );
+            }
+        }
+
+        Ok(self.collection_mut(collection)?.remove(key).unwrap())
+    }
+
+    /// Answers the AQL queries issued by the repositories.
+    fn run(&mut self, query: &str, vars: &Map<String, Value>) -> Rows {
+        let var = |name: &str| vars.get(name).cloned().unwrap_or(Value::Null);
+
+        if let Some(captures) = pattern(r"^INSERT @(\w+) INTO (\w+) RETURN NEW$").captures(query) {
+            return Ok(vec![self.insert(&captures[2], var(&captures[1]))?]);
+        }
+
+        if let Some(captures) = pattern(r"^RETURN DOCUMENT\((\w+), @(\w+)\)$").captures(query) {
+            let key = var(&captures[2]);
+            let document = self.collection(&captures[1])?.get(key.as_str().unwrap_or_default());
+            return Ok(vec![document.cloned().unwrap_or(Value::Null)]);
+        }
+
+        if query.starts_with("RETURN { total: FIRST( FOR item IN Item") {
+            return self.item_page(query, vars);
+        }
+
+        if query.starts_with("FOR item IN @@view SEARCH") {
+            return self.item_search(vars);
+        }
+
+        // AQL reports revision mismatches as a plain write conflict.
+        let write_conflict = |mut err: ArangoFailure| {
+            err.status = StatusCode::CONFLICT;
+            err
+        };
+
+        let update_rev = pattern(
+            concat!(
+                r"^UPDATE @(\w+) WITH (\{.*\}) IN (\w+) OPTIONS \{ ignoreRevs: false \}",
+                r"( RETURN NEW)?$"
+            )
+        );
+        if let Some(captures) = update_rev.captures(query) {
+            let document = var(&captures[1]);
+            let key = document["_key"].as_str().unwrap_or_default();
+            let patch = object_literal(&captures[2], vars);
+            let updated = self
+                .update(&captures[3], key, &patch, document["_rev"].as_str())
+                .map_err(write_conflict)?;
+            return Ok(captures.get(4).map(|_| updated).into_iter().collect());
+        }
+
+        let remove_rev = pattern(r"^REMOVE @(\w+) IN (\w+) OPTIONS \{ ignoreRevs: false \}$");
+        if let Some(captures) = remove_rev.captures(query) {
+            let document = var(&captures[1]);
+            let key = document["_key"].as_str().unwrap_or_default();
+            self.remove(&captures[2], key, document["_rev"].as_str()).map_err(write_conflict)?;
+            return Ok(Vec::new());
+        }
+
+        let upsert = pattern(
+            r"^UPSERT \{ _key: @(\w+)\._key \} INSERT @\w+ REPLACE @\w+ IN (\w+) RETURN NEW$"
+        );
+        if let Some(captures) = upsert.captures(query) {
+            let document = var(&captures[1]);
+            let key = document["_key"].as_str().unwrap_or_default();
+            self.collection_mut(&captures[2])?.remove(key);
+            return Ok(vec![self.insert(&captures[2], document)?]);
+        }
+
+        let for_filter = pattern(
+            concat!(
+                r"^FOR (\w+) IN (\w+) FILTER (.+?) ",
+                r"(RETURN \w+|REMOVE \w+ IN \w+(?: RETURN OLD\._key)?|",
+                r"UPDATE \w+ WITH (\{.*\}) IN \w+(?: RETURN NEW(?:\._key)?)?)$"
+            )
+        );
+        if let Some(captures) = for_filter.captures(query) {
+            let collection = &captures[2];
+            let keys: Vec<String> = self
+                .collection(collection)?
+                .values()
+                .filter(|document| matches_filter(&captures[1], &captures[3], document, vars))
+                .map(|document| document["_key"].as_str().unwrap().to_string())
+                .collect();
+
+            let operation = &captures[4];
+            let mut rows = Vec::new();
+            for key in keys {
+                if operation.starts_with("RETURN") {
+                    rows.push(self.document(collection, &key)?.clone());
+                } else if operation.starts_with("REMOVE") {
+                    let removed = self.remove(collection, &key, None)?;
+                    if operation.ends_with("RETURN OLD._key") {
+                        rows.push(removed["_key"].clone());
+                    }
+                } else {
+                    let patch = object_literal(&captures[5], vars);
+                    let updated = self.update(collection, &key, &patch, None)?;
+                    if operation.ends_with("RETURN NEW._key") {
+                        rows.push(updated["_key"].clone());
+                    } else if operation.ends_with("RETURN NEW") {
+                        rows.push(updated);
+                    }
+                }
+            }
+            return Ok(rows);
+        }
+
+        Err(
+            ArangoFailure::new(
+                StatusCode::BAD_REQUEST,
+                QUERY_PARSE,
+                format!("query not supported by the fake ArangoDB: {}", query)
+            )
+        )
+    }
+
+    fn item_page(&self, query: &str, vars: &Map<String, Value>) -> Rows {
+        let var = |name: &str| vars.get(name).cloned().unwrap_or(Value::Null);
+        let descending = query.contains("SORT item.@sort DESC");
+        let sort = var("sort");
+        let sort_value = |item: &Value| {
+            sort.as_array()
+                .into_iter()
+                .flatten()
+                .fold(item.clone(), |value, field| value[field.as_str().unwrap()].clone())
+        };
+        let price_within = |item: &Value, bound: Value, ordering: Ordering| {
+            bound.is_null() ||
+                (item["price"]["currency"] == bound["currency"] &&
+                    compare(&item["price"]["minor_units"], &bound["minor_units"]) != ordering)
+        };
+
+        let mut filtered: Vec<&Value> = self
+            .collection("Item")?
+            .values()
+            .filter(|item| price_within(item, var("min_price"), Ordering::Less))
+            .filter(|item| price_within(item, var("max_price"), Ordering::Greater))
+            .filter(|item| var("user_id").is_null() || item["user_id"] == var("user_id"))
+            .filter(|item| var("in_stock") != json!(true) || item["quantity"].as_i64() > Some(0))
+            .filter(|item| var("include_deleted") == json!(true) || item["deleted_at"].is_null())
+            .collect();
+        let total = filtered.len();
+
+        let order = |a: &Value, b: &Value| {
+            compare(&sort_value(a), &sort_value(b)).then_with(|| compare(&a["_key"], &b["_key"]))
+        };
+        filtered.sort_by(|a, b| if descending { order(b, a) } else { order(a, b) });
+
+        let after_value = var("after_value");
+        let after_key = var("after_key");
+        let items: Vec<Value> = filtered
+            .into_iter()
+            .filter(|item| {
+                if after_key.is_null() {
+                    return true;
+                }
+                let position = compare(&sort_value(item), &after_value).then_with(|| {
+                    compare(&item["_key"], &after_key)
+                });
+                if descending { position == Ordering::Less } else { position == Ordering::Greater }
+            })
+            .skip(var("offset").as_u64().unwrap_or(0) as usize)
+            .take(var("limit").as_u64().unwrap_or(u64::MAX) as usize)
+            .cloned()
+            .collect();
+
+        Ok(vec![json!({ "total": total, "items": items })])
+    }
+
+    /// Scores items by how many query terms their name (weighted twice) and description contain.
+    fn item_search(&self, vars: &Map<String, Value>) -> Rows {
+        let query = vars.get("query").and_then(Value::as_str).unwrap_or_default().to_lowercase();
+        let terms: Vec<&str> = query
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|term| !term.is_empty())
+            .collect();
+        let limit = vars.get("limit").and_then(Value::as_u64).unwrap_or(u64::MAX) as usize;
+
+        let mut hits: Vec<(f64, &Value)> = self
+            .collection("Item")?
+            .values()
+            .filter(|item| item["deleted_at"].is_null())
+            .map(|item| {
+                let name = item["name"].as_str().unwrap_or_default().to_lowercase();
+                let description = item["description"].as_str().unwrap_or_default().to_lowercase();
+                let score = terms
+                    .iter()
+                    .map(|term| {
+                        2.0 * (name.contains(term) as u8 as f64) +
+                            (description.contains(term) as u8 as f64)
+                    })
+                    .sum::<f64>();
+                (score, item)
+            })
+            .filter(|(score, _)| *score > 0.0)
+            .collect();
+
+        hits.sort_by(|(a, first), (b, second)| {
+            b.partial_cmp(a).unwrap().then_with(|| compare(&first["name"], &second["name"]))
+        });
+
+        Ok(
+            hits
+                .into_iter()
+                .take(limit)
+                .map(|(score, item)| json!({ "item": item, "score": score }))
+                .collect()
+        )
+    }
+}
+
+fn pattern(regex: &str) -> Regex {
+    Regex::new(regex).unwrap()
+}
+
+/// Evaluates `FILTER` clauses made of `OR`-ed groups of `AND`-ed comparisons (`==`, `!=`, `<`)
+/// between document fields, bind parameters and literals, or of bare boolean bind parameters.
+fn matches_filter(
+    variable: &str,
+    filter: &str,
+    document: &Value,
+    vars: &Map<String, Value>
+) -> bool {
+    let operand = |operand: &str| {
+        if let Some(field) = operand.strip_prefix(&format!("{}.", variable)) {
+            return document[field].clone();
+        }
+        match operand.strip_prefix('@') {
+            Some(name) => vars.get(name).cloned().unwrap_or(Value::Null),
+            None => serde_json::from_str(operand).unwrap_or(Value::Null),
+        }
+    };
+
+    let condition = |condition: &str| {
+        for (operator, expected) in [
+            (" == ", Ordering::Equal),
+            (" != ", Ordering::Equal),
+            (" < ", Ordering::Less),
+        ] {
+            if let Some((left, right)) = condition.split_once(operator) {
+                let ordering = compare(&operand(left), &operand(right));
+                return (ordering == expected) != (operator == " != ");
+            }
+        }
+        operand(condition) == json!(true)
+    };
+
+    filter.split(" FILTER ").all(|clause| {
+        clause.split(" OR ").any(|group| group.split(" AND ").all(condition))
+    })
+}
+
+/// Turns an AQL object literal such as `{ revoked: true }` into JSON, resolving bind parameters.
+fn object_literal(literal: &str, vars: &Map<String, Value>) -> Value {
+    let quoted = pattern(r"(\w+):").replace_all(literal, "\"$1\":");
+    let resolved = pattern(r"@(\w+)").replace_all(&quoted, |captures: &regex::Captures| {
+        vars.get(&captures[1]).cloned().unwrap_or(Value::Null).to_string()
+    });
+
+    serde_json
+        ::from_str(&resolved)
+        .unwrap_or_else(|_| panic!("unsupported object literal {}", literal))
+}
+
+fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => {
+            a.as_f64().partial_cmp(&b.as_f64()).unwrap_or(Ordering::Equal)
+        }
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Less,
+        (_, Value::Null) => Ordering::Greater,
+        _ => a.to_string().cmp(&b.to_string()),
+    }
+}
\ No newline at end of file
@@ -0,0 +1,169 @@
+#![allow(dead_code)]
+
+pub mod arango;
+
+use axum::{ http::StatusCode, Json };
+use serde::Serialize;
+use serde_json::{ json, Value };
+use server::api::{ ApiResult, TaggedResult };
+use server::mail::FileMailer;
+use server::models::{ Role, User };
+use server::repositories::memory::MemoryDatabase;
+use server::repositories::users::{ NewUser, UserRepository };
+use server::requests::jwt::generate_token;
+use server::state::AppState;
+use server::toml_env::Config;
+use std::path::{ Path, PathBuf };
+use std::sync::Arc;
+
+pub const SECRET: &str = "secret";
+
+pub fn config() -> Config {
+    Config::from_toml(
+        &format!(
+            r#"
+            [Database]
+            host = "http://127.0.0.1"
+            port = 8529
+            name = "rans_test"
+            username = "root"
+            password = "root"
+
+            [Server]
+            env = "production"
+            host = "127.0.0.1"
+            port = 3000
+            secret = "{}"
+
+            [Logs]
+            path = "{}"
+            level = "off"
+
+            [Mail]
+            dir = "{}"
+            reset_url = "http://rans.test/reset_password"
+            verify_url = "http://rans.test/verify_email"
+
+            [Passwords]
+            bcrypt_cost = 4 # Same as the users the tests store, so logins do not rehash them
+            "#,
+            SECRET,
+            std::env::temp_dir().join("rans-tests").display(),
+            std::env::temp_dir().join("rans-tests").join("mail").display()
+        )
+    ).unwrap()
+}
+
+pub fn state() -> AppState {
+    AppState::in_memory(config()).unwrap()
+}
+
+pub fn state_with(database: Arc<MemoryDatabase>) -> AppState {
+    AppState::from_repository(database, config()).unwrap()
+}
+
+/// Jane Doe, a verified customer whose password is `Password.1`.
+pub fn user_fixture() -> NewUser {
+    NewUser {
+        first_name: "Jane".to_string(),
+        last_name: "Doe".to_string(),
+        email: "jane@doe.com".to_string(),
+        password: bcrypt::hash("Password.1", 4).unwrap(),
+        role: Role::CUSTOMER,
+        email_verified: true,
+    }
+}
+
+/// State over a new in-memory database holding `user_fixture()`, which is returned as stored.
+pub async fn state_with_user(config: Config) -> (AppState, Arc<MemoryDatabase>, User) {
+    state_holding(config, user_fixture()).await
+}
+
+/// State over a new in-memory database holding `user`, which is returned as stored.
+pub async fn state_holding(config: Config, user: NewUser) -> (AppState, Arc<MemoryDatabase>, User) {
+    let database = Arc::new(MemoryDatabase::new());
+    let user = UserRepository::insert(&*database, user).await.unwrap();
+
+    (AppState::from_repository(database.clone(), config).unwrap(), database, user)
+}
+
+/// State backed by a real `Database` connected to a fresh fake ArangoDB server.
+pub async fn arango_state() -> (AppState, arango::FakeArango) {
+    let arango = arango::FakeArango::start().await;
+    let state = AppState::new(arango.database().await, config()).unwrap();
+
+    (state, arango)
+}
+
+/// Points the mailer of `state` at a new, empty directory and returns it.
+pub fn capture_mail(state: &mut AppState) -> PathBuf {
+    let dir = std::env::temp_dir().join("rans-tests").join("mail").join(generate_token());
+    state.mailer = Arc::new(FileMailer::new("RANS <no-reply@rans.com>", &dir));
+
+    dir
+}
+
+/// Emails written to `dir` by a `FileMailer`, oldest first.
+pub fn sent_mail(dir: &Path) -> Vec<String> {
+    let mut paths: Vec<PathBuf> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries.map(|entry| entry.unwrap().path()).collect(),
+        Err(_) => Vec::new(),
+    };
+    paths.sort();
+
+    paths.into_iter().map(|path| std::fs::read_to_string(path).unwrap()).collect()
+}
+
+/// Waits up to two seconds for `count` emails in `dir`, for mail sent in the background, and
+/// returns what was sent by then.
+pub async fn wait_for_mail(dir: &Path, count: usize) -> Vec<String> {
+    for _ in 0..200 {
+        let mail = sent_mail(dir);
+        if mail.len() >= count && mail.iter().all(|mail| mail.ends_with("\r\n")) {
+            return mail;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+
+    sent_mail(dir)
+}
+
+/// The `token` parameter of the link in an email.
+pub fn link_token(mail: &str) -> String {
+    let token = mail.split("token=").nth(1).expect("email has no link with a token");
+
+    token.split_whitespace().next().unwrap().to_string()
+}
+
+/// Handler results `respond` can flatten, with or without an `ETag`.
+pub trait HandlerResult<T> {
+    fn into_api_result(self) -> ApiResult<T>;
+}
+
+impl<T> HandlerResult<T> for ApiResult<T> {
+    fn into_api_result(self) -> ApiResult<T> {
+        self
+    }
+}
+
+impl<T> HandlerResult<T> for TaggedResult<T> {
+    fn into_api_result(self) -> ApiResult<T> {
+        self.map(|tagged| tagged.body)
+    }
+}
+
+/// Flattens a handler result into the status and `content` the client would receive.
+pub fn respond<T: Serialize>(result: impl HandlerResult<T>) -> (StatusCode, Value) {
+    match result.into_api_result() {
+        Ok(Json(response)) => {
+            (StatusCode::OK, serde_json::to_value(response).unwrap()["content"].take())
+        }
+        Err(err) => {
+            let mut body = json!({ "error_msg": err.message(), "error_code": err.error_code() });
+            if let Some(current) = err.current() {
+                body["current"] = current.clone();
+            }
+            (err.status(), body)
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,191 @@
+mod common;
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{ Request, StatusCode },
+    middleware,
+    routing::post,
+    Extension,
+    Router,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{ json, Value };
+use server::models::{ Role, User };
+use server::repositories::memory::MemoryDatabase;
+use server::requests::jwt::{ self, Claims };
+use server::requests::security::ClientIp;
+use server::requests::validation::Valid;
+use server::requests::{ auth, users };
+use server::state::AppState;
+use server::toml_env::VerifiedEmailGate;
+use std::path::{ Path, PathBuf };
+use std::sync::Arc;
+use tower::ServiceExt;
+
+fn valid<T: DeserializeOwned>(value: Value) -> Valid<T> {
+    Valid(serde_json::from_value(value).unwrap())
+}
+
+fn gated_state(gate: VerifiedEmailGate) -> (AppState, PathBuf) {
+    let mut config = common::config();
+    config.server.require_verified_email = gate;
+    let mut state = AppState::from_repository(Arc::new(MemoryDatabase::new()), config).unwrap();
+    let mailbox = common::capture_mail(&mut state);
+
+    (state, mailbox)
+}
+
+async fn sign_up(state: &AppState) -> (StatusCode, Value) {
+    let payload = json!({
+        "first_name": "Jane",
+        "last_name": "Doe",
+        "email": "jane@doe.com",
+        "password": "Password.1",
+        "role": "CUSTOMER"
+    });
+
+    common::respond(auth::handle_signup(State(state.clone()), valid(payload)).await)
+}
+
+async fn log_in(state: &AppState) -> (StatusCode, Value) {
+    let payload = json!({ "email": "jane@doe.com", "password": "Password.1" });
+
+    common::respond(auth::handle_login(State(state.clone()), ClientIp::default(), valid(payload)).await)
+}
+
+async fn verify(state: &AppState, token: &str) -> (StatusCode, Value) {
+    let payload = valid(json!({ "token": token }));
+
+    common::respond(auth::verify_email(State(state.clone()), payload).await)
+}
+
+fn verification_token(mailbox: &Path) -> String {
+    let mail = common::sent_mail(mailbox);
+    let last = mail.last().expect("no email was sent");
+    assert!(last.contains("http://rans.test/verify_email?token="));
+
+    common::link_token(last)
+}
+
+#[tokio::test]
+async fn signup_sends_a_link_that_verifies_the_email() {
+    let (state, mailbox) = gated_state(VerifiedEmailGate::Off);
+
+    let (status, signup) = sign_up(&state).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(signup["user"]["email_verified"], false);
+    assert!(signup["token"].is_string());
+    let token = verification_token(&mailbox);
+
+    assert_eq!(verify(&state, "not-a-token").await.0, StatusCode::BAD_REQUEST);
+    // Access tokens are signed with the same key but cannot verify an email.
+    assert_eq!(verify(&state, signup["token"].as_str().unwrap()).await.0, StatusCode::BAD_REQUEST);
+
+    let (status, user) = verify(&state, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(user["email_verified"], true);
+    assert_eq!(log_in(&state).await.1["user"]["email_verified"], true);
+}
+
+#[tokio::test]
+async fn unverified_users_cannot_log_in_when_required() {
+    let (state, mailbox) = gated_state(VerifiedEmailGate::Login);
+
+    let (status, signup) = sign_up(&state).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(signup.get("token").is_none());
+    assert!(signup.get("refresh_token").is_none());
+    assert_eq!(log_in(&state).await.0, StatusCode::FORBIDDEN);
+
+    let resend = |password: &str| {
+        let payload = valid(json!({ "email": "jane@doe.com", "password": password }));
+        auth::resend_verification(State(state.clone()), ClientIp::default(), payload)
+    };
+    assert_eq!(common::respond(resend("wrong").await).0, StatusCode::BAD_REQUEST);
+    assert_eq!(common::respond(resend("Password.1").await).0, StatusCode::OK);
+    assert_eq!(common::sent_mail(&mailbox).len(), 2);
+
+    assert_eq!(verify(&state, &verification_token(&mailbox)).await.0, StatusCode::OK);
+    assert_eq!(log_in(&state).await.0, StatusCode::OK);
+    assert_eq!(common::respond(resend("Password.1").await).0, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn changing_the_email_asks_for_a_new_verification() {
+    let (state, mailbox) = gated_state(VerifiedEmailGate::Off);
+    let (_, signup) = sign_up(&state).await;
+    let old_link = verification_token(&mailbox);
+    let claims = Claims {
+        sub: "jane@doe.com".to_string(),
+        key: signup["user"]["_key"].as_str().unwrap().to_string(),
+        role: Role::CUSTOMER,
+        iat: 0,
+        exp: usize::MAX,
+        ver: 0,
+    };
+
+    let payload = valid(json!({ "email": "janet@doe.com", "current_password": "Password.1" }));
+    let (status, profile) = common::respond(
+        users::update_profile(State(state.clone()), claims, payload).await
+    );
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(profile["email_verified"], false);
+
+    let mail = common::sent_mail(&mailbox);
+    assert_eq!(mail.len(), 2);
+    assert!(mail[1].contains("To: janet@doe.com"));
+
+    // Links sent to the previous address no longer work.
+    assert_eq!(verify(&state, &old_link).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(verify(&state, &verification_token(&mailbox)).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn unverified_users_cannot_order_when_required() {
+    for (gate, expected) in [
+        (VerifiedEmailGate::Off, StatusCode::OK),
+        (VerifiedEmailGate::Ordering, StatusCode::FORBIDDEN),
+    ] {
+        let (state, _) = gated_state(gate);
+        let (_, signup) = sign_up(&state).await;
+        let claims = Claims {
+            sub: "jane@doe.com".to_string(),
+            key: signup["user"]["_key"].as_str().unwrap().to_string(),
+            role: Role::CUSTOMER,
+            iat: 0,
+            exp: usize::MAX,
+            ver: 0,
+        };
+
+        let app = Router::new()
+            .route(
+                "/",
+                post(|| async { "ok" }).route_layer(
+                    middleware::from_fn_with_state(state.clone(), jwt::verified_guard)
+                )
+            )
+            .layer(Extension(claims));
+        let request = Request::post("/").body(Body::empty()).unwrap();
+
+        assert_eq!(app.oneshot(request).await.unwrap().status(), expected);
+    }
+}
+
+#[test]
+fn users_stored_before_verification_are_verified() {
+    let user: User = serde_json::from_value(
+        json!({
+            "_key": "1",
+            "_rev": "_rev1",
+            "_id": "User/1",
+            "first_name": "John",
+            "last_name": "Starbury",
+            "email": "jstarb@gmail.com",
+            "password": "$2b$12$hash",
+            "role": "CUSTOMER"
+        })
+    ).unwrap();
+
+    assert!(user.email_verified);
+}
\ No newline at end of file
@@ -0,0 +1,628 @@
+mod common;
+
+use axum::{ body::Body, http::{ header, HeaderMap, Method, Request, StatusCode }, Router };
+use chrono::{ Duration, Local, Utc };
+use common::arango::FakeArango;
+use serde_json::{ json, Value };
+use server::api::ApiError;
+use server::models::Deletion;
+use server::purge;
+use server::repositories::items::NewItem;
+use server::requests::routes::create_routes;
+use server::totp;
+use std::path::PathBuf;
+use tower::ServiceExt;
+
+/// The full router running against a real `Database` talking to the fake ArangoDB server.
+struct Api {
+    app: Router,
+    arango: FakeArango,
+    mailbox: PathBuf,
+}
+
+impl Api {
+    async fn start() -> Self {
+        let (mut state, arango) = common::arango_state().await;
+        let mailbox = common::capture_mail(&mut state);
+        let app = create_routes(state).await;
+
+        Self { app, arango, mailbox }
+    }
+
+    async fn call(
+        &self,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Option<Value>
+    ) -> (StatusCode, Value) {
+        let (status, _, content) = self.send(method, uri, token, HeaderMap::new(), body).await;
+
+        (status, content)
+    }
+
+    /// Like `call`, with extra request headers and returning the response headers too.
+    async fn send(
+        &self,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        headers: HeaderMap,
+        body: Option<Value>
+    ) -> (StatusCode, HeaderMap, Value) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json");
+        if let Some(token) = token {
+            request = request.header("Authorization", format!("Bearer {}", token));
+        }
+        request.headers_mut().unwrap().extend(headers);
+
+        let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+        let response = self.app.clone().oneshot(request.body(body).unwrap()).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: Value = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+
+        (status, headers, body["content"].clone())
+    }
+
+    /// Signs a user up and returns their access token and key.
+    async fn sign_up(&self, email: &str, role: &str) -> (String, String) {
+        let (status, auth) = self.call(
+            Method::POST,
+            "/api/auth/signup",
+            None,
+            Some(
+                json!({
+                    "first_name": "Jane",
+                    "last_name": "Doe",
+                    "email": email,
+                    "password": "Password.1",
+                    "role": role
+                })
+            )
+        ).await;
+        assert_eq!(status, StatusCode::OK, "{}", auth);
+
+        let token = auth["token"].as_str().unwrap().to_string();
+        let key = auth["user"]["_key"].as_str().unwrap().to_string();
+
+        (token, key)
+    }
+
+    async fn add_item(&self, token: &str, name: &str, quantity: i64) -> Value {
+        let (status, item) = self.call(
+            Method::POST,
+            "/api/add_item",
+            Some(token),
+            Some(
+                json!({
+                    "name": name,
+                    "description": "A sample item",
+                    "price": 2.5,
+                    "quantity": quantity
+                })
+            )
+        ).await;
+        assert_eq!(status, StatusCode::OK, "{}", item);
+
+        item
+    }
+}
+
+#[tokio::test]
+async fn users_sign_up_log_in_and_rotate_refresh_tokens() {
+    let api = Api::start().await;
+    api.sign_up("jane@doe.com", "CUSTOMER").await;
+
+    let stored = api.arango.documents("User");
+    assert_eq!(stored.len(), 1);
+    assert_ne!(stored[0]["password"], "Password.1");
+
+    let (status, body) = api.call(
+        Method::POST,
+        "/api/auth/signup",
+        None,
+        Some(
+            json!({
+                "first_name": "Jane",
+                "last_name": "Again",
+                "email": "jane@doe.com",
+                "password": "Password.1",
+                "role": "VENDOR"
+            })
+        )
+    ).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error_code"], "conflict");
+
+    let login = |password: &str| Some(json!({ "email": "jane@doe.com", "password": password }));
+    let (status, _) = api.call(Method::POST, "/api/auth/login", None, login("wrong")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, auth) = api.call(Method::POST, "/api/auth/login", None, login("Password.1")).await;
+    assert_eq!(status, StatusCode::OK);
+    let first = auth["refresh_token"].clone();
+
+    let (status, rotated) = api.call(
+        Method::POST,
+        "/api/auth/refresh",
+        None,
+        Some(json!({ "refresh_token": first }))
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(rotated["refresh_token"], first);
+
+    // Replaying the rotated token revokes the whole login family; only the signup token is left.
+    let (status, _) = api.call(
+        Method::POST,
+        "/api/auth/refresh",
+        None,
+        Some(json!({ "refresh_token": first }))
+    ).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let active = api.arango
+        .documents("RefreshToken")
+        .into_iter()
+        .filter(|token| token["revoked"] == false)
+        .count();
+    assert_eq!(active, 1);
+}
+
+#[tokio::test]
+async fn protected_routes_check_tokens_and_roles() {
+    let api = Api::start().await;
+    let (customer, _) = api.sign_up("customer@doe.com", "CUSTOMER").await;
+
+    let (status, _) = api.call(Method::GET, "/api/get_items", None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = api.call(Method::GET, "/api/get_items", Some("not.a.token"), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, page) = api.call(Method::GET, "/api/get_items", Some(&customer), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(page["total"], 0);
+
+    let (status, body) = api.call(
+        Method::POST,
+        "/api/add_item",
+        Some(&customer),
+        Some(json!({ "name": "Jar", "description": "A jar", "price": 1.0, "quantity": 1 }))
+    ).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["error_code"], "forbidden");
+    assert!(api.arango.documents("Item").is_empty());
+}
+
+#[tokio::test]
+async fn vendors_list_edit_and_delete_items() {
+    let api = Api::start().await;
+    let (vendor, vendor_key) = api.sign_up("vendor@doe.com", "VENDOR").await;
+
+    let item = api.add_item(&vendor, "Nutella Jar", 5).await;
+    api.add_item(&vendor, "Baba Cake", 2).await;
+    assert_eq!(item["user_id"], vendor_key.as_str());
+
+    let (status, page) = api.call(
+        Method::GET,
+        "/api/get_items?sort=price&order=desc&limit=1",
+        Some(&vendor),
+        None
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(page["total"], 2);
+    assert_eq!(page["items"].as_array().unwrap().len(), 1);
+    assert!(page["next_cursor"].is_string());
+
+    let uri = "/api/search_items?q=nutella";
+    let (status, hits) = api.call(Method::GET, uri, Some(&vendor), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(hits[0]["item"]["name"], "Nutella Jar");
+    assert_eq!(hits[0]["highlights"]["name"], "<mark>Nutella</mark> Jar");
+
+    let key = item["_key"].as_str().unwrap();
+    let (status, edited) = api.call(
+        Method::PUT,
+        "/api/edit_item",
+        Some(&vendor),
+        Some(json!({ "id": key, "price": "3" }))
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(edited["price"], json!({ "amount": "3.00", "currency": "USD", "minor_units": 300 }));
+    assert_eq!(edited["name"], "Nutella Jar");
+
+    let (status, _) = api.call(
+        Method::POST,
+        "/api/add_item",
+        Some(&vendor),
+        Some(json!({ "name": "Baba Cake", "description": "Again", "price": 1.0, "quantity": 1 }))
+    ).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, _) = api.call(
+        Method::DELETE,
+        "/api/delete_item",
+        Some(&vendor),
+        Some(json!({ "id": key }))
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = api.call(
+        Method::PUT,
+        "/api/edit_item",
+        Some(&vendor),
+        Some(json!({ "id": key, "price": 3.0 }))
+    ).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // The item is only soft-deleted: it stays stored but no longer shows up in listings.
+    let deleted: Vec<Value> = api.arango
+        .documents("Item")
+        .into_iter()
+        .filter(|item| !item["deleted_at"].is_null())
+        .collect();
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0]["deleted_by"], vendor_key.as_str());
+
+    let (_, page) = api.call(Method::GET, "/api/get_items", Some(&vendor), None).await;
+    assert_eq!(page["total"], 1);
+}
+
+#[tokio::test]
+async fn stale_item_edits_are_rejected() {
+    let api = Api::start().await;
+    let (vendor, _) = api.sign_up("vendor@rans.com", "VENDOR").await;
+    let item = api.add_item(&vendor, "Nutella Jar", 5).await;
+    let uri = format!("/api/items/{}", item["_key"].as_str().unwrap());
+
+    let (status, headers, read) = api.send(
+        Method::GET,
+        &uri,
+        Some(&vendor),
+        HeaderMap::new(),
+        None
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    let etag = headers[header::ETAG].clone();
+    assert_eq!(etag.to_str().unwrap(), format!("\"{}\"", read["_rev"].as_str().unwrap()));
+
+    let edit = |price: &str| Some(json!({ "id": read["_key"], "price": price }));
+    let if_match = HeaderMap::from_iter([(header::IF_MATCH, etag)]);
+    let (status, headers, edited) = api.send(
+        Method::PUT,
+        "/api/edit_item",
+        Some(&vendor),
+        if_match.clone(),
+        edit("3")
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(edited["_rev"], read["_rev"]);
+    let current_etag = headers[header::ETAG].clone();
+
+    // A second edit based on the first read loses the race.
+    let (status, headers, body) = api.send(
+        Method::PUT,
+        "/api/edit_item",
+        Some(&vendor),
+        if_match,
+        edit("4")
+    ).await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+    assert_eq!(body["error_code"], "precondition_failed");
+    assert_eq!(body["current"]["price"]["amount"], "3.00");
+    assert_eq!(headers[header::ETAG], current_etag);
+    assert_eq!(api.arango.documents("Item")[0]["price"]["amount"], "3.00");
+}
+
+#[tokio::test]
+async fn deleted_items_are_restored_or_purged() {
+    let (state, arango) = common::arango_state().await;
+    let now = Local::now().naive_local();
+    let item = |name: &str| NewItem {
+        name: name.to_string(),
+        user_id: "883".to_string(),
+        description: "A sample item".to_string(),
+        price: "2.50".parse().unwrap(),
+        quantity: 1,
+    };
+    let deletion = |days: i64| Deletion {
+        deleted_at: now - Duration::days(days),
+        deleted_by: "883".to_string(),
+    };
+
+    let first = state.items.insert(item("Nutella Jar")).await.unwrap();
+    state.items.remove(&first._key, deletion(40)).await.unwrap();
+    assert!(state.items.find_by_key(&first._key).await.is_err());
+
+    // The name is free again, so the deleted item cannot come back under it.
+    let second = state.items.insert(item("Nutella Jar")).await.unwrap();
+    let conflict = ApiError::from(state.items.restore(&first._key).await.unwrap_err());
+    assert_eq!(conflict.status(), StatusCode::CONFLICT);
+
+    state.items.remove(&second._key, deletion(1)).await.unwrap();
+    let restored = state.items.restore(&first._key).await.unwrap();
+    assert_eq!(restored.deleted_at, None);
+
+    state.items.remove(&first._key, deletion(40)).await.unwrap();
+    let purged = purge::purge_deleted(&state, now).await.unwrap();
+    assert_eq!(purged.items, 1);
+
+    let stored = arango.documents("Item");
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[0]["_key"], second._key.as_str());
+}
+
+#[tokio::test]
+async fn orders_take_stock_from_the_item() {
+    let api = Api::start().await;
+    let (vendor, _) = api.sign_up("vendor@doe.com", "VENDOR").await;
+    let (customer, customer_key) = api.sign_up("customer@doe.com", "CUSTOMER").await;
+    let item = api.add_item(&vendor, "Nutella Jar", 3).await;
+
+    let order = |quantity: i64| Some(json!({ "item_id": item["_key"], "quantity": quantity }));
+
+    let (status, placed) = api.call(
+        Method::POST,
+        "/api/add_order",
+        Some(&customer),
+        order(2)
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(placed["lines"][0]["item_name"], "Nutella Jar");
+    assert_eq!(placed["subtotal"]["amount"], "5.00");
+    assert_eq!(api.arango.documents("Item")[0]["quantity"], 1);
+
+    let (status, _) = api.call(Method::POST, "/api/add_order", Some(&customer), order(2)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(api.arango.documents("Item")[0]["quantity"], 1);
+
+    let (status, _) = api.call(
+        Method::POST,
+        "/api/add_order",
+        Some(&customer),
+        Some(json!({ "item_id": "missing", "quantity": 1 }))
+    ).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let uri = format!("/api/get_orders/{}", customer_key);
+    let (status, orders) = api.call(Method::GET, &uri, Some(&customer), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(orders.as_array().unwrap().len(), 1);
+    let (status, _) = api.call(Method::GET, &uri, Some(&vendor), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let uri = format!("/api/orders/{}/status", placed["_key"].as_str().unwrap());
+    let cancel = Some(json!({ "status": "CANCELLED" }));
+    let (status, cancelled) = api.call(Method::PATCH, &uri, Some(&customer), cancel).await;
+    assert_eq!(status, StatusCode::OK, "{}", cancelled);
+    assert_eq!(cancelled["status"], "CANCELLED");
+    assert_eq!(cancelled["status_history"].as_array().unwrap().len(), 2);
+    assert_eq!(api.arango.documents("Item")[0]["quantity"], 3);
+
+    let ship = Some(json!({ "status": "SHIPPED" }));
+    let (status, _) = api.call(Method::PATCH, &uri, Some(&vendor), ship).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn carts_are_checked_out_into_one_order() {
+    let api = Api::start().await;
+    let (vendor, _) = api.sign_up("vendor@doe.com", "VENDOR").await;
+    let (customer, _) = api.sign_up("customer@doe.com", "CUSTOMER").await;
+    let jar = api.add_item(&vendor, "Nutella Jar", 3).await;
+    let cake = api.add_item(&vendor, "Baba Cake", 1).await;
+
+    for (item, quantity) in [(&jar, 2), (&cake, 1)] {
+        let line = Some(json!({ "item_id": item["_key"], "quantity": quantity }));
+        let (status, _) = api.call(Method::POST, "/api/cart/lines", Some(&customer), line).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, cart) = api.call(Method::GET, "/api/cart", Some(&customer), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(cart["subtotal"]["amount"], "7.50");
+
+    let (status, order) = api.call(Method::POST, "/api/cart/checkout", Some(&customer), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", order);
+    assert_eq!(order["lines"].as_array().unwrap().len(), 2);
+    assert_eq!(order["subtotal"]["amount"], "7.50");
+    assert_eq!(api.arango.documents("Order").len(), 1);
+    assert!(api.arango.documents("Cart").is_empty());
+
+    let mut stock: Vec<(Value, Value)> = api.arango
+        .documents("Item")
+        .into_iter()
+        .map(|item| (item["name"].clone(), item["quantity"].clone()))
+        .collect();
+    stock.sort_by_key(|(name, _)| name.to_string());
+    assert_eq!(stock, vec![(json!("Baba Cake"), json!(0)), (json!("Nutella Jar"), json!(1))]);
+}
+
+#[tokio::test]
+async fn deleting_an_account_keeps_its_orders() {
+    let api = Api::start().await;
+    let (vendor, vendor_key) = api.sign_up("vendor@doe.com", "VENDOR").await;
+    let (customer, _) = api.sign_up("customer@doe.com", "CUSTOMER").await;
+    let item = api.add_item(&vendor, "Nutella Jar", 3).await;
+
+    let order = Some(json!({ "item_id": item["_key"], "quantity": 1 }));
+    let (status, _) = api.call(Method::POST, "/api/add_order", Some(&customer), order).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let password = |password: &str| Some(json!({ "password": password }));
+    for token in [&vendor, &customer] {
+        let delete = |body| api.call(Method::DELETE, "/api/users/me", Some(token), body);
+        assert_eq!(delete(password("wrong")).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(delete(password("Password.1")).await.0, StatusCode::OK);
+    }
+
+    assert!(api.arango.documents("User").is_empty());
+    let items = api.arango.documents("Item");
+    assert_eq!(items[0]["deleted_by"], vendor_key.as_str());
+    let orders = api.arango.documents("Order");
+    assert_eq!(orders[0]["user_id"], "deleted");
+    assert_eq!(orders[0]["lines"][0]["item_name"], "Nutella Jar");
+    assert!(api.arango.documents("RefreshToken").iter().all(|token| token["revoked"] == true));
+
+    // The access token of the deleted account is revoked with it.
+    let (status, _) = api.call(Method::GET, "/api/users/me", Some(&customer), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn forgotten_passwords_are_reset_by_email() {
+    let api = Api::start().await;
+    api.sign_up("jane@doe.com", "CUSTOMER").await;
+
+    let forgot = Some(json!({ "email": "jane@doe.com" }));
+    let (status, _) = api.call(Method::POST, "/api/auth/forgot_password", None, forgot).await;
+    assert_eq!(status, StatusCode::OK);
+    // The first email is the verification sent at signup.
+    let mail = common::wait_for_mail(&api.mailbox, 2).await;
+    assert_eq!(mail.len(), 2);
+    let token = common::link_token(&mail[1]);
+
+    let resets = api.arango.documents("PasswordReset");
+    assert_eq!(resets.len(), 1);
+    assert_ne!(resets[0]["token_hash"], token.as_str());
+
+    let reset = |password: &str| {
+        let body = Some(json!({ "token": token, "password": password }));
+        api.call(Method::POST, "/api/auth/reset_password", None, body)
+    };
+    assert_eq!(reset("Password.2").await.0, StatusCode::OK);
+    assert_eq!(reset("Password.3").await.0, StatusCode::BAD_REQUEST);
+    assert!(api.arango.documents("PasswordReset")[0]["used_at"].is_string());
+
+    let login = |password: &str| Some(json!({ "email": "jane@doe.com", "password": password }));
+    let (status, _) = api.call(Method::POST, "/api/auth/login", None, login("Password.1")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = api.call(Method::POST, "/api/auth/login", None, login("Password.2")).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn signup_emails_verify_the_address() {
+    let api = Api::start().await;
+    api.sign_up("jane@doe.com", "CUSTOMER").await;
+    assert_eq!(api.arango.documents("User")[0]["email_verified"], false);
+
+    let mail = common::sent_mail(&api.mailbox);
+    let verify = Some(json!({ "token": common::link_token(&mail[0]) }));
+    let (status, user) = api.call(Method::POST, "/api/auth/verify", None, verify).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(user["email_verified"], true);
+    assert_eq!(api.arango.documents("User")[0]["email_verified"], true);
+}
+
+#[tokio::test]
+async fn failed_logins_lock_the_account_out() {
+    let api = Api::start().await;
+    api.sign_up("jane@doe.com", "CUSTOMER").await;
+    let credentials = |password: &str| {
+        Some(json!({ "email": "jane@doe.com", "password": password }))
+    };
+
+    for _ in 0..4 {
+        let (status, _) = api.call(Method::POST, "/api/auth/login", None, credentials("x")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+    let (status, headers, error) = api.send(
+        Method::POST,
+        "/api/auth/login",
+        None,
+        HeaderMap::new(),
+        credentials("x")
+    ).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(error["error_code"], "too_many_requests");
+    assert_eq!(headers[header::RETRY_AFTER], "30");
+
+    let login = credentials("Password.1");
+    let (status, _) = api.call(Method::POST, "/api/auth/login", None, login).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(api.arango.documents("LoginAttempt").len(), 5);
+    let audit = api.arango.documents("AuditLog");
+    assert_eq!(audit.len(), 1);
+    assert_eq!(audit[0]["event"], "login_lockout");
+    assert_eq!(audit[0]["subject"], "email:jane@doe.com");
+}
+
+#[tokio::test]
+async fn failed_logins_expire_at_a_unix_timestamp_the_ttl_index_understands() {
+    let api = Api::start().await;
+    api.sign_up("jane@doe.com", "CUSTOMER").await;
+    let credentials = Some(json!({ "email": "jane@doe.com", "password": "x" }));
+
+    let before = Utc::now() + common::config().security.failure_window();
+    api.call(Method::POST, "/api/auth/login", None, credentials).await;
+    let after = Utc::now() + common::config().security.failure_window();
+
+    let attempts = api.arango.documents("LoginAttempt");
+    assert_eq!(attempts.len(), 1);
+    let expires_at = attempts[0]["expires_at"].as_i64().expect("expires_at is not an integer");
+    assert!((before.timestamp()..=after.timestamp()).contains(&expires_at));
+}
+
+#[tokio::test]
+async fn vendors_log_in_with_a_totp_code() {
+    let api = Api::start().await;
+    let (token, _) = api.sign_up("jane@doe.com", "VENDOR").await;
+    let password = json!({ "password": "Password.1" });
+
+    let (status, enrollment) = api.call(
+        Method::POST,
+        "/api/users/me/2fa",
+        Some(&token),
+        Some(password)
+    ).await;
+    assert_eq!(status, StatusCode::OK, "{}", enrollment);
+    let secret = enrollment["secret"].as_str().unwrap();
+    assert_eq!(api.arango.documents("User")[0]["totp_secret"], secret);
+
+    let now = Utc::now().timestamp() as u64;
+    let code = totp::code(secret, totp::step(now)).unwrap();
+    let (status, confirmed) = api.call(
+        Method::POST,
+        "/api/users/me/2fa/confirm",
+        Some(&token),
+        Some(json!({ "code": code }))
+    ).await;
+    assert_eq!(status, StatusCode::OK, "{}", confirmed);
+    let recovery_code = confirmed["recovery_codes"][0].as_str().unwrap();
+    let stored = &api.arango.documents("User")[0];
+    assert_eq!(stored["totp_enabled"], true);
+    assert_eq!(stored["recovery_codes"].as_array().unwrap().len(), 10);
+    let enrolled = stored["totp_last_step"].as_u64().unwrap();
+
+    let credentials = Some(json!({ "email": "jane@doe.com", "password": "Password.1" }));
+    let (status, login) = api.call(Method::POST, "/api/auth/login", None, credentials).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(login.get("token").is_none());
+
+    let challenge = json!({ "mfa_token": login["mfa_token"], "code": recovery_code });
+    let (status, auth) = api.call(Method::POST, "/api/auth/login/2fa", None, Some(challenge)).await;
+    assert_eq!(status, StatusCode::OK, "{}", auth);
+    let token = auth["token"].as_str().unwrap();
+    api.add_item(token, "Nutella Jar", 3).await;
+    assert_eq!(api.arango.documents("User")[0]["recovery_codes"].as_array().unwrap().len(), 9);
+
+    let code = totp::code(secret, enrolled + 1).unwrap();
+    let disable = json!({ "password": "Password.1", "code": code });
+    let (status, _) = api.call(
+        Method::DELETE,
+        "/api/users/me/2fa",
+        Some(token),
+        Some(disable)
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    let stored = &api.arango.documents("User")[0];
+    assert_eq!(stored["totp_enabled"], false);
+    assert_eq!(stored["totp_secret"], Value::Null);
+}
\ No newline at end of file
@@ -0,0 +1,59 @@
+use arangors::{ ArangoError, ClientError };
+use axum::{ http::{ header, StatusCode }, response::IntoResponse };
+use serde_json::{ json, Value };
+use server::api::ApiError;
+use server::db::DatabaseError;
+
+fn arango_error(code: u16, error_num: u16) -> ArangoError {
+    let error = json!({ "code": code, "errorNum": error_num, "errorMessage": "arango says no" });
+    serde_json::from_value(error).unwrap()
+}
+
+async fn body(error: ApiError) -> (StatusCode, Value) {
+    let response = error.into_response();
+    let status = response.status();
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[tokio::test]
+async fn unique_violations_map_to_conflict() {
+    let error = ApiError::from(ClientError::Arango(arango_error(409, 1210)));
+    let (status, body) = body(error).await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["result"], "error");
+    assert_eq!(body["content"]["error_code"], "conflict");
+}
+
+#[tokio::test]
+async fn missing_documents_map_to_not_found() {
+    let error = ApiError::from(DatabaseError::ArangoError(arango_error(404, 1202)));
+    let (status, body) = body(error).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["content"]["error_code"], "not_found");
+}
+
+#[tokio::test]
+async fn unknown_database_errors_are_not_leaked() {
+    let error = ApiError::from(DatabaseError::QueryError("FOR u IN User RETURN u".to_string()));
+    let (status, body) = body(error).await;
+
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(body["content"]["error_code"], "database_error");
+    assert_eq!(body["content"]["error_msg"], "Error querying the database");
+}
+
+#[tokio::test]
+async fn too_many_requests_tell_clients_when_to_retry() {
+    let error = ApiError::TooManyRequests { message: "Slow down".to_string(), retry_after: 42 };
+    let response = error.into_response();
+
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "42");
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let body: Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["content"]["error_code"], "too_many_requests");
+}
\ No newline at end of file
@@ -0,0 +1,103 @@
+mod common;
+
+use axum::{ extract::{ FromRequestParts, Query, State }, http::{ Request, StatusCode } };
+use serde_json::Value;
+use server::repositories::items::{ ItemRepository, NewItem };
+use server::repositories::memory::MemoryDatabase;
+use server::requests::items;
+use server::state::AppState;
+use std::sync::Arc;
+
+async fn seeded_state() -> AppState {
+    let database = Arc::new(MemoryDatabase::new());
+    let catalog = [
+        ("Apple Pie", "1", "12.50", 4),
+        ("Baba Cake", "1", "6.00", 0),
+        ("Cannoli", "2", "3.50", 10),
+        ("Donut", "2", "1.25", 7),
+        ("Eclair", "3", "4.00", 2),
+    ];
+
+    for (name, user_id, price, quantity) in catalog {
+        database
+            .insert(NewItem {
+                name: name.to_string(),
+                user_id: user_id.to_string(),
+                description: format!("Freshly baked {}", name),
+                price: price.parse().unwrap(),
+                quantity,
+            }).await
+            .unwrap();
+    }
+
+    common::state_with(database)
+}
+
+async fn list(state: &AppState, query: &str) -> (StatusCode, Value) {
+    let request = Request::get(format!("/api/get_items?{}", query)).body(()).unwrap();
+    let (mut parts, _) = request.into_parts();
+    let params = Query::from_request_parts(&mut parts, &()).await.unwrap();
+
+    common::respond(items::get_items(State(state.clone()), None, params).await)
+}
+
+fn names(page: &Value) -> Vec<&str> {
+    page["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["name"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn cursor_pagination_walks_every_item_once() {
+    let state = seeded_state().await;
+
+    let (status, first) = list(&state, "limit=2").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(names(&first), ["Apple Pie", "Baba Cake"]);
+    assert_eq!(first["total"], 5);
+
+    let cursor = first["next_cursor"].as_str().unwrap();
+    let (_, second) = list(&state, &format!("limit=2&cursor={}", cursor)).await;
+    assert_eq!(names(&second), ["Cannoli", "Donut"]);
+
+    let cursor = second["next_cursor"].as_str().unwrap();
+    let (_, last) = list(&state, &format!("limit=2&cursor={}", cursor)).await;
+    assert_eq!(names(&last), ["Eclair"]);
+    assert!(last["next_cursor"].is_null());
+}
+
+#[tokio::test]
+async fn filters_and_sorting_are_combined() {
+    let state = seeded_state().await;
+
+    let (_, page) = list(&state, "in_stock=true&sort=price&order=desc").await;
+    assert_eq!(names(&page), ["Apple Pie", "Eclair", "Cannoli", "Donut"]);
+    assert_eq!(page["total"], 4);
+
+    let (_, page) = list(&state, "min_price=3&max_price=10&sort=quantity").await;
+    assert_eq!(names(&page), ["Baba Cake", "Eclair", "Cannoli"]);
+
+    let (_, page) = list(&state, "min_price=3%20EUR").await;
+    assert_eq!(page["total"], 0);
+
+    let (_, page) = list(&state, "user_id=2&offset=1").await;
+    assert_eq!(names(&page), ["Donut"]);
+    assert_eq!(page["total"], 2);
+}
+
+#[tokio::test]
+async fn invalid_parameters_are_rejected() {
+    let state = seeded_state().await;
+
+    let (status, _) = list(&state, "cursor=not-a-cursor").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = list(&state, "min_price=10&max_price=1").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = list(&state, "min_price=1%20EUR&max_price=10").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
\ No newline at end of file
@@ -0,0 +1,135 @@
+mod common;
+
+use axum::{
+    extract::{ Path, State },
+    http::{ header, HeaderMap, HeaderValue, StatusCode },
+    response::IntoResponse,
+};
+use serde_json::{ json, Value };
+use server::api::expected_rev;
+use server::models::Role;
+use server::requests::items;
+use server::requests::jwt::Claims;
+use server::requests::validation::Valid;
+use server::state::AppState;
+
+fn vendor() -> Claims {
+    Claims {
+        sub: "1@rans.com".to_string(),
+        key: "1".to_string(),
+        role: Role::VENDOR,
+        iat: 0,
+        exp: usize::MAX,
+        ver: 0,
+    }
+}
+
+fn if_match(value: &str) -> HeaderMap {
+    HeaderMap::from_iter([(header::IF_MATCH, HeaderValue::from_str(value).unwrap())])
+}
+
+async fn seed_item(state: &AppState) -> Value {
+    let payload = json!({
+        "name": "Nutella Jar",
+        "description": "Hazelnut spread",
+        "price": 4.0,
+        "quantity": 3
+    });
+    let (status, item) = common::respond(
+        items::add_item(
+            State(state.clone()),
+            vendor(),
+            Valid(serde_json::from_value(payload).unwrap())
+        ).await
+    );
+    assert_eq!(status, StatusCode::OK);
+
+    item
+}
+
+async fn edit(state: &AppState, headers: HeaderMap, payload: Value) -> (StatusCode, Value) {
+    common::respond(
+        items::edit_item(
+            State(state.clone()),
+            vendor(),
+            headers,
+            Valid(serde_json::from_value(payload).unwrap())
+        ).await
+    )
+}
+
+#[tokio::test]
+async fn edits_based_on_a_stale_revision_fail() {
+    let state = common::state();
+    let item = seed_item(&state).await;
+    let key = &item["_key"];
+
+    let (status, edited) = edit(
+        &state,
+        HeaderMap::new(),
+        json!({ "id": key, "price": 5.0, "_rev": item["_rev"] })
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = edit(
+        &state,
+        HeaderMap::new(),
+        json!({ "id": key, "price": 6.0, "_rev": item["_rev"] })
+    ).await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+    assert_eq!(body["error_code"], "precondition_failed");
+    assert_eq!(body["current"]["_rev"], edited["_rev"]);
+    assert_eq!(body["current"]["price"]["amount"], "5.00");
+
+    // Edits without a revision still overwrite blindly.
+    let (status, _) = edit(&state, HeaderMap::new(), json!({ "id": key, "price": 6.0 })).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn if_match_is_checked_like_the_body_revision() {
+    let state = common::state();
+    let item = seed_item(&state).await;
+    let rev = item["_rev"].as_str().unwrap();
+    let quoted = format!("\"{}\"", rev);
+
+    let (status, _) = edit(&state, if_match("\"stale\""), json!({ "id": item["_key"] })).await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+
+    let payload = json!({ "id": item["_key"], "_rev": "stale" });
+    let (status, _) = edit(&state, if_match(&quoted), payload).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = edit(&state, if_match(&quoted), json!({ "id": item["_key"] })).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[test]
+fn if_match_values_are_parsed() {
+    let parse = |value: &str| expected_rev(&if_match(value), None).map_err(|err| err.status());
+
+    assert_eq!(parse("\"_rev1\""), Ok(Some("_rev1".to_string())));
+    assert_eq!(parse("W/\"_rev1\""), Ok(Some("_rev1".to_string())));
+    assert_eq!(parse("*"), Ok(None));
+    assert_eq!(parse("_rev1"), Err(StatusCode::BAD_REQUEST));
+    let body_rev = expected_rev(&HeaderMap::new(), Some("_rev2".to_string()));
+    assert_eq!(body_rev.unwrap().as_deref(), Some("_rev2"));
+}
+
+#[tokio::test]
+async fn item_reads_carry_an_etag() {
+    let state = common::state();
+    let item = seed_item(&state).await;
+
+    let missing = Path("missing".to_string());
+    let response = items::get_item_by_key(State(state.clone()), missing).await.into_response();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let key = item["_key"].as_str().unwrap().to_string();
+    let response = items::get_item_by_key(State(state), Path(key)).await.into_response();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::ETAG],
+        format!("\"{}\"", item["_rev"].as_str().unwrap()).as_str()
+    );
+}
@@ -0,0 +1,252 @@
+mod common;
+
+use axum::{ body::Body, http::{ Request, StatusCode } };
+use jsonwebtoken::{
+    decode,
+    decode_header,
+    encode,
+    jwk::{ AlgorithmParameters, JwkSet },
+    Algorithm,
+    DecodingKey,
+    EncodingKey,
+    Header,
+    Validation,
+};
+use server::models::Role;
+use server::requests::jwt::{ self, Claims, JwtKeys };
+use server::requests::routes::create_routes;
+use server::state::AppState;
+use server::toml_env::{ Config, JwtConfig, JwtKeyConfig };
+use tower::ServiceExt;
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/jwt/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+/// A `[[Jwt.Keys]]` entry for the fixture key pair `name`, with its private key when `signs`.
+fn key(name: &str, algorithm: Algorithm, signs: bool) -> JwtKeyConfig {
+    JwtKeyConfig {
+        kid: name.to_string(),
+        algorithm,
+        public_key: fixture(&format!("{}.pub.pem", name)),
+        private_key: signs.then(|| fixture(&format!("{}.pem", name))),
+    }
+}
+
+fn config_with(keys: Vec<JwtKeyConfig>) -> Config {
+    let mut config = common::config();
+    config.jwt = JwtConfig { active: None, keys };
+
+    config
+}
+
+fn keys(keys: Vec<JwtKeyConfig>) -> JwtKeys {
+    JwtKeys::from_config(&config_with(keys)).unwrap()
+}
+
+fn claims() -> Claims {
+    Claims {
+        sub: "jane@doe.com".to_string(),
+        key: "1".to_string(),
+        role: Role::CUSTOMER,
+        iat: 0,
+        exp: usize::MAX,
+        ver: 0,
+    }
+}
+
+/// Verifies `token` the way a downstream service would: with the JWKS entry named by its `kid`.
+fn verify_with_jwks(jwks: &JwkSet, token: &str) -> Claims {
+    let header = decode_header(token).unwrap();
+    let jwk = jwks.find(header.kid.as_deref().unwrap()).expect("kid is not in the JWKS");
+    let key = DecodingKey::from_jwk(jwk).unwrap();
+
+    decode::<Claims>(token, &key, &Validation::new(header.alg)).unwrap().claims
+}
+
+#[test]
+fn tokens_carry_the_kid_of_the_active_key_and_verify_with_the_jwks() {
+    let keys = keys(
+        vec![
+            key("rsa-2026-10", Algorithm::RS256, true),
+            key("rsa-2026-04", Algorithm::RS256, false)
+        ]
+    );
+
+    let token = keys.encode(&claims()).unwrap();
+    let header = decode_header(&token).unwrap();
+    assert_eq!(header.kid.as_deref(), Some("rsa-2026-10"));
+    assert_eq!(header.alg, Algorithm::RS256);
+
+    assert_eq!(keys.jwks().keys.len(), 2);
+    assert_eq!(verify_with_jwks(keys.jwks(), &token).key, "1");
+    assert_eq!(jwt::validate_jwt(&token, &keys).unwrap().key, "1");
+}
+
+#[test]
+fn tokens_signed_with_a_rotated_out_key_still_verify() {
+    let old = keys(vec![key("rsa-2026-04", Algorithm::RS256, true)]);
+    let token = old.encode(&claims()).unwrap();
+
+    let mut config = config_with(
+        vec![
+            key("rsa-2026-04", Algorithm::RS256, false),
+            key("rsa-2026-10", Algorithm::RS256, true)
+        ]
+    );
+    config.jwt.active = Some("rsa-2026-10".to_string());
+    let rotated = JwtKeys::from_config(&config).unwrap();
+
+    assert!(jwt::validate_jwt(&token, &rotated).is_ok());
+    let renewed = rotated.encode(&claims()).unwrap();
+    assert_eq!(decode_header(&renewed).unwrap().kid.as_deref(), Some("rsa-2026-10"));
+
+    // Servers that never had the new key reject its tokens.
+    assert!(jwt::validate_jwt(&renewed, &old).is_err());
+}
+
+#[test]
+fn secret_and_forged_tokens_are_rejected_once_keys_are_configured() {
+    let keys = keys(vec![key("rsa-2026-10", Algorithm::RS256, true)]);
+
+    let secret = EncodingKey::from_secret(common::SECRET.as_ref());
+    let hs256 = encode(&Header::default(), &claims(), &secret).unwrap();
+    assert!(jwt::validate_jwt(&hs256, &keys).is_err());
+
+    // Signing with the public key as an HMAC secret must not pass for the RSA key.
+    let public = std::fs::read(fixture("rsa-2026-10.pub.pem")).unwrap();
+    let header = Header { kid: Some("rsa-2026-10".to_string()), ..Header::default() };
+    let confused = encode(&header, &claims(), &EncodingKey::from_secret(&public)).unwrap();
+    assert!(jwt::validate_jwt(&confused, &keys).is_err());
+
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some("rsa-2027-01".to_string());
+    let private = std::fs::read(fixture("rsa-2026-10.pem")).unwrap();
+    let unknown = encode(&header, &claims(), &EncodingKey::from_rsa_pem(&private).unwrap());
+    assert!(jwt::validate_jwt(&unknown.unwrap(), &keys).is_err());
+}
+
+#[test]
+fn ed25519_keys_sign_eddsa_tokens() {
+    let keys = keys(vec![key("ed-2026-10", Algorithm::EdDSA, true)]);
+
+    let token = keys.encode(&claims()).unwrap();
+    assert_eq!(decode_header(&token).unwrap().alg, Algorithm::EdDSA);
+    assert!(jwt::validate_jwt(&token, &keys).is_ok());
+
+    let jwk = &keys.jwks().keys[0];
+    assert!(matches!(jwk.algorithm, AlgorithmParameters::OctetKeyPair(_)));
+    assert_eq!(verify_with_jwks(keys.jwks(), &token).key, "1");
+}
+
+#[test]
+fn invalid_key_configs_are_rejected() {
+    let load = |keys: Vec<JwtKeyConfig>| JwtKeys::from_config(&config_with(keys)).err();
+
+    let error = load(vec![key("rsa-2026-10", Algorithm::RS256, false)]).unwrap();
+    assert!(error.to_string().contains("has no private_key"), "{}", error);
+
+    let error = load(vec![key("rsa-2026-10", Algorithm::EdDSA, true)]).unwrap();
+    assert!(error.to_string().contains("cannot be used with"), "{}", error);
+
+    let mut mismatched = key("rsa-2026-10", Algorithm::RS256, true);
+    mismatched.private_key = Some(fixture("rsa-2026-04.pem"));
+    let error = load(vec![mismatched]).unwrap();
+    assert!(error.to_string().contains("does not belong"), "{}", error);
+
+    let error = load(
+        vec![key("rsa-2026-10", Algorithm::RS256, true), key("rsa-2026-10", Algorithm::RS256, true)]
+    ).unwrap();
+    assert!(error.to_string().contains("configured twice"), "{}", error);
+
+    let mut config = config_with(vec![key("rsa-2026-10", Algorithm::RS256, true)]);
+    config.jwt.active = Some("rsa-2027-01".to_string());
+    assert!(JwtKeys::from_config(&config).is_err());
+
+    // The server reports them instead of panicking.
+    let config = config_with(vec![key("rsa-2026-10", Algorithm::RS256, false)]);
+    let error = AppState::in_memory(config).err().unwrap();
+    assert!(error.to_string().starts_with("Invalid [Jwt] config"), "{}", error);
+}
+
+#[test]
+fn jwt_keys_are_read_from_the_config_file() {
+    let config = Config::from_toml(
+        &format!(
+            r#"
+            [Database]
+            host = "http://127.0.0.1"
+            port = 8529
+            name = "rans_test"
+            username = "root"
+            password = "root"
+
+            [Server]
+            env = "production"
+            host = "127.0.0.1"
+            port = 3000
+            secret = "secret"
+
+            [Logs]
+            path = "logs"
+            level = "off"
+
+            [Jwt]
+            active = "ed-2026-10"
+
+            [[Jwt.Keys]]
+            kid = "rsa-2026-10"
+            algorithm = "RS256"
+            public_key = "{}"
+
+            [[Jwt.Keys]]
+            kid = "ed-2026-10"
+            algorithm = "EdDSA"
+            public_key = "{}"
+            private_key = "{}"
+            "#,
+            fixture("rsa-2026-10.pub.pem"),
+            fixture("ed-2026-10.pub.pem"),
+            fixture("ed-2026-10.pem")
+        )
+    ).unwrap();
+
+    let keys = JwtKeys::from_config(&config).unwrap();
+    let token = keys.encode(&claims()).unwrap();
+    assert_eq!(decode_header(&token).unwrap().kid.as_deref(), Some("ed-2026-10"));
+    assert_eq!(keys.jwks().keys.len(), 2);
+}
+
+async fn fetch_jwks(state: AppState) -> JwkSet {
+    let app = create_routes(state).await;
+    let request = Request::get("/.well-known/jwks.json")
+        .header("Accept", "application/json")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+#[tokio::test]
+async fn the_jwks_endpoint_publishes_only_public_keys() {
+    let config = config_with(
+        vec![
+            key("rsa-2026-10", Algorithm::RS256, true),
+            key("ed-2026-10", Algorithm::EdDSA, false)
+        ]
+    );
+    let state = AppState::in_memory(config).unwrap();
+    let token = state.keys.encode(&claims()).unwrap();
+
+    let jwks = fetch_jwks(state).await;
+    assert_eq!(jwks.keys.len(), 2);
+    assert_eq!(verify_with_jwks(&jwks, &token).key, "1");
+    let published = serde_json::to_string(&jwks).unwrap();
+    assert!(!published.contains("\"d\""));
+
+    assert!(fetch_jwks(common::state()).await.keys.is_empty());
+}
\ No newline at end of file
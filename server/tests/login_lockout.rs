@@ -0,0 +1,264 @@
+mod common;
+
+use axum::{ body::Body, extract::State, http::{ Request, StatusCode }, routing::get, Router };
+use chrono::{ Duration, Utc };
+use serde_json::json;
+use server::models::AuditEvent;
+use server::repositories::audit::AuditRepository;
+use server::repositories::login_attempts::{ LoginAttemptRepository, NewLoginAttempt };
+use server::repositories::memory::MemoryDatabase;
+use server::requests::{ auth, security::ClientIp, validation::Valid };
+use server::state::AppState;
+use server::toml_env::{ AttemptStore, Config };
+use std::net::IpAddr;
+use tower::ServiceExt;
+
+fn security_config() -> Config {
+    let mut config = common::config();
+    config.security.max_failures = 3;
+    config.security.max_ip_failures = 5;
+    config.security.lockout_seconds = 60;
+    config.security.max_lockout_seconds = 600;
+
+    config
+}
+
+fn ip(address: &str) -> ClientIp {
+    ClientIp(Some(address.parse::<IpAddr>().unwrap()))
+}
+
+/// Logs in and returns the status, with the `Retry-After` seconds of a lockout.
+async fn log_in(state: &AppState, ip: ClientIp, email: &str, password: &str) -> (StatusCode, u64) {
+    let payload = serde_json::from_value(json!({ "email": email, "password": password })).unwrap();
+
+    match auth::handle_login(State(state.clone()), ip, Valid(payload)).await {
+        Ok(_) => (StatusCode::OK, 0),
+        Err(err) => (err.status(), err.retry_after().unwrap_or_default()),
+    }
+}
+
+/// Stores `count` failures of `subject` that happened `minutes_ago`.
+async fn past_failures(database: &MemoryDatabase, subject: &str, count: usize, minutes_ago: i64) {
+    let created_at = Utc::now().naive_utc() - Duration::minutes(minutes_ago);
+    for _ in 0..count {
+        let attempt = NewLoginAttempt {
+            subject: subject.to_string(),
+            created_at,
+            expires_at: created_at + Duration::hours(1),
+        };
+        LoginAttemptRepository::insert(database, attempt).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn repeated_failures_lock_the_account_out() {
+    let (state, database, _) = common::state_with_user(security_config()).await;
+    let client = ip("10.0.0.1");
+
+    assert_eq!(log_in(&state, client, "jane@doe.com", "wrong").await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(log_in(&state, client, "jane@doe.com", "wrong").await.0, StatusCode::BAD_REQUEST);
+
+    let (status, retry_after) = log_in(&state, client, "jane@doe.com", "wrong").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert!((59..=60).contains(&retry_after), "{}", retry_after);
+
+    // The right password does not help while locked out, and is not even checked.
+    let (status, _) = log_in(&state, client, "jane@doe.com", "Password.1").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(log_in(&state, client, "JANE@doe.com", "Password.1").await.0, status);
+
+    let audit = database.find_by_subject("email:jane@doe.com").await.unwrap();
+    assert_eq!(audit.len(), 1);
+    assert_eq!(audit[0].event, AuditEvent::LoginLockout);
+    assert_eq!(audit[0].ip.as_deref(), Some("10.0.0.1"));
+    assert_eq!(audit[0].details["failures"], 3);
+}
+
+#[tokio::test]
+async fn lockouts_expire_and_double_with_every_further_failure() {
+    let (state, database, _) = common::state_with_user(security_config()).await;
+    let subject = "email:jane@doe.com";
+
+    // Three failures five minutes ago locked the account out for a minute, which has passed.
+    past_failures(&database, subject, 3, 5).await;
+    let (status, retry_after) = log_in(&state, ClientIp::default(), "jane@doe.com", "wrong").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert!((119..=120).contains(&retry_after), "{}", retry_after);
+
+    let audit = database.find_by_subject(subject).await.unwrap();
+    assert_eq!(audit.len(), 1);
+    assert_eq!(audit[0].details["failures"], 4);
+
+    // Failures far enough apart never reach the limit, and a login forgets them.
+    let (state, database, _) = common::state_with_user(security_config()).await;
+    past_failures(&database, subject, 2, 5).await;
+    let (status, _) = log_in(&state, ClientIp::default(), "jane@doe.com", "Password.1").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(database.find_active(subject, Utc::now().naive_utc()).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn lockouts_are_capped() {
+    let (state, database, _) = common::state_with_user(security_config()).await;
+    past_failures(&database, "email:jane@doe.com", 20, 30).await;
+
+    let (status, retry_after) = log_in(&state, ClientIp::default(), "jane@doe.com", "x").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert!((599..=600).contains(&retry_after), "{}", retry_after);
+}
+
+#[tokio::test]
+async fn client_ips_are_locked_out_across_emails() {
+    let (state, database, _) = common::state_with_user(security_config()).await;
+    let attacker = ip("10.0.0.66");
+
+    for (index, email) in ["a@doe.com", "b@doe.com", "c@doe.com", "d@doe.com"].iter().enumerate() {
+        let (status, _) = log_in(&state, attacker, email, "Password.1").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "attempt {}", index + 1);
+    }
+    let (status, _) = log_in(&state, attacker, "e@doe.com", "Password.1").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    let (status, _) = log_in(&state, attacker, "jane@doe.com", "Password.1").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    let (status, _) = log_in(&state, ip("10.0.0.1"), "jane@doe.com", "Password.1").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let audit = database.find_by_subject("ip:10.0.0.66").await.unwrap();
+    assert_eq!(audit.len(), 1);
+    assert_eq!(audit[0].details["failures"], 5);
+}
+
+#[tokio::test]
+async fn lockouts_can_be_turned_off() {
+    let mut config = security_config();
+    config.security.max_failures = 0;
+    config.security.max_ip_failures = 0;
+    let (state, database, _) = common::state_with_user(config).await;
+
+    for _ in 0..10 {
+        let (status, _) = log_in(&state, ip("10.0.0.1"), "jane@doe.com", "wrong").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+    assert!(database.find_by_subject("email:jane@doe.com").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn the_memory_store_keeps_attempts_out_of_the_database() {
+    let mut config = security_config();
+    config.security.attempt_store = AttemptStore::Memory;
+    let (state, database, _) = common::state_with_user(config).await;
+
+    for _ in 0..3 {
+        log_in(&state, ClientIp::default(), "jane@doe.com", "wrong").await;
+    }
+    let (status, _) = log_in(&state, ClientIp::default(), "jane@doe.com", "Password.1").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    let now = Utc::now().naive_utc();
+    assert!(database.find_active("email:jane@doe.com", now).await.unwrap().is_empty());
+    assert_eq!(state.attempts.find_active("email:jane@doe.com", now).await.unwrap().len(), 3);
+    // Lockouts are audited in the database either way.
+    assert_eq!(database.find_by_subject("email:jane@doe.com").await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn resending_verification_emails_counts_towards_the_lockout() {
+    let (state, _, _) = common::state_with_user(security_config()).await;
+
+    for _ in 0..3 {
+        let payload = serde_json::from_value(
+            json!({ "email": "jane@doe.com", "password": "wrong" })
+        ).unwrap();
+        let result = auth::resend_verification(
+            State(state.clone()),
+            ClientIp::default(),
+            Valid(payload)
+        ).await;
+        assert!(result.is_err());
+    }
+
+    let (status, _) = log_in(&state, ClientIp::default(), "jane@doe.com", "Password.1").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[test]
+fn security_settings_are_read_from_the_config_file() {
+    let config = Config::from_toml(
+        r#"
+        [Database]
+        host = "http://127.0.0.1"
+        port = 8529
+        name = "rans_test"
+        username = "root"
+        password = "root"
+
+        [Server]
+        env = "production"
+        host = "127.0.0.1"
+        port = 3000
+        secret = "secret"
+
+        [Logs]
+        path = "logs"
+        level = "off"
+
+        [Security]
+        max_failures = 10
+        attempt_store = "memory"
+        trust_forwarded_for = true
+        "#
+    ).unwrap();
+
+    assert_eq!(config.security.max_failures, 10);
+    assert_eq!(config.security.max_ip_failures, 50);
+    assert_eq!(config.security.attempt_store, AttemptStore::Memory);
+    assert!(config.security.trust_forwarded_for);
+    assert_eq!(config.security.lockout(9, 10), None);
+    assert_eq!(config.security.lockout(11, 10), Some(Duration::seconds(60)));
+}
+
+#[test]
+fn the_shipped_config_trusts_the_shipped_proxy() {
+    let nginx = std::fs::read_to_string("../config/nginx.conf").unwrap();
+    assert!(nginx.contains("X-Forwarded-For $proxy_add_x_forwarded_for"));
+
+    let config = Config::parse("../config/config.toml").unwrap();
+    assert!(config.security.trust_forwarded_for);
+}
+
+async fn client_ip(config: Config, forwarded_for: &str) -> String {
+    let app = Router::new()
+        .route("/", get(|ClientIp(ip): ClientIp| async move { format!("{:?}", ip) }))
+        .with_state(AppState::in_memory(config).unwrap());
+    let request = Request::get("/").header("X-Forwarded-For", forwarded_for);
+
+    let response = app.oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+    String::from_utf8(bytes.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn forwarded_client_ips_are_only_trusted_when_configured() {
+    let forwarded_for = "203.0.113.7";
+    assert_eq!(client_ip(security_config(), forwarded_for).await, "None");
+
+    let mut config = security_config();
+    config.security.trust_forwarded_for = true;
+    assert_eq!(client_ip(config, forwarded_for).await, "Some(203.0.113.7)");
+}
+
+#[tokio::test]
+async fn forwarded_entries_sent_by_the_client_are_ignored() {
+    let trusting = || {
+        let mut config = security_config();
+        config.security.trust_forwarded_for = true;
+        config
+    };
+
+    // The proxy appends the address it saw to whatever the client sent.
+    let spoofed = "198.51.100.1, 192.0.2.2 , 203.0.113.7";
+    assert_eq!(client_ip(trusting(), spoofed).await, "Some(203.0.113.7)");
+    assert_eq!(client_ip(trusting(), "not an ip, 203.0.113.7").await, "Some(203.0.113.7)");
+}
\ No newline at end of file
@@ -0,0 +1,118 @@
+use serde_json::json;
+use server::migrations::{ self, Step, MIGRATIONS };
+
+#[test]
+fn migrations_have_unique_ascending_versions() {
+    let versions: Vec<u32> = MIGRATIONS.iter().map(|migration| migration.version).collect();
+
+    assert!(versions.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(versions.first(), Some(&1));
+    assert_eq!(migrations::latest_version(), *versions.last().unwrap());
+    assert!(MIGRATIONS.iter().all(|migration| !migration.name.is_empty()));
+}
+
+#[test]
+fn pending_migrations_skip_applied_versions() {
+    let all: Vec<u32> = migrations::pending(&[]).iter().map(|migration| migration.version).collect();
+    assert_eq!(all, MIGRATIONS.iter().map(|migration| migration.version).collect::<Vec<_>>());
+
+    let pending: Vec<u32> = migrations::pending(&[1, 2])
+        .iter()
+        .map(|migration| migration.version)
+        .collect();
+    assert!(!pending.contains(&1) && !pending.contains(&2));
+    assert!(migrations::pending(&all).is_empty());
+}
+
+#[test]
+fn migrations_are_reverted_newest_first() {
+    let latest = migrations::latest_version();
+    let applied: Vec<u32> = (1..=latest).collect();
+
+    let reverted: Vec<u32> = migrations::to_revert(&applied, 0)
+        .iter()
+        .map(|migration| migration.version)
+        .collect();
+    assert_eq!(reverted, applied.iter().rev().copied().collect::<Vec<_>>());
+
+    let reverted: Vec<u32> = migrations::to_revert(&applied, latest - 1)
+        .iter()
+        .map(|migration| migration.version)
+        .collect();
+    assert_eq!(reverted, vec![latest]);
+
+    assert!(migrations::to_revert(&[], 0).is_empty());
+}
+
+#[test]
+fn core_collections_get_schemas_and_unique_indexes() {
+    let steps: Vec<&Step> = MIGRATIONS.iter()
+        .flat_map(|migration| migration.steps.iter())
+        .collect();
+
+    for collection in ["User", "Item", "Order"] {
+        let schema = steps.iter().find_map(|step| {
+            match step {
+                Step::Collection { name, schema } if *name == collection => Some(schema()),
+                _ => None,
+            }
+        });
+        let schema = schema.unwrap_or_else(|| panic!("{} is never created", collection));
+        assert_eq!(schema["rule"]["additionalProperties"], false);
+        assert!(schema["rule"]["required"].as_array().is_some_and(|required| !required.is_empty()));
+    }
+
+    for (collection, field) in [("User", "email"), ("Item", "name")] {
+        assert!(
+            steps.iter().any(|step| {
+                matches!(
+                    step,
+                    Step::Index { collection: c, fields, unique: true }
+                        if *c == collection && *fields == [field]
+                )
+            }),
+            "{}.{} has no unique index",
+            collection,
+            field
+        );
+    }
+}
+
+#[test]
+fn prices_are_migrated_to_money() {
+    for collection in ["Item", "Order"] {
+        let step = MIGRATIONS.iter()
+            .flat_map(|migration| migration.steps.iter())
+            .find_map(|step| {
+                match step {
+                    Step::MoneyPrices { collection: c, schema, previous } if *c == collection =>
+                        Some((schema(), previous())),
+                    _ => None,
+                }
+            });
+        let (schema, previous) = step.unwrap_or_else(|| {
+            panic!("{} prices are not migrated", collection)
+        });
+
+        assert_eq!(previous["rule"]["properties"]["price"]["type"], "number");
+        assert_eq!(schema["rule"]["properties"]["price"]["type"], "object");
+        assert_eq!(
+            schema["rule"]["properties"]["price"]["required"],
+            json!(["amount", "currency", "minor_units"])
+        );
+    }
+}
+
+#[test]
+fn login_attempts_expire_through_a_ttl_index() {
+    let ttl = MIGRATIONS.iter()
+        .flat_map(|migration| migration.steps.iter())
+        .any(|step| {
+            matches!(
+                step,
+                Step::TtlIndex { collection: "LoginAttempt", field: "expires_at" }
+            )
+        });
+
+    assert!(ttl, "LoginAttempt has no TTL index on expires_at");
+}
\ No newline at end of file
@@ -0,0 +1,88 @@
+use serde_json::json;
+use server::models::{ Currency, Money, MoneyError };
+
+fn usd(amount: &str) -> Money {
+    Money::parse(amount, Currency::USD).unwrap()
+}
+
+#[test]
+fn decimal_amounts_are_parsed_into_minor_units() {
+    assert_eq!(usd("12.5").minor_units(), 1250);
+    assert_eq!(usd("0.07").minor_units(), 7);
+    assert_eq!(usd(".5").minor_units(), 50);
+    assert_eq!(usd("-3").minor_units(), -300);
+    assert_eq!(Money::parse("1500", Currency::JPY).unwrap().minor_units(), 1500);
+
+    for invalid in ["", ".", "1.2.3", "12,50", "1e3", "--1", "abc"] {
+        assert!(Money::parse(invalid, Currency::USD).is_err(), "{} was accepted", invalid);
+    }
+    assert_eq!(Money::parse("99999999999999999999", Currency::USD), Err(MoneyError::Overflow));
+}
+
+#[test]
+fn extra_digits_are_rounded_half_to_even() {
+    assert_eq!(usd("1.005").minor_units(), 100);
+    assert_eq!(usd("1.015").minor_units(), 102);
+    assert_eq!(usd("1.0051").minor_units(), 101);
+    assert_eq!(usd("1.004999").minor_units(), 100);
+    assert_eq!(usd("-1.015").minor_units(), -102);
+    assert_eq!(Money::parse("2.5", Currency::JPY).unwrap().minor_units(), 2);
+
+    // 0.1 + 0.2 is 0.30000000000000004 as a float.
+    assert_eq!(Money::from_f64(0.1 + 0.2, Currency::USD).unwrap(), usd("0.30"));
+    assert!(Money::from_f64(f64::NAN, Currency::USD).is_err());
+    assert!(Money::from_f64(f64::INFINITY, Currency::USD).is_err());
+}
+
+#[test]
+fn money_reads_strings_numbers_and_objects() {
+    let read = |value| serde_json::from_value::<Money>(value);
+
+    assert_eq!(read(json!("12.50")).unwrap(), usd("12.50"));
+    assert_eq!(read(json!(12.5)).unwrap(), usd("12.50"));
+    assert_eq!(read(json!(12)).unwrap(), usd("12"));
+
+    let euros = read(json!("7.25 EUR")).unwrap();
+    assert_eq!(euros.currency(), Currency::EUR);
+    assert_eq!(euros.minor_units(), 725);
+
+    let object = read(json!({ "amount": "7.25", "currency": "GBP", "minor_units": 1 })).unwrap();
+    assert_eq!(object, Money::from_minor_units(725, Currency::GBP));
+
+    assert!(read(json!("7.25 XYZ")).is_err());
+    assert!(read(json!({ "currency": "EUR" })).is_err());
+    assert!(read(json!({ "amount": "1", "cents": 100 })).is_err());
+    assert!(read(json!(true)).is_err());
+}
+
+#[test]
+fn money_writes_amount_currency_and_minor_units() {
+    assert_eq!(
+        json!(usd("1234.5")),
+        json!({ "amount": "1234.50", "currency": "USD", "minor_units": 123450 })
+    );
+    assert_eq!(json!(usd("-0.05"))["amount"], "-0.05");
+    assert_eq!(json!(Money::from_minor_units(1500, Currency::JPY))["amount"], "1500");
+
+    let written = json!(Money::from_minor_units(999, Currency::EUR));
+    assert_eq!(serde_json::from_value::<Money>(written).unwrap().to_string(), "9.99 EUR");
+}
+
+#[test]
+fn totals_stay_exact_and_within_one_currency() {
+    let line = usd("19.99").checked_mul(3).unwrap();
+    assert_eq!(line, usd("59.97"));
+
+    let total = Money::sum(&[line, usd("0.03")], Currency::USD).unwrap();
+    assert_eq!(total.amount(), "60.00");
+
+    let euros = Money::from_minor_units(100, Currency::EUR);
+    assert_eq!(
+        usd("1").checked_add(&euros),
+        Err(MoneyError::CurrencyMismatch(Currency::USD, Currency::EUR))
+    );
+    assert_eq!(usd("1").partial_cmp(&euros), None);
+    assert!(usd("1") < usd("1.01"));
+
+    assert_eq!(usd("1").checked_mul(i64::MAX), Err(MoneyError::Overflow));
+}
\ No newline at end of file
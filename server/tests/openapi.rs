@@ -0,0 +1,106 @@
+mod common;
+
+use serde_json::{ json, Value };
+use server::models::User;
+use server::openapi::ApiDoc;
+use server::requests::auth::AuthRes;
+use std::collections::BTreeSet;
+use utoipa::OpenApi;
+
+/// Adds the component schemas `value` refers to, and the ones they refer to in turn, to `found`.
+fn collect_refs(value: &Value, schemas: &Value, found: &mut BTreeSet<String>) {
+    match value {
+        Value::Object(fields) => {
+            if let Some(Value::String(reference)) = fields.get("$ref") {
+                let name = reference.trim_start_matches("#/components/schemas/").to_string();
+                if found.insert(name.clone()) {
+                    collect_refs(&schemas[&name], schemas, found);
+                }
+            }
+            fields.values().for_each(|field| collect_refs(field, schemas, found));
+        }
+        Value::Array(values) => values.iter().for_each(|value| collect_refs(value, schemas, found)),
+        _ => (),
+    }
+}
+
+/// Whether `schema` declares a property called `name` at any depth.
+fn has_property(schema: &Value, name: &str) -> bool {
+    match schema {
+        Value::Object(fields) => {
+            let declared = fields
+                .get("properties")
+                .and_then(Value::as_object)
+                .is_some_and(|properties| properties.contains_key(name));
+
+            declared || fields.values().any(|field| has_property(field, name))
+        }
+        Value::Array(values) => values.iter().any(|value| has_property(value, name)),
+        _ => false,
+    }
+}
+
+#[test]
+fn no_response_schema_has_a_password() {
+    let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+    let schemas = &doc["components"]["schemas"];
+
+    let mut responses = BTreeSet::new();
+    for operations in doc["paths"].as_object().unwrap().values() {
+        for operation in operations.as_object().unwrap().values() {
+            collect_refs(&operation["responses"], schemas, &mut responses);
+        }
+    }
+    assert!(responses.contains("AuthRes"));
+    assert!(responses.contains("UserProfile"));
+
+    let leaking: Vec<&String> = responses
+        .iter()
+        .filter(|name| has_property(&schemas[name.as_str()], "password"))
+        .collect();
+    assert!(leaking.is_empty(), "response schemas with a password: {:?}", leaking);
+
+    // Requests still take passwords, so the check does see them.
+    assert!(has_property(&schemas["LoginParams"], "password"));
+}
+
+#[test]
+fn auth_responses_do_not_serialize_the_password_hash() {
+    let fixture = common::user_fixture();
+    let user = User {
+        _key: "1".to_string(),
+        _rev: "_rev1".to_string(),
+        _id: "User/1".to_string(),
+        first_name: fixture.first_name,
+        last_name: fixture.last_name,
+        email: fixture.email,
+        password: "$2b$12$hash".to_string(),
+        role: fixture.role,
+        email_verified: fixture.email_verified,
+        totp_secret: Some("GEZDGNBVGY3TQOJQ".to_string()),
+        totp_enabled: true,
+        totp_last_step: Some(1),
+        recovery_codes: vec!["recovery-hash".to_string()],
+        token_version: 0,
+    };
+
+    let auth = serde_json::to_value(AuthRes::new(user, "jwt".to_string(), "refresh".to_string()));
+    let auth = auth.unwrap();
+
+    assert_eq!(
+        auth["user"],
+        json!({
+            "_key": "1",
+            "_id": "User/1",
+            "first_name": "Jane",
+            "last_name": "Doe",
+            "email": "jane@doe.com",
+            "role": "CUSTOMER",
+            "email_verified": true,
+            "totp_enabled": true
+        })
+    );
+    assert!(!auth.to_string().contains("$2b$12$hash"));
+    assert!(!auth.to_string().contains("GEZDGNBVGY3TQOJQ"));
+    assert!(!auth.to_string().contains("recovery-hash"));
+}
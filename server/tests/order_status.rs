@@ -0,0 +1,217 @@
+mod common;
+
+use axum::{ extract::{ Path, State }, http::StatusCode };
+use chrono::Local;
+use serde_json::{ json, Value };
+use server::models::{ OrderStatus, Role, StatusChange };
+use server::repositories::items::{ ItemRepository, NewItem };
+use server::repositories::memory::MemoryDatabase;
+use server::repositories::orders::{ NewOrder, NewOrderLine, OrderRepository };
+use server::requests::jwt::Claims;
+use server::requests::orders;
+use server::requests::validation::Valid;
+use server::state::AppState;
+use std::sync::Arc;
+
+const VENDOR: &str = "883";
+const OTHER_VENDOR: &str = "884";
+const CUSTOMER: &str = "750";
+const ADMIN: &str = "1";
+
+fn claims(key: &str, role: Role) -> Claims {
+    Claims {
+        sub: format!("{}@rans.com", key),
+        key: key.to_string(),
+        role,
+        iat: 0,
+        exp: usize::MAX,
+        ver: 0,
+    }
+}
+
+async fn list_item(database: &MemoryDatabase, name: &str, vendor: &str) -> String {
+    let item = database
+        .insert(NewItem {
+            name: name.to_string(),
+            user_id: vendor.to_string(),
+            description: format!("Fresh {}", name),
+            price: "3.50".parse().unwrap(),
+            quantity: 5,
+        }).await
+        .unwrap();
+
+    item._key
+}
+
+async fn place(database: &MemoryDatabase, items: &[&str]) -> String {
+    let order = database
+        .place(NewOrder {
+            user_id: CUSTOMER.to_string(),
+            lines: items
+                .iter()
+                .map(|item| NewOrderLine { item_id: item.to_string(), quantity: 2 })
+                .collect(),
+            date: Local::now().naive_local(),
+            cart_rev: None,
+        }).await
+        .unwrap();
+
+    order._key
+}
+
+/// Places an order of 2 out of 5 jars listed by `VENDOR` and returns its key and the item's.
+async fn placed_order() -> (AppState, Arc<MemoryDatabase>, String, String) {
+    let database = Arc::new(MemoryDatabase::new());
+    let item = list_item(&database, "Nutella Jar", VENDOR).await;
+    let order = place(&database, &[&item]).await;
+
+    (common::state_with(database.clone()), database, order, item)
+}
+
+async fn change(
+    state: &AppState,
+    claims: Claims,
+    order: &str,
+    status: &str
+) -> (StatusCode, Value) {
+    let payload = serde_json::from_value(json!({ "status": status })).unwrap();
+    common::respond(
+        orders::update_order_status(
+            State(state.clone()),
+            claims,
+            Path(order.to_string()),
+            Valid(payload)
+        ).await
+    )
+}
+
+async fn stock(database: &MemoryDatabase, item_id: &str) -> i64 {
+    ItemRepository::find_by_key(database, item_id).await.unwrap().quantity
+}
+
+#[tokio::test]
+async fn vendors_move_orders_through_fulfillment() {
+    let (state, database, order, _) = placed_order().await;
+    let vendor = || claims(VENDOR, Role::VENDOR);
+
+    for status in ["PAID", "SHIPPED", "DELIVERED"] {
+        let (code, body) = change(&state, vendor(), &order, status).await;
+        assert_eq!(code, StatusCode::OK, "{}", body);
+        assert_eq!(body["status"], status);
+    }
+
+    let order = OrderRepository::find_by_key(&*database, &order).await.unwrap();
+    let history: Vec<OrderStatus> = order.status_history
+        .iter()
+        .map(|change| change.status)
+        .collect();
+    assert_eq!(history, vec![
+        OrderStatus::PENDING,
+        OrderStatus::PAID,
+        OrderStatus::SHIPPED,
+        OrderStatus::DELIVERED,
+    ]);
+    assert_eq!(order.status_history[3].changed_by, VENDOR);
+
+    let (code, _) = change(&state, vendor(), &order._key, "SHIPPED").await;
+    assert_eq!(code, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn cancelling_returns_the_stock() {
+    let (state, database, order, item) = placed_order().await;
+    assert_eq!(stock(&database, &item).await, 3);
+
+    let (code, body) = change(&state, claims(CUSTOMER, Role::CUSTOMER), &order, "CANCELLED").await;
+    assert_eq!(code, StatusCode::OK);
+    assert_eq!(body["status_history"][1]["changed_by"], CUSTOMER);
+    assert_eq!(stock(&database, &item).await, 5);
+
+    // Cancelled is final, so the stock cannot be returned twice.
+    let (code, _) = change(&state, claims(CUSTOMER, Role::CUSTOMER), &order, "CANCELLED").await;
+    assert_eq!(code, StatusCode::CONFLICT);
+    assert_eq!(stock(&database, &item).await, 5);
+}
+
+#[tokio::test]
+async fn only_the_customer_and_the_vendor_can_change_an_order() {
+    let (state, _, order, _) = placed_order().await;
+
+    let (code, _) = change(&state, claims(CUSTOMER, Role::CUSTOMER), &order, "PAID").await;
+    assert_eq!(code, StatusCode::FORBIDDEN);
+
+    let (code, _) = change(&state, claims("751", Role::CUSTOMER), &order, "CANCELLED").await;
+    assert_eq!(code, StatusCode::FORBIDDEN);
+
+    let (code, _) = change(&state, claims(OTHER_VENDOR, Role::VENDOR), &order, "PAID").await;
+    assert_eq!(code, StatusCode::FORBIDDEN);
+
+    let (code, _) = change(&state, claims(VENDOR, Role::VENDOR), "missing", "PAID").await;
+    assert_eq!(code, StatusCode::NOT_FOUND);
+
+    // Customers can no longer cancel once the order left the warehouse.
+    change(&state, claims(VENDOR, Role::VENDOR), &order, "PAID").await;
+    change(&state, claims(VENDOR, Role::VENDOR), &order, "SHIPPED").await;
+    let (code, _) = change(&state, claims(CUSTOMER, Role::CUSTOMER), &order, "CANCELLED").await;
+    assert_eq!(code, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn vendors_cannot_cancel_or_refund() {
+    let (state, _, order, _) = placed_order().await;
+    let vendor = || claims(VENDOR, Role::VENDOR);
+
+    let (code, _) = change(&state, vendor(), &order, "CANCELLED").await;
+    assert_eq!(code, StatusCode::FORBIDDEN);
+
+    for status in ["PAID", "SHIPPED", "DELIVERED"] {
+        change(&state, vendor(), &order, status).await;
+    }
+    let (code, _) = change(&state, vendor(), &order, "REFUNDED").await;
+    assert_eq!(code, StatusCode::FORBIDDEN);
+
+    let (code, body) = change(&state, claims(ADMIN, Role::ADMIN), &order, "REFUNDED").await;
+    assert_eq!(code, StatusCode::OK, "{}", body);
+}
+
+#[tokio::test]
+async fn vendors_cannot_change_orders_mixing_other_vendors_items() {
+    let database = Arc::new(MemoryDatabase::new());
+    let jar = list_item(&database, "Nutella Jar", VENDOR).await;
+    let bread = list_item(&database, "Bread", OTHER_VENDOR).await;
+    let order = place(&database, &[&jar, &bread]).await;
+    let state = common::state_with(database.clone());
+
+    for vendor in [VENDOR, OTHER_VENDOR] {
+        for status in ["PAID", "CANCELLED"] {
+            let (code, _) = change(&state, claims(vendor, Role::VENDOR), &order, status).await;
+            assert_eq!(code, StatusCode::FORBIDDEN, "{} {}", vendor, status);
+        }
+    }
+    assert_eq!(stock(&database, &bread).await, 3);
+
+    // The customer and admins still can.
+    let (code, _) = change(&state, claims(ADMIN, Role::ADMIN), &order, "PAID").await;
+    assert_eq!(code, StatusCode::OK);
+    let (code, _) = change(&state, claims(CUSTOMER, Role::CUSTOMER), &order, "CANCELLED").await;
+    assert_eq!(code, StatusCode::OK);
+    assert_eq!(stock(&database, &bread).await, 5);
+}
+
+#[tokio::test]
+async fn stale_orders_are_not_overwritten() {
+    let (_, database, order, _) = placed_order().await;
+    let stale = OrderRepository::find_by_key(&*database, &order).await.unwrap();
+    let change = |status| StatusChange {
+        status,
+        changed_at: Local::now().naive_local(),
+        changed_by: VENDOR.to_string(),
+    };
+
+    database.change_status(&stale, change(OrderStatus::PAID)).await.unwrap();
+    let result = database.change_status(&stale, change(OrderStatus::CANCELLED)).await;
+
+    assert!(result.is_err());
+    let order = OrderRepository::find_by_key(&*database, &order).await.unwrap();
+    assert_eq!(order.status, OrderStatus::PAID);
+}
\ No newline at end of file
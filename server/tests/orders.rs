@@ -0,0 +1,150 @@
+use chrono::Local;
+use serde_json::json;
+use server::models::{ Order, OrderStatus };
+use server::repositories::items::{ ItemRepository, NewItem };
+use server::repositories::memory::MemoryDatabase;
+use server::repositories::orders::{ NewOrder, NewOrderLine, OrderRepository };
+use std::sync::Arc;
+
+async fn seed_item(database: &MemoryDatabase, price: &str, quantity: i64) -> String {
+    seed_named_item(database, "Miniature Car", price, quantity).await
+}
+
+async fn seed_named_item(
+    database: &MemoryDatabase,
+    name: &str,
+    price: &str,
+    quantity: i64
+) -> String {
+    let item = database
+        .insert(NewItem {
+            name: name.to_string(),
+            user_id: "791".to_string(),
+            description: "Miniature car mint edition 2000x".to_string(),
+            price: price.parse().unwrap(),
+            quantity,
+        }).await
+        .unwrap();
+
+    item._key
+}
+
+async fn stock(database: &MemoryDatabase, item_id: &str) -> i64 {
+    ItemRepository::find_by_key(database, item_id).await.unwrap().quantity
+}
+
+fn new_order(user_id: &str, item_id: &str, quantity: i64) -> NewOrder {
+    multi_line_order(user_id, &[(item_id, quantity)])
+}
+
+fn multi_line_order(user_id: &str, lines: &[(&str, i64)]) -> NewOrder {
+    NewOrder {
+        user_id: user_id.to_string(),
+        lines: lines
+            .iter()
+            .map(|(item_id, quantity)| NewOrderLine {
+                item_id: item_id.to_string(),
+                quantity: *quantity,
+            })
+            .collect(),
+        date: Local::now().naive_local(),
+        cart_rev: None,
+    }
+}
+
+#[tokio::test]
+async fn placing_an_order_snapshots_the_item_and_decrements_stock() {
+    let database = MemoryDatabase::new();
+    let item_id = seed_item(&database, "56.50", 10).await;
+
+    let order = database.place(new_order("750", &item_id, 3)).await.unwrap();
+
+    assert_eq!(order.lines.len(), 1);
+    assert_eq!(order.lines[0].item_name, "Miniature Car");
+    assert_eq!(order.lines[0].unit_price, "56.50".parse().unwrap());
+    assert_eq!(order.subtotal, "169.50".parse().unwrap());
+    assert_eq!(stock(&database, &item_id).await, 7);
+}
+
+#[tokio::test]
+async fn orders_exceeding_stock_leave_the_item_untouched() {
+    let database = MemoryDatabase::new();
+    let item_id = seed_item(&database, "56.50", 2).await;
+
+    assert!(database.place(new_order("750", &item_id, 3)).await.is_err());
+    assert_eq!(stock(&database, &item_id).await, 2);
+    assert!(database.find_by_user("750", false).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn multi_line_orders_reserve_every_line_or_none() {
+    let database = MemoryDatabase::new();
+    let car = seed_named_item(&database, "Miniature Car", "56.50", 10).await;
+    let cake = seed_named_item(&database, "Baba Cake", "4.25", 1).await;
+
+    let failed = database.place(multi_line_order("750", &[(&car, 2), (&cake, 2)])).await;
+    assert!(failed.is_err());
+    assert_eq!(stock(&database, &car).await, 10);
+
+    let order = database.place(multi_line_order("750", &[(&car, 2), (&cake, 1)])).await.unwrap();
+    assert_eq!(order.lines.len(), 2);
+    assert_eq!(order.lines[1].total, "4.25".parse().unwrap());
+    assert_eq!(order.subtotal, "117.25".parse().unwrap());
+    assert_eq!(stock(&database, &car).await, 8);
+    assert_eq!(stock(&database, &cake).await, 0);
+}
+
+#[test]
+fn single_item_orders_read_as_one_line() {
+    let order: Order = serde_json
+        ::from_value(
+            json!({
+                "_key": "973",
+                "_id": "Order/973",
+                "_rev": "_f4627ty---",
+                "user_id": "750",
+                "item_id": "816",
+                "item_name": "Miniature Car",
+                "quantity": 7,
+                "price": { "amount": "395.50", "currency": "USD", "minor_units": 39550 },
+                "date": "2023-04-23T13:45:58.775698900"
+            })
+        )
+        .unwrap();
+
+    assert_eq!(order.subtotal, "395.50".parse().unwrap());
+    assert_eq!(order.lines.len(), 1);
+    assert_eq!(order.lines[0].item_id, "816");
+    assert_eq!(order.lines[0].quantity, 7);
+    assert_eq!(order.lines[0].unit_price, "56.50".parse().unwrap());
+    assert_eq!(order.status, OrderStatus::PENDING);
+    assert_eq!(order.status_history[0].changed_at, order.date);
+
+    let written = json!(order);
+    assert!(written.get("item_id").is_none());
+    assert_eq!(written["lines"][0]["total"]["amount"], "395.50");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_orders_never_oversell() {
+    let database = Arc::new(MemoryDatabase::new());
+    let item_id = seed_item(&database, "7.00", 10).await;
+
+    let handles: Vec<_> = (0..50)
+        .map(|n| {
+            let database = database.clone();
+            let order = new_order(&n.to_string(), &item_id, 1);
+            tokio::spawn(async move { database.place(order).await.is_ok() })
+        })
+        .collect();
+
+    let mut placed = 0;
+    for handle in handles {
+        if handle.await.unwrap() {
+            placed += 1;
+        }
+    }
+
+    assert_eq!(placed, 10);
+    assert_eq!(stock(&database, &item_id).await, 0);
+}
\ No newline at end of file
@@ -0,0 +1,242 @@
+mod common;
+
+use axum::{ extract::State, http::StatusCode };
+use serde::de::DeserializeOwned;
+use serde_json::{ json, Value };
+use server::password::{ Argon2Hasher, BcryptHasher, PasswordHasher, Passwords };
+use server::repositories::memory::MemoryDatabase;
+use server::repositories::users::{ NewUser, UserRepository };
+use server::requests::jwt::Claims;
+use server::requests::security::ClientIp;
+use server::requests::validation::Valid;
+use server::requests::{ auth, users };
+use server::state::AppState;
+use server::toml_env::{ Config, HashAlgorithm };
+use std::sync::Arc;
+
+fn valid<T: DeserializeOwned>(value: Value) -> Valid<T> {
+    Valid(serde_json::from_value(value).unwrap())
+}
+
+/// Test config hashing with cheap Argon2id parameters.
+fn argon2_config() -> Config {
+    let mut config = common::config();
+    config.passwords.algorithm = HashAlgorithm::Argon2id;
+    config.passwords.argon2_memory_kib = 1024;
+    config.passwords.argon2_iterations = 1;
+
+    config
+}
+
+async fn state_with_hash(
+    config: Config,
+    hash: String
+) -> (AppState, Arc<MemoryDatabase>, Claims) {
+    let user = NewUser { password: hash, ..common::user_fixture() };
+    let (state, database, user) = common::state_holding(config, user).await;
+
+    (state, database, Claims::new(&user))
+}
+
+async fn log_in(state: &AppState, password: &str) -> StatusCode {
+    let payload = valid(json!({ "email": "jane@doe.com", "password": password }));
+
+    common::respond(auth::handle_login(State(state.clone()), ClientIp::default(), payload).await).0
+}
+
+async fn stored_hash(database: &MemoryDatabase) -> String {
+    database.find_by_email("jane@doe.com").await.unwrap().unwrap().password
+}
+
+async fn sign_up(state: &AppState, password: &str) -> (StatusCode, Value) {
+    let payload = json!({
+        "first_name": "Jane",
+        "last_name": "Doe",
+        "email": "jane.doe@gmail.com",
+        "password": password,
+        "role": "CUSTOMER"
+    });
+
+    common::respond(auth::handle_signup(State(state.clone()), valid(payload)).await)
+}
+
+#[test]
+fn hashers_only_recognize_their_own_hashes() {
+    let bcrypt = BcryptHasher::new(4).unwrap();
+    let argon2 = Argon2Hasher::new(1024, 1, 1).unwrap();
+
+    let bcrypt_hash = bcrypt.hash("Password.1").unwrap();
+    let argon2_hash = argon2.hash("Password.1").unwrap();
+    assert!(argon2_hash.starts_with("$argon2id$v=19$m=1024,t=1,p=1$"));
+
+    assert!(bcrypt.recognizes(&bcrypt_hash) && !bcrypt.recognizes(&argon2_hash));
+    assert!(argon2.recognizes(&argon2_hash) && !argon2.recognizes(&bcrypt_hash));
+    assert!(bcrypt.verify("Password.1", &bcrypt_hash) && !bcrypt.verify("wrong", &bcrypt_hash));
+    assert!(argon2.verify("Password.1", &argon2_hash) && !argon2.verify("wrong", &argon2_hash));
+
+    // Parameters only matter to `is_current`: hashes verify with the ones they were made with.
+    let costlier = BcryptHasher::new(5).unwrap();
+    let bigger = Argon2Hasher::new(2048, 1, 1).unwrap();
+    assert!(bcrypt.is_current(&bcrypt_hash) && !costlier.is_current(&bcrypt_hash));
+    assert!(argon2.is_current(&argon2_hash) && !bigger.is_current(&argon2_hash));
+    assert!(costlier.verify("Password.1", &bcrypt_hash));
+    assert!(bigger.verify("Password.1", &argon2_hash));
+}
+
+#[test]
+fn invalid_parameters_are_refused() {
+    assert!(BcryptHasher::new(3).is_err());
+    assert!(BcryptHasher::new(32).is_err());
+    assert!(Argon2Hasher::new(1024, 0, 1).is_err());
+
+    let mut config = common::config();
+    config.passwords.bcrypt_cost = 40;
+    assert!(Passwords::from_config(&config.passwords).is_err());
+}
+
+#[test]
+fn passwords_verify_hashes_of_every_algorithm() {
+    let passwords = Passwords::from_config(&argon2_config().passwords).unwrap();
+    let bcrypt_hash = bcrypt::hash("Password.1", 4).unwrap();
+    let argon2_hash = passwords.hash("Password.1").unwrap();
+
+    assert!(passwords.verify("Password.1", &bcrypt_hash));
+    assert!(passwords.verify("Password.1", &argon2_hash));
+    assert!(!passwords.verify("wrong", &bcrypt_hash));
+    assert!(!passwords.verify("Password.1", "not a hash"));
+
+    assert!(passwords.needs_rehash(&bcrypt_hash));
+    assert!(!passwords.needs_rehash(&argon2_hash));
+}
+
+#[tokio::test]
+async fn logins_upgrade_hashes_to_the_configured_algorithm() {
+    let hash = bcrypt::hash("Password.1", 4).unwrap();
+    let (state, database, _) = state_with_hash(argon2_config(), hash.clone()).await;
+
+    assert_eq!(log_in(&state, "wrong").await, StatusCode::BAD_REQUEST);
+    assert_eq!(stored_hash(&database).await, hash);
+
+    assert_eq!(log_in(&state, "Password.1").await, StatusCode::OK);
+    let upgraded = stored_hash(&database).await;
+    assert!(upgraded.starts_with("$argon2id$v=19$m=1024,t=1,p=1$"), "{}", upgraded);
+
+    // The new hash is current, so the next login keeps it.
+    assert_eq!(log_in(&state, "Password.1").await, StatusCode::OK);
+    assert_eq!(stored_hash(&database).await, upgraded);
+}
+
+#[tokio::test]
+async fn logins_upgrade_hashes_to_the_configured_cost() {
+    let mut config = common::config();
+    config.passwords.bcrypt_cost = 5;
+    let hash = bcrypt::hash("Password.1", 4).unwrap();
+    let (state, database, _) = state_with_hash(config, hash).await;
+
+    assert_eq!(log_in(&state, "Password.1").await, StatusCode::OK);
+    assert!(stored_hash(&database).await.starts_with("$2b$05$"));
+    assert_eq!(log_in(&state, "Password.1").await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn signup_enforces_the_password_policy() {
+    let mut config = argon2_config();
+    config.passwords.policy.min_length = 10;
+    config.passwords.policy.min_character_classes = 3;
+    let state = AppState::in_memory(config).unwrap();
+
+    let rejected = [
+        ("Short.1", "must be at least 10 characters long"),
+        (
+            "longbutlowercase",
+            "must mix at least 3 of lowercase letters, uppercase letters, digits and symbols",
+        ),
+        ("Jane.Doe.2024", "cannot contain your name or email address"),
+        ("X-jane.doe-1", "cannot contain your name or email address"),
+    ];
+    for (password, message) in rejected {
+        let (status, body) = sign_up(&state, password).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", password);
+        assert_eq!(body["error_code"], "invalid_fields");
+        assert_eq!(body["error_msg"], format!("password: {}", message));
+    }
+
+    let (status, body) = sign_up(&state, "Correct.Horse.7").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[tokio::test]
+async fn common_passwords_are_rejected() {
+    let state = common::state();
+
+    let (status, body) = sign_up(&state, "PassWord123").await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error_msg"], "password: is too common");
+
+    let mut config = common::config();
+    config.passwords.policy.reject_common = false;
+    let (status, _) = sign_up(&AppState::in_memory(config).unwrap(), "PassWord123").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn new_passwords_follow_the_policy() {
+    let hash = bcrypt::hash("Password.1", 4).unwrap();
+    let (state, _, claims) = state_with_hash(common::config(), hash).await;
+    let change = |new_password: &str| {
+        let payload = json!({ "current_password": "Password.1", "new_password": new_password });
+        users::change_password(State(state.clone()), claims.clone(), valid(payload))
+    };
+
+    let (status, body) = common::respond(change("iloveyou").await);
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error_msg"], "new_password: is too common");
+    let (status, body) = common::respond(change("Doe.Family.9").await);
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error_msg"], "new_password: cannot contain your name or email address");
+
+    let (status, _) = common::respond(change("Password.2").await);
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(log_in(&state, "Password.2").await, StatusCode::OK);
+}
+
+#[test]
+fn password_config_has_defaults() {
+    let config = Config::from_toml(
+        r#"
+        [Database]
+        host = "http://127.0.0.1"
+        port = 8529
+        name = "rans_test"
+        username = "root"
+        password = "root"
+
+        [Server]
+        env = "production"
+        host = "127.0.0.1"
+        port = 3000
+        secret = "secret"
+
+        [Logs]
+        path = "logs"
+        level = "off"
+
+        [Passwords]
+        algorithm = "argon2id"
+
+        [Passwords.Policy]
+        min_character_classes = 3
+        "#
+    ).unwrap();
+
+    let passwords = &config.passwords;
+    assert_eq!(passwords.algorithm, HashAlgorithm::Argon2id);
+    assert_eq!(passwords.bcrypt_cost, 12);
+    assert_eq!(passwords.argon2_memory_kib, 19456);
+    assert_eq!(passwords.argon2_iterations, 2);
+    assert_eq!(passwords.argon2_parallelism, 1);
+    assert_eq!(passwords.policy.min_length, 8);
+    assert_eq!(passwords.policy.min_character_classes, 3);
+    assert!(passwords.policy.reject_common);
+    assert!(passwords.policy.reject_personal);
+}
\ No newline at end of file
@@ -0,0 +1,239 @@
+mod common;
+
+use async_trait::async_trait;
+use axum::{ extract::State, http::StatusCode, Json };
+use chrono::{ Duration, Local, Utc };
+use serde_json::{ json, Value };
+use server::db::DatabaseError;
+use server::mail::{ Mail, MailError, Mailer };
+use server::models::{ Role, User };
+use server::purge;
+use server::repositories::memory::MemoryDatabase;
+use server::repositories::password_resets::{ NewPasswordReset, PasswordResetRepository };
+use server::repositories::users::{ NewUser, UserRepository, UserUpdate };
+use server::requests::{ auth, jwt, security::ClientIp, validation::Valid };
+use server::state::AppState;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+async fn seeded() -> (AppState, Arc<MemoryDatabase>, PathBuf) {
+    let database = Arc::new(MemoryDatabase::new());
+    UserRepository::insert(&*database, NewUser {
+        first_name: "John".to_string(),
+        last_name: "Starbury".to_string(),
+        email: "jstarb@gmail.com".to_string(),
+        password: bcrypt::hash("Password.1", 4).unwrap(),
+        role: Role::CUSTOMER,
+        email_verified: true,
+    }).await.unwrap();
+
+    let mut state = common::state_with(database.clone());
+    let mailbox = common::capture_mail(&mut state);
+
+    (state, database, mailbox)
+}
+
+async fn forgot(state: &AppState, email: &str) -> StatusCode {
+    let payload = serde_json::from_value(json!({ "email": email })).unwrap();
+    let (status, content) = common::respond(
+        auth::forgot_password(State(state.clone()), Valid(payload)).await
+    );
+    assert!(status != StatusCode::OK || content == json!(true));
+
+    status
+}
+
+async fn reset(state: &AppState, token: &str, password: &str) -> (StatusCode, Value) {
+    let payload = serde_json::from_value(json!({ "token": token, "password": password })).unwrap();
+
+    common::respond(auth::reset_password(State(state.clone()), Valid(payload)).await)
+}
+
+async fn login(state: &AppState, password: &str) -> (StatusCode, Value) {
+    let payload = json!({ "email": "jstarb@gmail.com", "password": password });
+    let payload = serde_json::from_value(payload).unwrap();
+
+    common::respond(auth::handle_login(State(state.clone()), ClientIp::default(), Valid(payload)).await)
+}
+
+#[tokio::test]
+async fn reset_tokens_change_the_password_once() {
+    let (state, _, mailbox) = seeded().await;
+    let (_, session) = login(&state, "Password.1").await;
+
+    assert_eq!(forgot(&state, "jstarb@gmail.com").await, StatusCode::OK);
+    common::wait_for_mail(&mailbox, 1).await;
+    assert_eq!(forgot(&state, "jstarb@gmail.com").await, StatusCode::OK);
+    let mail = common::wait_for_mail(&mailbox, 2).await;
+    assert_eq!(mail.len(), 2);
+    assert!(mail[0].contains("To: jstarb@gmail.com"));
+    assert!(mail[0].contains("http://rans.test/reset_password?token="));
+    let (older, newer) = (common::link_token(&mail[0]), common::link_token(&mail[1]));
+
+    assert_eq!(reset(&state, "not-a-token", "Password.2").await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(reset(&state, &newer, "Password.2").await, (StatusCode::OK, json!(true)));
+    assert_eq!(reset(&state, &newer, "Password.3").await.0, StatusCode::BAD_REQUEST);
+    // Resetting the password also voids the links sent before.
+    assert_eq!(reset(&state, &older, "Password.3").await.0, StatusCode::BAD_REQUEST);
+
+    assert_eq!(login(&state, "Password.1").await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(login(&state, "Password.2").await.0, StatusCode::OK);
+
+    let refresh = jwt::refresh(
+        State(state.clone()),
+        Json(serde_json::from_value(json!({ "refresh_token": session["refresh_token"] })).unwrap())
+    ).await;
+    assert_eq!(common::respond(refresh).0, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn unknown_emails_get_the_same_answer_and_no_mail() {
+    let (state, _, mailbox) = seeded().await;
+
+    assert_eq!(forgot(&state, "nobody@gmail.com").await, StatusCode::OK);
+    assert_eq!(forgot(&state, "jstarb@gmail.com").await, StatusCode::OK);
+
+    // Requests are handled in order, so the unknown email would have been mailed first.
+    let mail = common::wait_for_mail(&mailbox, 1).await;
+    assert_eq!(mail.len(), 1);
+    assert!(mail[0].contains("To: jstarb@gmail.com"));
+}
+
+/// Looks users up by email slower than any request may take.
+struct SlowUsers(Arc<MemoryDatabase>);
+
+#[async_trait]
+impl UserRepository for SlowUsers {
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, DatabaseError> {
+        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+        self.0.find_by_email(email).await
+    }
+
+    async fn find_by_key(&self, key: &str) -> Result<Option<User>, DatabaseError> {
+        self.0.find_by_key(key).await
+    }
+
+    async fn insert(&self, user: NewUser) -> Result<User, DatabaseError> {
+        UserRepository::insert(&*self.0, user).await
+    }
+
+    async fn update(&self, key: &str, update: UserUpdate) -> Result<User, DatabaseError> {
+        UserRepository::update(&*self.0, key, update).await
+    }
+
+    async fn claim_totp_step(&self, key: &str, step: u64) -> Result<bool, DatabaseError> {
+        self.0.claim_totp_step(key, step).await
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        key: &str,
+        current: &[String],
+        remaining: Vec<String>
+    ) -> Result<bool, DatabaseError> {
+        self.0.replace_recovery_codes(key, current, remaining).await
+    }
+
+    async fn remove(&self, key: &str) -> Result<(), DatabaseError> {
+        UserRepository::remove(&*self.0, key).await
+    }
+}
+
+#[tokio::test]
+async fn answers_do_not_wait_for_the_lookup_or_the_email() {
+    let (mut state, database, mailbox) = seeded().await;
+    state.users = Arc::new(SlowUsers(database));
+
+    for email in ["jstarb@gmail.com", "nobody@gmail.com"] {
+        let started = std::time::Instant::now();
+        assert_eq!(forgot(&state, email).await, StatusCode::OK);
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+    }
+    assert!(common::sent_mail(&mailbox).is_empty());
+}
+
+#[tokio::test]
+async fn refused_passwords_keep_the_link_working() {
+    let (state, _, mailbox) = seeded().await;
+    forgot(&state, "jstarb@gmail.com").await;
+    let token = common::link_token(&common::wait_for_mail(&mailbox, 1).await[0]);
+
+    let (status, body) = reset(&state, &token, "Starbury.99").await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error_msg"], "password: cannot contain your name or email address");
+
+    assert_eq!(reset(&state, &token, "Password.2").await.0, StatusCode::OK);
+    assert_eq!(login(&state, "Password.2").await.0, StatusCode::OK);
+}
+
+struct BrokenMailer;
+
+#[async_trait]
+impl Mailer for BrokenMailer {
+    async fn send(&self, _mail: Mail) -> Result<(), MailError> {
+        Err(MailError::Transport("relay unreachable".to_string()))
+    }
+}
+
+#[tokio::test]
+async fn mail_errors_do_not_reveal_known_emails() {
+    let (mut state, _, _) = seeded().await;
+    state.mailer = Arc::new(BrokenMailer);
+
+    assert_eq!(forgot(&state, "jstarb@gmail.com").await, StatusCode::OK);
+    assert_eq!(forgot(&state, "nobody@gmail.com").await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn reset_requests_are_limited_per_email() {
+    let (state, _, mailbox) = seeded().await;
+
+    for sent in [1, 2, 3, 3, 3] {
+        assert_eq!(forgot(&state, "jstarb@gmail.com").await, StatusCode::OK);
+        common::wait_for_mail(&mailbox, sent).await;
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert_eq!(common::sent_mail(&mailbox).len(), 3);
+}
+
+#[tokio::test]
+async fn expired_reset_tokens_are_rejected() {
+    let (state, database, _) = seeded().await;
+    let user = database.find_by_email("jstarb@gmail.com").await.unwrap().unwrap();
+    let created_at = Utc::now().naive_utc() - Duration::hours(2);
+    PasswordResetRepository::insert(&*database, NewPasswordReset {
+        user_id: user._key,
+        email: user.email,
+        token_hash: jwt::hash_token("expired"),
+        created_at,
+        expires_at: created_at + Duration::minutes(30),
+        used_at: None,
+    }).await.unwrap();
+
+    let (status, body) = reset(&state, "expired", "Password.2").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error_msg"], "Reset token is invalid or expired");
+    assert_eq!(login(&state, "Password.1").await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn expired_resets_are_purged() {
+    let (state, database, mailbox) = seeded().await;
+    forgot(&state, "jstarb@gmail.com").await;
+    let token = common::link_token(&common::wait_for_mail(&mailbox, 1).await[0]);
+
+    let user = database.find_by_email("jstarb@gmail.com").await.unwrap().unwrap();
+    let created_at = Utc::now().naive_utc() - Duration::hours(2);
+    PasswordResetRepository::insert(&*database, NewPasswordReset {
+        user_id: user._key,
+        email: user.email,
+        token_hash: jwt::hash_token("expired"),
+        created_at,
+        expires_at: created_at + Duration::minutes(30),
+        used_at: None,
+    }).await.unwrap();
+
+    let purged = purge::purge_deleted(&state, Local::now().naive_local()).await.unwrap();
+    assert_eq!(purged.password_resets, 1);
+    assert_eq!(reset(&state, &token, "Password.2").await.0, StatusCode::OK);
+}
\ No newline at end of file
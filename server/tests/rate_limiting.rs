@@ -0,0 +1,324 @@
+mod common;
+
+use async_trait::async_trait;
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{ header, HeaderMap, Method, Request, StatusCode },
+    Router,
+};
+use chrono::{ Duration, NaiveDate, NaiveDateTime };
+use serde_json::{ json, Value };
+use server::rate_limit::{
+    Budget,
+    MemoryRateLimitStore,
+    RateLimit,
+    RateLimitError,
+    RateLimitStore,
+};
+use server::requests::routes::create_routes;
+use server::state::AppState;
+use server::toml_env::{ Config, RouteLimitConfig };
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+fn at(seconds: i64) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2026, 10, 18).unwrap().and_hms_opt(12, 0, 0).unwrap() +
+        Duration::seconds(seconds)
+}
+
+fn limited_config(requests: u32) -> Config {
+    let mut config = common::config();
+    config.rate_limit.requests = requests;
+    config.rate_limit.per_seconds = 60;
+    config.rate_limit.routes.push(RouteLimitConfig {
+        path: "/api/auth/signup".to_string(),
+        method: Some("post".to_string()),
+        requests: 2,
+        per_seconds: 3600,
+    });
+
+    config
+}
+
+async fn app(config: Config) -> Router {
+    create_routes(AppState::in_memory(config).unwrap()).await
+}
+
+async fn call(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    from: &str,
+    token: Option<&str>,
+    body: Option<Value>
+) -> (StatusCode, HeaderMap, Value) {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("Accept", "application/json")
+        .header("Content-Type", "application/json");
+    if let Some(token) = token {
+        request = request.header("Authorization", format!("Bearer {}", token));
+    }
+    let peer: SocketAddr = format!("{}:50000", from).parse().unwrap();
+    request = request.extension(ConnectInfo(peer));
+
+    let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+    let response = app.clone().oneshot(request.body(body).unwrap()).await.unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+    (status, headers, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+async fn sign_up(app: &Router, from: &str, email: &str) -> (StatusCode, HeaderMap, Value) {
+    let payload = json!({
+        "first_name": "Jane",
+        "last_name": "Doe",
+        "email": email,
+        "password": "Password.1",
+        "role": "CUSTOMER"
+    });
+
+    call(app, Method::POST, "/api/auth/signup", from, None, Some(payload)).await
+}
+
+fn header_value(headers: &HeaderMap, name: &str) -> u64 {
+    headers[name].to_str().unwrap().parse().unwrap()
+}
+
+#[tokio::test]
+async fn buckets_allow_bursts_and_refill_over_time() {
+    let store = MemoryRateLimitStore::new();
+    let budget = Budget { requests: 3, per_seconds: 60 };
+
+    for remaining in [2, 1, 0] {
+        let limit = store.take("client", &budget, at(0)).await.unwrap();
+        assert!(limit.allowed);
+        assert_eq!(limit.remaining, remaining);
+    }
+
+    let refused = store.take("client", &budget, at(0)).await.unwrap();
+    assert!(!refused.allowed);
+    assert_eq!(refused.retry_after, Some(20));
+    assert_eq!(refused.reset, 60);
+    assert!(store.take("other", &budget, at(0)).await.unwrap().allowed);
+
+    let refilled = store.take("client", &budget, at(20)).await.unwrap();
+    assert!(refilled.allowed);
+    assert_eq!(refilled.remaining, 0);
+    assert!(!store.take("client", &budget, at(21)).await.unwrap().allowed);
+
+    // Buckets never hold more than the budget.
+    let limit = store.take("client", &budget, at(3600)).await.unwrap();
+    assert_eq!((limit.remaining, limit.reset), (2, 20));
+
+    store.refund("client", &budget).await.unwrap();
+    store.refund("client", &budget).await.unwrap();
+    let limit = store.take("client", &budget, at(3600)).await.unwrap();
+    assert_eq!(limit.remaining, 2);
+}
+
+#[tokio::test]
+async fn route_budgets_limit_signups_per_ip() {
+    let app = app(limited_config(100)).await;
+
+    let (status, headers, _) = sign_up(&app, "10.0.0.1", "a@doe.com").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(header_value(&headers, "x-ratelimit-limit"), 2);
+    assert_eq!(header_value(&headers, "x-ratelimit-remaining"), 1);
+    assert!((1790..=1800).contains(&header_value(&headers, "x-ratelimit-reset")));
+
+    assert_eq!(sign_up(&app, "10.0.0.1", "b@doe.com").await.0, StatusCode::OK);
+    let (status, headers, body) = sign_up(&app, "10.0.0.1", "c@doe.com").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["content"]["error_code"], "too_many_requests");
+    assert!((1790..=1800).contains(&header_value(&headers, header::RETRY_AFTER.as_str())));
+    assert_eq!(header_value(&headers, "x-ratelimit-remaining"), 0);
+
+    assert_eq!(sign_up(&app, "10.0.0.2", "c@doe.com").await.0, StatusCode::OK);
+
+    // Other routes only spend from the global budget.
+    let (status, headers, _) = call(
+        &app,
+        Method::POST,
+        "/api/auth/login",
+        "10.0.0.1",
+        None,
+        Some(json!({ "email": "a@doe.com", "password": "Password.1" }))
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(header_value(&headers, "x-ratelimit-limit"), 100);
+    // Signing up hashes passwords slowly, so the global bucket refills a little meanwhile.
+    assert!((96..100).contains(&header_value(&headers, "x-ratelimit-remaining")));
+}
+
+#[tokio::test]
+async fn refused_requests_do_not_spend_the_global_budget() {
+    let mut config = limited_config(5);
+    config.rate_limit.per_seconds = 3600;
+    let app = app(config).await;
+
+    assert_eq!(sign_up(&app, "10.0.0.1", "a@doe.com").await.0, StatusCode::OK);
+    assert_eq!(sign_up(&app, "10.0.0.1", "b@doe.com").await.0, StatusCode::OK);
+    for _ in 0..5 {
+        let (status, _, _) = sign_up(&app, "10.0.0.1", "c@doe.com").await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    let (status, headers, _) = call(
+        &app,
+        Method::POST,
+        "/api/auth/login",
+        "10.0.0.1",
+        None,
+        Some(json!({ "email": "a@doe.com", "password": "Password.1" }))
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(header_value(&headers, "x-ratelimit-remaining"), 2);
+}
+
+/// Signs up through a proxy at `10.0.0.254` that appends `client` to `X-Forwarded-For`.
+async fn sign_up_through_proxy(app: &Router, client: &str, email: &str) -> StatusCode {
+    let payload = json!({
+        "first_name": "Jane",
+        "last_name": "Doe",
+        "email": email,
+        "password": "Password.1",
+        "role": "CUSTOMER"
+    });
+    let peer: SocketAddr = "10.0.0.254:50000".parse().unwrap();
+    let request = Request::post("/api/auth/signup")
+        .header("Content-Type", "application/json")
+        .header("X-Forwarded-For", format!("198.51.100.1, {}", client))
+        .extension(ConnectInfo(peer))
+        .body(Body::from(payload.to_string()))
+        .unwrap();
+
+    app.clone().oneshot(request).await.unwrap().status()
+}
+
+#[tokio::test]
+async fn clients_behind_the_proxy_get_their_own_budget() {
+    let mut config = limited_config(100);
+    config.security.trust_forwarded_for = true;
+    let app = app(config).await;
+
+    assert_eq!(sign_up_through_proxy(&app, "203.0.113.1", "a@doe.com").await, StatusCode::OK);
+    assert_eq!(sign_up_through_proxy(&app, "203.0.113.1", "b@doe.com").await, StatusCode::OK);
+    let refused = sign_up_through_proxy(&app, "203.0.113.1", "c@doe.com").await;
+    assert_eq!(refused, StatusCode::TOO_MANY_REQUESTS);
+
+    assert_eq!(sign_up_through_proxy(&app, "203.0.113.2", "c@doe.com").await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn signed_in_clients_are_limited_by_user() {
+    let app = app(limited_config(4)).await;
+    let (_, _, jane) = sign_up(&app, "10.0.0.1", "jane@doe.com").await;
+    let (_, _, john) = sign_up(&app, "10.0.0.2", "john@doe.com").await;
+    let jane = jane["content"]["token"].as_str().unwrap().to_string();
+    let john = john["content"]["token"].as_str().unwrap().to_string();
+
+    for from in ["10.0.0.3", "10.0.0.4", "10.0.0.5", "10.0.0.6"] {
+        let (status, _, _) = call(&app, Method::GET, "/api/cart", from, Some(&jane), None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let (status, _, _) = call(&app, Method::GET, "/api/cart", "10.0.0.7", Some(&jane), None).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    let (status, _, _) = call(&app, Method::GET, "/api/cart", "10.0.0.3", Some(&john), None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn disabled_rate_limits_send_no_headers() {
+    let mut config = limited_config(1);
+    config.rate_limit.enabled = false;
+    let app = app(config).await;
+
+    for email in ["a@doe.com", "b@doe.com", "c@doe.com"] {
+        let (status, headers, _) = sign_up(&app, "10.0.0.1", email).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!headers.contains_key("x-ratelimit-limit"));
+    }
+}
+
+struct BrokenStore;
+
+#[async_trait]
+impl RateLimitStore for BrokenStore {
+    async fn take(
+        &self,
+        _key: &str,
+        _budget: &Budget,
+        _now: NaiveDateTime
+    ) -> Result<RateLimit, RateLimitError> {
+        Err(RateLimitError("store is down".to_string()))
+    }
+
+    async fn refund(&self, _key: &str, _budget: &Budget) -> Result<(), RateLimitError> {
+        Err(RateLimitError("store is down".to_string()))
+    }
+}
+
+#[tokio::test]
+async fn requests_go_through_when_the_store_fails() {
+    let mut state = AppState::in_memory(limited_config(1)).unwrap();
+    state.limiter = Arc::new(BrokenStore);
+    let app = create_routes(state).await;
+
+    for email in ["a@doe.com", "b@doe.com", "c@doe.com"] {
+        let (status, headers, _) = sign_up(&app, "10.0.0.1", email).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!headers.contains_key("x-ratelimit-limit"));
+    }
+}
+
+#[test]
+fn route_budgets_are_read_from_the_config_file() {
+    let config = Config::from_toml(
+        r#"
+        [Database]
+        host = "http://127.0.0.1"
+        port = 8529
+        name = "rans_test"
+        username = "root"
+        password = "root"
+
+        [Server]
+        env = "production"
+        host = "127.0.0.1"
+        port = 3000
+        secret = "secret"
+
+        [Logs]
+        path = "logs"
+        level = "off"
+
+        [RateLimit]
+        requests = 600
+
+        [[RateLimit.Routes]]
+        path = "/api/get_items"
+        requests = 30
+        per_seconds = 60
+
+        [[RateLimit.Routes]]
+        path = "/api/auth/signup"
+        method = "POST"
+        requests = 5
+        per_seconds = 3600
+        "#
+    ).unwrap();
+
+    assert!(config.rate_limit.enabled);
+    assert_eq!(config.rate_limit.budget(), Budget { requests: 600, per_seconds: 60 });
+    let signup = config.rate_limit.route("POST", "/api/auth/signup").unwrap();
+    assert_eq!(signup.budget(), Budget { requests: 5, per_seconds: 3600 });
+    assert!(config.rate_limit.route("GET", "/api/auth/signup").is_none());
+    assert!(config.rate_limit.route("GET", "/api/get_items").is_some());
+}
\ No newline at end of file
@@ -0,0 +1,100 @@
+mod common;
+
+use axum::{ extract::State, http::StatusCode, Json };
+use serde_json::{ json, Value };
+use server::repositories::memory::MemoryDatabase;
+use server::models::Role;
+use server::repositories::users::{ NewUser, UserRepository };
+use server::requests::{ auth, jwt, security::ClientIp, validation::Valid };
+use server::state::AppState;
+use std::sync::Arc;
+
+async fn logged_in_state() -> (AppState, String) {
+    let database = Arc::new(MemoryDatabase::new());
+    database
+        .insert(NewUser {
+            first_name: "John".to_string(),
+            last_name: "Starbury".to_string(),
+            email: "jstarb@gmail.com".to_string(),
+            password: bcrypt::hash("Password.1", 4).unwrap(),
+            role: Role::CUSTOMER,
+            email_verified: true,
+        }).await
+        .unwrap();
+
+    let state = common::state_with(database);
+    let (status, response) = common::respond(auth::handle_login(
+        State(state.clone()),
+        ClientIp::default(),
+        Valid(
+            serde_json::from_value(
+                json!({ "email": "jstarb@gmail.com", "password": "Password.1" })
+            ).unwrap()
+        )
+    ).await);
+    assert_eq!(status, StatusCode::OK);
+
+    let refresh_token = response["refresh_token"].as_str().unwrap().to_string();
+    (state, refresh_token)
+}
+
+async fn refresh(state: &AppState, refresh_token: &str) -> (StatusCode, Value) {
+    let (status, response) = common::respond(jwt::refresh(
+        State(state.clone()),
+        Json(serde_json::from_value(json!({ "refresh_token": refresh_token })).unwrap())
+    ).await);
+
+    (status, response)
+}
+
+#[tokio::test]
+async fn refresh_rotates_the_token() {
+    let (state, first) = logged_in_state().await;
+
+    let (status, body) = refresh(&state, &first).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["user"]["email"], "jstarb@gmail.com");
+    assert!(body["user"].get("password").is_none());
+
+    let second = body["refresh_token"].as_str().unwrap();
+    assert_ne!(second, first);
+
+    let (status, _) = refresh(&state, second).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn reusing_a_rotated_token_revokes_the_family() {
+    let (state, first) = logged_in_state().await;
+
+    let (_, body) = refresh(&state, &first).await;
+    let second = body["refresh_token"].as_str().unwrap().to_string();
+
+    let (status, _) = refresh(&state, &first).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = refresh(&state, &second).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn logout_revokes_refresh_tokens() {
+    let (state, first) = logged_in_state().await;
+
+    let (status, _) = common::respond(jwt::logout(
+        State(state.clone()),
+        Json(serde_json::from_value(json!({ "refresh_token": first })).unwrap())
+    ).await);
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = refresh(&state, &first).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn unknown_refresh_tokens_are_rejected() {
+    let (state, _) = logged_in_state().await;
+
+    let (status, _) = refresh(&state, "not-a-token").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
\ No newline at end of file
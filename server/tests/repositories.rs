@@ -0,0 +1,187 @@
+mod common;
+
+use axum::{ extract::{ Path, Query, State }, http::{ HeaderMap, StatusCode }, Json };
+use serde::de::DeserializeOwned;
+use serde_json::{ json, Value };
+use server::models::Role;
+use server::requests::jwt::Claims;
+use server::requests::security::ClientIp;
+use server::requests::validation::Valid;
+use server::requests::{ auth, items, orders };
+use server::state::AppState;
+
+fn payload<T: DeserializeOwned>(value: Value) -> Json<T> {
+    Json(serde_json::from_value(value).unwrap())
+}
+
+fn valid<T: DeserializeOwned>(value: Value) -> Valid<T> {
+    Valid(serde_json::from_value(value).unwrap())
+}
+
+fn claims(key: &str, role: Role) -> Claims {
+    Claims {
+        sub: format!("{}@rans.com", key),
+        key: key.to_string(),
+        role,
+        iat: 0,
+        exp: usize::MAX,
+        ver: 0,
+    }
+}
+
+async fn seed_item(state: &AppState, name: &str, quantity: i64) -> Value {
+    let (status, response) = common::respond(items::add_item(
+        State(state.clone()),
+        claims("1", Role::VENDOR),
+        valid(
+            json!({
+                "name": name,
+                "description": "A sample item",
+                "price": 9.99,
+                "quantity": quantity
+            })
+        )
+    ).await);
+
+    assert_eq!(status, StatusCode::OK);
+    response
+}
+
+#[tokio::test]
+async fn items_are_listed_and_searched_by_name() {
+    let state = common::state();
+    seed_item(&state, "Nutella Jar", 5).await;
+    seed_item(&state, "Baba Cake", 3).await;
+
+    let result = items::get_items(State(state.clone()), None, Query(Default::default())).await;
+    let (status, response) = common::respond(result);
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response["items"].as_array().unwrap().len(), 2);
+    assert_eq!(response["total"], 2);
+
+    let result = items::get_item(State(state.clone()), Path("nutella".into())).await;
+    let (status, response) = common::respond(result);
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response[0]["name"], "Nutella Jar");
+
+    let (status, _) = common::respond(items::get_item(State(state), Path("pizza".into())).await);
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn duplicate_item_names_are_rejected() {
+    let state = common::state();
+    seed_item(&state, "Nutella Jar", 5).await;
+
+    let (status, body) = common::respond(items::add_item(
+        State(state),
+        claims("1", Role::VENDOR),
+        valid(
+            json!({
+                "name": "Nutella Jar",
+                "description": "Another jar",
+                "price": 1.0,
+                "quantity": 1
+            })
+        )
+    ).await);
+
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error_code"], "conflict");
+}
+
+#[tokio::test]
+async fn items_are_edited_and_deleted() {
+    let state = common::state();
+    let item = seed_item(&state, "Nutella Jar", 5).await;
+    let key = item["_key"].as_str().unwrap();
+
+    let (status, response) = common::respond(items::edit_item(
+        State(state.clone()),
+        claims("1", Role::VENDOR),
+        HeaderMap::new(),
+        valid(json!({ "id": key, "price": 4.5 }))
+    ).await);
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response["price"]["amount"], "4.50");
+    assert_ne!(response["_rev"], item["_rev"]);
+
+    let (status, _) = common::respond(items::edit_item(
+        State(state.clone()),
+        claims("1", Role::VENDOR),
+        HeaderMap::new(),
+        valid(json!({ "id": "missing", "price": 4.5 }))
+    ).await);
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = common::respond(items::delete_item(
+        State(state.clone()),
+        claims("1", Role::VENDOR),
+        payload(json!({ "id": key }))
+    ).await);
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = common::respond(items::delete_item(
+        State(state),
+        claims("1", Role::VENDOR),
+        payload(json!({ "id": key }))
+    ).await);
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn orders_cannot_exceed_item_quantity() {
+    let state = common::state();
+    let item = seed_item(&state, "Nutella Jar", 2).await;
+
+    let (status, _) = common::respond(orders::add_order(
+        State(state.clone()),
+        claims("7", Role::CUSTOMER),
+        valid(json!({ "item_id": item["_key"], "quantity": 3 }))
+    ).await);
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, _) = common::respond(
+        orders::get_orders(
+            State(state),
+            claims("7", Role::CUSTOMER),
+            Path("7".into()),
+            Query(Default::default())
+        ).await
+    );
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn users_sign_up_and_log_in() {
+    let state = common::state();
+
+    let (status, response) = common::respond(auth::handle_signup(
+        State(state.clone()),
+        valid(
+            json!({
+                "first_name": "Jane",
+                "last_name": "Doe",
+                "email": "jane@doe.com",
+                "password": "Password.1",
+                "role": "CUSTOMER"
+            })
+        )
+    ).await);
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response["user"]["email"], "jane@doe.com");
+
+    let (status, _) = common::respond(auth::handle_login(
+        State(state.clone()),
+        ClientIp::default(),
+        valid(json!({ "email": "jane@doe.com", "password": "Password.1" }))
+    ).await);
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = common::respond(auth::handle_login(
+        State(state),
+        ClientIp::default(),
+        valid(json!({ "email": "jane@doe.com", "password": "wrong" }))
+    ).await);
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
\ No newline at end of file
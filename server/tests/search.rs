@@ -0,0 +1,85 @@
+mod common;
+
+use axum::{ extract::{ FromRequestParts, Query, State }, http::{ Request, StatusCode } };
+use serde_json::Value;
+use server::repositories::items::{ ItemRepository, NewItem };
+use server::repositories::memory::MemoryDatabase;
+use server::requests::items;
+use server::search::{ highlight, terms };
+use server::state::AppState;
+use std::sync::Arc;
+
+async fn seeded_state() -> AppState {
+    let database = Arc::new(MemoryDatabase::new());
+    let catalog = [
+        ("Nutella Jar", "Hazelnut cocoa spread"),
+        ("Hazelnut Cake", "Sponge cake with chocolate frosting"),
+        ("Miniature Car", "Die-cast collectible"),
+    ];
+
+    for (name, description) in catalog {
+        database
+            .insert(NewItem {
+                name: name.to_string(),
+                user_id: "1".to_string(),
+                description: description.to_string(),
+                price: "5.00".parse().unwrap(),
+                quantity: 3,
+            }).await
+            .unwrap();
+    }
+
+    common::state_with(database)
+}
+
+async fn search(state: &AppState, query: &str) -> (StatusCode, Value) {
+    let request = Request::get(format!("/api/search_items?{}", query)).body(()).unwrap();
+    let (mut parts, _) = request.into_parts();
+    let params = Query::from_request_parts(&mut parts, &()).await.unwrap();
+
+    common::respond(items::search_items(State(state.clone()), params).await)
+}
+
+#[test]
+fn highlight_marks_matching_words_only() {
+    let terms = terms("hazel SPREAD");
+
+    assert_eq!(
+        highlight("Hazelnut cocoa spread", &terms).unwrap(),
+        "<mark>Hazelnut</mark> cocoa <mark>spread</mark>"
+    );
+    assert_eq!(highlight("Die-cast collectible", &terms), None);
+}
+
+#[tokio::test]
+async fn name_matches_rank_above_description_matches() {
+    let state = seeded_state().await;
+
+    let (status, hits) = search(&state, "q=hazelnut").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let hits = hits.as_array().unwrap();
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0]["item"]["name"], "Hazelnut Cake");
+    assert_eq!(hits[0]["highlights"]["name"], "<mark>Hazelnut</mark> Cake");
+    assert!(hits[0]["highlights"].get("description").is_none());
+    assert_eq!(hits[1]["item"]["name"], "Nutella Jar");
+    assert_eq!(hits[1]["highlights"]["description"], "<mark>Hazelnut</mark> cocoa spread");
+}
+
+#[tokio::test]
+async fn misspelled_terms_still_match() {
+    let state = seeded_state().await;
+
+    let (status, hits) = search(&state, "q=minature").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(hits[0]["item"]["name"], "Miniature Car");
+}
+
+#[tokio::test]
+async fn empty_queries_are_rejected() {
+    let state = seeded_state().await;
+
+    let (status, _) = search(&state, "q=%20-").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
\ No newline at end of file
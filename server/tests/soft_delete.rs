@@ -0,0 +1,275 @@
+mod common;
+
+use axum::{
+    body::Body,
+    extract::{ FromRequestParts, Path, Query, State },
+    http::{ Request, StatusCode },
+    middleware,
+    routing::post,
+    Extension,
+    Json,
+    Router,
+};
+use chrono::{ Duration, Local };
+use serde::de::DeserializeOwned;
+use serde_json::{ json, Value };
+use server::models::{ Deletion, Role };
+use server::purge;
+use server::repositories::items::{ ItemRepository, NewItem };
+use server::repositories::memory::MemoryDatabase;
+use server::repositories::orders::{ NewOrder, NewOrderLine, OrderRepository };
+use server::requests::jwt::{ self, Claims };
+use server::requests::{ admin, items, orders };
+use server::state::AppState;
+use server::toml_env::Config;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+const VENDOR: &str = "883";
+
+fn claims(key: &str, role: Role) -> Claims {
+    Claims {
+        sub: format!("{}@rans.com", key),
+        key: key.to_string(),
+        role,
+        iat: 0,
+        exp: usize::MAX,
+        ver: 0,
+    }
+}
+
+async fn seed_item(database: &MemoryDatabase, name: &str) -> String {
+    let item = database
+        .insert(NewItem {
+            name: name.to_string(),
+            user_id: VENDOR.to_string(),
+            description: format!("Fresh {}", name),
+            price: "3.50".parse().unwrap(),
+            quantity: 5,
+        }).await
+        .unwrap();
+
+    item._key
+}
+
+async fn delete_item(state: &AppState, key: &str) -> StatusCode {
+    let payload = serde_json::from_value(json!({ "id": key })).unwrap();
+    let vendor = claims(VENDOR, Role::VENDOR);
+
+    common::respond(items::delete_item(State(state.clone()), vendor, Json(payload)).await).0
+}
+
+async fn query<T: DeserializeOwned>(uri: &str) -> Query<T> {
+    let (mut parts, _) = Request::get(uri).body(()).unwrap().into_parts();
+    Query::from_request_parts(&mut parts, &()).await.unwrap()
+}
+
+async fn list_items(
+    state: &AppState,
+    claims: Option<Claims>,
+    params: &str
+) -> (StatusCode, Value) {
+    let params = query(&format!("/api/get_items?{}", params)).await;
+    common::respond(items::get_items(State(state.clone()), claims, params).await)
+}
+
+#[tokio::test]
+async fn deleted_items_are_hidden_until_restored() {
+    let database = Arc::new(MemoryDatabase::new());
+    let state = common::state_with(database.clone());
+    let jar = seed_item(&database, "Nutella Jar").await;
+    seed_item(&database, "Baba Cake").await;
+
+    assert_eq!(delete_item(&state, &jar).await, StatusCode::OK);
+    assert_eq!(delete_item(&state, &jar).await, StatusCode::NOT_FOUND);
+
+    let (_, page) = list_items(&state, None, "").await;
+    assert_eq!(page["total"], 1);
+    assert!(database.search("nutella", 10).await.unwrap().is_empty());
+    assert!(ItemRepository::find_by_key(&*database, &jar).await.is_err());
+
+    let vendor = Some(claims(VENDOR, Role::VENDOR));
+    let (status, _) = list_items(&state, vendor, "include_deleted=true").await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let admin = Some(claims("1", Role::ADMIN));
+    let (status, page) = list_items(&state, admin, "include_deleted=true").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(page["total"], 2);
+    assert_eq!(page["items"][1]["deleted_by"], VENDOR);
+
+    let (status, restored) = common::respond(
+        admin::restore_item(State(state.clone()), Path(jar.clone())).await
+    );
+    assert_eq!(status, StatusCode::OK);
+    assert!(restored.get("deleted_at").is_none());
+    let (_, page) = list_items(&state, None, "").await;
+    assert_eq!(page["total"], 2);
+
+    let (status, _) = common::respond(admin::restore_item(State(state), Path(jar)).await);
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn deleted_names_can_be_reused() {
+    let database = Arc::new(MemoryDatabase::new());
+    let state = common::state_with(database.clone());
+    let first = seed_item(&database, "Nutella Jar").await;
+
+    delete_item(&state, &first).await;
+    seed_item(&database, "Nutella Jar").await;
+
+    // Restoring would list the name twice.
+    let (status, _) = common::respond(admin::restore_item(State(state), Path(first)).await);
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn deleted_orders_keep_their_history() {
+    let database = Arc::new(MemoryDatabase::new());
+    let state = common::state_with(database.clone());
+    let jar = seed_item(&database, "Nutella Jar").await;
+    let order = database
+        .place(NewOrder {
+            user_id: "750".to_string(),
+            lines: vec![NewOrderLine { item_id: jar.clone(), quantity: 1 }],
+            date: Local::now().naive_local(),
+            cart_rev: None,
+        }).await
+        .unwrap();
+
+    // Orders keep their lines even once the item they reference is deleted.
+    delete_item(&state, &jar).await;
+    let delete = |claims: Claims| {
+        let payload = serde_json::from_value(json!({ "user_id": "750" })).unwrap();
+        orders::delete_orders(State(state.clone()), claims, Json(payload))
+    };
+    let (status, deleted) = common::respond(delete(claims("750", Role::CUSTOMER)).await);
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(deleted[0]["lines"][0]["item_name"], "Nutella Jar");
+
+    let get = |claims: Claims, params: &str| {
+        let uri = format!("/api/get_orders/750?{}", params);
+        let state = state.clone();
+        async move {
+            let params = query(&uri).await;
+            orders::get_orders(State(state), claims, Path("750".to_string()), params).await
+        }
+    };
+    let customer = || claims("750", Role::CUSTOMER);
+    assert_eq!(common::respond(get(customer(), "").await).0, StatusCode::NOT_FOUND);
+    let (status, _) = common::respond(get(customer(), "include_deleted=true").await);
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, listed) = common::respond(
+        get(claims("1", Role::ADMIN), "include_deleted=true").await
+    );
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(listed[0]["deleted_by"], "750");
+
+    let (status, _) = common::respond(
+        admin::restore_order(State(state.clone()), Path(order._key)).await
+    );
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(common::respond(get(customer(), "").await).0, StatusCode::OK);
+    let (status, _) = common::respond(get(claims("751", Role::CUSTOMER), "").await);
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn users_cannot_delete_the_orders_of_others() {
+    let database = Arc::new(MemoryDatabase::new());
+    let state = common::state_with(database.clone());
+    let jar = seed_item(&database, "Nutella Jar").await;
+    database
+        .place(NewOrder {
+            user_id: "750".to_string(),
+            lines: vec![NewOrderLine { item_id: jar, quantity: 1 }],
+            date: Local::now().naive_local(),
+            cart_rev: None,
+        }).await
+        .unwrap();
+
+    let delete = |claims: Claims| {
+        let payload = serde_json::from_value(json!({ "user_id": "750" })).unwrap();
+        orders::delete_orders(State(state.clone()), claims, Json(payload))
+    };
+    let (status, _) = common::respond(delete(claims("751", Role::CUSTOMER)).await);
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = common::respond(delete(claims(VENDOR, Role::VENDOR)).await);
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(database.find_by_user("750", false).await.unwrap().len(), 1);
+
+    let (status, deleted) = common::respond(delete(claims("1", Role::ADMIN)).await);
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(deleted[0]["deleted_by"], "1");
+}
+
+#[tokio::test]
+async fn purging_only_removes_records_past_the_retention() {
+    let database = Arc::new(MemoryDatabase::new());
+    let state = common::state_with(database.clone());
+    let now = Local::now().naive_local();
+    let deletion = |days: i64| Deletion {
+        deleted_at: now - Duration::days(days),
+        deleted_by: VENDOR.to_string(),
+    };
+
+    let old = seed_item(&database, "Nutella Jar").await;
+    let recent = seed_item(&database, "Baba Cake").await;
+    seed_item(&database, "Croissant").await;
+    database.remove(&old, deletion(31)).await.unwrap();
+    database.remove(&recent, deletion(29)).await.unwrap();
+
+    let purged = purge::purge_deleted(&state, now).await.unwrap();
+    assert_eq!(purged, purge::Purged { items: 1, orders: 0, password_resets: 0 });
+
+    assert!(ItemRepository::restore(&*database, &old).await.is_err());
+    assert!(ItemRepository::restore(&*database, &recent).await.is_ok());
+}
+
+#[test]
+fn retention_defaults_when_the_section_is_missing() {
+    let config = common::config();
+    assert_eq!(config.retention.deleted_days, 30);
+    assert_eq!(config.retention.purge_interval_minutes, 60);
+
+    let config = Config::from_toml(
+        r#"
+        [Database]
+        host = "http://127.0.0.1"
+        port = 8529
+        name = "rans"
+        username = "root"
+        password = "root"
+
+        [Server]
+        env = "production"
+        host = "127.0.0.1"
+        port = 3000
+        secret = "secret"
+
+        [Logs]
+        path = "/tmp"
+        level = "off"
+
+        [Retention]
+        deleted_days = 7
+        "#
+    ).unwrap();
+    assert_eq!(config.retention.deleted_days, 7);
+    assert_eq!(config.retention.purge_interval_minutes, 60);
+}
+
+#[tokio::test]
+async fn admin_guard_only_admits_admins() {
+    let status = |claims: Claims| async move {
+        Router::new()
+            .route("/", post(|| async { "ok" }).route_layer(middleware::from_fn(jwt::admin_guard)))
+            .layer(Extension(claims))
+            .oneshot(Request::post("/").body(Body::empty()).unwrap()).await
+            .unwrap()
+            .status()
+    };
+
+    assert_eq!(status(claims("1", Role::ADMIN)).await, StatusCode::OK);
+    assert_eq!(status(claims("2", Role::VENDOR)).await, StatusCode::FORBIDDEN);
+}
\ No newline at end of file
@@ -0,0 +1,478 @@
+mod common;
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{ Request, StatusCode },
+    middleware,
+    routing::post,
+    Extension,
+    Router,
+};
+use chrono::Utc;
+use serde::de::DeserializeOwned;
+use serde_json::{ json, Value };
+use server::models::Role;
+use server::repositories::memory::MemoryDatabase;
+use server::repositories::users::{ NewUser, UserRepository, UserUpdate };
+use server::requests::jwt::{ self, Claims };
+use server::requests::security::ClientIp;
+use server::requests::validation::Valid;
+use server::requests::{ auth, two_factor, users };
+use server::state::AppState;
+use server::toml_env::{ Config, VerifiedEmailGate };
+use server::totp;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+/// `12345678901234567890`, the secret of the RFC 6238 test vectors.
+const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+fn valid<T: DeserializeOwned>(value: Value) -> Valid<T> {
+    Valid(serde_json::from_value(value).unwrap())
+}
+
+/// State holding the user fixture with `role`, and the claims of that user.
+async fn signed_in(config: Config, role: Role) -> (AppState, Arc<MemoryDatabase>, Claims) {
+    let (state, database, user) = common::state_holding(config, NewUser {
+        role,
+        ..common::user_fixture()
+    }).await;
+
+    (state, database, Claims::new(&user))
+}
+
+/// Code of `secret` for `offset` steps from now.
+fn code(secret: &str, offset: u64) -> String {
+    let now = Utc::now().timestamp() as u64;
+    totp::code(secret, totp::step(now) + offset).unwrap()
+}
+
+/// Enrolls the user with the current code and returns the secret and recovery codes.
+async fn enroll(state: &AppState, claims: &Claims) -> (String, Vec<String>) {
+    let payload = valid(json!({ "password": "Password.1" }));
+    let (status, enrollment) = common::respond(
+        two_factor::start_enrollment(State(state.clone()), claims.clone(), payload).await
+    );
+    assert_eq!(status, StatusCode::OK);
+    let secret = enrollment["secret"].as_str().unwrap().to_string();
+
+    let payload = valid(json!({ "code": code(&secret, 0) }));
+    let (status, confirmed) = common::respond(
+        two_factor::confirm_enrollment(State(state.clone()), claims.clone(), payload).await
+    );
+    assert_eq!(status, StatusCode::OK);
+    let recovery_codes = serde_json::from_value(confirmed["recovery_codes"].clone()).unwrap();
+
+    (secret, recovery_codes)
+}
+
+async fn log_in(state: &AppState) -> (StatusCode, Value) {
+    let payload = valid(json!({ "email": "jane@doe.com", "password": "Password.1" }));
+
+    common::respond(auth::handle_login(State(state.clone()), ClientIp::default(), payload).await)
+}
+
+async fn complete_login(state: &AppState, mfa_token: &str, code: &str) -> (StatusCode, Value) {
+    let payload = valid(json!({ "mfa_token": mfa_token, "code": code }));
+
+    common::respond(
+        two_factor::complete_login(State(state.clone()), ClientIp::default(), payload).await
+    )
+}
+
+/// Logs in with the password and returns the challenge.
+async fn challenge(state: &AppState) -> String {
+    let (status, login) = log_in(state).await;
+    assert_eq!(status, StatusCode::OK);
+
+    login["mfa_token"].as_str().expect("login returned no challenge").to_string()
+}
+
+#[test]
+fn codes_match_the_rfc_6238_test_vectors() {
+    assert_eq!(totp::base32_decode(RFC_SECRET).unwrap(), b"12345678901234567890");
+    assert_eq!(totp::base32_encode(b"12345678901234567890"), RFC_SECRET);
+
+    assert_eq!(totp::code(RFC_SECRET, totp::step(59)).unwrap(), "287082");
+    assert_eq!(totp::code(RFC_SECRET, totp::step(1111111109)).unwrap(), "081804");
+    assert_eq!(totp::code(RFC_SECRET, totp::step(1234567890)).unwrap(), "005924");
+    assert_eq!(totp::code("not base32!", 1), None);
+}
+
+#[test]
+fn codes_are_accepted_within_the_skew_and_only_once() {
+    let now = 1111111109;
+    let step = totp::step(now);
+    let previous = totp::code(RFC_SECRET, step - 1).unwrap();
+    let next = totp::code(RFC_SECRET, step + 1).unwrap();
+    let later = totp::code(RFC_SECRET, step + 2).unwrap();
+
+    assert_eq!(totp::verify(RFC_SECRET, "081804", now, 1, None), Some(step));
+    assert_eq!(totp::verify(RFC_SECRET, "081 804", now, 1, None), Some(step));
+    assert_eq!(totp::verify(RFC_SECRET, &previous, now, 1, None), Some(step - 1));
+    assert_eq!(totp::verify(RFC_SECRET, &next, now, 1, None), Some(step + 1));
+    assert_eq!(totp::verify(RFC_SECRET, &later, now, 1, None), None);
+    assert_eq!(totp::verify(RFC_SECRET, &next, now, 0, None), None);
+
+    // Codes of the step last accepted, or of any before it, are replays.
+    assert_eq!(totp::verify(RFC_SECRET, "081804", now, 1, Some(step)), None);
+    assert_eq!(totp::verify(RFC_SECRET, &next, now, 1, Some(step)), Some(step + 1));
+}
+
+#[test]
+fn otpauth_uris_name_the_issuer_and_account() {
+    let uri = totp::otpauth_uri("RANS Shop", "jane@doe.com", RFC_SECRET);
+
+    assert_eq!(
+        uri,
+        "otpauth://totp/RANS%20Shop:jane%40doe.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\
+        &issuer=RANS%20Shop&algorithm=SHA1&digits=6&period=30"
+    );
+}
+
+#[tokio::test]
+async fn enrollment_is_confirmed_with_a_code() {
+    let (state, database, claims) = signed_in(common::config(), Role::CUSTOMER).await;
+
+    let payload = valid(json!({ "password": "wrong" }));
+    let (status, _) = common::respond(
+        two_factor::start_enrollment(State(state.clone()), claims.clone(), payload).await
+    );
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let payload = valid(json!({ "code": "123456" }));
+    let (status, _) = common::respond(
+        two_factor::confirm_enrollment(State(state.clone()), claims.clone(), payload).await
+    );
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let payload = valid(json!({ "password": "Password.1" }));
+    let (status, enrollment) = common::respond(
+        two_factor::start_enrollment(State(state.clone()), claims.clone(), payload).await
+    );
+    assert_eq!(status, StatusCode::OK);
+    let secret = enrollment["secret"].as_str().unwrap();
+    assert_eq!(totp::base32_decode(secret).unwrap().len(), 20);
+    assert_eq!(
+        enrollment["otpauth_uri"],
+        format!(
+            "otpauth://totp/RANS:jane%40doe.com?secret={}&issuer=RANS&algorithm=SHA1&digits=6\
+            &period=30",
+            secret
+        )
+    );
+
+    // Until the enrollment is confirmed, logins do not ask for a code.
+    let (status, login) = log_in(&state).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(login["token"].is_string());
+
+    let payload = valid(json!({ "code": "not a code" }));
+    let (status, _) = common::respond(
+        two_factor::confirm_enrollment(State(state.clone()), claims.clone(), payload).await
+    );
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let payload = valid(json!({ "code": code(secret, 0) }));
+    let (status, confirmed) = common::respond(
+        two_factor::confirm_enrollment(State(state.clone()), claims.clone(), payload).await
+    );
+    assert_eq!(status, StatusCode::OK);
+    let recovery_codes = confirmed["recovery_codes"].as_array().unwrap();
+    assert_eq!(recovery_codes.len(), 10);
+
+    // Only hashes of the recovery codes are stored.
+    let user = UserRepository::find_by_key(&*database, &claims.key).await.unwrap().unwrap();
+    assert!(user.totp_enabled);
+    assert_eq!(user.recovery_codes.len(), 10);
+    for code in recovery_codes {
+        assert!(!user.recovery_codes.contains(&code.as_str().unwrap().to_string()));
+    }
+
+    let profile = users::get_profile(State(state.clone()), claims.clone()).await;
+    let (_, profile) = common::respond(profile);
+    assert_eq!(profile["totp_enabled"], true);
+
+    let payload = valid(json!({ "password": "Password.1" }));
+    let (status, _) = common::respond(
+        two_factor::start_enrollment(State(state.clone()), claims.clone(), payload).await
+    );
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn logins_return_a_challenge_exchanged_with_a_code() {
+    let (state, database, claims) = signed_in(common::config(), Role::CUSTOMER).await;
+    let (secret, _) = enroll(&state, &claims).await;
+    let user = UserRepository::find_by_key(&*database, &claims.key).await.unwrap().unwrap();
+    let enrolled = user.totp_last_step.unwrap();
+
+    let (status, login) = log_in(&state).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(login.get("token").is_none());
+    assert!(login.get("refresh_token").is_none());
+    assert!(login.get("user").is_none());
+    let mfa_token = login["mfa_token"].as_str().unwrap();
+
+    // The challenge is no access token.
+    assert!(jwt::validate_jwt(mfa_token, &state.keys).is_err());
+
+    let (status, _) = complete_login(&state, mfa_token, "not a code").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // The code of the enrollment was used up, the next one works.
+    let used = totp::code(&secret, enrolled).unwrap();
+    let (status, _) = complete_login(&state, mfa_token, &used).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let next = totp::code(&secret, enrolled + 1).unwrap();
+    let (status, auth) = complete_login(&state, mfa_token, &next).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(auth["user"]["email"], "jane@doe.com");
+    assert!(jwt::validate_jwt(auth["token"].as_str().unwrap(), &state.keys).is_ok());
+    assert!(auth["refresh_token"].is_string());
+
+    let (status, _) = complete_login(&state, mfa_token, &next).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn challenges_cannot_be_forged_from_other_tokens() {
+    let (state, _, claims) = signed_in(common::config(), Role::CUSTOMER).await;
+    let (secret, _) = enroll(&state, &claims).await;
+
+    let (_, auth) = common::respond(
+        auth::handle_signup(
+            State(state.clone()),
+            valid(
+                json!({
+                    "first_name": "John",
+                    "last_name": "Doe",
+                    "email": "john@doe.com",
+                    "password": "Password.1",
+                    "role": "CUSTOMER"
+                })
+            )
+        ).await
+    );
+    let access_token = auth["token"].as_str().unwrap();
+
+    let (status, _) = complete_login(&state, access_token, &code(&secret, 1)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = complete_login(&state, "not a token", &code(&secret, 1)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn password_changes_void_outstanding_challenges() {
+    let (state, _, claims) = signed_in(common::config(), Role::CUSTOMER).await;
+    let (secret, _) = enroll(&state, &claims).await;
+    let mfa_token = challenge(&state).await;
+
+    let payload = valid(json!({ "current_password": "Password.1", "new_password": "Password.2" }));
+    let (status, _) = common::respond(
+        users::change_password(State(state.clone()), claims.clone(), payload).await
+    );
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, error) = complete_login(&state, &mfa_token, &code(&secret, 1)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(error["error_msg"], "Login challenge is invalid or expired");
+}
+
+#[tokio::test]
+async fn recovery_codes_work_once() {
+    let (state, database, claims) = signed_in(common::config(), Role::CUSTOMER).await;
+    let (_, recovery_codes) = enroll(&state, &claims).await;
+
+    // Case and dashes do not matter.
+    let typed = recovery_codes[0].replace('-', " ").to_uppercase();
+    let (status, _) = complete_login(&state, &challenge(&state).await, &typed).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = complete_login(&state, &challenge(&state).await, &recovery_codes[0]).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = complete_login(&state, &challenge(&state).await, &recovery_codes[1]).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let user = UserRepository::find_by_key(&*database, &claims.key).await.unwrap().unwrap();
+    assert_eq!(user.recovery_codes.len(), 8);
+}
+
+#[tokio::test]
+async fn concurrent_logins_cannot_share_a_code() {
+    let (state, database, claims) = signed_in(common::config(), Role::CUSTOMER).await;
+    let (secret, recovery_codes) = enroll(&state, &claims).await;
+
+    for code in [code(&secret, 1), recovery_codes[0].clone()] {
+        let (first, second) = (challenge(&state).await, challenge(&state).await);
+        let (first, second) = tokio::join!(
+            complete_login(&state, &first, &code),
+            complete_login(&state, &second, &code)
+        );
+        let mut statuses = [first.0, second.0];
+        statuses.sort();
+        assert_eq!(statuses, [StatusCode::OK, StatusCode::BAD_REQUEST]);
+    }
+
+    // Requests that read the user before another one used the code do not get to use it again.
+    let stale = UserRepository::find_by_key(&*database, &claims.key).await.unwrap().unwrap();
+    let step = stale.totp_last_step.unwrap() + 1;
+    assert!(database.claim_totp_step(&claims.key, step).await.unwrap());
+    assert!(!database.claim_totp_step(&claims.key, step).await.unwrap());
+
+    let remaining = stale.recovery_codes[1..].to_vec();
+    assert!(
+        database
+            .replace_recovery_codes(&claims.key, &stale.recovery_codes, remaining.clone()).await
+            .unwrap()
+    );
+    assert!(
+        !database
+            .replace_recovery_codes(&claims.key, &stale.recovery_codes, remaining).await
+            .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn blocked_logins_do_not_spend_the_code() {
+    let mut config = common::config();
+    config.server.require_verified_email = VerifiedEmailGate::Login;
+    let (state, database, claims) = signed_in(config, Role::CUSTOMER).await;
+    let (secret, _) = enroll(&state, &claims).await;
+    let mfa_token = challenge(&state).await;
+
+    let unverified = UserUpdate { email_verified: Some(false), ..Default::default() };
+    UserRepository::update(&*database, &claims.key, unverified).await.unwrap();
+    let (status, _) = complete_login(&state, &mfa_token, &code(&secret, 1)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let verified = UserUpdate { email_verified: Some(true), ..Default::default() };
+    UserRepository::update(&*database, &claims.key, verified).await.unwrap();
+    let (status, _) = complete_login(&state, &mfa_token, &code(&secret, 1)).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn wrong_codes_count_towards_the_lockout() {
+    let mut config = common::config();
+    config.security.max_failures = 3;
+    let (state, _, claims) = signed_in(config, Role::CUSTOMER).await;
+    let (secret, _) = enroll(&state, &claims).await;
+
+    let mfa_token = challenge(&state).await;
+    for _ in 0..2 {
+        let (status, _) = complete_login(&state, &mfa_token, "not a code").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    // The right password alone does not forget the wrong codes.
+    let mfa_token = challenge(&state).await;
+    let (status, error) = complete_login(&state, &mfa_token, "not a code").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(error["error_code"], "too_many_requests");
+
+    let (status, _) = complete_login(&state, &mfa_token, &code(&secret, 1)).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn disabling_asks_for_the_password_and_a_code() {
+    let (state, database, claims) = signed_in(common::config(), Role::CUSTOMER).await;
+    let (secret, recovery_codes) = enroll(&state, &claims).await;
+
+    let disable = |payload: Value| {
+        two_factor::disable(State(state.clone()), claims.clone(), valid(payload))
+    };
+
+    let payload = json!({ "password": "wrong", "code": code(&secret, 1) });
+    let (status, _) = common::respond(disable(payload).await);
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let payload = json!({ "password": "Password.1", "code": "not a code" });
+    let (status, _) = common::respond(disable(payload).await);
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let payload = json!({ "password": "Password.1", "code": recovery_codes[0] });
+    let (status, _) = common::respond(disable(payload).await);
+    assert_eq!(status, StatusCode::OK);
+
+    let user = UserRepository::find_by_key(&*database, &claims.key).await.unwrap().unwrap();
+    assert!(!user.totp_enabled);
+    assert_eq!(user.totp_secret, None);
+    assert!(user.recovery_codes.is_empty());
+
+    let (status, login) = log_in(&state).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(login["token"].is_string());
+
+    let payload = json!({ "password": "Password.1", "code": code(&secret, 1) });
+    let (status, _) = common::respond(disable(payload).await);
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn vendors_must_enroll_when_the_server_requires_it() {
+    let mut config = common::config();
+    config.two_factor.require_for_vendors = true;
+    let (state, database, claims) = signed_in(config, Role::VENDOR).await;
+
+    let status = |state: AppState, claims: Claims| async move {
+        let app = Router::new()
+            .route(
+                "/",
+                post(|| async { "ok" }).route_layer(
+                    middleware::from_fn_with_state(state, jwt::vendor_guard)
+                )
+            )
+            .layer(Extension(claims));
+
+        app.oneshot(Request::post("/").body(Body::empty()).unwrap()).await.unwrap().status()
+    };
+
+    // Vendors can still log in to enroll.
+    let (login_status, login) = log_in(&state).await;
+    assert_eq!(login_status, StatusCode::OK);
+    assert!(login["token"].is_string());
+    assert_eq!(status(state.clone(), claims.clone()).await, StatusCode::FORBIDDEN);
+
+    let update = UserUpdate { totp_enabled: Some(true), ..Default::default() };
+    UserRepository::update(&*database, &claims.key, update).await.unwrap();
+    assert_eq!(status(state.clone(), claims).await, StatusCode::OK);
+}
+
+#[test]
+fn two_factor_config_has_defaults() {
+    let config = common::config();
+    assert_eq!(config.two_factor.issuer, "RANS");
+    assert!(!config.two_factor.require_for_vendors);
+    assert_eq!(config.two_factor.skew_steps, 1);
+    assert_eq!(config.two_factor.recovery_codes, 10);
+
+    let config = Config::from_toml(
+        r#"
+        [Database]
+        host = "http://127.0.0.1"
+        port = 8529
+        name = "rans_test"
+        username = "root"
+        password = "root"
+
+        [Server]
+        env = "production"
+        host = "127.0.0.1"
+        port = 3000
+        secret = "secret"
+
+        [Logs]
+        path = "logs"
+        level = "off"
+
+        [TwoFactor]
+        issuer = "RANS Shop"
+        require_for_vendors = true
+        "#
+    ).unwrap();
+    assert_eq!(config.two_factor.issuer, "RANS Shop");
+    assert!(config.two_factor.require_for_vendors);
+    assert_eq!(config.two_factor.recovery_codes, 10);
+}
\ No newline at end of file
@@ -0,0 +1,168 @@
+mod common;
+
+use axum::{ body::Body, http::{ Request, StatusCode }, routing::post, Router };
+use serde_json::{ json, Value };
+use server::requests::items::{ AddItemReq, UpdateItemReq };
+use server::requests::orders::AddOrderReq;
+use server::requests::routes::create_routes;
+use server::requests::validation::Valid;
+use server::state::AppState;
+use tower::ServiceExt;
+
+async fn send(app: Router, uri: &str, body: Value) -> (StatusCode, Value) {
+    let request = Request::post(uri)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+    (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+fn validated() -> Router {
+    Router::new()
+        .route("/items", post(|_: Valid<AddItemReq>| async { "ok" }))
+        .route("/items/edit", post(|_: Valid<UpdateItemReq>| async { "ok" }))
+        .route("/orders", post(|_: Valid<AddOrderReq>| async { "ok" }))
+}
+
+fn failed_fields(body: &Value) -> Vec<&str> {
+    body["content"]["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|field| field["field"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn signup_reports_every_invalid_field() {
+    let app = create_routes(common::state()).await;
+
+    let (status, body) = send(
+        app,
+        "/api/auth/signup",
+        json!({
+            "first_name": "  ",
+            "last_name": "Doe",
+            "email": "not-an-email",
+            "password": "x".repeat(129),
+            "role": "CUSTOMER"
+        })
+    ).await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["result"], "error");
+    assert_eq!(body["content"]["error_code"], "invalid_fields");
+    assert_eq!(failed_fields(&body), vec!["email", "first_name", "password"]);
+    assert_eq!(body["content"]["fields"][0]["code"], "email");
+}
+
+#[tokio::test]
+async fn unknown_roles_and_missing_fields_are_rejected() {
+    let app = create_routes(common::state()).await;
+    let (status, body) = send(
+        app,
+        "/api/auth/signup",
+        json!({
+            "first_name": "Jane",
+            "last_name": "Doe",
+            "email": "jane@doe.com",
+            "password": "Password.1",
+            "role": "ADMIN"
+        })
+    ).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(failed_fields(&body), vec!["role"]);
+    assert_eq!(body["content"]["fields"][0]["code"], "invalid_type");
+
+    let app = create_routes(common::state()).await;
+    let (status, body) = send(app, "/api/auth/login", json!({ "email": "jane@doe.com" })).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(failed_fields(&body), vec!["password"]);
+    assert_eq!(body["content"]["fields"][0]["code"], "required");
+}
+
+#[tokio::test]
+async fn password_length_only_follows_the_policy() {
+    let mut config = common::config();
+    config.passwords.policy.min_length = 6;
+    let app = create_routes(AppState::in_memory(config).unwrap()).await;
+    let signup = |email: &str, password: &str| {
+        json!({
+            "first_name": "Jane",
+            "last_name": "Doe",
+            "email": email,
+            "password": password,
+            "role": "CUSTOMER"
+        })
+    };
+
+    let (status, body) = send(app.clone(), "/api/auth/signup", signup("a@doe.com", "Ab.1x")).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["content"]["error_msg"], "password: must be at least 6 characters long");
+
+    let (status, body) = send(app, "/api/auth/signup", signup("b@doe.com", "Ab.12x")).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[tokio::test]
+async fn item_payloads_reject_negative_and_blank_values() {
+    let (status, body) = send(
+        validated(),
+        "/items",
+        json!({ "name": "", "description": "A jar", "price": -1.0, "quantity": -2 })
+    ).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(failed_fields(&body), vec!["name", "price", "quantity"]);
+
+    let (status, body) = send(
+        validated(),
+        "/items",
+        json!({ "name": "Jar", "description": "A jar", "price": "free", "quantity": 1 })
+    ).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(failed_fields(&body), vec!["price"]);
+    assert_eq!(body["content"]["error_msg"], "price: invalid amount `free`");
+
+    let (status, _) = send(
+        validated(),
+        "/items",
+        json!({ "name": "Jar", "description": "A jar", "price": 0.0, "quantity": 0 })
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send(
+        validated(),
+        "/items/edit",
+        json!({ "id": "1", "name": " ", "price": -0.5 })
+    ).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(failed_fields(&body), vec!["name", "price"]);
+
+    let (status, _) = send(validated(), "/items/edit", json!({ "id": "1", "price": 2.5 })).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn orders_need_a_positive_quantity() {
+    let order = json!({ "item_id": "1", "quantity": 0 });
+    let (status, body) = send(validated(), "/orders", order).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(failed_fields(&body), vec!["quantity"]);
+    assert_eq!(body["content"]["error_msg"], "quantity: must be at least 1");
+}
+
+#[tokio::test]
+async fn malformed_json_is_a_bad_request() {
+    let request = Request::post("/orders")
+        .header("Content-Type", "application/json")
+        .body(Body::from("{ \"item_id\": "))
+        .unwrap();
+
+    let response = validated().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
\ No newline at end of file
@@ -213,7 +213,6 @@ fi
 
 sudo chmod a+x db_setup.js
 arangosh --javascript.execute db_setup.js
-arangorestore --server.database project2 --input-directory db_dump
 
 echo
 echo "============ Set Up Files ============"
@@ -243,6 +242,13 @@ if [[ ! -f "$api_bin" ]]; then
     fi
 fi
 
+"$api_bin" --config "$configs_local"/config.toml migrate
+if [ $? -ne 0 ]; then
+    echo "Error: Failed to run database migrations. Stopping gracefully..."
+    exit 1
+fi
+arangorestore --server.database project2 --input-directory db_dump --create-collection false
+
 copy ./rans.service "$systemd_remote"
 copy "$services" "$systemd_remote"
 copy "$configs_local"/nginx.conf "$nginx"